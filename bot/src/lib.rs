@@ -0,0 +1,142 @@
+//! Headless bots on top of `libtw2-client`.
+//!
+//! The inputs of a bot are decided by a [`Policy`] that gets every snapshot
+//! the bot receives, decoded. [`run`] connects any number of bots to a server
+//! from a single process, e.g. for load tests.
+
+#[macro_use]
+extern crate log;
+
+pub mod replay;
+
+pub use self::replay::Replay;
+
+use libtw2_client::Client;
+use libtw2_client::Config;
+use libtw2_client::Context;
+use libtw2_client::Event;
+use libtw2_client::Handler;
+use libtw2_client::Protocol;
+use libtw2_common::pretty;
+use libtw2_event_loop::Addr;
+use libtw2_event_loop::Loop;
+use libtw2_snapshot::Snap;
+use std::thread;
+use std::time::Duration;
+
+/// A decoded snapshot.
+pub struct Snapshot<'a, P: Protocol> {
+    /// Game tick of the snapshot.
+    pub tick: i32,
+    /// Objects of the snapshot along with their IDs.
+    pub objects: &'a [(u16, P::SnapObj)],
+}
+
+/// Decides the inputs of a bot.
+pub trait Policy<P: Protocol> {
+    /// Called for each snapshot received while in game, returns the input
+    /// sent to the server.
+    fn input(&mut self, snapshot: &Snapshot<P>) -> P::PlayerInput;
+    /// Whether the policy is done, the bot disconnects once this returns
+    /// `true`.
+    fn is_finished(&self) -> bool {
+        false
+    }
+}
+
+/// [`Handler`] playing according to a [`Policy`].
+pub struct Bot<P: Protocol, T> {
+    name: String,
+    policy: T,
+    objects: Vec<(u16, P::SnapObj)>,
+}
+
+impl<P: Protocol, T: Policy<P>> Bot<P, T> {
+    /// Creates a bot, `name` is only used for logging.
+    pub fn new(name: String, policy: T) -> Bot<P, T> {
+        Bot {
+            name,
+            policy,
+            objects: Vec::new(),
+        }
+    }
+    pub fn policy(&self) -> &T {
+        &self.policy
+    }
+    pub fn policy_mut(&mut self) -> &mut T {
+        &mut self.policy
+    }
+}
+
+impl<P: Protocol, T: Policy<P>> Handler<P> for Bot<P, T> {
+    fn on_event<L: Loop>(&mut self, _: &mut Context<L, P>, event: Event<P>) {
+        match event {
+            Event::Connected => info!("{}: connected", self.name),
+            Event::MapReady(path) => info!("{}: map {}", self.name, path.display()),
+            Event::EnterGame => info!("{}: entered game", self.name),
+            Event::Error(err) => error!("{}: {}", self.name, err),
+            Event::Disconnected { remote, reason } => info!(
+                "{}: disconnected remote={} reason={}",
+                self.name,
+                remote,
+                pretty::AlmostString::new(reason)
+            ),
+            _ => {}
+        }
+    }
+    fn on_snapshot<L: Loop>(
+        &mut self,
+        ctx: &mut Context<L, P>,
+        tick: i32,
+        snap: &Snap,
+    ) -> Option<P::PlayerInput> {
+        self.objects.clear();
+        self.objects.extend(libtw2_client::snap_objects::<P>(snap));
+        let input = self.policy.input(&Snapshot {
+            tick,
+            objects: &self.objects,
+        });
+        if self.policy.is_finished() {
+            info!("{}: finished", self.name);
+            ctx.disconnect(b"finished");
+        }
+        Some(input)
+    }
+}
+
+/// Runs bots against the server at `addr` until all of them are
+/// disconnected.
+///
+/// Servers tell clients apart by their address, so each bot gets its own
+/// event loop, and thus its own socket, on its own thread. The bots connect
+/// `connect_interval` apart to not trip the server's flood protection.
+pub fn run<L, P, T, I>(addr: Addr, bots: I, connect_interval: Duration)
+where
+    L: Loop + 'static,
+    P: Protocol,
+    T: Policy<P> + Send + 'static,
+    I: IntoIterator<Item = (Config, T)>,
+{
+    let mut threads = Vec::new();
+    for (i, (config, policy)) in bots.into_iter().enumerate() {
+        if i != 0 {
+            thread::sleep(connect_interval);
+        }
+        let name = String::from_utf8_lossy(&config.player.name).into_owned();
+        let thread = thread::Builder::new()
+            .name(format!("bot {}", name))
+            .spawn(move || {
+                let mut loop_ = L::client();
+                let mut client = Client::<P, _>::new();
+                client.connect(&mut loop_, addr, config, Bot::new(name, policy));
+                loop_.run(client);
+            })
+            .unwrap();
+        threads.push(thread);
+    }
+    for thread in threads {
+        if thread.join().is_err() {
+            error!("bot thread panicked");
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use clap::value_t;
+use clap::App;
+use clap::Arg;
+use clap::Error;
+use clap::ErrorKind;
+use libtw2_bot::replay;
+use libtw2_bot::Replay;
+use libtw2_client::Config;
+use libtw2_client::PlayerInfo;
+use libtw2_event_loop::Addr;
+use libtw2_event_loop::SocketLoop;
+use libtw2_gamenet_ddnet::Protocol;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+fn main() {
+    libtw2_logger::init();
+
+    let matches = App::new("Teehistorian replay bots")
+        .about(
+            "Connects one bot per player recorded in a teehistorian file to a \
+             DDNet server and replays the player's inputs.",
+        )
+        .arg(
+            Arg::with_name("name")
+                .help("Sets the prefix of the bot names, followed by a number")
+                .long("name")
+                .takes_value(true)
+                .value_name("NAME")
+                .default_value("bot"),
+        )
+        .arg(
+            Arg::with_name("bots")
+                .help("Sets the maximum number of bots")
+                .long("bots")
+                .takes_value(true)
+                .value_name("BOTS"),
+        )
+        .arg(
+            Arg::with_name("interval")
+                .help("Sets the time between two bots connecting, in milliseconds")
+                .long("interval")
+                .takes_value(true)
+                .value_name("MS")
+                .default_value("100"),
+        )
+        .arg(
+            Arg::with_name("SERVER")
+                .help("Server to connect to")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("TEEHISTORIAN")
+                .help("Teehistorian file to replay")
+                .required(true),
+        )
+        .get_matches();
+
+    let addr = value_t!(matches, "SERVER", Addr).unwrap_or_else(|e| e.exit());
+    let path = Path::new(matches.value_of_os("TEEHISTORIAN").unwrap());
+    let name = matches.value_of("name").unwrap();
+    let interval = value_t!(matches, "interval", u64).unwrap_or_else(|e| e.exit());
+    let max_bots = if matches.is_present("bots") {
+        value_t!(matches, "bots", usize).unwrap_or_else(|e| e.exit())
+    } else {
+        usize::MAX
+    };
+
+    // Leave room for the number.
+    if name.len() >= 11 {
+        Error::with_description(
+            "Name prefix can have at most 10 bytes",
+            ErrorKind::ValueValidation,
+        )
+        .exit();
+    }
+
+    let recordings = match replay::read_recordings(path) {
+        Ok(r) => r,
+        Err(err) => {
+            eprintln!("{}: {:?}", path.display(), err);
+            process::exit(1);
+        }
+    };
+    if recordings.is_empty() {
+        eprintln!("{}: no player inputs found", path.display());
+        process::exit(1);
+    }
+
+    fs::create_dir_all("maps").unwrap();
+    fs::create_dir_all("downloading").unwrap();
+    let bots = recordings
+        .into_iter()
+        .take(max_bots)
+        .enumerate()
+        .map(|(i, recording)| {
+            let player = PlayerInfo::new(format!("{}{}", name, i).as_bytes());
+            (Config::new(player), Replay::new(recording))
+        });
+    libtw2_bot::run::<SocketLoop, Protocol, _, _>(addr, bots, Duration::from_millis(interval));
+}
@@ -0,0 +1,232 @@
+//! Policy replaying the inputs of players recorded in a teehistorian file.
+
+use crate::Policy;
+use crate::Snapshot;
+use libtw2_common::num::Cast;
+use libtw2_packer::IntUnpacker;
+use libtw2_teehistorian::format::item::INPUT_LEN;
+use libtw2_teehistorian::Buffer;
+use libtw2_teehistorian::Error;
+use libtw2_teehistorian::Item;
+use libtw2_teehistorian::Reader;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Inputs of a player from their first input until they left the server.
+#[derive(Clone, Debug)]
+pub struct Recording {
+    pub cid: i32,
+    /// Tick of the first input.
+    pub start_tick: i32,
+    /// Input of every tick starting at `start_tick`.
+    pub inputs: Vec<[i32; INPUT_LEN]>,
+}
+
+#[derive(Default)]
+struct Recorder {
+    active: BTreeMap<i32, Recording>,
+    finished: Vec<Recording>,
+}
+
+impl Recorder {
+    /// `input` returns the current input of a player, like
+    /// [`Reader::input`].
+    fn item<F>(&mut self, item: &Item, input: F)
+    where
+        F: Fn(i32) -> Option<[i32; INPUT_LEN]>,
+    {
+        match *item {
+            Item::Input(ref i) => {
+                self.active.entry(i.cid).or_insert_with(|| Recording {
+                    cid: i.cid,
+                    start_tick: 0,
+                    inputs: Vec::new(),
+                });
+            }
+            Item::TickEnd(tick) => {
+                for (&cid, recording) in &mut self.active {
+                    if let Some(i) = input(cid) {
+                        if recording.inputs.is_empty() {
+                            recording.start_tick = tick;
+                        }
+                        recording.inputs.push(i);
+                    }
+                }
+            }
+            Item::Drop(ref d) => self.finish(d.cid),
+            _ => {}
+        }
+    }
+    fn finish(&mut self, cid: i32) {
+        if let Some(recording) = self.active.remove(&cid) {
+            if !recording.inputs.is_empty() {
+                self.finished.push(recording);
+            }
+        }
+    }
+    fn into_recordings(mut self) -> Vec<Recording> {
+        let cids: Vec<i32> = self.active.keys().cloned().collect();
+        for cid in cids {
+            self.finish(cid);
+        }
+        self.finished.sort_by_key(|r| (r.start_tick, r.cid));
+        self.finished
+    }
+}
+
+/// Reads the inputs of all players from a teehistorian file, ordered by the
+/// time they started playing.
+///
+/// A client ID reused by a later player results in a separate recording.
+pub fn read_recordings<P: AsRef<Path>>(path: P) -> Result<Vec<Recording>, Error> {
+    let mut buffer = Buffer::new();
+    let (_, mut reader) = Reader::open(path, &mut buffer)?;
+    let mut recorder = Recorder::default();
+    while let Some(item) = reader.read(&mut buffer)? {
+        recorder.item(&item, |cid| reader.input(cid));
+    }
+    Ok(recorder.into_recordings())
+}
+
+/// Replays a [`Recording`] tick by tick, starting at the first snapshot.
+///
+/// After the end of the recording, the bot stands still and disconnects.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    recording: Recording,
+    first_tick: Option<i32>,
+    position: usize,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Replay {
+        Replay {
+            recording,
+            first_tick: None,
+            position: 0,
+        }
+    }
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+    fn input_at(&mut self, tick: i32) -> [i32; INPUT_LEN] {
+        let first_tick = *self.first_tick.get_or_insert(tick);
+        self.position = match (tick - first_tick).try_usize() {
+            Some(p) => p,
+            None => {
+                // The tick went backwards, e.g. due to a map change. Start
+                // over.
+                self.first_tick = Some(tick);
+                0
+            }
+        };
+        self.recording
+            .inputs
+            .get(self.position)
+            .cloned()
+            .unwrap_or_default()
+    }
+    fn is_past_end(&self) -> bool {
+        self.position >= self.recording.inputs.len()
+    }
+}
+
+// The teehistorian inputs have the layout of the `PlayerInput` snapshot
+// object.
+
+impl Policy<libtw2_gamenet_ddnet::Protocol> for Replay {
+    fn input(
+        &mut self,
+        snapshot: &Snapshot<libtw2_gamenet_ddnet::Protocol>,
+    ) -> libtw2_gamenet_ddnet::snap_obj::PlayerInput {
+        let input = self.input_at(snapshot.tick);
+        libtw2_gamenet_ddnet::snap_obj::PlayerInput::decode_inner(&mut IntUnpacker::new(&input))
+            .unwrap()
+    }
+    fn is_finished(&self) -> bool {
+        self.is_past_end()
+    }
+}
+
+impl Policy<libtw2_gamenet_teeworlds_0_6::Protocol> for Replay {
+    fn input(
+        &mut self,
+        snapshot: &Snapshot<libtw2_gamenet_teeworlds_0_6::Protocol>,
+    ) -> libtw2_gamenet_teeworlds_0_6::snap_obj::PlayerInput {
+        let input = self.input_at(snapshot.tick);
+        libtw2_gamenet_teeworlds_0_6::snap_obj::PlayerInput::decode_inner(&mut IntUnpacker::new(
+            &input,
+        ))
+        .unwrap()
+    }
+    fn is_finished(&self) -> bool {
+        self.is_past_end()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Recorder;
+    use super::Replay;
+    use crate::Policy;
+    use crate::Snapshot;
+    use libtw2_gamenet_ddnet::Protocol;
+    use libtw2_teehistorian::format::item;
+    use libtw2_teehistorian::Input;
+    use libtw2_teehistorian::Item;
+
+    fn input(cid: i32, direction: i32) -> Item<'static> {
+        let mut input = [0; 10];
+        input[0] = direction;
+        Item::Input(Input { cid, input })
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let mut current = [None, None];
+        let mut recorder = Recorder::default();
+        let mut feed = |current: &[Option<[i32; 10]>; 2], item: Item| {
+            recorder.item(&item, |cid| current[cid as usize]);
+        };
+        let items = [
+            (10, vec![input(1, -1)]),
+            (11, vec![input(0, 1)]),
+            (12, vec![]),
+            (
+                13,
+                vec![Item::Drop(item::Drop {
+                    cid: 1,
+                    reason: b"",
+                })],
+            ),
+        ];
+        for (tick, items) in items {
+            feed(&current, Item::TickStart(tick));
+            for item in items {
+                match item {
+                    Item::Input(ref i) => current[i.cid as usize] = Some(i.input),
+                    Item::Drop(ref d) => current[d.cid as usize] = None,
+                    _ => {}
+                }
+                feed(&current, item);
+            }
+            feed(&current, Item::TickEnd(tick));
+        }
+        let recordings = recorder.into_recordings();
+
+        assert_eq!(recordings.len(), 2);
+        assert_eq!((recordings[0].cid, recordings[0].start_tick), (1, 10));
+        assert_eq!(recordings[0].inputs.len(), 3);
+        assert_eq!((recordings[1].cid, recordings[1].start_tick), (0, 11));
+        assert_eq!(recordings[1].inputs.len(), 3);
+
+        let mut replay = Replay::new(recordings[1].clone());
+        let snapshot = |tick| Snapshot::<Protocol> { tick, objects: &[] };
+        assert_eq!(replay.input(&snapshot(500)).direction, 1);
+        assert!(!Policy::<Protocol>::is_finished(&replay));
+        assert_eq!(replay.input(&snapshot(502)).direction, 1);
+        assert!(!Policy::<Protocol>::is_finished(&replay));
+        assert_eq!(replay.input(&snapshot(504)).direction, 0);
+        assert!(Policy::<Protocol>::is_finished(&replay));
+    }
+}
@@ -0,0 +1,848 @@
+use crate::map;
+use crate::map::Download;
+use crate::protocol::Control;
+use crate::protocol::Game;
+use crate::protocol::MapChange;
+use crate::protocol::PlayerInfo;
+use crate::protocol::Protocol;
+use crate::protocol::Request;
+use crate::protocol::System;
+use crate::Error;
+use arrayvec::ArrayVec;
+use libtw2_common::digest::Sha256;
+use libtw2_common::num::Cast;
+use libtw2_common::pretty;
+use libtw2_event_loop::collections::PeerMap;
+use libtw2_event_loop::Addr;
+use libtw2_event_loop::Application;
+use libtw2_event_loop::Chunk;
+use libtw2_event_loop::ConnlessChunk;
+use libtw2_event_loop::Loop;
+use libtw2_event_loop::PeerId;
+use libtw2_event_loop::Timeout;
+use libtw2_event_loop::Timestamp;
+use libtw2_gamenet_common::error::Error as DecodeError;
+use libtw2_gamenet_common::msg::SystemOrGame;
+use libtw2_gamenet_common::traits;
+use libtw2_gamenet_common::traits::MessageExt;
+use libtw2_gamenet_snap::SnapMsg;
+use libtw2_packer::with_packer;
+use libtw2_packer::Unpacker;
+use libtw2_packer::Warning;
+use libtw2_snapshot::Snap;
+use libtw2_warn as warn;
+use libtw2_warn::Log;
+use std::borrow::Cow;
+use std::cmp;
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::path::PathBuf;
+use std::sync::mpsc;
+#[cfg(feature = "https")]
+use std::thread;
+use std::time::Duration;
+
+/// Initial number of ticks the inputs are sent ahead of the last snapshot.
+///
+/// Adjusted using the server's `InputTiming` responses.
+const INITIAL_TICK_OFFSET: i32 = 5;
+const MAX_TICK_OFFSET: i32 = 50;
+/// Inputs arriving more than this many milliseconds early reduce the tick
+/// offset.
+const MAX_TIME_LEFT: i32 = 100;
+
+/// Per-connection configuration.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub player: PlayerInfo,
+    pub password: Vec<u8>,
+    /// Directory the maps are looked up in and downloaded to.
+    pub maps_directory: PathBuf,
+    /// Directory for incomplete downloads, should be on the same file system
+    /// as `maps_directory`.
+    pub download_directory: PathBuf,
+    /// Store maps with known SHA256 as `<sha256>.map`, so that the same map
+    /// served under different names is only downloaded once.
+    pub content_addressed_maps: bool,
+    /// Download maps over HTTPS if the server announces a URL for them,
+    /// falling back to downloading them from the server. Only has an effect
+    /// with the `https` feature.
+    pub https_maps: bool,
+    /// Keep incomplete map downloads in `download_directory` and continue
+    /// them on the next attempt.
+    pub resume_downloads: bool,
+}
+
+impl Config {
+    pub fn new(player: PlayerInfo) -> Config {
+        Config {
+            player,
+            password: Vec::new(),
+            maps_directory: PathBuf::from("maps"),
+            download_directory: PathBuf::from("downloading"),
+            content_addressed_maps: false,
+            https_maps: false,
+            resume_downloads: false,
+        }
+    }
+}
+
+/// Events of a single connection.
+pub enum Event<'a, P: traits::Protocol<'a>> {
+    /// The connection is established and the `Info` message has been sent.
+    Connected,
+    /// The server changed the map, snapshots are reset.
+    MapChange(MapChange<'a>),
+    /// A chunk of the current map was downloaded.
+    MapDownload {
+        received: usize,
+        size: usize,
+    },
+    /// The current map is available at the given path, either because it
+    /// was downloaded before or because its download just finished.
+    MapReady(PathBuf),
+    /// The client entered the game, snapshots are going to follow.
+    EnterGame,
+    /// A game message.
+    Game(Game<'a, P>),
+    /// A system message not handled by the client itself.
+    System(System<'a, P>),
+    /// The connection failed, the client disconnects after this event if the
+    /// handler didn't already do so.
+    Error(Error),
+    Disconnected {
+        remote: bool,
+        reason: &'a [u8],
+    },
+}
+
+impl<'a, P: traits::Protocol<'a>> fmt::Debug for Event<'a, P>
+where
+    Game<'a, P>: fmt::Debug,
+    System<'a, P>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Event::Connected => f.write_str("Connected"),
+            Event::MapChange(ref m) => f.debug_tuple("MapChange").field(m).finish(),
+            Event::MapDownload { received, size } => f
+                .debug_struct("MapDownload")
+                .field("received", &received)
+                .field("size", &size)
+                .finish(),
+            Event::MapReady(ref p) => f.debug_tuple("MapReady").field(p).finish(),
+            Event::EnterGame => f.write_str("EnterGame"),
+            Event::Game(ref m) => f.debug_tuple("Game").field(m).finish(),
+            Event::System(ref m) => f.debug_tuple("System").field(m).finish(),
+            Event::Error(ref e) => f.debug_tuple("Error").field(e).finish(),
+            Event::Disconnected { remote, reason } => f
+                .debug_struct("Disconnected")
+                .field("remote", &remote)
+                .field("reason", &pretty::AlmostString::new(reason))
+                .finish(),
+        }
+    }
+}
+
+/// Decides what to do with a connection.
+pub trait Handler<P: Protocol> {
+    fn on_event<L: Loop>(&mut self, ctx: &mut Context<L, P>, event: Event<P>);
+    /// Called for each complete snapshot received while in game, `tick` is
+    /// the game tick of the snapshot.
+    ///
+    /// The returned input is sent to the server, `None` repeats the previous
+    /// input.
+    fn on_snapshot<L: Loop>(
+        &mut self,
+        ctx: &mut Context<L, P>,
+        tick: i32,
+        snap: &Snap,
+    ) -> Option<P::PlayerInput> {
+        let _ = (ctx, tick, snap);
+        None
+    }
+    /// Time at which `on_tick` wants to be called next.
+    fn needs_tick(&mut self) -> Timeout {
+        Timeout::inactive()
+    }
+    /// Called on every iteration of the event loop.
+    fn on_tick<L: Loop>(&mut self, ctx: &mut Context<L, P>) {
+        let _ = ctx;
+    }
+    /// Called after [`Event::MapChange`], before the map is looked up or
+    /// downloaded.
+    ///
+    /// If it returns `false`, the map is neither looked up nor downloaded
+    /// and the client enters the game without it, there's no
+    /// [`Event::MapReady`].
+    fn need_file(&mut self, name: &[u8], crc: i32, sha256: Option<Sha256>) -> bool {
+        let _ = (name, crc, sha256);
+        true
+    }
+}
+
+/// Access to a single connection from a [`Handler`].
+///
+/// Sent messages are batched, the connection is flushed once by the event
+/// loop after the current callback returns.
+pub struct Context<'a, L: Loop, P> {
+    loop_: &'a mut L,
+    pid: PeerId,
+    disconnected: bool,
+    sent: bool,
+    protocol: PhantomData<P>,
+}
+
+impl<'a, L: Loop, P: Protocol> Context<'a, L, P> {
+    fn new(loop_: &'a mut L, pid: PeerId, disconnected: bool) -> Context<'a, L, P> {
+        Context {
+            loop_,
+            pid,
+            disconnected,
+            sent: false,
+            protocol: PhantomData,
+        }
+    }
+    pub fn pid(&self) -> PeerId {
+        self.pid
+    }
+    pub fn time(&mut self) -> Timestamp {
+        self.loop_.time()
+    }
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected
+    }
+    pub fn send_system<'m, S: Into<System<'m, P>>>(&mut self, msg: S) {
+        self.send(&msg.into());
+    }
+    pub fn send_game<'m, G: Into<Game<'m, P>>>(&mut self, msg: G) {
+        self.send(&msg.into());
+    }
+    /// Sends the pending messages immediately instead of at the end of the
+    /// event loop iteration.
+    pub fn force_flush(&mut self) {
+        if !self.disconnected {
+            self.loop_.force_flush(self.pid);
+            self.sent = false;
+        }
+    }
+    pub fn disconnect(&mut self, reason: &[u8]) {
+        if !self.disconnected {
+            self.loop_.disconnect(self.pid, reason);
+            self.disconnected = true;
+        }
+    }
+    fn send<'m, M: traits::Message<'m>>(&mut self, msg: &M) {
+        if self.disconnected {
+            return;
+        }
+        let mut buf: ArrayVec<[u8; 2048]> = ArrayVec::new();
+        with_packer(&mut buf, |p| msg.encode(p).unwrap());
+        self.loop_.send(Chunk {
+            pid: self.pid,
+            vital: true,
+            data: &buf,
+        });
+        self.sent = true;
+    }
+}
+
+impl<'a, L: Loop, P> Drop for Context<'a, L, P> {
+    fn drop(&mut self) {
+        if self.sent && !self.disconnected {
+            self.loop_.flush(self.pid);
+        }
+    }
+}
+
+struct Warn<'a>(&'a [u8]);
+
+impl<'a, W: fmt::Debug> warn::Warn<W> for Warn<'a> {
+    fn warn(&mut self, w: W) {
+        warn!("{:?} in {:?}", w, pretty::Bytes::new(self.0));
+    }
+}
+
+fn decode<'a, P: Protocol, W: warn::Warn<Warning>>(
+    warn: &mut W,
+    p: &mut Unpacker<'a>,
+) -> Result<SystemOrGame<System<'a, P>, Game<'a, P>>, DecodeError> {
+    let id = SystemOrGame::decode_id(warn, p)?;
+    Ok(match id {
+        SystemOrGame::System(_) => SystemOrGame::System(traits::Message::decode_msg(warn, id, p)?),
+        SystemOrGame::Game(_) => SystemOrGame::Game(traits::Message::decode_msg(warn, id, p)?),
+    })
+}
+
+struct OwnedMapDetails {
+    name: Vec<u8>,
+    crc: i32,
+    sha256: Sha256,
+    url: Option<String>,
+}
+
+/// Map that needs to be downloaded.
+#[derive(Clone)]
+struct MapFile {
+    path: PathBuf,
+    size: usize,
+    crc: i32,
+    sha256: Option<Sha256>,
+}
+
+/// How often a running HTTPS download is checked for completion.
+const HTTPS_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+enum State {
+    Connection,
+    MapChange,
+    MapData {
+        download: Box<Download>,
+        chunks_per_request: i32,
+        /// Data of a previous attempt is present, but it's not known yet
+        /// which chunks it corresponds to.
+        resuming: bool,
+    },
+    /// The map is downloaded over HTTPS in a background thread.
+    #[cfg_attr(not(feature = "https"), allow(dead_code))]
+    MapHttps {
+        result: mpsc::Receiver<Result<PathBuf, map::Error>>,
+        map: MapFile,
+        chunks_per_request: i32,
+        next_poll: Timestamp,
+    },
+    ConReady,
+    ReadyToEnter,
+    InGame,
+}
+
+struct Peer<P: Protocol, H> {
+    handler: H,
+    config: Config,
+    state: State,
+    disconnected: bool,
+    map_details: Option<OwnedMapDetails>,
+    snaps: libtw2_snapshot::Manager,
+    input: P::PlayerInput,
+    tick_offset: i32,
+}
+
+impl<P: Protocol, H: Handler<P>> Peer<P, H> {
+    fn new(config: Config, handler: H) -> Peer<P, H> {
+        Peer {
+            handler,
+            config,
+            state: State::Connection,
+            disconnected: false,
+            map_details: None,
+            snaps: libtw2_snapshot::Manager::new(),
+            input: Default::default(),
+            tick_offset: INITIAL_TICK_OFFSET,
+        }
+    }
+    fn context<'a, L: Loop>(&self, loop_: &'a mut L, pid: PeerId) -> Context<'a, L, P> {
+        Context::new(loop_, pid, self.disconnected)
+    }
+    fn event<L: Loop>(&mut self, loop_: &mut L, pid: PeerId, event: Event<P>) {
+        let mut ctx = self.context(loop_, pid);
+        self.handler.on_event(&mut ctx, event);
+        self.disconnected = ctx.disconnected;
+    }
+    fn request<L: Loop>(&mut self, loop_: &mut L, pid: PeerId, request: Request<P::PlayerInput>) {
+        self.context(loop_, pid).send(&P::request(request));
+    }
+    fn error<L: Loop>(&mut self, loop_: &mut L, pid: PeerId, err: Error) {
+        self.event(loop_, pid, Event::Error(err));
+        self.context(loop_, pid).disconnect(b"error");
+        self.disconnected = true;
+    }
+    fn needs_tick(&mut self) -> Timeout {
+        let poll = match self.state {
+            State::MapHttps { next_poll, .. } => Timeout::active(next_poll),
+            _ => Timeout::inactive(),
+        };
+        cmp::min(poll, self.handler.needs_tick())
+    }
+    fn tick<L: Loop>(&mut self, loop_: &mut L, pid: PeerId) {
+        if self.disconnected {
+            return;
+        }
+        self.poll_https(loop_, pid);
+        if self.disconnected {
+            return;
+        }
+        let mut ctx = self.context(loop_, pid);
+        self.handler.on_tick(&mut ctx);
+        self.disconnected = ctx.disconnected;
+    }
+    fn on_ready<L: Loop>(&mut self, loop_: &mut L, pid: PeerId) {
+        self.state = State::MapChange;
+        self.context(loop_, pid).send(&P::request(Request::Info {
+            password: &self.config.password,
+        }));
+        self.event(loop_, pid, Event::Connected);
+    }
+    fn on_packet<L: Loop>(&mut self, loop_: &mut L, pid: PeerId, data: &[u8]) {
+        if self.disconnected {
+            return;
+        }
+        let msg = match decode::<P, _>(&mut Warn(data), &mut Unpacker::new(data)) {
+            Ok(m) => m,
+            Err(err) => {
+                warn!("decode error {:?} in {:?}", err, pretty::Bytes::new(data));
+                return;
+            }
+        };
+        match msg {
+            SystemOrGame::System(msg) => match P::control(&msg) {
+                Some(control) => self.on_control(loop_, pid, control),
+                None => self.event(loop_, pid, Event::System(msg)),
+            },
+            SystemOrGame::Game(msg) => {
+                if P::is_ready_to_enter(&msg) {
+                    self.on_ready_to_enter(loop_, pid);
+                } else {
+                    self.event(loop_, pid, Event::Game(msg));
+                }
+            }
+        }
+    }
+    fn on_control<L: Loop>(&mut self, loop_: &mut L, pid: PeerId, control: Control) {
+        match control {
+            Control::MapChange(map_change) => self.on_map_change(loop_, pid, map_change),
+            Control::MapDetails(details) => {
+                // DDNet servers send an empty URL if the map isn't available
+                // via HTTPS.
+                let url = details.url.filter(|url| !url.is_empty());
+                let url = url.and_then(|url| {
+                    if !map::is_valid_url(url) {
+                        warn!("invalid map url {:?}", pretty::AlmostString::new(url));
+                        return None;
+                    }
+                    // Valid URLs are ASCII.
+                    Some(String::from_utf8(url.to_owned()).unwrap())
+                });
+                self.map_details = Some(OwnedMapDetails {
+                    name: details.name.to_owned(),
+                    crc: details.crc,
+                    sha256: details.sha256,
+                    url,
+                });
+            }
+            Control::MapData(data) => self.on_map_data(loop_, pid, data),
+            Control::ConReady => {
+                if let State::ConReady = self.state {
+                    self.state = State::ReadyToEnter;
+                    let mut ctx = self.context(loop_, pid);
+                    ctx.send(&P::start_info(&self.config.player));
+                } else {
+                    warn!("unexpected con ready");
+                }
+            }
+            Control::Snap(snap) => self.on_snap(loop_, pid, snap),
+            Control::InputTiming {
+                intended_tick: _,
+                time_left,
+            } => {
+                if time_left < 0 {
+                    self.tick_offset = cmp::min(self.tick_offset + 1, MAX_TICK_OFFSET);
+                } else if time_left > MAX_TIME_LEFT {
+                    self.tick_offset = cmp::max(self.tick_offset - 1, 1);
+                }
+            }
+        }
+    }
+    fn on_map_change<L: Loop>(&mut self, loop_: &mut L, pid: PeerId, map_change: MapChange) {
+        let size = match map_change.size.try_usize() {
+            Some(s) => s,
+            None => return self.error(loop_, pid, Error::InvalidMapSize(map_change.size)),
+        };
+        if !map::is_valid_name(map_change.name) {
+            return self.error(loop_, pid, Error::InvalidMapName);
+        }
+        let details = self
+            .map_details
+            .take()
+            .filter(|d| d.name == map_change.name && d.crc == map_change.crc);
+        let sha256 = map_change.sha256.or(details.as_ref().map(|d| d.sha256));
+        self.snaps.reset();
+        self.input = Default::default();
+        self.state = State::MapChange;
+        self.event(loop_, pid, Event::MapChange(map_change));
+        if self.disconnected {
+            return;
+        }
+
+        if !self
+            .handler
+            .need_file(map_change.name, map_change.crc, sha256)
+        {
+            self.state = State::ConReady;
+            self.request(loop_, pid, Request::Ready);
+            return;
+        }
+
+        let name = String::from_utf8_lossy(map_change.name);
+        if let Cow::Owned(..) = name {
+            warn!("weird characters in map name");
+        }
+        let filename = match sha256 {
+            Some(sha256) if self.config.content_addressed_maps => {
+                map::content_addressed_filename(sha256)
+            }
+            _ => map::filename(&name, map_change.crc, sha256),
+        };
+        let path = self.config.maps_directory.join(filename);
+        if path.exists() {
+            return self.map_ready(loop_, pid, path);
+        }
+        let map = MapFile {
+            path,
+            size,
+            crc: map_change.crc,
+            sha256,
+        };
+        let chunks_per_request = cmp::max(map_change.chunks_per_request, 1);
+        if let Some(url) = details.and_then(|d| d.url) {
+            if self.config.https_maps {
+                return self.fetch_map(loop_, pid, url, map, chunks_per_request);
+            }
+        }
+        self.download_map(loop_, pid, map, chunks_per_request);
+    }
+    fn download_map<L: Loop>(
+        &mut self,
+        loop_: &mut L,
+        pid: PeerId,
+        map: MapFile,
+        chunks_per_request: i32,
+    ) {
+        match self.new_download(map) {
+            Ok(download) => {
+                self.state = State::MapData {
+                    resuming: download.received() != 0,
+                    download: Box::new(download),
+                    chunks_per_request,
+                };
+                self.request(loop_, pid, Request::RequestMapData { chunk: 0 });
+            }
+            Err(e) => self.error(loop_, pid, map::Error::from(e).into()),
+        }
+    }
+    fn new_download(&self, map: MapFile) -> Result<Download, io::Error> {
+        let new = if self.config.resume_downloads {
+            Download::resume
+        } else {
+            Download::new
+        };
+        new(
+            &self.config.download_directory,
+            map.path,
+            map.size,
+            map.crc,
+            map.sha256,
+        )
+    }
+    #[cfg(feature = "https")]
+    fn fetch_map<L: Loop>(
+        &mut self,
+        loop_: &mut L,
+        pid: PeerId,
+        url: String,
+        map: MapFile,
+        chunks_per_request: i32,
+    ) {
+        let download = match self.new_download(map.clone()) {
+            Ok(d) => d,
+            Err(e) => return self.error(loop_, pid, map::Error::from(e).into()),
+        };
+        info!("downloading map from {}", url);
+        let (sender, result) = mpsc::channel();
+        thread::spawn(move || {
+            // The receiver is gone if the connection was closed in the
+            // meantime.
+            let _ = sender.send(map::fetch(&url, download));
+        });
+        self.state = State::MapHttps {
+            result,
+            map,
+            chunks_per_request,
+            next_poll: loop_.time() + HTTPS_POLL_INTERVAL,
+        };
+    }
+    #[cfg(not(feature = "https"))]
+    fn fetch_map<L: Loop>(
+        &mut self,
+        loop_: &mut L,
+        pid: PeerId,
+        url: String,
+        map: MapFile,
+        chunks_per_request: i32,
+    ) {
+        let _ = url;
+        self.download_map(loop_, pid, map, chunks_per_request);
+    }
+    fn poll_https<L: Loop>(&mut self, loop_: &mut L, pid: PeerId) {
+        let result = match self.state {
+            State::MapHttps {
+                ref result,
+                ref mut next_poll,
+                ..
+            } => {
+                if loop_.time() < *next_poll {
+                    return;
+                }
+                *next_poll = loop_.time() + HTTPS_POLL_INTERVAL;
+                match result.try_recv() {
+                    Ok(r) => r,
+                    Err(mpsc::TryRecvError::Empty) => return,
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        panic!("map download thread died")
+                    }
+                }
+            }
+            _ => return,
+        };
+        let (map, chunks_per_request) = match mem::replace(&mut self.state, State::MapChange) {
+            State::MapHttps {
+                map,
+                chunks_per_request,
+                ..
+            } => (map, chunks_per_request),
+            _ => unreachable!(),
+        };
+        match result {
+            Ok(path) => self.map_ready(loop_, pid, path),
+            Err(e) => {
+                warn!("https map download failed, downloading from server: {}", e);
+                self.download_map(loop_, pid, map, chunks_per_request);
+            }
+        }
+    }
+    fn on_map_data<L: Loop>(&mut self, loop_: &mut L, pid: PeerId, data: crate::protocol::MapData) {
+        let (download, chunks_per_request, resuming) = match self.state {
+            State::MapData {
+                ref mut download,
+                chunks_per_request,
+                ref mut resuming,
+            } => (download, chunks_per_request, resuming),
+            _ => {
+                warn!("unsolicited map data");
+                return;
+            }
+        };
+        if mem::replace(resuming, false) {
+            // The first chunk tells us the chunk size, keep the whole
+            // requests' worth of chunks we already have.
+            let (chunks, chunk_size) = match data.chunk {
+                Some(0) if !data.data.is_empty() => {
+                    let chunks = (download.received() / data.data.len()).assert_i32();
+                    (chunks - chunks % chunks_per_request, data.data.len())
+                }
+                // Without chunk numbers, there's no way to skip chunks.
+                _ => (0, 1),
+            };
+            if let Err(e) = download.truncate_chunks(chunks, chunk_size) {
+                return self.error(loop_, pid, map::Error::from(e).into());
+            }
+            if chunks != 0 {
+                info!("resuming map download at chunk {}", chunks);
+                if !download.is_complete() {
+                    self.request(loop_, pid, Request::RequestMapData { chunk: chunks });
+                    return;
+                }
+                return self.finish_download(loop_, pid);
+            }
+        }
+        if data.chunk.map(|c| c < download.chunks()).unwrap_or(false) {
+            // Chunk requested before resuming the download.
+            debug!("ignoring map data chunk={:?}", data.chunk);
+            return;
+        }
+        if data.chunk.map(|c| c != download.chunks()).unwrap_or(false)
+            || data.crc.map(|c| c != download.crc()).unwrap_or(false)
+        {
+            warn!(
+                "unexpected map data crc={:?} chunk={:?}, want crc={:08x} chunk={}",
+                data.crc,
+                data.chunk,
+                download.crc(),
+                download.chunks(),
+            );
+            return;
+        }
+        if let Err(e) = download.write(data.data) {
+            return self.error(loop_, pid, e.into());
+        }
+        let received = download.received();
+        let size = download.size();
+        let chunks = download.chunks();
+        let last = data.last.unwrap_or_else(|| download.is_complete());
+        self.event(loop_, pid, Event::MapDownload { received, size });
+        if self.disconnected {
+            return;
+        }
+        if !last {
+            if chunks % chunks_per_request == 0 {
+                self.request(loop_, pid, Request::RequestMapData { chunk: chunks });
+            }
+            return;
+        }
+        self.finish_download(loop_, pid);
+    }
+    fn finish_download<L: Loop>(&mut self, loop_: &mut L, pid: PeerId) {
+        let download = match mem::replace(&mut self.state, State::MapChange) {
+            State::MapData { download, .. } => download,
+            _ => unreachable!(),
+        };
+        match download.finish() {
+            Ok(path) => self.map_ready(loop_, pid, path),
+            Err(e) => self.error(loop_, pid, e.into()),
+        }
+    }
+    fn map_ready<L: Loop>(&mut self, loop_: &mut L, pid: PeerId, path: PathBuf) {
+        self.state = State::ConReady;
+        self.request(loop_, pid, Request::Ready);
+        self.event(loop_, pid, Event::MapReady(path));
+    }
+    fn on_ready_to_enter<L: Loop>(&mut self, loop_: &mut L, pid: PeerId) {
+        if let State::ReadyToEnter = self.state {
+            self.state = State::InGame;
+            self.request(loop_, pid, Request::EnterGame);
+            self.event(loop_, pid, Event::EnterGame);
+        } else {
+            warn!("unexpected ready to enter");
+        }
+    }
+    fn on_snap<L: Loop>(&mut self, loop_: &mut L, pid: PeerId, msg: SnapMsg) {
+        let obj_size = <P as traits::ProtocolStatic>::obj_size;
+        let tick = match msg {
+            SnapMsg::Snap(ref s) => s.tick,
+            SnapMsg::SnapEmpty(ref s) => s.tick,
+            SnapMsg::SnapSingle(ref s) => s.tick,
+        };
+        let res = match msg {
+            SnapMsg::Snap(s) => self.snaps.snap(&mut Log, obj_size, s),
+            SnapMsg::SnapEmpty(s) => self.snaps.snap_empty(&mut Log, obj_size, s),
+            SnapMsg::SnapSingle(s) => self.snaps.snap_single(&mut Log, obj_size, s),
+        };
+        let snap = match res {
+            Ok(Some(snap)) => snap,
+            Ok(None) => return,
+            Err(err) => {
+                warn!("snapshot error {:?}", err);
+                return;
+            }
+        };
+        if let State::InGame = self.state {
+            let mut ctx = Context::new(loop_, pid, self.disconnected);
+            if let Some(input) = self.handler.on_snapshot(&mut ctx, tick, snap) {
+                self.input = input;
+            }
+            self.disconnected = ctx.disconnected;
+        }
+        if self.disconnected {
+            return;
+        }
+        let ack_snapshot = self.snaps.ack_tick().unwrap_or(-1);
+        let mut ctx = self.context(loop_, pid);
+        // DDNet needs the `Input` message as the first chunk of the packet.
+        ctx.force_flush();
+        ctx.send(&P::request(Request::Input {
+            ack_snapshot,
+            intended_tick: ack_snapshot + self.tick_offset,
+            input: self.input,
+        }));
+    }
+}
+
+/// Client for any number of connections speaking the same protocol.
+///
+/// Run it by passing it to [`Loop::run`].
+pub struct Client<P: Protocol, H> {
+    peers: PeerMap<Peer<P, H>>,
+}
+
+impl<P: Protocol, H: Handler<P>> Default for Client<P, H> {
+    fn default() -> Client<P, H> {
+        Client::new()
+    }
+}
+
+impl<P: Protocol, H: Handler<P>> Client<P, H> {
+    pub fn new() -> Client<P, H> {
+        Client {
+            peers: PeerMap::new(),
+        }
+    }
+    /// Connects to a server, the connection is handled by `handler`.
+    pub fn connect<L: Loop>(
+        &mut self,
+        loop_: &mut L,
+        addr: Addr,
+        config: Config,
+        handler: H,
+    ) -> PeerId {
+        let pid = loop_.connect(addr);
+        self.peers.insert(pid, Peer::new(config, handler));
+        pid
+    }
+    /// Number of connections that are not disconnected yet.
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+    pub fn handler(&self, pid: PeerId) -> Option<&H> {
+        self.peers.get(pid).map(|p| &p.handler)
+    }
+    pub fn handler_mut(&mut self, pid: PeerId) -> Option<&mut H> {
+        self.peers.get_mut(pid).map(|p| &mut p.handler)
+    }
+}
+
+impl<P: Protocol, H: Handler<P>, L: Loop> Application<L> for Client<P, H> {
+    fn needs_tick(&mut self) -> Timeout {
+        self.peers
+            .iter_mut()
+            .filter(|(_, p)| !p.disconnected)
+            .map(|(_, p)| p.needs_tick())
+            .min()
+            .unwrap_or_default()
+    }
+    fn on_tick(&mut self, loop_: &mut L) {
+        for (pid, peer) in self.peers.iter_mut() {
+            peer.tick(loop_, pid);
+        }
+    }
+    fn on_packet(&mut self, loop_: &mut L, chunk: Chunk) {
+        if let Some(peer) = self.peers.get_mut(chunk.pid) {
+            peer.on_packet(loop_, chunk.pid, chunk.data);
+        }
+    }
+    fn on_connless_packet(&mut self, _: &mut L, chunk: ConnlessChunk) {
+        warn!(
+            "connless packet {} {:?}",
+            chunk.addr,
+            pretty::Bytes::new(chunk.data)
+        );
+    }
+    fn on_connect(&mut self, _: &mut L, pid: PeerId) {
+        warn!("unexpected incoming connection pid={:?}", pid);
+    }
+    fn on_ready(&mut self, loop_: &mut L, pid: PeerId) {
+        if let Some(peer) = self.peers.get_mut(pid) {
+            peer.on_ready(loop_, pid);
+        }
+    }
+    fn on_disconnect(&mut self, loop_: &mut L, pid: PeerId, remote: bool, reason: &[u8]) {
+        if let Some(peer) = self.peers.get_mut(pid) {
+            peer.disconnected = true;
+            peer.event(loop_, pid, Event::Disconnected { remote, reason });
+            self.peers.remove(pid);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Protocol-generic game client on top of `libtw2-event-loop`.
+//!
+//! [`Client`] connects to servers, downloads and verifies their maps, enters
+//! the game, receives snapshots and sends inputs. What to do with the
+//! connection is decided by a [`Handler`] that receives typed [`Event`]s and
+//! the decoded snapshots.
+//!
+//! The state machine works with the messages of the Teeworlds 0.6, DDNet and
+//! Teeworlds 0.7 protocol crates, see [`Protocol`]. Use
+//! [`libtw2_event_loop::SocketLoop`] for the Teeworlds 0.6 transport, which
+//! DDNet shares, and [`libtw2_event_loop::SocketLoop7`] for the Teeworlds 0.7
+//! one.
+//!
+//! With the `https` feature, maps that the server offers over HTTPS are
+//! fetched from there instead of through the game connection.
+
+#[macro_use]
+extern crate log;
+
+pub mod map;
+pub mod protocol;
+
+mod client;
+
+pub use self::client::Client;
+pub use self::client::Config;
+pub use self::client::Context;
+pub use self::client::Event;
+pub use self::client::Handler;
+pub use self::protocol::PlayerInfo;
+pub use self::protocol::Protocol;
+
+use libtw2_gamenet_common::traits::SnapObj as _;
+use libtw2_packer::IntUnpacker;
+use libtw2_snapshot::format::Item as SnapItem;
+use libtw2_snapshot::Snap;
+use libtw2_warn as warn;
+use std::error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidMapName,
+    InvalidMapSize(i32),
+    Map(map::Error),
+}
+
+impl From<map::Error> for Error {
+    fn from(err: map::Error) -> Error {
+        Error::Map(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidMapName => f.write_str("invalid map name"),
+            Error::InvalidMapSize(size) => write!(f, "invalid map size {}", size),
+            Error::Map(ref e) => e.fmt(f),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+#[derive(Debug)]
+struct WarnSnap<'a>(SnapItem<'a>);
+
+impl<'a, W: fmt::Debug> warn::Warn<W> for WarnSnap<'a> {
+    fn warn(&mut self, w: W) {
+        warn!("{:?} for {:?}", w, self.0);
+    }
+}
+
+/// Decodes the objects of a snapshot, returning their IDs along with them.
+///
+/// Objects that fail to decode are logged and skipped.
+pub fn snap_objects<P: Protocol>(snap: &Snap) -> impl Iterator<Item = (u16, P::SnapObj)> + '_ {
+    snap.items().filter_map(|item| {
+        match P::SnapObj::decode_obj(
+            &mut WarnSnap(item),
+            item.type_id,
+            &mut IntUnpacker::new(item.data),
+        ) {
+            Ok(obj) => Some((item.id, obj)),
+            Err(e) => {
+                warn!("item decode error {:?}: {:?}", e, item);
+                None
+            }
+        }
+    })
+}
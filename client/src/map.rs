@@ -0,0 +1,476 @@
+//! Verified map downloads.
+
+use libtw2_common::digest::Sha256;
+use libtw2_common::num::Cast;
+use sha2::Digest;
+use std::cmp;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use tempfile::NamedTempFile;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    #[cfg(feature = "https")]
+    Http(reqwest::Error),
+    /// More or less data than announced was received.
+    SizeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    CrcMismatch {
+        expected: i32,
+        actual: i32,
+    },
+    Sha256Mismatch {
+        expected: Sha256,
+        actual: Sha256,
+    },
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+#[cfg(feature = "https")]
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Error {
+        Error::Http(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => e.fmt(f),
+            #[cfg(feature = "https")]
+            Error::Http(ref e) => e.fmt(f),
+            Error::SizeMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "map size mismatch, expected {}, got {}",
+                    expected, actual
+                )
+            }
+            Error::CrcMismatch { expected, actual } => write!(
+                f,
+                "map crc mismatch, expected {:08x}, got {:08x}",
+                expected, actual
+            ),
+            Error::Sha256Mismatch { expected, actual } => write!(
+                f,
+                "map sha256 mismatch, expected {}, got {}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Checks whether a map name received from a server can be used as part of a
+/// file name.
+pub fn is_valid_name(name: &[u8]) -> bool {
+    !name.is_empty() && !name.iter().any(|&b| b == b'/' || b == b'\\' || b == 0)
+}
+
+/// File name under which a map is stored.
+///
+/// Maps are identified by their SHA256 if it is known and by their CRC
+/// otherwise.
+pub fn filename(name: &str, crc: i32, sha256: Option<Sha256>) -> String {
+    match sha256 {
+        Some(sha256) => format!("{}_{}.map", name, sha256),
+        None => format!("{}_{:08x}.map", name, crc),
+    }
+}
+
+/// File name under which a map is stored if maps are stored by their
+/// content.
+///
+/// Identical maps served under different names share the same file.
+pub fn content_addressed_filename(sha256: Sha256) -> String {
+    format!("{}.map", sha256)
+}
+
+/// Checks whether a map download URL received from a server should be
+/// used.
+pub fn is_valid_url(url: &[u8]) -> bool {
+    url.starts_with(b"https://") && url.iter().all(|&b| b.is_ascii_graphic())
+}
+
+/// File name of the incomplete data of a resumable download to `path`.
+fn partial_filename(path: &Path) -> String {
+    let name = path.file_name().unwrap_or_default();
+    format!("{}.part", name.to_string_lossy())
+}
+
+/// Where the data of a download is written to.
+enum Partial {
+    /// Deleted if the download isn't finished.
+    Temporary(NamedTempFile),
+    /// Kept so that the download can be resumed later.
+    Kept(File, PathBuf),
+}
+
+impl Partial {
+    fn file(&mut self) -> &mut File {
+        match *self {
+            Partial::Temporary(ref mut f) => f.as_file_mut(),
+            Partial::Kept(ref mut f, _) => f,
+        }
+    }
+}
+
+/// A map download in progress.
+///
+/// The data is written to a temporary file that is only moved to its final
+/// location once its size, CRC and, if known, SHA256 have been verified.
+pub struct Download {
+    partial: Partial,
+    path: PathBuf,
+    size: usize,
+    crc: i32,
+    sha256: Option<Sha256>,
+    received: usize,
+    chunks: i32,
+    crc_hasher: crc32fast::Hasher,
+    sha256_hasher: sha2::Sha256,
+}
+
+impl Download {
+    /// Starts a download to `path`, using a temporary file in
+    /// `download_directory`.
+    ///
+    /// `download_directory` should be on the same file system as `path`.
+    pub fn new(
+        download_directory: &Path,
+        path: PathBuf,
+        size: usize,
+        crc: i32,
+        sha256: Option<Sha256>,
+    ) -> Result<Download, io::Error> {
+        let prefix = path
+            .file_stem()
+            .map(|s| format!("{}_", s.to_string_lossy()))
+            .unwrap_or_default();
+        Ok(Download {
+            partial: Partial::Temporary(
+                tempfile::Builder::new()
+                    .prefix(&prefix)
+                    .suffix(".map")
+                    .tempfile_in(download_directory)?,
+            ),
+            path,
+            size,
+            crc,
+            sha256,
+            received: 0,
+            chunks: 0,
+            crc_hasher: crc32fast::Hasher::new(),
+            sha256_hasher: sha2::Sha256::new(),
+        })
+    }
+    /// Starts a download to `path` like `Download::new`, but keeps the
+    /// incomplete data in `download_directory` if the download is
+    /// interrupted.
+    ///
+    /// Data left over from a previous attempt is continued, see
+    /// `received`, `truncate_chunks` and `restart`.
+    pub fn resume(
+        download_directory: &Path,
+        path: PathBuf,
+        size: usize,
+        crc: i32,
+        sha256: Option<Sha256>,
+    ) -> Result<Download, io::Error> {
+        let partial = download_directory.join(partial_filename(&path));
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&partial)?;
+        let len = file.metadata()?.len();
+        let mut result = Download {
+            partial: Partial::Kept(file, partial),
+            path,
+            size,
+            crc,
+            sha256,
+            received: 0,
+            chunks: 0,
+            crc_hasher: crc32fast::Hasher::new(),
+            sha256_hasher: sha2::Sha256::new(),
+        };
+        // Too much data can't belong to this map.
+        let len = len.try_usize().filter(|&l| l <= size).unwrap_or(0);
+        result.rehash(len)?;
+        Ok(result)
+    }
+    /// Keeps the first `len` bytes of the downloaded data, recomputing the
+    /// checksums.
+    fn rehash(&mut self, len: usize) -> Result<(), io::Error> {
+        self.crc_hasher = crc32fast::Hasher::new();
+        self.sha256_hasher = sha2::Sha256::new();
+        self.received = 0;
+        let file = self.partial.file();
+        file.set_len(len.u64())?;
+        file.seek(SeekFrom::Start(0))?;
+        let mut buffer = [0; 16384];
+        while self.received < len {
+            let read = cmp::min(buffer.len(), len - self.received);
+            file.read_exact(&mut buffer[..read])?;
+            self.crc_hasher.update(&buffer[..read]);
+            self.sha256_hasher.update(&buffer[..read]);
+            self.received += read;
+        }
+        Ok(())
+    }
+    /// Discards the downloaded data.
+    pub fn restart(&mut self) -> Result<(), io::Error> {
+        self.chunks = 0;
+        self.rehash(0)
+    }
+    /// Keeps only the data of the first `chunks` chunks of `chunk_size`
+    /// bytes, so that the download can continue at chunk `chunks`.
+    ///
+    /// Used when resuming a download, since the chunk size is only known
+    /// after receiving data.
+    pub fn truncate_chunks(&mut self, chunks: i32, chunk_size: usize) -> Result<(), io::Error> {
+        let len = chunks.assert_usize() * chunk_size;
+        assert!(len <= self.received);
+        self.rehash(len)?;
+        self.chunks = chunks;
+        Ok(())
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    pub fn size(&self) -> usize {
+        self.size
+    }
+    pub fn crc(&self) -> i32 {
+        self.crc
+    }
+    /// Number of bytes received so far.
+    pub fn received(&self) -> usize {
+        self.received
+    }
+    /// Number of chunks received so far.
+    pub fn chunks(&self) -> i32 {
+        self.chunks
+    }
+    pub fn is_complete(&self) -> bool {
+        self.received >= self.size
+    }
+    pub fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        if self.received + data.len() > self.size {
+            return Err(Error::SizeMismatch {
+                expected: self.size,
+                actual: self.received + data.len(),
+            });
+        }
+        self.partial.file().write_all(data)?;
+        self.crc_hasher.update(data);
+        self.sha256_hasher.update(data);
+        self.received += data.len();
+        self.chunks += 1;
+        Ok(())
+    }
+    /// Verifies the downloaded map and moves it to its final location.
+    ///
+    /// The data of a resumable download is deleted if it doesn't match.
+    pub fn finish(self) -> Result<PathBuf, Error> {
+        if let Err(e) = self.verify() {
+            if let Partial::Kept(_, ref partial) = self.partial {
+                let _ = fs::remove_file(partial);
+            }
+            return Err(e);
+        }
+        match self.partial {
+            Partial::Temporary(file) => {
+                file.persist(&self.path).map_err(|e| e.error)?;
+            }
+            Partial::Kept(file, partial) => {
+                drop(file);
+                fs::rename(partial, &self.path)?;
+            }
+        }
+        Ok(self.path)
+    }
+    fn verify(&self) -> Result<(), Error> {
+        if self.received != self.size {
+            return Err(Error::SizeMismatch {
+                expected: self.size,
+                actual: self.received,
+            });
+        }
+        let crc = self.crc_hasher.clone().finalize() as i32;
+        if crc != self.crc {
+            return Err(Error::CrcMismatch {
+                expected: self.crc,
+                actual: crc,
+            });
+        }
+        if let Some(expected) = self.sha256 {
+            let actual = Sha256(self.sha256_hasher.clone().finalize().into());
+            if actual != expected {
+                return Err(Error::Sha256Mismatch { expected, actual });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Downloads a map over HTTPS, blocking until it's complete.
+///
+/// The map is verified as in `Download::finish`. Resumed downloads only
+/// request the missing data if the web server supports it.
+#[cfg(feature = "https")]
+pub fn fetch(url: &str, mut download: Download) -> Result<PathBuf, Error> {
+    if download.is_complete() {
+        return download.finish();
+    }
+    let mut request = reqwest::blocking::Client::new().get(url);
+    let resume = download.received() != 0;
+    if resume {
+        let range = format!("bytes={}-", download.received());
+        request = request.header(reqwest::header::RANGE, range);
+    }
+    let mut response = request.send()?.error_for_status()?;
+    if resume && response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+        download.restart()?;
+    }
+    let mut buffer = [0; 16384];
+    loop {
+        let read = match response.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(r) => r,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        download.write(&buffer[..read])?;
+    }
+    download.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::content_addressed_filename;
+    use super::filename;
+    use super::is_valid_url;
+    use super::Download;
+    use super::Error;
+    use libtw2_common::digest::Sha256;
+    use std::fs;
+
+    const DATA: &[u8] = b"123456789";
+    const CRC: i32 = 0xcbf43926u32 as i32;
+    const SHA256: &str = "15e2b0d3c33891ebb0f1ef609ec419420c20e320ce94c65fbc8c3312448eb225";
+
+    fn download(dir: &tempfile::TempDir, sha256: Option<Sha256>) -> Download {
+        let path = dir.path().join(filename("test", CRC, sha256));
+        Download::new(dir.path(), path, DATA.len(), CRC, sha256).unwrap()
+    }
+
+    #[test]
+    fn success() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut d = download(&dir, Some(SHA256.parse().unwrap()));
+        d.write(&DATA[..4]).unwrap();
+        assert!(!d.is_complete());
+        d.write(&DATA[4..]).unwrap();
+        assert!(d.is_complete());
+        assert_eq!(d.chunks(), 2);
+        let path = d.finish().unwrap();
+        assert_eq!(path, dir.path().join(format!("test_{}.map", SHA256)));
+        assert_eq!(fs::read(path).unwrap(), DATA);
+    }
+
+    #[test]
+    fn resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(filename("test", CRC, None));
+        let resume = || Download::resume(dir.path(), path.clone(), DATA.len(), CRC, None).unwrap();
+        let part = dir.path().join("test_cbf43926.map.part");
+
+        let mut d = resume();
+        assert_eq!(d.received(), 0);
+        d.write(&DATA[..7]).unwrap();
+        drop(d);
+        assert_eq!(fs::read(&part).unwrap(), &DATA[..7]);
+
+        let mut d = resume();
+        assert_eq!(d.received(), 7);
+        d.truncate_chunks(2, 3).unwrap();
+        assert_eq!((d.received(), d.chunks()), (6, 2));
+        d.write(&DATA[6..]).unwrap();
+        assert_eq!(d.finish().unwrap(), path);
+        assert_eq!(fs::read(&path).unwrap(), DATA);
+        assert!(!part.exists());
+
+        fs::write(&part, b"987").unwrap();
+        let mut d = resume();
+        d.write(&DATA[3..]).unwrap();
+        assert!(matches!(d.finish(), Err(Error::CrcMismatch { .. })));
+        assert!(!part.exists());
+
+        fs::write(&part, b"987").unwrap();
+        let mut d = resume();
+        d.restart().unwrap();
+        d.write(DATA).unwrap();
+        d.finish().unwrap();
+    }
+
+    #[test]
+    fn names() {
+        let sha256 = SHA256.parse().unwrap();
+        assert_eq!(filename("test", CRC, None), "test_cbf43926.map");
+        assert_eq!(
+            content_addressed_filename(sha256),
+            format!("{}.map", SHA256)
+        );
+        assert!(is_valid_url(b"https://maps.ddnet.org/test.map"));
+        assert!(!is_valid_url(b"http://maps.ddnet.org/test.map"));
+        assert!(!is_valid_url(b"https://maps.ddnet.org/te st.map"));
+        assert!(!is_valid_url(b""));
+    }
+
+    #[test]
+    fn mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut d = download(&dir, None);
+        d.write(b"12345678").unwrap();
+        assert!(matches!(d.finish(), Err(Error::SizeMismatch { .. })));
+
+        let mut d = download(&dir, None);
+        assert!(matches!(
+            d.write(b"1234567890"),
+            Err(Error::SizeMismatch { .. })
+        ));
+        drop(d);
+
+        let mut d = download(&dir, None);
+        d.write(b"123456780").unwrap();
+        assert!(matches!(d.finish(), Err(Error::CrcMismatch { .. })));
+
+        let mut d = download(&dir, Some(Sha256([0; 32])));
+        d.write(DATA).unwrap();
+        assert!(matches!(d.finish(), Err(Error::Sha256Mismatch { .. })));
+
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,387 @@
+//! Version-specific parts of joining a game server.
+//!
+//! The generated protocol crates differ in the exact shape of the messages
+//! needed to connect, download the map and enter the game. [`Protocol`] maps
+//! them to the version-independent types of this module.
+
+use libtw2_common::digest::Sha256;
+use libtw2_common::num::Cast;
+use libtw2_gamenet_common::traits;
+use libtw2_gamenet_snap::SnapMsg;
+use std::fmt;
+use std::mem;
+
+pub type System<'a, P> = <P as traits::Protocol<'a>>::System;
+pub type Game<'a, P> = <P as traits::Protocol<'a>>::Game;
+
+/// Player information sent to the server before entering the game.
+#[derive(Clone, Debug)]
+pub struct PlayerInfo {
+    pub name: Vec<u8>,
+    pub clan: Vec<u8>,
+    pub country: i32,
+    /// Skin name, ignored by Teeworlds 0.7 which always uses the default
+    /// skin parts.
+    pub skin: Vec<u8>,
+}
+
+impl PlayerInfo {
+    pub fn new(name: &[u8]) -> PlayerInfo {
+        PlayerInfo {
+            name: name.to_owned(),
+            clan: Vec::new(),
+            country: -1,
+            skin: b"default".to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MapChange<'a> {
+    pub name: &'a [u8],
+    pub crc: i32,
+    pub size: i32,
+    /// Only sent by Teeworlds 0.7, DDNet sends it in a separate `MapDetails`
+    /// message.
+    pub sha256: Option<Sha256>,
+    /// Number of `MapData` messages sent in response to each
+    /// `RequestMapData` message.
+    pub chunks_per_request: i32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MapDetails<'a> {
+    pub name: &'a [u8],
+    pub crc: i32,
+    pub sha256: Sha256,
+    /// HTTPS URL the map can be downloaded from instead, sent by DDNet
+    /// servers that have one configured.
+    pub url: Option<&'a [u8]>,
+}
+
+/// Map chunk.
+///
+/// Teeworlds 0.7 doesn't number its chunks and doesn't mark the last one, the
+/// download is finished once the announced map size has been received.
+#[derive(Clone, Copy, Debug)]
+pub struct MapData<'a> {
+    pub crc: Option<i32>,
+    pub chunk: Option<i32>,
+    pub last: Option<bool>,
+    pub data: &'a [u8],
+}
+
+/// System messages driving the client's state machine.
+#[derive(Clone, Copy, Debug)]
+pub enum Control<'a> {
+    MapChange(MapChange<'a>),
+    MapDetails(MapDetails<'a>),
+    MapData(MapData<'a>),
+    ConReady,
+    Snap(SnapMsg<'a>),
+    InputTiming { intended_tick: i32, time_left: i32 },
+}
+
+/// System messages sent by the client's state machine.
+#[derive(Clone, Copy, Debug)]
+pub enum Request<'a, I> {
+    Info {
+        password: &'a [u8],
+    },
+    /// `chunk` is ignored by Teeworlds 0.7 which sends the next chunks.
+    RequestMapData {
+        chunk: i32,
+    },
+    Ready,
+    EnterGame,
+    Input {
+        ack_snapshot: i32,
+        intended_tick: i32,
+        input: I,
+    },
+}
+
+/// A protocol version the client can speak.
+///
+/// Implemented for the `Protocol` types of the Teeworlds 0.6, DDNet and
+/// Teeworlds 0.7 crates.
+pub trait Protocol: for<'a> traits::Protocol<'a> + 'static {
+    type PlayerInput: Copy + Default + fmt::Debug;
+    fn control<'a>(msg: &System<'a, Self>) -> Option<Control<'a>>;
+    fn is_ready_to_enter<'a>(msg: &Game<'a, Self>) -> bool;
+    fn request<'a>(request: Request<'a, Self::PlayerInput>) -> System<'a, Self>;
+    fn start_info<'a>(player: &'a PlayerInfo) -> Game<'a, Self>;
+}
+
+fn input_size<I>() -> i32 {
+    mem::size_of::<I>().assert_i32()
+}
+
+mod teeworlds_0_6 {
+    use super::Control;
+    use super::MapChange;
+    use super::MapData;
+    use super::PlayerInfo;
+    use super::Request;
+    use libtw2_gamenet_snap::SnapMsg;
+    use libtw2_gamenet_teeworlds_0_6::enums::VERSION;
+    use libtw2_gamenet_teeworlds_0_6::msg::game;
+    use libtw2_gamenet_teeworlds_0_6::msg::system;
+    use libtw2_gamenet_teeworlds_0_6::msg::Game;
+    use libtw2_gamenet_teeworlds_0_6::msg::System;
+    use libtw2_gamenet_teeworlds_0_6::snap_obj::PlayerInput;
+    use libtw2_gamenet_teeworlds_0_6::Protocol;
+
+    impl super::Protocol for Protocol {
+        type PlayerInput = PlayerInput;
+        fn control<'a>(msg: &super::System<'a, Self>) -> Option<Control<'a>> {
+            Some(match *msg {
+                System::MapChange(m) => Control::MapChange(MapChange {
+                    name: m.name,
+                    crc: m.crc,
+                    size: m.size,
+                    sha256: None,
+                    chunks_per_request: 1,
+                }),
+                System::MapData(m) => Control::MapData(MapData {
+                    crc: Some(m.crc),
+                    chunk: Some(m.chunk),
+                    last: Some(m.last != 0),
+                    data: m.data,
+                }),
+                System::ConReady(_) => Control::ConReady,
+                System::Snap(s) => Control::Snap(SnapMsg::Snap(s)),
+                System::SnapEmpty(s) => Control::Snap(SnapMsg::SnapEmpty(s)),
+                System::SnapSingle(s) => Control::Snap(SnapMsg::SnapSingle(s)),
+                System::InputTiming(t) => Control::InputTiming {
+                    intended_tick: t.input_pred_tick,
+                    time_left: t.time_left,
+                },
+                _ => return None,
+            })
+        }
+        fn is_ready_to_enter<'a>(msg: &super::Game<'a, Self>) -> bool {
+            matches!(*msg, Game::SvReadyToEnter(_))
+        }
+        fn request<'a>(request: Request<'a, PlayerInput>) -> System<'a> {
+            match request {
+                Request::Info { password } => System::from(system::Info {
+                    version: VERSION.as_bytes(),
+                    password: Some(password),
+                }),
+                Request::RequestMapData { chunk } => System::from(system::RequestMapData { chunk }),
+                Request::Ready => System::from(system::Ready),
+                Request::EnterGame => System::from(system::EnterGame),
+                Request::Input {
+                    ack_snapshot,
+                    intended_tick,
+                    input,
+                } => System::from(system::Input {
+                    ack_snapshot,
+                    intended_tick,
+                    input_size: super::input_size::<PlayerInput>(),
+                    input,
+                }),
+            }
+        }
+        fn start_info<'a>(player: &'a PlayerInfo) -> Game<'a> {
+            Game::from(game::ClStartInfo {
+                name: &player.name,
+                clan: &player.clan,
+                country: player.country,
+                skin: &player.skin,
+                use_custom_color: false,
+                color_body: 0,
+                color_feet: 0,
+            })
+        }
+    }
+}
+
+mod ddnet {
+    use super::Control;
+    use super::MapChange;
+    use super::MapData;
+    use super::MapDetails;
+    use super::PlayerInfo;
+    use super::Request;
+    use libtw2_gamenet_ddnet::enums::VERSION;
+    use libtw2_gamenet_ddnet::msg::game;
+    use libtw2_gamenet_ddnet::msg::system;
+    use libtw2_gamenet_ddnet::msg::Game;
+    use libtw2_gamenet_ddnet::msg::System;
+    use libtw2_gamenet_ddnet::snap_obj::PlayerInput;
+    use libtw2_gamenet_ddnet::Protocol;
+    use libtw2_gamenet_snap::SnapMsg;
+
+    impl super::Protocol for Protocol {
+        type PlayerInput = PlayerInput;
+        fn control<'a>(msg: &super::System<'a, Self>) -> Option<Control<'a>> {
+            Some(match *msg {
+                System::MapChange(m) => Control::MapChange(MapChange {
+                    name: m.name,
+                    crc: m.crc,
+                    size: m.size,
+                    sha256: None,
+                    chunks_per_request: 1,
+                }),
+                System::MapDetails(m) => Control::MapDetails(MapDetails {
+                    name: m.name,
+                    crc: m.crc,
+                    sha256: m.sha256,
+                    url: m.url.filter(|u| !u.is_empty()),
+                }),
+                System::MapData(m) => Control::MapData(MapData {
+                    crc: Some(m.crc),
+                    chunk: Some(m.chunk),
+                    last: Some(m.last != 0),
+                    data: m.data,
+                }),
+                System::ConReady(_) => Control::ConReady,
+                System::Snap(s) => Control::Snap(SnapMsg::Snap(s)),
+                System::SnapEmpty(s) => Control::Snap(SnapMsg::SnapEmpty(s)),
+                System::SnapSingle(s) => Control::Snap(SnapMsg::SnapSingle(s)),
+                System::InputTiming(t) => Control::InputTiming {
+                    intended_tick: t.input_pred_tick,
+                    time_left: t.time_left,
+                },
+                _ => return None,
+            })
+        }
+        fn is_ready_to_enter<'a>(msg: &super::Game<'a, Self>) -> bool {
+            matches!(*msg, Game::SvReadyToEnter(_))
+        }
+        fn request<'a>(request: Request<'a, PlayerInput>) -> System<'a> {
+            match request {
+                Request::Info { password } => System::from(system::Info {
+                    version: VERSION.as_bytes(),
+                    password: Some(password),
+                }),
+                Request::RequestMapData { chunk } => System::from(system::RequestMapData { chunk }),
+                Request::Ready => System::from(system::Ready),
+                Request::EnterGame => System::from(system::EnterGame),
+                Request::Input {
+                    ack_snapshot,
+                    intended_tick,
+                    input,
+                } => System::from(system::Input {
+                    ack_snapshot,
+                    intended_tick,
+                    input_size: super::input_size::<PlayerInput>(),
+                    input,
+                }),
+            }
+        }
+        fn start_info<'a>(player: &'a PlayerInfo) -> Game<'a> {
+            Game::from(game::ClStartInfo {
+                name: &player.name,
+                clan: &player.clan,
+                country: player.country,
+                skin: &player.skin,
+                use_custom_color: false,
+                color_body: 0,
+                color_feet: 0,
+            })
+        }
+    }
+}
+
+mod teeworlds_0_7 {
+    use super::Control;
+    use super::MapChange;
+    use super::MapData;
+    use super::PlayerInfo;
+    use super::Request;
+    use libtw2_gamenet_snap as snap;
+    use libtw2_gamenet_snap::SnapMsg;
+    use libtw2_gamenet_teeworlds_0_7::enums::VERSION;
+    use libtw2_gamenet_teeworlds_0_7::msg::game;
+    use libtw2_gamenet_teeworlds_0_7::msg::system;
+    use libtw2_gamenet_teeworlds_0_7::msg::Game;
+    use libtw2_gamenet_teeworlds_0_7::msg::System;
+    use libtw2_gamenet_teeworlds_0_7::snap_obj::PlayerInput;
+    use libtw2_gamenet_teeworlds_0_7::Protocol;
+
+    /// Client version sent in the `Info` message, Teeworlds 0.7.5.
+    const CLIENT_VERSION: i32 = 0x0705;
+
+    impl super::Protocol for Protocol {
+        type PlayerInput = PlayerInput;
+        fn control<'a>(msg: &super::System<'a, Self>) -> Option<Control<'a>> {
+            Some(match *msg {
+                System::MapChange(m) => Control::MapChange(MapChange {
+                    name: m.name,
+                    crc: m.crc,
+                    size: m.size,
+                    sha256: Some(m.sha256),
+                    chunks_per_request: m.num_response_chunks_per_request,
+                }),
+                System::MapData(m) => Control::MapData(MapData {
+                    crc: None,
+                    chunk: None,
+                    last: None,
+                    data: m.data,
+                }),
+                System::ConReady(_) => Control::ConReady,
+                System::Snap(s) => Control::Snap(SnapMsg::Snap(snap::Snap {
+                    tick: s.tick,
+                    delta_tick: s.delta_tick,
+                    num_parts: s.num_parts,
+                    part: s.part,
+                    crc: s.crc,
+                    data: s.data,
+                })),
+                System::SnapEmpty(s) => Control::Snap(SnapMsg::SnapEmpty(snap::SnapEmpty {
+                    tick: s.tick,
+                    delta_tick: s.delta_tick,
+                })),
+                System::SnapSingle(s) => Control::Snap(SnapMsg::SnapSingle(snap::SnapSingle {
+                    tick: s.tick,
+                    delta_tick: s.delta_tick,
+                    crc: s.crc,
+                    data: s.data,
+                })),
+                System::InputTiming(t) => Control::InputTiming {
+                    intended_tick: t.input_pred_tick,
+                    time_left: t.time_left,
+                },
+                _ => return None,
+            })
+        }
+        fn is_ready_to_enter<'a>(msg: &super::Game<'a, Self>) -> bool {
+            matches!(*msg, Game::SvReadyToEnter(_))
+        }
+        fn request<'a>(request: Request<'a, PlayerInput>) -> System<'a> {
+            match request {
+                Request::Info { password } => System::from(system::Info {
+                    version: VERSION.as_bytes(),
+                    password: Some(password),
+                    client_version: Some(CLIENT_VERSION),
+                }),
+                Request::RequestMapData { chunk: _ } => System::from(system::RequestMapData),
+                Request::Ready => System::from(system::Ready),
+                Request::EnterGame => System::from(system::EnterGame),
+                Request::Input {
+                    ack_snapshot,
+                    intended_tick,
+                    input,
+                } => System::from(system::Input {
+                    ack_snapshot,
+                    intended_tick,
+                    input_size: super::input_size::<PlayerInput>(),
+                    input,
+                }),
+            }
+        }
+        fn start_info<'a>(player: &'a PlayerInfo) -> Game<'a> {
+            Game::from(game::ClStartInfo {
+                name: &player.name,
+                clan: &player.clan,
+                country: player.country,
+                skin_part_names: [b"standard", b"", b"", b"standard", b"standard", b"standard"],
+                use_custom_colors: [false; 6],
+                skin_part_colors: [0; 6],
+            })
+        }
+    }
+}
@@ -0,0 +1,377 @@
+use libtw2_client::Client;
+use libtw2_client::Config;
+use libtw2_client::Context;
+use libtw2_client::Event;
+use libtw2_client::Handler;
+use libtw2_client::PlayerInfo;
+use libtw2_common::digest::Sha256;
+use libtw2_event_loop::Addr;
+use libtw2_event_loop::Application;
+use libtw2_event_loop::Chunk;
+use libtw2_event_loop::Loop;
+use libtw2_event_loop::PeerId;
+use libtw2_event_loop::Timestamp;
+use libtw2_gamenet_common::traits::MessageExt;
+use libtw2_gamenet_ddnet::msg;
+use libtw2_gamenet_ddnet::msg::game;
+use libtw2_gamenet_ddnet::msg::system;
+use libtw2_gamenet_ddnet::msg::Game;
+use libtw2_gamenet_ddnet::msg::System;
+use libtw2_gamenet_ddnet::msg::SystemOrGame;
+use libtw2_gamenet_ddnet::snap_obj::PlayerInput;
+use libtw2_gamenet_ddnet::Protocol;
+use libtw2_packer::with_packer;
+use libtw2_packer::Unpacker;
+use libtw2_snapshot::Snap;
+use libtw2_warn::Panic;
+use std::fs;
+use std::mem;
+
+const PID: PeerId = PeerId(0);
+const MAP: &[u8] = b"123456789";
+const MAP_CRC: i32 = 0xcbf43926u32 as i32;
+const MAP_SHA256: &str = "15e2b0d3c33891ebb0f1ef609ec419420c20e320ce94c65fbc8c3312448eb225";
+
+#[derive(Default)]
+struct MockLoop {
+    sent: Vec<Vec<u8>>,
+    disconnected: Option<Vec<u8>>,
+}
+
+impl MockLoop {
+    fn take_sent(&mut self) -> Vec<String> {
+        mem::take(&mut self.sent)
+            .iter()
+            .map(|data| {
+                let msg = msg::decode(&mut Panic, &mut Unpacker::new(data)).unwrap();
+                match msg {
+                    SystemOrGame::System(m) => format!("{:?}", m),
+                    SystemOrGame::Game(m) => format!("{:?}", m),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Loop for MockLoop {
+    fn client() -> MockLoop {
+        MockLoop::default()
+    }
+    fn run<A: Application<MockLoop>>(self, _: A) {
+        unimplemented!();
+    }
+    fn time(&mut self) -> Timestamp {
+        Timestamp::from_secs_since_epoch(0)
+    }
+    fn connect(&mut self, _: Addr) -> PeerId {
+        PID
+    }
+    fn peer_addr(&mut self, _: PeerId) -> Addr {
+        unimplemented!();
+    }
+    fn disconnect(&mut self, pid: PeerId, reason: &[u8]) {
+        assert_eq!(pid, PID);
+        assert!(self.disconnected.is_none());
+        self.disconnected = Some(reason.to_owned());
+    }
+    fn send_connless(&mut self, _: Addr, _: &[u8]) {
+        unimplemented!();
+    }
+    fn send(&mut self, chunk: Chunk) {
+        assert_eq!(chunk.pid, PID);
+        assert!(chunk.vital);
+        self.sent.push(chunk.data.to_owned());
+    }
+    fn force_flush(&mut self, _: PeerId) {}
+    fn flush(&mut self, _: PeerId) {}
+}
+
+#[derive(Default)]
+struct Recorder {
+    events: Vec<String>,
+    skip_maps: bool,
+}
+
+impl Handler<Protocol> for Recorder {
+    fn on_event<L: Loop>(&mut self, _: &mut Context<L, Protocol>, event: Event<Protocol>) {
+        self.events.push(match event {
+            Event::MapDownload { .. } => "MapDownload".to_owned(),
+            Event::MapReady(_) => "MapReady".to_owned(),
+            Event::Error(_) => "Error".to_owned(),
+            e => format!("{:?}", e),
+        });
+    }
+    fn need_file(&mut self, _: &[u8], _: i32, _: Option<Sha256>) -> bool {
+        !self.skip_maps
+    }
+    fn on_snapshot<L: Loop>(
+        &mut self,
+        _: &mut Context<L, Protocol>,
+        tick: i32,
+        snap: &Snap,
+    ) -> Option<PlayerInput> {
+        self.events
+            .push(format!("snapshot {} {}", tick, snap.items().count()));
+        Some(PlayerInput {
+            direction: 1,
+            ..PlayerInput::default()
+        })
+    }
+}
+
+fn feed<'a, M: MessageExt<'a>>(
+    client: &mut Client<Protocol, Recorder>,
+    loop_: &mut MockLoop,
+    msg: M,
+) {
+    let mut buf = Vec::with_capacity(2048);
+    let data = with_packer(&mut buf, |p| msg.encode(p)).unwrap();
+    client.on_packet(
+        loop_,
+        Chunk {
+            pid: PID,
+            vital: true,
+            data,
+        },
+    );
+}
+
+fn events(client: &mut Client<Protocol, Recorder>) -> Vec<String> {
+    mem::take(&mut client.handler_mut(PID).unwrap().events)
+}
+
+fn config(dir: &tempfile::TempDir) -> Config {
+    let mut config = Config::new(PlayerInfo::new(b"nameless tee"));
+    config.maps_directory = dir.path().to_owned();
+    config.download_directory = dir.path().to_owned();
+    config
+}
+
+fn connect(dir: &tempfile::TempDir) -> (Client<Protocol, Recorder>, MockLoop) {
+    connect_with(config(dir))
+}
+
+fn connect_with(config: Config) -> (Client<Protocol, Recorder>, MockLoop) {
+    let mut loop_ = MockLoop::client();
+    let mut client = Client::new();
+    let addr = "127.0.0.1:8303".parse().unwrap();
+    client.connect(&mut loop_, addr, config, Recorder::default());
+    client.on_ready(&mut loop_, PID);
+    assert_eq!(
+        loop_.take_sent(),
+        [r#"Info { version: b"0.6 626fce9a778df4d4", password: Some(b"") }"#]
+    );
+    assert_eq!(events(&mut client), ["Connected"]);
+    (client, loop_)
+}
+
+#[test]
+fn join() {
+    let dir = tempfile::tempdir().unwrap();
+    let (mut client, mut loop_) = connect(&dir);
+
+    feed(
+        &mut client,
+        &mut loop_,
+        System::from(system::MapDetails {
+            name: b"test",
+            sha256: MAP_SHA256.parse().unwrap(),
+            crc: MAP_CRC,
+            size: Some(MAP.len() as i32),
+            url: Some(b""),
+        }),
+    );
+    feed(
+        &mut client,
+        &mut loop_,
+        System::from(system::MapChange {
+            name: b"test",
+            crc: MAP_CRC,
+            size: MAP.len() as i32,
+        }),
+    );
+    assert_eq!(loop_.take_sent(), ["RequestMapData { chunk: 0 }"]);
+    assert_eq!(events(&mut client).len(), 1);
+
+    for (i, data) in MAP.chunks(5).enumerate() {
+        let last = (i + 1) * 5 >= MAP.len();
+        feed(
+            &mut client,
+            &mut loop_,
+            System::from(system::MapData {
+                last: last as i32,
+                crc: MAP_CRC,
+                chunk: i as i32,
+                data,
+            }),
+        );
+    }
+    assert_eq!(loop_.take_sent(), ["RequestMapData { chunk: 1 }", "Ready"]);
+    assert_eq!(
+        events(&mut client),
+        ["MapDownload", "MapDownload", "MapReady"]
+    );
+    let path = dir.path().join(format!("test_{}.map", MAP_SHA256));
+    assert_eq!(fs::read(path).unwrap(), MAP);
+
+    feed(&mut client, &mut loop_, System::from(system::ConReady));
+    let sent = loop_.take_sent();
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0].starts_with(r#"ClStartInfo { name: b"nameless tee""#));
+
+    feed(&mut client, &mut loop_, Game::from(game::SvReadyToEnter));
+    assert_eq!(loop_.take_sent(), ["EnterGame"]);
+    assert_eq!(events(&mut client), ["EnterGame"]);
+
+    feed(
+        &mut client,
+        &mut loop_,
+        System::from(system::SnapEmpty {
+            tick: 100,
+            delta_tick: 101,
+        }),
+    );
+    assert_eq!(events(&mut client), ["snapshot 100 0"]);
+    let sent = loop_.take_sent();
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0].starts_with("Input { ack_snapshot: 100, intended_tick: 105, input_size: 40"));
+    assert!(sent[0].contains("direction: 1"));
+
+    feed(
+        &mut client,
+        &mut loop_,
+        Game::from(game::SvChat {
+            team: 0,
+            client_id: -1,
+            message: b"hello",
+        }),
+    );
+    assert_eq!(events(&mut client).len(), 1);
+    assert!(loop_.disconnected.is_none());
+}
+
+#[test]
+fn map_crc_mismatch() {
+    let dir = tempfile::tempdir().unwrap();
+    let (mut client, mut loop_) = connect(&dir);
+
+    feed(
+        &mut client,
+        &mut loop_,
+        System::from(system::MapChange {
+            name: b"test",
+            crc: MAP_CRC,
+            size: MAP.len() as i32,
+        }),
+    );
+    feed(
+        &mut client,
+        &mut loop_,
+        System::from(system::MapData {
+            last: 1,
+            crc: MAP_CRC,
+            chunk: 0,
+            data: b"987654321",
+        }),
+    );
+    assert_eq!(loop_.take_sent(), ["RequestMapData { chunk: 0 }"]);
+    assert_eq!(events(&mut client)[1..], ["MapDownload", "Error"]);
+    assert_eq!(loop_.disconnected.as_deref(), Some(&b"error"[..]));
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn content_addressed_map() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = config(&dir);
+    config.content_addressed_maps = true;
+    let (mut client, mut loop_) = connect_with(config);
+    // Same map as in `join`, but under a different name.
+    fs::write(dir.path().join(format!("{}.map", MAP_SHA256)), MAP).unwrap();
+
+    feed(
+        &mut client,
+        &mut loop_,
+        System::from(system::MapDetails {
+            name: b"renamed",
+            sha256: MAP_SHA256.parse().unwrap(),
+            crc: MAP_CRC,
+            size: Some(MAP.len() as i32),
+            url: Some(b"https://maps.example.org/renamed.map"),
+        }),
+    );
+    feed(
+        &mut client,
+        &mut loop_,
+        System::from(system::MapChange {
+            name: b"renamed",
+            crc: MAP_CRC,
+            size: MAP.len() as i32,
+        }),
+    );
+    assert_eq!(loop_.take_sent(), ["Ready"]);
+    assert_eq!(events(&mut client)[1..], ["MapReady"]);
+}
+
+#[test]
+fn skip_map() {
+    let dir = tempfile::tempdir().unwrap();
+    let (mut client, mut loop_) = connect(&dir);
+    client.handler_mut(PID).unwrap().skip_maps = true;
+
+    feed(
+        &mut client,
+        &mut loop_,
+        System::from(system::MapChange {
+            name: b"test",
+            crc: MAP_CRC,
+            size: MAP.len() as i32,
+        }),
+    );
+    assert_eq!(loop_.take_sent(), ["Ready"]);
+    assert_eq!(events(&mut client).len(), 1);
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn resume_map_download() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = config(&dir);
+    config.resume_downloads = true;
+    let (mut client, mut loop_) = connect_with(config);
+    fs::write(dir.path().join("test_cbf43926.map.part"), &MAP[..7]).unwrap();
+
+    feed(
+        &mut client,
+        &mut loop_,
+        System::from(system::MapChange {
+            name: b"test",
+            crc: MAP_CRC,
+            size: MAP.len() as i32,
+        }),
+    );
+    assert_eq!(loop_.take_sent(), ["RequestMapData { chunk: 0 }"]);
+    assert_eq!(events(&mut client).len(), 1);
+
+    let mut map_data = |chunk: usize| {
+        let data = MAP.chunks(3).nth(chunk).unwrap();
+        feed(
+            &mut client,
+            &mut loop_,
+            System::from(system::MapData {
+                last: (chunk == 2) as i32,
+                crc: MAP_CRC,
+                chunk: chunk as i32,
+                data,
+            }),
+        );
+    };
+    // The first chunk tells the client to continue at the third one.
+    map_data(0);
+    map_data(2);
+    assert_eq!(loop_.take_sent(), ["RequestMapData { chunk: 2 }", "Ready"]);
+    assert_eq!(events(&mut client), ["MapDownload", "MapReady"]);
+    let path = dir.path().join("test_cbf43926.map");
+    assert_eq!(fs::read(path).unwrap(), MAP);
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
@@ -1,17 +1,22 @@
 #[macro_use]
 extern crate log;
 
-use arrayvec::ArrayVec;
+use self::manifest::Manifest;
+use self::protocol::Message;
+use self::protocol::Protocol;
+use clap::value_t;
 use clap::values_t;
 use clap::App;
 use clap::Arg;
 use clap::Error;
 use clap::ErrorKind;
-use hexdump::hexdump_iter;
-use itertools::Itertools;
-use libtw2_common::num::Cast;
+use libtw2_client::Client;
+use libtw2_client::Context;
+use libtw2_client::Event;
+use libtw2_client::Handler;
+use libtw2_client::PlayerInfo;
+use libtw2_common::digest::Sha256;
 use libtw2_common::pretty;
-use libtw2_event_loop::collections::PeerMap;
 use libtw2_event_loop::Addr;
 use libtw2_event_loop::Application;
 use libtw2_event_loop::Chunk;
@@ -19,179 +24,88 @@ use libtw2_event_loop::ConnlessChunk;
 use libtw2_event_loop::Loop;
 use libtw2_event_loop::PeerId;
 use libtw2_event_loop::SocketLoop;
+use libtw2_event_loop::SocketLoop7;
 use libtw2_event_loop::Timeout;
 use libtw2_event_loop::Timestamp;
-use libtw2_gamenet::enums;
-use libtw2_gamenet::enums::Team;
-use libtw2_gamenet::enums::VERSION;
-use libtw2_gamenet::msg;
-use libtw2_gamenet::msg::game::ClCallVote;
-use libtw2_gamenet::msg::game::ClSetTeam;
-use libtw2_gamenet::msg::game::ClStartInfo;
-use libtw2_gamenet::msg::game::SvVoteOptionAdd;
-use libtw2_gamenet::msg::game::SvVoteOptionRemove;
-use libtw2_gamenet::msg::system::EnterGame;
-use libtw2_gamenet::msg::system::Info;
-use libtw2_gamenet::msg::system::Input;
-use libtw2_gamenet::msg::system::MapChange;
-use libtw2_gamenet::msg::system::MapData;
-use libtw2_gamenet::msg::system::Ready;
-use libtw2_gamenet::msg::system::RequestMapData;
-use libtw2_gamenet::msg::Game;
-use libtw2_gamenet::msg::System;
-use libtw2_gamenet::msg::SystemOrGame;
-use libtw2_gamenet::snap_obj::obj_size;
-use libtw2_gamenet::snap_obj::PlayerInput;
-use libtw2_gamenet::SnapObj;
-use libtw2_packer::with_packer;
-use libtw2_packer::IntUnpacker;
-use libtw2_packer::Unpacker;
-use libtw2_snapshot::format::Item as SnapItem;
+use libtw2_serverbrowse::json;
 use libtw2_snapshot::Snap;
-use libtw2_warn as warn;
-use libtw2_warn::Log;
-use log::LogLevel;
-use std::borrow::Cow;
+use sha2::Digest;
+use std::cell::RefCell;
 use std::cmp;
 use std::collections::HashSet;
-use std::fmt;
+use std::collections::VecDeque;
+use std::error;
 use std::fs;
-use std::io;
-use std::io::Write;
-use std::mem;
+use std::marker::PhantomData;
+use std::path::Path;
 use std::path::PathBuf;
+use std::process;
+use std::rc::Rc;
 use std::str;
 use std::time::Duration;
-use std::u32;
-use tempfile::NamedTempFile;
 
-fn hexdump(level: LogLevel, data: &[u8]) {
-    if log_enabled!(level) {
-        hexdump_iter(data).foreach(|s| log!(level, "{}", s));
-    }
-}
-
-struct Warn<'a>(&'a [u8]);
-
-impl<'a, W: fmt::Debug> warn::Warn<W> for Warn<'a> {
-    fn warn(&mut self, w: W) {
-        warn!("{:?}", w);
-        hexdump(LogLevel::Warn, self.0);
-    }
-}
-
-#[derive(Debug)]
-struct WarnSnap<'a>(SnapItem<'a>);
-
-impl<'a, W: fmt::Debug> warn::Warn<W> for WarnSnap<'a> {
-    fn warn(&mut self, w: W) {
-        warn!("{:?} for {:?}", w, self.0);
-    }
-}
-
-fn check_dummy_map(name: &[u8], crc: u32, size: i32) -> bool {
-    if name != b"dummy" {
-        return false;
-    }
-    match (crc, size) {
-        (0xbeae0b9f, 549) => {}
-        (0x6c760ac4, 306) => {}
-        _ => warn!("unknown dummy map, crc={}, size={}", crc, size),
-    }
-    true
-}
+mod manifest;
+mod protocol;
 
-struct Download {
-    file: NamedTempFile,
+/// Map the server is currently on, if it needs to be archived.
+struct PendingMap {
+    name: Vec<u8>,
     crc: i32,
-    name: String,
+    sha256: Option<Sha256>,
 }
 
-struct Peer {
+struct Peer<P> {
+    config: Config,
+    addr: Addr,
+    manifest: Rc<RefCell<Manifest>>,
+    pending_map: Option<PendingMap>,
+    /// Time at which the server is given up on.
+    deadline: Timestamp,
     visited_votes: HashSet<Vec<u8>>,
     current_votes: HashSet<Vec<u8>>,
     list_votes: HashSet<Vec<u8>>,
     completed_list_votes: HashSet<Vec<u8>>,
     previous_list_vote: Option<Vec<u8>>,
     previous_vote: Option<Vec<u8>>,
-    snaps: libtw2_snapshot::Manager,
-    num_snaps_since_reset: u64,
-    dummy_map: bool,
     state: PeerState,
-    download: Option<Download>,
     progress_timeout: Timestamp,
+    protocol: PhantomData<P>,
 }
 
-fn need_file(crc: i32, name: &str) -> bool {
-    let mut path = PathBuf::new();
-    path.push("maps");
-    path.push(format!("{}_{:08x}.map", name, crc));
-    !path.exists()
-}
-
-impl Peer {
-    fn new<L: Loop>(loop_: &mut L) -> Peer {
+impl<P: Protocol> Peer<P> {
+    fn new(
+        config: Config,
+        addr: Addr,
+        manifest: Rc<RefCell<Manifest>>,
+        time: Timestamp,
+    ) -> Peer<P> {
         let mut result = Peer {
+            deadline: time + config.server_timeout,
+            config,
+            addr,
+            manifest,
+            pending_map: None,
             visited_votes: HashSet::new(),
             current_votes: HashSet::new(),
             list_votes: HashSet::new(),
             completed_list_votes: HashSet::new(),
             previous_list_vote: None,
             previous_vote: None,
-            snaps: libtw2_snapshot::Manager::new(),
-            num_snaps_since_reset: 0,
-            dummy_map: false,
-            state: PeerState::Connection,
-            download: None,
+            state: PeerState::Joining,
             progress_timeout: Timestamp::sentinel(),
+            protocol: PhantomData,
         };
-        result.progress(loop_);
+        result.progress(time);
         result
     }
-    fn needs_tick(&self) -> Timeout {
-        cmp::min(
-            Timeout::active(self.progress_timeout),
-            self.state.needs_tick(),
-        )
-    }
-    fn tick<L: Loop>(&mut self, pid: PeerId, config: &Config, loop_: &mut L) {
-        // TODO: What happens with peers that are already disconnected?
-        let vote;
-        match self.state {
-            PeerState::VoteSet(timeout) => vote = loop_.time() >= timeout,
-            PeerState::VoteResult(timeout) => vote = loop_.time() >= timeout,
-            _ => vote = false,
-        }
-        if vote {
-            if self.vote(pid, config, loop_) {
-                info!("voting done");
-                loop_.disconnect(pid, config.nick.as_bytes());
-                return;
-            }
-            loop_.flush(pid);
-        }
-        if self.has_timed_out(loop_) {
-            error!("timed out due to lack of progress");
-            loop_.disconnect(pid, config.timeout.as_bytes());
-            return;
-        }
-    }
-    fn vote<L: Loop>(&mut self, pid: PeerId, config: &Config, loop_: &mut L) -> bool {
-        fn send_vote<L: Loop>(
+    fn vote<L: Loop>(&mut self, ctx: &mut Context<L, P>) -> bool {
+        fn send_vote<L: Loop, P: Protocol>(
             visited_votes: &mut HashSet<Vec<u8>>,
             vote: &[u8],
-            pid: PeerId,
             reason: &[u8],
-            loop_: &mut L,
+            ctx: &mut Context<L, P>,
         ) {
-            loop_.sendg(
-                pid,
-                ClCallVote {
-                    type_: enums::CL_CALL_VOTE_TYPE_OPTION.as_bytes(),
-                    value: vote,
-                    reason: reason,
-                },
-            );
+            ctx.send_game(P::call_vote(vote, reason));
             visited_votes.insert(vote.to_owned());
         }
         // TODO: This probably has bad performance:
@@ -204,9 +118,8 @@ impl Peer {
             send_vote(
                 &mut self.visited_votes,
                 vote,
-                pid,
-                config.nick.as_bytes(),
-                loop_,
+                self.config.nick.as_bytes(),
+                ctx,
             );
             info!("voting for {}", pretty::AlmostString::new(vote));
         } else {
@@ -227,60 +140,248 @@ impl Peer {
                 info!("list-voting for {}", pretty::AlmostString::new(vote));
                 send_vote(
                     &mut self.visited_votes,
-                    &vote,
-                    pid,
-                    config.nick.as_bytes(),
-                    loop_,
+                    vote,
+                    self.config.nick.as_bytes(),
+                    ctx,
                 );
             } else {
                 return true;
             }
         }
-        self.state = PeerState::VoteSet(loop_.time() + Duration::from_secs(5));
-        self.progress(loop_);
+        self.state = PeerState::VoteSet(ctx.time() + Duration::from_secs(5));
+        self.progress(ctx.time());
         false
     }
-    fn has_timed_out<L: Loop>(&self, loop_: &mut L) -> bool {
-        loop_.time() >= self.progress_timeout
+    fn progress(&mut self, time: Timestamp) {
+        self.progress_timeout = time + Duration::from_secs(120);
     }
-    fn progress<L: Loop>(&mut self, loop_: &mut L) {
-        self.progress_timeout = loop_.time() + Duration::from_secs(120);
+    fn archive(&mut self, path: &Path) {
+        let map = match self.pending_map.take() {
+            Some(m) => m,
+            None => return,
+        };
+        let data = match fs::read(path) {
+            Ok(d) => d,
+            Err(e) => {
+                error!("{}: can't read {}: {}", self.addr, path.display(), e);
+                return;
+            }
+        };
+        let sha256 = map
+            .sha256
+            .unwrap_or_else(|| Sha256(sha2::Sha256::digest(&data).into()));
+        let size = data.len() as u64;
+        match self
+            .manifest
+            .borrow_mut()
+            .insert(&map.name, map.crc, sha256, size, self.addr)
+        {
+            Ok(true) => info!(
+                "{}: archived {} {}",
+                self.addr,
+                pretty::AlmostString::new(&map.name),
+                sha256
+            ),
+            Ok(false) => {}
+            Err(e) => error!("{}: can't write manifest: {}", self.addr, e),
+        }
     }
-    fn open_file(&mut self, crc: i32, name: String) -> Result<(), io::Error> {
-        self.download = Some(Download {
-            file: tempfile::Builder::new()
-                .prefix(&format!("{}_{:08x}_", name, crc))
-                .suffix(".map")
-                .tempfile_in("downloading")?,
-            crc: crc,
-            name: name,
-        });
-        Ok(())
+    fn on_game<L: Loop>(&mut self, ctx: &mut Context<L, P>, msg: Message) {
+        let mut ignored = false;
+        let mut progress = false;
+        match msg {
+            Message::Ignored => {
+                ignored = true;
+            }
+            Message::ServerChat(message) => {
+                ignored = true;
+                info!("*** {}", pretty::AlmostString::new(message));
+            }
+            Message::Broadcast(message) => {
+                info!("broadcast: {}", pretty::AlmostString::new(message));
+                ignored = true;
+            }
+            Message::VoteClearOptions => {
+                ignored = true;
+                self.current_votes.clear();
+            }
+            Message::VoteOptionListAdd(ref descriptions) => {
+                ignored = true;
+                for &desc in descriptions {
+                    self.current_votes.insert(desc.to_owned());
+                }
+            }
+            Message::VoteOptionAdd(description) => {
+                ignored = true;
+                self.current_votes.insert(description.to_owned());
+            }
+            Message::VoteOptionRemove(description) => {
+                ignored = true;
+                if !self.current_votes.remove(description) {
+                    warn!("vote option removed even though it didn't exist");
+                }
+            }
+            _ => {}
+        }
+        match self.state {
+            PeerState::Joining => {}
+            PeerState::VoteSet(_) => match msg {
+                Message::ServerChat(message) => {
+                    if let Ok(message) = str::from_utf8(message) {
+                        if message.contains("Wait") || message.contains("wait") {
+                            progress = true;
+                            self.visited_votes
+                                .remove(self.previous_vote.as_ref().unwrap());
+                            self.state = PeerState::VoteResult(ctx.time() + Duration::from_secs(5));
+                        }
+                    }
+                }
+                Message::VoteSet { timeout } if timeout != 0 => {
+                    progress = true;
+                    self.state = PeerState::VoteEnd;
+                }
+                _ => {}
+            },
+            PeerState::VoteEnd => match msg {
+                Message::VoteSet { timeout: 0 } => {
+                    progress = true;
+                    self.state = PeerState::VoteResult(ctx.time() + Duration::from_secs(3));
+                }
+                Message::VoteClearOptions
+                | Message::VoteOptionAdd(..)
+                | Message::VoteOptionListAdd(..)
+                | Message::VoteOptionRemove(..) => {
+                    let prev = self.previous_vote.as_ref().unwrap();
+                    if self.list_votes.insert(prev.to_owned()) {
+                        info!("list vote {}", pretty::AlmostString::new(prev));
+                    }
+                }
+                _ => {}
+            },
+            PeerState::VoteResult(..) => {}
+        }
+        if progress {
+            self.progress(ctx.time());
+        }
+        if !progress && !ignored {
+            if let Message::Other(msg) = msg {
+                warn!("unprocessed message {:?}", msg);
+            }
+        }
     }
-    fn write_file(&mut self, data: &[u8]) -> Result<(), io::Error> {
-        self.download.as_mut().unwrap().file.write_all(data)
+}
+
+impl<P: Protocol> Handler<P> for Peer<P> {
+    fn on_event<L: Loop>(&mut self, ctx: &mut Context<L, P>, event: Event<P>) {
+        match event {
+            Event::Connected => self.progress(ctx.time()),
+            Event::MapChange(map_change) => {
+                match self.state {
+                    PeerState::Joining | PeerState::VoteResult(..) => {}
+                    _ => warn!("map change from state {:?}", self.state),
+                }
+                self.current_votes.clear();
+                self.state = PeerState::Joining;
+                info!("map change: {}", pretty::AlmostString::new(map_change.name));
+                self.progress(ctx.time());
+            }
+            Event::MapDownload { received, size } => {
+                debug!("downloaded {}/{} bytes", received, size);
+                self.progress(ctx.time());
+            }
+            Event::MapReady(path) => {
+                info!("map ready: {}", path.display());
+                self.archive(&path);
+                self.progress(ctx.time());
+            }
+            Event::EnterGame => {
+                self.progress(ctx.time());
+                ctx.send_game(P::join_game());
+                if self.vote(ctx) {
+                    self.state = PeerState::VoteResult(ctx.time() + Duration::from_secs(3));
+                }
+            }
+            Event::Game(msg) => self.on_game(ctx, P::game_message(msg)),
+            Event::System(msg) => {
+                if let Some(msg) = P::system_message(msg) {
+                    warn!("unprocessed message {:?}", msg);
+                }
+            }
+            Event::Error(err) => {
+                error!("{}", err);
+                ctx.disconnect(self.config.error.as_bytes());
+            }
+            Event::Disconnected { remote, reason } => {
+                if remote {
+                    error!(
+                        "disconnected pid={:?} error={}",
+                        ctx.pid(),
+                        pretty::AlmostString::new(reason)
+                    );
+                }
+            }
+        }
+    }
+    fn on_snapshot<L: Loop>(
+        &mut self,
+        ctx: &mut Context<L, P>,
+        _: i32,
+        snap: &Snap,
+    ) -> Option<P::PlayerInput> {
+        let num_players = libtw2_client::snap_objects::<P>(snap)
+            .filter(|(_, obj)| P::is_player_info(obj))
+            .count();
+        if num_players > 1 {
+            error!("more than one player ({}) detected, quitting", num_players);
+            ctx.disconnect(self.config.nick.as_bytes());
+        }
+        None
+    }
+    fn needs_tick(&mut self) -> Timeout {
+        cmp::min(
+            cmp::min(
+                Timeout::active(self.progress_timeout),
+                Timeout::active(self.deadline),
+            ),
+            self.state.needs_tick(),
+        )
     }
-    fn finish_file(&mut self) -> Result<(), io::Error> {
-        let download = self.download.take().unwrap();
-        let mut path = PathBuf::new();
-        path.push("maps");
-        path.push(format!("{}_{:08x}.map", &download.name, download.crc));
-        download
-            .file
-            .persist(&path)
-            .map(|_| ())
-            .map_err(|e| e.error)
+    fn need_file(&mut self, name: &[u8], crc: i32, sha256: Option<Sha256>) -> bool {
+        if self.manifest.borrow().contains(name, crc, sha256) {
+            info!("map already archived");
+            return false;
+        }
+        self.pending_map = Some(PendingMap {
+            name: name.to_owned(),
+            crc,
+            sha256,
+        });
+        true
+    }
+    fn on_tick<L: Loop>(&mut self, ctx: &mut Context<L, P>) {
+        let vote = match self.state {
+            PeerState::VoteSet(timeout) => ctx.time() >= timeout,
+            PeerState::VoteResult(timeout) => ctx.time() >= timeout,
+            _ => false,
+        };
+        if vote && self.vote(ctx) {
+            info!("{}: voting done", self.addr);
+            ctx.disconnect(self.config.nick.as_bytes());
+            return;
+        }
+        if ctx.time() >= self.progress_timeout {
+            error!("{}: timed out due to lack of progress", self.addr);
+            ctx.disconnect(self.config.timeout.as_bytes());
+        } else if ctx.time() >= self.deadline {
+            error!("{}: giving up, server took too long", self.addr);
+            ctx.disconnect(self.config.timeout.as_bytes());
+        }
     }
 }
 
 #[derive(Clone, Copy, Debug)]
 enum PeerState {
-    Connection,
-    MapChange,
-    // MapData(crc, chunk)
-    MapData(i32, i32),
-    ConReady,
-    ReadyToEnter,
+    Joining,
     // VoteSet(timeout)
     VoteSet(Timestamp),
     VoteEnd,
@@ -288,12 +389,6 @@ enum PeerState {
     VoteResult(Timestamp),
 }
 
-impl Default for PeerState {
-    fn default() -> PeerState {
-        PeerState::Connection
-    }
-}
-
 impl PeerState {
     fn needs_tick(&self) -> Timeout {
         match *self {
@@ -303,439 +398,117 @@ impl PeerState {
     }
 }
 
-trait LoopExt: Loop {
-    fn sends<'a, S: Into<System<'a>>>(&mut self, pid: PeerId, msg: S) {
-        fn inner<L: Loop + ?Sized>(msg: System, pid: PeerId, loop_: &mut L) {
-            let mut buf: ArrayVec<[u8; 2048]> = ArrayVec::new();
-            with_packer(&mut buf, |p| msg.encode(p).unwrap());
-            loop_.send(Chunk {
-                pid: pid,
-                vital: true,
-                data: &buf,
-            })
-        }
-        inner(msg.into(), pid, self)
-    }
-    fn sendg<'a, G: Into<Game<'a>>>(&mut self, pid: PeerId, msg: G) {
-        fn inner<L: Loop + ?Sized>(msg: Game, pid: PeerId, loop_: &mut L) {
-            let mut buf: ArrayVec<[u8; 2048]> = ArrayVec::new();
-            with_packer(&mut buf, |p| msg.encode(p).unwrap());
-            loop_.send(Chunk {
-                pid: pid,
-                vital: true,
-                data: &buf,
-            })
-        }
-        inner(msg.into(), pid, self)
-    }
-}
-impl<L: Loop> LoopExt for L {}
-
-fn num_players(snap: &Snap) -> u32 {
-    let mut num_players = 0;
-    for item in snap.items() {
-        match SnapObj::decode_obj(
-            &mut WarnSnap(item),
-            item.type_id.into(),
-            &mut IntUnpacker::new(item.data),
-        ) {
-            Ok(SnapObj::PlayerInfo(..)) => num_players += 1,
-            Ok(_) => {}
-            Err(e) => warn!("item decode error {:?}: {:?}", e, item),
-        }
-    }
-    num_players
-}
-
+#[derive(Clone)]
 struct Config {
     nick: String,
     clan: String,
     timeout: String,
     error: String,
+    /// Maximum time spent on a single server.
+    server_timeout: Duration,
 }
 
-struct Main {
-    peers: PeerMap<Peer>,
+/// Scrapes a list of servers, at most `parallel` at once.
+struct Scraper<P: Protocol> {
+    client: Client<P, Peer<P>>,
+    queue: VecDeque<Addr>,
+    parallel: usize,
     config: Config,
+    client_config: libtw2_client::Config,
+    manifest: Rc<RefCell<Manifest>>,
 }
 
-struct MainLoop<'a, L: Loop + 'a> {
-    peers: &'a mut PeerMap<Peer>,
-    config: &'a Config,
-    loop_: &'a mut L,
+impl<P: Protocol> Scraper<P> {
+    fn connect_more<L: Loop>(&mut self, loop_: &mut L) {
+        while self.client.len() < self.parallel {
+            let addr = match self.queue.pop_front() {
+                Some(a) => a,
+                None => break,
+            };
+            info!("{}: connecting, {} servers left", addr, self.queue.len());
+            let peer = Peer::new(
+                self.config.clone(),
+                addr,
+                self.manifest.clone(),
+                loop_.time(),
+            );
+            self.client
+                .connect(loop_, addr, self.client_config.clone(), peer);
+        }
+    }
 }
 
-impl<'a, L: Loop> Application<L> for Main {
+impl<P: Protocol, L: Loop> Application<L> for Scraper<P> {
     fn needs_tick(&mut self) -> Timeout {
-        self.peers
-            .values()
-            .map(|p| p.needs_tick())
-            .min()
-            .unwrap_or_default()
+        Application::<L>::needs_tick(&mut self.client)
     }
     fn on_tick(&mut self, loop_: &mut L) {
-        for (pid, peer) in self.peers.iter_mut() {
-            peer.tick(pid, &self.config, loop_);
-        }
+        self.client.on_tick(loop_);
     }
     fn on_packet(&mut self, loop_: &mut L, chunk: Chunk) {
-        self.loop_(loop_)
-            .on_packet(chunk.pid, chunk.vital, chunk.data);
+        self.client.on_packet(loop_, chunk);
     }
-    fn on_connless_packet(&mut self, _: &mut L, chunk: ConnlessChunk) {
-        warn!(
-            "connless packet {} {:?}",
-            chunk.addr,
-            pretty::Bytes::new(chunk.data)
-        );
+    fn on_connless_packet(&mut self, loop_: &mut L, chunk: ConnlessChunk) {
+        self.client.on_connless_packet(loop_, chunk);
     }
-    fn on_connect(&mut self, _: &mut L, _: PeerId) {
-        unreachable!();
+    fn on_connect(&mut self, loop_: &mut L, pid: PeerId) {
+        self.client.on_connect(loop_, pid);
     }
     fn on_ready(&mut self, loop_: &mut L, pid: PeerId) {
-        self.loop_(loop_).on_ready(pid);
+        self.client.on_ready(loop_, pid);
     }
-    fn on_disconnect(&mut self, _: &mut L, pid: PeerId, remote: bool, reason: &[u8]) {
-        if remote {
-            error!(
-                "disconnected pid={:?} error={}",
-                pid,
-                pretty::AlmostString::new(reason)
-            );
-        }
-        self.peers.remove(pid);
+    fn on_disconnect(&mut self, loop_: &mut L, pid: PeerId, remote: bool, reason: &[u8]) {
+        self.client.on_disconnect(loop_, pid, remote, reason);
+        self.connect_more(loop_);
     }
 }
 
-impl Main {
-    fn run<L: Loop>(addresses: &[Addr], config: Config) {
-        let mut main = Main {
-            peers: PeerMap::with_capacity(addresses.len()),
-            config: config,
-        };
-        let mut loop_ = L::client();
-        for &addr in addresses {
-            let pid = loop_.connect(addr);
-            main.peers.insert(pid, Peer::new(&mut loop_));
-        }
-        fs::create_dir_all("maps").unwrap();
-        fs::create_dir_all("downloading").unwrap();
-        loop_.run(main);
-    }
-    fn loop_<'a, L: Loop + 'a>(&'a mut self, loop_: &'a mut L) -> MainLoop<'a, L> {
-        MainLoop {
-            peers: &mut self.peers,
-            config: &self.config,
-            loop_: loop_,
-        }
-    }
+/// Returns the empty, unpassworded servers of the server list at `url`.
+fn fetch_servers(url: &str, version: json::Version) -> Result<Vec<Addr>, Box<dyn error::Error>> {
+    let body = reqwest::blocking::get(url)?.error_for_status()?.bytes()?;
+    let list = json::parse(&body)?;
+    Ok(list
+        .servers
+        .iter()
+        .filter(|s| !s.info.passworded && s.info.clients.is_empty())
+        .filter_map(|s| s.addresses.iter().find(|a| a.version == version))
+        .map(|a| Addr::from(a.addr))
+        .collect())
 }
-impl<'a, L: Loop> MainLoop<'a, L> {
-    fn on_packet(&mut self, pid: PeerId, vital: bool, data: &[u8]) {
-        let _ = vital;
-        let msg;
-        match msg::decode(&mut Warn(data), &mut Unpacker::new(data)) {
-            Ok(m) => msg = m,
-            Err(err) => {
-                warn!("decode error {:?}:", err);
-                hexdump(LogLevel::Warn, data);
-                return;
-            }
-        }
-        debug!("{:?}", msg);
-        let mut ignored = false;
-        let mut progress = false;
-        match msg {
-            SystemOrGame::Game(Game::SvMotd(..))
-            | SystemOrGame::Game(Game::SvKillMsg(..))
-            | SystemOrGame::Game(Game::SvTuneParams(..))
-            | SystemOrGame::Game(Game::SvWeaponPickup(..))
-            | SystemOrGame::System(System::InputTiming(..))
-            | SystemOrGame::Game(Game::SvExtraProjectile(..)) => {
-                ignored = true;
-            }
-            SystemOrGame::Game(Game::SvChat(chat)) => {
-                if !chat.team && chat.client_id == -1 {
-                    ignored = true;
-                    info!("*** {}", pretty::AlmostString::new(chat.message));
-                }
-            }
-            SystemOrGame::Game(Game::SvBroadcast(broadcast)) => {
-                info!(
-                    "broadcast: {}",
-                    pretty::AlmostString::new(broadcast.message)
-                );
-                ignored = true;
-            }
-            _ => {}
-        }
-        {
-            let peer = &mut self.peers[pid];
-            match msg {
-                SystemOrGame::System(ref msg) => match *msg {
-                    System::MapChange(MapChange { crc, size, name }) => {
-                        if let Some(_) = size.try_usize() {
-                            if name.iter().any(|&b| b == b'/' || b == b'\\') {
-                                error!("invalid map name");
-                                self.loop_.disconnect(pid, self.config.error.as_bytes());
-                                return;
-                            }
-                            match peer.state {
-                                PeerState::MapChange => {}
-                                PeerState::VoteResult(..) => {}
-                                PeerState::ReadyToEnter if peer.dummy_map => {}
-                                _ => warn!("map change from state {:?}", peer.state),
-                            }
-                            peer.dummy_map = check_dummy_map(name, crc as u32, size);
-                            peer.current_votes.clear();
-                            peer.num_snaps_since_reset = 0;
-                            peer.snaps.reset();
-                            info!("map change: {}", pretty::AlmostString::new(name));
-                            let name = String::from_utf8_lossy(name);
-                            if let Cow::Owned(..) = name {
-                                warn!("weird characters in map name");
-                            }
-                            let mut start_download = false;
-                            if need_file(crc, &name) {
-                                if let Err(e) = peer.open_file(crc, name.into_owned()) {
-                                    error!("error opening file {:?}", e);
-                                } else {
-                                    start_download = true;
-                                }
-                            }
-                            if start_download {
-                                info!("download starting");
-                                self.loop_.sends(pid, RequestMapData { chunk: 0 });
-                                peer.state = PeerState::MapData(crc, 0);
-                            } else {
-                                peer.state = PeerState::ConReady;
-                                self.loop_.sends(pid, Ready);
-                            }
-                            progress = true;
-                        } else {
-                            error!("invalid map size");
-                            self.loop_.disconnect(pid, self.config.error.as_bytes());
-                            return;
-                        }
-                    }
-                    System::Snap(_) | System::SnapEmpty(_) | System::SnapSingle(_) => {
-                        let mut check_num_snaps = true;
-                        peer.num_snaps_since_reset += 1;
-                        {
-                            let res = match *msg {
-                                System::Snap(s) => peer.snaps.snap(&mut Log, obj_size, s),
-                                System::SnapEmpty(s) => {
-                                    peer.snaps.snap_empty(&mut Log, obj_size, s)
-                                }
-                                System::SnapSingle(s) => {
-                                    peer.snaps.snap_single(&mut Log, obj_size, s)
-                                }
-                                _ => unreachable!(),
-                            };
-                            match res {
-                                Ok(Some(snap)) => {
-                                    let num_players = num_players(snap);
-                                    if num_players > 1 {
-                                        error!(
-                                            "more than one player ({}) detected, quitting",
-                                            num_players
-                                        );
-                                        self.loop_.disconnect(pid, self.config.nick.as_bytes());
-                                        return;
-                                    }
-                                }
-                                Ok(None) => {
-                                    peer.num_snaps_since_reset -= 1;
-                                    check_num_snaps = false;
-                                }
-                                Err(err) => warn!("snapshot error {:?}", err),
-                            }
-                        }
-                        if check_num_snaps && peer.num_snaps_since_reset % 25 == 3 {
-                            if peer.dummy_map && peer.num_snaps_since_reset == 3 {
-                                // DDNet needs the INPUT message as the first
-                                // chunk of the packet.
-                                self.loop_.force_flush(pid);
-                            }
-                            let tick = peer.snaps.ack_tick().unwrap_or(-1);
-                            self.loop_.sends(
-                                pid,
-                                Input {
-                                    ack_snapshot: tick,
-                                    intended_tick: tick,
-                                    input_size: mem::size_of::<PlayerInput>().assert_i32(),
-                                    input: PlayerInput::default(),
-                                },
-                            );
-                        }
-                        ignored = true;
-                    }
-                    _ => {}
-                },
-                SystemOrGame::Game(ref msg) => match *msg {
-                    Game::SvVoteClearOptions(..) => {
-                        ignored = true;
-                        peer.current_votes.clear();
-                    }
-                    Game::SvVoteOptionListAdd(l) => {
-                        ignored = true;
-                        // `len` is bounded by the unpacking.
-                        let len = l.num_options.assert_usize();
-                        for &desc in l.description.iter().take(len) {
-                            peer.current_votes.insert(desc.to_owned());
-                        }
-                    }
-                    Game::SvVoteOptionAdd(SvVoteOptionAdd { description }) => {
-                        ignored = true;
-                        peer.current_votes.insert(description.to_owned());
-                    }
-                    Game::SvVoteOptionRemove(SvVoteOptionRemove { description }) => {
-                        ignored = true;
-                        if !peer.current_votes.remove(description) {
-                            warn!("vote option removed even though it didn't exist");
-                        }
-                    }
-                    _ => {}
-                },
-            }
-            match peer.state {
-                PeerState::Connection => unreachable!(),
-                PeerState::MapChange => {} // Handled above.
-                PeerState::MapData(cur_crc, cur_chunk) => match msg {
-                    SystemOrGame::System(System::MapData(MapData {
-                        last,
-                        crc,
-                        chunk,
-                        data,
-                    })) => {
-                        if cur_crc == crc && cur_chunk == chunk {
-                            let res = peer.write_file(data);
-                            if let Err(ref err) = res {
-                                error!("error writing file {:?}", err);
-                            }
-                            if last != 0 || res.is_err() {
-                                if !res.is_err() {
-                                    if let Err(err) = peer.finish_file() {
-                                        error!("error finishing file {:?}", err);
-                                    }
-                                    if last != 1 {
-                                        warn!("weird map data packet");
-                                    }
-                                }
-                                peer.state = PeerState::ConReady;
-                                self.loop_.sends(pid, Ready);
-                                info!("download finished");
-                            } else {
-                                let cur_chunk = cur_chunk.checked_add(1).unwrap();
-                                peer.state = PeerState::MapData(cur_crc, cur_chunk);
-                                self.loop_.sends(pid, RequestMapData { chunk: cur_chunk });
-                            }
-                        } else {
-                            if cur_crc != crc || cur_chunk < chunk {
-                                warn!("unsolicited map data crc={:08x} chunk={}", crc, chunk);
-                                warn!("want crc={:08x} chunk={}", cur_crc, cur_chunk);
-                            }
-                        }
-                        progress = true;
-                    }
-                    _ => {}
-                },
-                PeerState::ConReady => match msg {
-                    SystemOrGame::System(System::ConReady(..)) => {
-                        progress = true;
-                        self.loop_.sendg(
-                            pid,
-                            ClStartInfo {
-                                name: self.config.nick.as_bytes(),
-                                clan: self.config.clan.as_bytes(),
-                                country: -1,
-                                skin: b"default",
-                                use_custom_color: false,
-                                color_body: 0,
-                                color_feet: 0,
-                            },
-                        );
-                        peer.state = PeerState::ReadyToEnter;
-                    }
-                    _ => {}
-                },
-                PeerState::ReadyToEnter => match msg {
-                    SystemOrGame::Game(Game::SvReadyToEnter(..)) => {
-                        progress = true;
-                        self.loop_.sends(pid, EnterGame);
-                        self.loop_.sendg(pid, ClSetTeam { team: Team::Red });
-                        if peer.vote(pid, &self.config, self.loop_) {
-                            peer.state =
-                                PeerState::VoteResult(self.loop_.time() + Duration::from_secs(3));
-                        }
-                    }
-                    _ => {}
-                },
-                PeerState::VoteSet(_) => match msg {
-                    SystemOrGame::Game(Game::SvChat(chat)) => {
-                        if !chat.team && chat.client_id == -1 {
-                            if let Ok(message) = str::from_utf8(chat.message) {
-                                if message.contains("Wait") || message.contains("wait") {
-                                    progress = true;
-                                    peer.visited_votes
-                                        .remove(peer.previous_vote.as_ref().unwrap());
-                                    peer.state = PeerState::VoteResult(
-                                        self.loop_.time() + Duration::from_secs(5),
-                                    );
-                                }
-                            }
-                        }
-                    }
-                    SystemOrGame::Game(Game::SvVoteSet(vote_set)) => {
-                        if vote_set.timeout != 0 {
-                            progress = true;
-                            peer.state = PeerState::VoteEnd;
-                        }
-                    }
-                    _ => {}
-                },
-                PeerState::VoteEnd => match msg {
-                    SystemOrGame::Game(Game::SvVoteSet(vote_set)) => {
-                        if vote_set.timeout == 0 {
-                            progress = true;
-                            peer.state =
-                                PeerState::VoteResult(self.loop_.time() + Duration::from_secs(3));
-                        }
-                    }
-                    SystemOrGame::Game(Game::SvVoteClearOptions(..))
-                    | SystemOrGame::Game(Game::SvVoteOptionAdd(..))
-                    | SystemOrGame::Game(Game::SvVoteOptionListAdd(..))
-                    | SystemOrGame::Game(Game::SvVoteOptionRemove(..)) => {
-                        ignored = true;
-                        let prev = peer.previous_vote.as_ref().unwrap();
-                        if peer.list_votes.insert(prev.to_owned()) {
-                            info!("list vote {}", pretty::AlmostString::new(prev));
-                        }
-                    }
-                    _ => {}
-                },
-                PeerState::VoteResult(..) => {}
-            }
-            if progress {
-                peer.progress(self.loop_);
-            }
-        }
-        if !progress && !ignored {
-            warn!("unprocessed message {:?}", msg);
+
+fn run<L: Loop, P: Protocol>(
+    addresses: Vec<Addr>,
+    parallel: usize,
+    manifest: &Path,
+    config: Config,
+) {
+    fs::create_dir_all("maps").unwrap();
+    fs::create_dir_all("downloading").unwrap();
+    let manifest = match Manifest::open(manifest) {
+        Ok(m) => m,
+        Err(e) => {
+            error!("can't open manifest {}: {}", manifest.display(), e);
+            process::exit(1);
         }
-        self.loop_.flush(pid);
-    }
-    fn on_ready(&mut self, pid: PeerId) {
-        self.peers[pid].state = PeerState::MapChange;
-        self.loop_.sends(
-            pid,
-            Info {
-                version: VERSION.as_bytes(),
-                password: Some(b""),
-            },
-        );
-        self.loop_.flush(pid);
-    }
+    };
+    let mut player = PlayerInfo::new(config.nick.as_bytes());
+    player.clan = config.clan.as_bytes().to_owned();
+    let mut client_config = libtw2_client::Config::new(player);
+    client_config.content_addressed_maps = true;
+    client_config.https_maps = true;
+    client_config.resume_downloads = true;
+
+    let mut loop_ = L::client();
+    let mut scraper = Scraper::<P> {
+        client: Client::new(),
+        queue: addresses.into(),
+        parallel,
+        config,
+        client_config,
+        manifest: Rc::new(RefCell::new(manifest)),
+    };
+    scraper.connect_more(&mut loop_);
+    loop_.run(scraper);
 }
 
 fn main() {
@@ -759,16 +532,70 @@ fn main() {
                 .value_name("CLAN")
                 .default_value(""),
         )
+        .arg(
+            Arg::with_name("protocol")
+                .help("Sets the protocol version spoken with the servers")
+                .long("protocol")
+                .takes_value(true)
+                .value_name("PROTOCOL")
+                .possible_values(&["0.6", "ddnet", "0.7"])
+                .default_value("0.6"),
+        )
+        .arg(
+            Arg::with_name("parallel")
+                .help("Sets the number of servers scraped at once")
+                .long("parallel")
+                .takes_value(true)
+                .value_name("NUM")
+                .default_value("16"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .help("Sets the maximum time spent on a single server")
+                .long("timeout")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .default_value("3600"),
+        )
+        .arg(
+            Arg::with_name("manifest")
+                .help("Sets the file recording the archived maps")
+                .long("manifest")
+                .takes_value(true)
+                .value_name("FILE")
+                .default_value("manifest.csv"),
+        )
+        .arg(
+            Arg::with_name("master")
+                .help("Scrapes the empty servers of the HTTPS master server list")
+                .long("master"),
+        )
+        .arg(
+            Arg::with_name("master-url")
+                .help("Sets the URL of the server list used with --master")
+                .long("master-url")
+                .takes_value(true)
+                .value_name("URL")
+                .default_value(json::DEFAULT_URL),
+        )
         .arg(
             Arg::with_name("server")
                 .help("Server to scrape")
                 .multiple(true)
-                .required(true)
+                .required_unless("master")
                 .value_name("SERVER"),
         )
         .get_matches();
 
-    let addresses = values_t!(matches, "server", Addr).unwrap_or_else(|e| e.exit());
+    let mut addresses = if matches.is_present("server") {
+        values_t!(matches, "server", Addr).unwrap_or_else(|e| e.exit())
+    } else {
+        Vec::new()
+    };
+    let parallel = value_t!(matches, "parallel", usize).unwrap_or_else(|e| e.exit());
+    let server_timeout = value_t!(matches, "timeout", u64).unwrap_or_else(|e| e.exit());
+    let manifest = PathBuf::from(matches.value_of_os("manifest").unwrap());
+    let protocol = matches.value_of("protocol").unwrap();
     let nick = matches.value_of("nick").unwrap();
     let clan = matches.value_of("clan").unwrap();
 
@@ -786,7 +613,47 @@ fn main() {
         clan: clan.to_owned(),
         timeout: format!("{} (timeout)", nick),
         error: format!("{} (error", nick),
+        server_timeout: Duration::from_secs(server_timeout),
     };
 
-    Main::run::<SocketLoop>(&addresses, config);
+    if parallel == 0 {
+        Error::with_description(
+            "Must scrape at least one server at once",
+            ErrorKind::ValueValidation,
+        )
+        .exit();
+    }
+    if matches.is_present("master") {
+        let url = matches.value_of("master-url").unwrap();
+        let version = match protocol {
+            "0.7" => json::Version::V7,
+            _ => json::Version::V6,
+        };
+        match fetch_servers(url, version) {
+            Ok(servers) => {
+                info!("{} servers from {}", servers.len(), url);
+                addresses.extend(servers);
+            }
+            Err(e) => {
+                error!("can't fetch server list from {}: {}", url, e);
+                process::exit(1);
+            }
+        }
+    }
+    // Connecting to the same server twice is not supported.
+    let mut seen = HashSet::new();
+    addresses.retain(|&addr| seen.insert(addr));
+
+    match protocol {
+        "0.6" => run::<SocketLoop, libtw2_gamenet_teeworlds_0_6::Protocol>(
+            addresses, parallel, &manifest, config,
+        ),
+        "ddnet" => run::<SocketLoop, libtw2_gamenet_ddnet::Protocol>(
+            addresses, parallel, &manifest, config,
+        ),
+        "0.7" => run::<SocketLoop7, libtw2_gamenet_teeworlds_0_7::Protocol>(
+            addresses, parallel, &manifest, config,
+        ),
+        _ => unreachable!(),
+    }
 }
@@ -0,0 +1,242 @@
+//! Record of the archived maps.
+//!
+//! The manifest is a CSV file with one row per map, rows are only ever
+//! appended. Maps are identified by their SHA256.
+
+use libtw2_common::digest::Sha256;
+use libtw2_common::num::Cast;
+use libtw2_event_loop::Addr;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use std::collections::HashSet;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::path::Path;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+#[derive(Deserialize, Serialize)]
+struct Entry {
+    name: String,
+    /// CRC32 of the map in hexadecimal, as in the server list.
+    crc: String,
+    sha256: String,
+    size: u64,
+    /// Server the map was first downloaded from.
+    server: String,
+    /// Seconds since the Unix epoch.
+    first_seen: u64,
+}
+
+pub struct Manifest {
+    writer: csv::Writer<File>,
+    sha256s: HashSet<Sha256>,
+    /// Name and CRC of the archived maps, for servers that don't announce
+    /// the SHA256 of their maps.
+    names: HashSet<(String, i32)>,
+}
+
+impl Manifest {
+    /// Opens the manifest at `path`, creating it if it doesn't exist.
+    ///
+    /// An incomplete last row, left by an interrupted write, is discarded.
+    pub fn open(path: &Path) -> Result<Manifest, csv::Error> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        let complete = contents
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        if complete != contents.len() {
+            warn!("discarding incomplete last row of {}", path.display());
+            file.set_len(complete.u64())?;
+        }
+        let mut sha256s = HashSet::new();
+        let mut names = HashSet::new();
+        for entry in csv::Reader::from_reader(&contents[..complete]).deserialize() {
+            let entry: Entry = entry?;
+            match (entry.sha256.parse(), u32::from_str_radix(&entry.crc, 16)) {
+                (Ok(sha256), Ok(crc)) => {
+                    sha256s.insert(sha256);
+                    names.insert((entry.name, crc as i32));
+                }
+                _ => warn!("invalid manifest entry for {:?}", entry.name),
+            }
+        }
+        info!("{} maps in manifest {}", sha256s.len(), path.display());
+        // Only write the header if we start a new file.
+        let is_new = complete == 0;
+        Ok(Manifest {
+            writer: csv::WriterBuilder::new()
+                .has_headers(is_new)
+                .from_writer(file),
+            sha256s,
+            names,
+        })
+    }
+    /// Checks whether the given map is already archived.
+    pub fn contains(&self, name: &[u8], crc: i32, sha256: Option<Sha256>) -> bool {
+        match sha256 {
+            Some(sha256) => self.sha256s.contains(&sha256),
+            None => {
+                let name = String::from_utf8_lossy(name).into_owned();
+                self.names.contains(&(name, crc))
+            }
+        }
+    }
+    /// Adds a map to the manifest, returns `false` if it was already
+    /// archived.
+    pub fn insert(
+        &mut self,
+        name: &[u8],
+        crc: i32,
+        sha256: Sha256,
+        size: u64,
+        server: Addr,
+    ) -> Result<bool, csv::Error> {
+        let name = String::from_utf8_lossy(name).into_owned();
+        if !self.sha256s.insert(sha256) {
+            return Ok(false);
+        }
+        self.names.insert((name.clone(), crc));
+        let first_seen = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.writer.serialize(Entry {
+            name,
+            crc: format!("{:08x}", crc),
+            sha256: sha256.to_string(),
+            size,
+            server: server.to_string(),
+            first_seen,
+        })?;
+        self.writer.flush()?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Manifest;
+    use libtw2_common::digest::Sha256;
+    use libtw2_event_loop::Addr;
+    use std::fs;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::path::Path;
+
+    fn server() -> Addr {
+        "192.0.2.1:8303".parse().unwrap()
+    }
+
+    fn insert(manifest: &mut Manifest, name: &[u8], crc: i32, sha256: u8) -> bool {
+        manifest
+            .insert(name, crc, Sha256([sha256; 32]), 5805, server())
+            .unwrap()
+    }
+
+    fn rows(path: &Path) -> Vec<String> {
+        let contents = fs::read_to_string(path).unwrap();
+        contents.lines().map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn insert_contains() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("manifest.csv");
+        let mut manifest = Manifest::open(&path).unwrap();
+        assert!(!manifest.contains(b"dm1", 0x12345678, Some(Sha256([1; 32]))));
+        assert!(!manifest.contains(b"dm1", 0x12345678, None));
+
+        assert!(insert(&mut manifest, b"dm1", 0x12345678, 1));
+        assert!(manifest.contains(b"dm1", 0x12345678, Some(Sha256([1; 32]))));
+        assert!(manifest.contains(b"dm1", 0x12345678, None));
+        // The SHA256 takes precedence over name and CRC.
+        assert!(manifest.contains(b"dm2", 0, Some(Sha256([1; 32]))));
+        assert!(!manifest.contains(b"dm1", 0x12345678, Some(Sha256([2; 32]))));
+        assert!(!manifest.contains(b"dm1", 0x12345679, None));
+        assert!(!manifest.contains(b"dm2", 0x12345678, None));
+
+        let rows = rows(&path);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], "name,crc,sha256,size,server,first_seen");
+        assert!(rows[1].starts_with(&format!(
+            "dm1,12345678,{},5805,192.0.2.1:8303,",
+            Sha256([1; 32])
+        )));
+    }
+
+    #[test]
+    fn reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("manifest.csv");
+        {
+            let mut manifest = Manifest::open(&path).unwrap();
+            assert!(insert(&mut manifest, b"dm1", 1, 1));
+            assert!(insert(&mut manifest, b"dm2", -2, 2));
+        }
+        // Simulate a write interrupted in the middle of a row.
+        let complete = fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"dm3,00000003,0303")
+            .unwrap();
+
+        let mut manifest = Manifest::open(&path).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), complete);
+        assert!(manifest.contains(b"dm1", 1, None));
+        assert!(manifest.contains(b"dm2", -2, Some(Sha256([2; 32]))));
+        assert!(!manifest.contains(b"dm3", 3, None));
+        assert!(!insert(&mut manifest, b"dm1", 1, 1));
+        assert!(insert(&mut manifest, b"dm3", 3, 3));
+        drop(manifest);
+
+        // No second header, and the new row starts on a line of its own.
+        let rows = rows(&path);
+        assert_eq!(rows.len(), 4);
+        assert!(rows[1].starts_with("dm1,00000001,"));
+        assert!(rows[2].starts_with("dm2,fffffffe,"));
+        assert!(rows[3].starts_with("dm3,00000003,"));
+        let manifest = Manifest::open(&path).unwrap();
+        assert!(manifest.contains(b"dm3", 3, Some(Sha256([3; 32]))));
+    }
+
+    #[test]
+    fn duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("manifest.csv");
+        let mut manifest = Manifest::open(&path).unwrap();
+        assert!(insert(&mut manifest, b"dm1", 1, 1));
+        // Same map under a different name, not archived again.
+        assert!(!insert(&mut manifest, b"dm1_copy", 1, 1));
+        assert!(!manifest.contains(b"dm1_copy", 1, None));
+        // Same name and CRC, but a different map.
+        assert!(insert(&mut manifest, b"dm1", 1, 2));
+        assert_eq!(rows(&path).len(), 3);
+        drop(manifest);
+
+        // Duplicate rows, e.g. from concurrent runs, are tolerated.
+        let row = rows(&path)[1].clone();
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(format!("{}\n", row).as_bytes())
+            .unwrap();
+        let mut manifest = Manifest::open(&path).unwrap();
+        assert!(manifest.contains(b"dm1", 1, Some(Sha256([1; 32]))));
+        assert!(manifest.contains(b"dm1", 1, Some(Sha256([2; 32]))));
+        assert!(!insert(&mut manifest, b"dm1", 1, 2));
+        assert_eq!(rows(&path).len(), 4);
+    }
+}
@@ -0,0 +1,222 @@
+//! Version-specific messages used for voting through the maps of a server.
+
+use libtw2_client::protocol::Game;
+use libtw2_client::protocol::System;
+use std::fmt;
+
+/// Game messages the downloader reacts to.
+pub enum Message<'a> {
+    /// Message that doesn't matter for voting.
+    Ignored,
+    /// Chat message sent by the server itself.
+    ServerChat(&'a [u8]),
+    Broadcast(&'a [u8]),
+    VoteClearOptions,
+    VoteOptionAdd(&'a [u8]),
+    VoteOptionListAdd(Vec<&'a [u8]>),
+    VoteOptionRemove(&'a [u8]),
+    VoteSet {
+        timeout: i32,
+    },
+    Other(Box<dyn fmt::Debug + 'a>),
+}
+
+/// A protocol version the downloader can scrape.
+pub trait Protocol: libtw2_client::Protocol {
+    fn game_message<'a>(msg: Game<'a, Self>) -> Message<'a>;
+    /// Returns the system messages that should be logged as unprocessed.
+    fn system_message<'a>(msg: System<'a, Self>) -> Option<Box<dyn fmt::Debug + 'a>>;
+    fn call_vote<'a>(option: &'a [u8], reason: &'a [u8]) -> Game<'a, Self>;
+    fn join_game<'a>() -> Game<'a, Self>;
+    fn is_player_info(obj: &Self::SnapObj) -> bool;
+}
+
+mod teeworlds_0_6 {
+    use super::Message;
+    use libtw2_common::num::Cast;
+    use libtw2_gamenet_teeworlds_0_6::enums;
+    use libtw2_gamenet_teeworlds_0_6::enums::Team;
+    use libtw2_gamenet_teeworlds_0_6::msg::game::ClCallVote;
+    use libtw2_gamenet_teeworlds_0_6::msg::game::ClSetTeam;
+    use libtw2_gamenet_teeworlds_0_6::msg::Game;
+    use libtw2_gamenet_teeworlds_0_6::msg::System;
+    use libtw2_gamenet_teeworlds_0_6::Protocol;
+    use libtw2_gamenet_teeworlds_0_6::SnapObj;
+    use std::fmt;
+
+    impl super::Protocol for Protocol {
+        fn game_message<'a>(msg: super::Game<'a, Self>) -> Message<'a> {
+            match msg {
+                Game::SvMotd(..)
+                | Game::SvKillMsg(..)
+                | Game::SvTuneParams(..)
+                | Game::SvWeaponPickup(..)
+                | Game::SvExtraProjectile(..) => Message::Ignored,
+                Game::SvChat(chat) if !chat.team && chat.client_id == -1 => {
+                    Message::ServerChat(chat.message)
+                }
+                Game::SvBroadcast(broadcast) => Message::Broadcast(broadcast.message),
+                Game::SvVoteClearOptions(..) => Message::VoteClearOptions,
+                Game::SvVoteOptionAdd(o) => Message::VoteOptionAdd(o.description),
+                // `num_options` is bounded by the unpacking.
+                Game::SvVoteOptionListAdd(l) => Message::VoteOptionListAdd(
+                    l.description
+                        .iter()
+                        .take(l.num_options.assert_usize())
+                        .cloned()
+                        .collect(),
+                ),
+                Game::SvVoteOptionRemove(o) => Message::VoteOptionRemove(o.description),
+                Game::SvVoteSet(v) => Message::VoteSet { timeout: v.timeout },
+                msg => Message::Other(Box::new(msg)),
+            }
+        }
+        fn system_message<'a>(msg: super::System<'a, Self>) -> Option<Box<dyn fmt::Debug + 'a>> {
+            match msg {
+                System::RconAuthStatus(..) | System::PingReply(..) => None,
+                msg => Some(Box::new(msg)),
+            }
+        }
+        fn call_vote<'a>(option: &'a [u8], reason: &'a [u8]) -> Game<'a> {
+            Game::from(ClCallVote {
+                type_: enums::CL_CALL_VOTE_TYPE_OPTION.as_bytes(),
+                value: option,
+                reason,
+            })
+        }
+        fn join_game<'a>() -> Game<'a> {
+            Game::from(ClSetTeam { team: Team::Red })
+        }
+        fn is_player_info(obj: &SnapObj) -> bool {
+            matches!(obj, SnapObj::PlayerInfo(..))
+        }
+    }
+}
+
+mod ddnet {
+    use super::Message;
+    use libtw2_common::num::Cast;
+    use libtw2_gamenet_ddnet::enums;
+    use libtw2_gamenet_ddnet::enums::Team;
+    use libtw2_gamenet_ddnet::msg::game::ClCallVote;
+    use libtw2_gamenet_ddnet::msg::game::ClSetTeam;
+    use libtw2_gamenet_ddnet::msg::Game;
+    use libtw2_gamenet_ddnet::msg::System;
+    use libtw2_gamenet_ddnet::Protocol;
+    use libtw2_gamenet_ddnet::SnapObj;
+    use std::fmt;
+
+    impl super::Protocol for Protocol {
+        fn game_message<'a>(msg: super::Game<'a, Self>) -> Message<'a> {
+            match msg {
+                Game::SvMotd(..)
+                | Game::SvKillMsg(..)
+                | Game::SvTuneParams(..)
+                | Game::SvWeaponPickup(..) => Message::Ignored,
+                Game::SvChat(chat) if chat.team == 0 && chat.client_id == -1 => {
+                    Message::ServerChat(chat.message)
+                }
+                Game::SvBroadcast(broadcast) => Message::Broadcast(broadcast.message),
+                Game::SvVoteClearOptions(..) => Message::VoteClearOptions,
+                Game::SvVoteOptionAdd(o) => Message::VoteOptionAdd(o.description),
+                // `num_options` is bounded by the unpacking.
+                Game::SvVoteOptionListAdd(l) => Message::VoteOptionListAdd(
+                    l.description
+                        .iter()
+                        .take(l.num_options.assert_usize())
+                        .cloned()
+                        .collect(),
+                ),
+                Game::SvVoteOptionRemove(o) => Message::VoteOptionRemove(o.description),
+                Game::SvVoteSet(v) => Message::VoteSet { timeout: v.timeout },
+                msg => Message::Other(Box::new(msg)),
+            }
+        }
+        fn system_message<'a>(msg: super::System<'a, Self>) -> Option<Box<dyn fmt::Debug + 'a>> {
+            match msg {
+                System::RconAuthStatus(..) | System::PingReply(..) => None,
+                msg => Some(Box::new(msg)),
+            }
+        }
+        fn call_vote<'a>(option: &'a [u8], reason: &'a [u8]) -> Game<'a> {
+            Game::from(ClCallVote {
+                type_: enums::CL_CALL_VOTE_TYPE_OPTION.as_bytes(),
+                value: option,
+                reason,
+            })
+        }
+        fn join_game<'a>() -> Game<'a> {
+            Game::from(ClSetTeam { team: Team::Red })
+        }
+        fn is_player_info(obj: &SnapObj) -> bool {
+            matches!(obj, SnapObj::PlayerInfo(..))
+        }
+    }
+}
+
+mod teeworlds_0_7 {
+    use super::Message;
+    use libtw2_gamenet_teeworlds_0_7::enums;
+    use libtw2_gamenet_teeworlds_0_7::enums::Chat;
+    use libtw2_gamenet_teeworlds_0_7::enums::Team;
+    use libtw2_gamenet_teeworlds_0_7::msg::game::ClCallVote;
+    use libtw2_gamenet_teeworlds_0_7::msg::game::ClSetTeam;
+    use libtw2_gamenet_teeworlds_0_7::msg::Game;
+    use libtw2_gamenet_teeworlds_0_7::msg::System;
+    use libtw2_gamenet_teeworlds_0_7::Protocol;
+    use libtw2_gamenet_teeworlds_0_7::SnapObj;
+    use std::fmt;
+
+    impl super::Protocol for Protocol {
+        fn game_message<'a>(msg: super::Game<'a, Self>) -> Message<'a> {
+            match msg {
+                Game::SvMotd(..)
+                | Game::SvKillMsg(..)
+                | Game::SvTuneParams(..)
+                | Game::SvWeaponPickup(..)
+                | Game::SvExtraProjectile(..)
+                | Game::SvTeam(..)
+                | Game::SvServerSettings(..)
+                | Game::SvClientInfo(..)
+                | Game::SvClientDrop(..)
+                | Game::SvGameInfo(..)
+                | Game::SvGameMsg(..)
+                | Game::SvVoteStatus(..)
+                | Game::SvCommandInfo(..)
+                | Game::SvCommandInfoRemove(..) => Message::Ignored,
+                Game::SvChat(chat) if chat.mode == Chat::All && chat.client_id == -1 => {
+                    Message::ServerChat(chat.message)
+                }
+                Game::SvBroadcast(broadcast) => Message::Broadcast(broadcast.message),
+                Game::SvVoteClearOptions(..) => Message::VoteClearOptions,
+                Game::SvVoteOptionAdd(o) => Message::VoteOptionAdd(o.description),
+                // The options of this message aren't decoded, so only
+                // options added later on are seen.
+                Game::SvVoteOptionListAdd(..) => Message::VoteOptionListAdd(Vec::new()),
+                Game::SvVoteOptionRemove(o) => Message::VoteOptionRemove(o.description),
+                Game::SvVoteSet(v) => Message::VoteSet { timeout: v.timeout },
+                msg => Message::Other(Box::new(msg)),
+            }
+        }
+        fn system_message<'a>(msg: super::System<'a, Self>) -> Option<Box<dyn fmt::Debug + 'a>> {
+            match msg {
+                System::RconAuthOn(..) | System::RconAuthOff(..) | System::PingReply(..) => None,
+                msg => Some(Box::new(msg)),
+            }
+        }
+        fn call_vote<'a>(option: &'a [u8], reason: &'a [u8]) -> Game<'a> {
+            Game::from(ClCallVote {
+                type_: enums::CL_CALL_VOTE_TYPE_OPTION.as_bytes(),
+                value: option,
+                reason,
+                force: false,
+            })
+        }
+        fn join_game<'a>() -> Game<'a> {
+            Game::from(ClSetTeam { team: Team::Red })
+        }
+        fn is_player_info(obj: &SnapObj) -> bool {
+            matches!(obj, SnapObj::PlayerInfo(..))
+        }
+    }
+}
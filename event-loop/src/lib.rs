@@ -15,6 +15,9 @@ use log::LogLevel;
 use std::cmp;
 use std::fmt;
 
+mod loop7;
+
+pub use self::loop7::SocketLoop7;
 pub use libtw2_net::collections;
 pub use libtw2_net::net::PeerId;
 pub use libtw2_net::Timeout;
@@ -25,7 +28,6 @@ pub type Chunk<'a> = libtw2_net::net::Chunk<'a>;
 pub type ConnlessChunk<'a> = libtw2_net::net::ConnlessChunk<'a, Addr>;
 
 pub trait Loop {
-    fn accept_connections_on_port(port: u16) -> Self;
     fn client() -> Self;
     fn run<A: Application<Self>>(self, application: A)
     where
@@ -33,11 +35,17 @@ pub trait Loop {
 
     fn time(&mut self) -> Timestamp;
     fn connect(&mut self, addr: Addr) -> PeerId;
+    fn peer_addr(&mut self, pid: PeerId) -> Addr;
     fn disconnect(&mut self, pid: PeerId, reason: &[u8]);
     fn send_connless(&mut self, addr: Addr, data: &[u8]);
     fn send(&mut self, chunk: Chunk);
     fn force_flush(&mut self, pid: PeerId);
     fn flush(&mut self, pid: PeerId);
+}
+
+/// Event loop that can also handle incoming connections.
+pub trait ServerLoop: Loop {
+    fn accept_connections_on_port(port: u16) -> Self;
     fn ignore(&mut self, pid: PeerId);
     fn accept(&mut self, pid: PeerId);
     fn reject(&mut self, pid: PeerId, reason: &[u8]);
@@ -62,15 +70,6 @@ pub struct SocketLoop {
 }
 
 impl Loop for SocketLoop {
-    fn accept_connections_on_port(port: u16) -> SocketLoop {
-        SocketLoop {
-            socket: Socket::bound(port).unwrap(),
-            net: Net::server(),
-            want_to_flush: PeerSet::new(),
-            disconnected: Default::default(),
-            server: true,
-        }
-    }
     fn client() -> SocketLoop {
         SocketLoop {
             socket: Socket::new().unwrap(),
@@ -151,6 +150,9 @@ impl Loop for SocketLoop {
         res.unwrap();
         pid
     }
+    fn peer_addr(&mut self, pid: PeerId) -> Addr {
+        self.net.peer_addr(pid)
+    }
     fn disconnect(&mut self, pid: PeerId, reason: &[u8]) {
         if self.want_to_flush.contains(pid) {
             self.net.flush(&mut self.socket, pid).unwrap();
@@ -177,6 +179,18 @@ impl Loop for SocketLoop {
     fn flush(&mut self, pid: PeerId) {
         self.want_to_flush.insert(pid);
     }
+}
+
+impl ServerLoop for SocketLoop {
+    fn accept_connections_on_port(port: u16) -> SocketLoop {
+        SocketLoop {
+            socket: Socket::bound(port).unwrap(),
+            net: Net::server(),
+            want_to_flush: PeerSet::new(),
+            disconnected: Default::default(),
+            server: true,
+        }
+    }
     fn ignore(&mut self, pid: PeerId) {
         self.net.ignore(pid);
     }
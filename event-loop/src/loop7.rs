@@ -0,0 +1,256 @@
+use crate::Addr;
+use crate::Application;
+use crate::Chunk;
+use crate::ConnlessChunk;
+use crate::Loop;
+use crate::PeerId;
+use crate::Timestamp;
+use crate::Warn;
+use arrayvec::ArrayVec;
+use libtw2_common::Takeable;
+use libtw2_net::collections::PeerMap;
+use libtw2_net::collections::PeerSet;
+use libtw2_net::connection7;
+use libtw2_net::connection7::Connection;
+use libtw2_net::connection7::ReceiveChunk;
+use libtw2_net::net::Callback;
+use libtw2_socket::Socket;
+use std::cmp;
+use std::collections::HashMap;
+use std::io;
+
+struct Peer {
+    addr: Addr,
+    conn: Connection,
+}
+
+struct ConnectionCallback<'a> {
+    socket: &'a mut Socket,
+    addr: Addr,
+}
+
+// Create `ConnectionCallback`.
+fn cc(socket: &mut Socket, addr: Addr) -> ConnectionCallback<'_> {
+    ConnectionCallback { socket, addr }
+}
+
+impl<'a> connection7::Callback for ConnectionCallback<'a> {
+    type Error = io::Error;
+    fn secure_random(&mut self, buffer: &mut [u8]) {
+        self.socket.secure_random(buffer)
+    }
+    fn send(&mut self, data: &[u8]) -> Result<(), io::Error> {
+        self.socket.send(self.addr, data)
+    }
+    fn time(&mut self) -> Timestamp {
+        Callback::time(self.socket)
+    }
+}
+
+/// Event loop speaking the Teeworlds 0.7 transport.
+///
+/// Only supports outgoing connections, there's no 0.7 counterpart to
+/// `libtw2_net::Net` that would handle incoming ones. Hence it only
+/// implements `Loop`, not `ServerLoop`.
+pub struct SocketLoop7 {
+    socket: Socket,
+    peers: PeerMap<Peer>,
+    pids: HashMap<Addr, PeerId>,
+    next_pid: u32,
+    want_to_flush: PeerSet,
+    disconnected: Takeable<PeerMap<ArrayVec<[u8; 1024]>>>,
+}
+
+impl SocketLoop7 {
+    fn report_disconnects<A: Application<SocketLoop7>>(&mut self, application: &mut A) {
+        let mut disconnected = self.disconnected.take();
+        for (pid, reason) in disconnected.drain() {
+            application.on_disconnect(self, pid, false, &reason);
+        }
+        self.disconnected.restore(disconnected);
+    }
+    fn remove_peer(&mut self, pid: PeerId) {
+        let addr = self.peers.get(pid).unwrap().addr;
+        self.pids.remove(&addr);
+        self.peers.remove(pid);
+        if self.want_to_flush.contains(pid) {
+            self.want_to_flush.remove(pid);
+        }
+    }
+    fn on_data<A: Application<SocketLoop7>>(
+        &mut self,
+        application: &mut A,
+        addr: Addr,
+        data: &[u8],
+    ) {
+        let pid = match self.pids.get(&addr) {
+            Some(&pid) => pid,
+            None => {
+                debug!("{}: packet from unknown address", addr);
+                return;
+            }
+        };
+        let mut buf: ArrayVec<[u8; 4096]> = ArrayVec::new();
+        let (iter, res) = {
+            let peer = self.peers.get_mut(pid).unwrap();
+            peer.conn.feed(
+                &mut cc(&mut self.socket, addr),
+                &mut Warn(addr, data),
+                data,
+                &mut buf,
+            )
+        };
+        res.unwrap();
+        for chunk in iter {
+            // The application might have disconnected the peer while
+            // processing the previous chunks.
+            if self.peers.get(pid).is_none() {
+                break;
+            }
+            match chunk {
+                ReceiveChunk::Connected(data, vital) => {
+                    application.on_packet(self, Chunk { pid, vital, data })
+                }
+                ReceiveChunk::Connless(data) => application.on_connless_packet(
+                    self,
+                    ConnlessChunk {
+                        addr,
+                        pid: Some(pid),
+                        extra_data: None,
+                        data,
+                    },
+                ),
+                ReceiveChunk::Ready => application.on_ready(self, pid),
+                ReceiveChunk::Disconnect(reason) => {
+                    self.remove_peer(pid);
+                    application.on_disconnect(self, pid, true, reason);
+                }
+            }
+        }
+    }
+}
+
+impl Loop for SocketLoop7 {
+    fn client() -> SocketLoop7 {
+        SocketLoop7 {
+            socket: Socket::new().unwrap(),
+            peers: PeerMap::new(),
+            pids: HashMap::new(),
+            next_pid: 0,
+            want_to_flush: PeerSet::new(),
+            disconnected: Default::default(),
+        }
+    }
+    fn run<A: Application<SocketLoop7>>(mut self, mut application: A) {
+        let mut buf: ArrayVec<[u8; 4096]> = ArrayVec::new();
+
+        loop {
+            for (pid, peer) in self.peers.iter_mut() {
+                if let Err(e) = peer.conn.tick(&mut cc(&mut self.socket, peer.addr)) {
+                    warn!("{}: error while ticking pid={:?}, {}", peer.addr, pid, e);
+                }
+            }
+            application.on_tick(&mut self);
+
+            for pid in self.want_to_flush.drain() {
+                let peer = self.peers.get_mut(pid).unwrap();
+                peer.conn
+                    .flush(&mut cc(&mut self.socket, peer.addr))
+                    .unwrap();
+            }
+
+            self.report_disconnects(&mut application);
+
+            let net_timeout = self
+                .peers
+                .values()
+                .map(|p| p.conn.needs_tick())
+                .min()
+                .unwrap_or_default();
+            let sleep_timeout = cmp::min(net_timeout, application.needs_tick());
+            let sleep_duration = sleep_timeout.time_from(self.socket.time());
+            if sleep_duration.is_none() {
+                break;
+            }
+            self.socket.sleep(sleep_duration).unwrap();
+
+            while let Some(res) = {
+                buf.clear();
+                self.socket.receive(&mut buf)
+            } {
+                let (addr, data) = res.unwrap();
+                self.on_data(&mut application, addr, data);
+            }
+
+            self.report_disconnects(&mut application);
+        }
+    }
+    fn time(&mut self) -> Timestamp {
+        self.socket.time()
+    }
+    fn connect(&mut self, addr: Addr) -> PeerId {
+        assert!(
+            !self.pids.contains_key(&addr),
+            "already connected to {}",
+            addr
+        );
+        let pid = PeerId(self.next_pid);
+        self.next_pid = self.next_pid.wrapping_add(1);
+        let mut conn = Connection::new();
+        conn.connect(&mut cc(&mut self.socket, addr)).unwrap();
+        self.peers.insert(pid, Peer { addr, conn });
+        self.pids.insert(addr, pid);
+        pid
+    }
+    fn peer_addr(&mut self, pid: PeerId) -> Addr {
+        self.peers.get(pid).unwrap().addr
+    }
+    fn disconnect(&mut self, pid: PeerId, reason: &[u8]) {
+        let peer = self.peers.get_mut(pid).unwrap();
+        let cb = &mut cc(&mut self.socket, peer.addr);
+        if self.want_to_flush.contains(pid) {
+            peer.conn.flush(cb).unwrap();
+        }
+        peer.conn.disconnect(cb, reason).unwrap();
+        self.remove_peer(pid);
+        self.disconnected
+            .insert(pid, reason.iter().cloned().collect());
+    }
+    fn send_connless(&mut self, addr: Addr, data: &[u8]) {
+        // Connectionless packets carry the tokens of a connection in 0.7,
+        // they can only be sent to peers we're connected to.
+        let pid = match self.pids.get(&addr) {
+            Some(&pid) => pid,
+            None => {
+                warn!("{}: dropping connless packet to unknown address", addr);
+                return;
+            }
+        };
+        let peer = self.peers.get_mut(pid).unwrap();
+        peer.conn
+            .send_connless(&mut cc(&mut self.socket, addr), data)
+            .unwrap();
+    }
+    fn send(&mut self, chunk: Chunk) {
+        let peer = self.peers.get_mut(chunk.pid).unwrap();
+        peer.conn
+            .send(
+                &mut cc(&mut self.socket, peer.addr),
+                chunk.data,
+                chunk.vital,
+            )
+            .unwrap();
+    }
+    fn force_flush(&mut self, pid: PeerId) {
+        if self.want_to_flush.contains(pid) {
+            self.want_to_flush.remove(pid);
+        }
+        let peer = self.peers.get_mut(pid).unwrap();
+        peer.conn
+            .flush(&mut cc(&mut self.socket, peer.addr))
+            .unwrap();
+    }
+    fn flush(&mut self, pid: PeerId) {
+        self.want_to_flush.insert(pid);
+    }
+}
@@ -1,5 +1,6 @@
 pub mod debug;
 pub mod error;
 pub mod msg;
+pub mod serialize;
 pub mod snap_obj;
 pub mod traits;
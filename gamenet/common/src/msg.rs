@@ -59,7 +59,7 @@ impl AddrPackedSliceExt for [AddrPacked] {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 pub struct TuneParam(pub i32);
 
 impl TuneParam {
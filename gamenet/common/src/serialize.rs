@@ -0,0 +1,568 @@
+//! Helpers for the optional serde support of the generated protocol crates.
+//!
+//! Byte strings are serialized as strings if they're valid UTF-8 and as byte
+//! arrays otherwise. Since the generated structs borrow their byte strings,
+//! deserializing them also borrows from the input. Inputs that don't contain
+//! the byte strings verbatim, e.g. JSON strings with escape sequences or byte
+//! strings serialized as arrays, have to be deserialized through a
+//! [`Buffer`].
+//!
+//! Strings stored as integers in snapshot objects are serialized as strings,
+//! too.
+
+use crate::msg::AddrPacked;
+use crate::msg::AddrPackedSliceExt;
+use crate::msg::ClientsData;
+use libtw2_packer::string_to_ints;
+use libtw2_warn::Ignore;
+use serde::de;
+use serde::de::value::MapDeserializer;
+use serde::de::value::SeqDeserializer;
+use serde::de::IntoDeserializer;
+use serde::forward_to_deserialize_any;
+use serde::ser::SerializeTuple;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::str;
+
+fn serialize_bytes<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    match str::from_utf8(bytes) {
+        Ok(s) => serializer.serialize_str(s),
+        Err(_) => serializer.serialize_bytes(bytes),
+    }
+}
+
+fn deserialize_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<&'de [u8], D::Error> {
+    deserializer.deserialize_bytes(BorrowedBytesVisitor)
+}
+
+struct BorrowedBytesVisitor;
+
+impl<'de> de::Visitor<'de> for BorrowedBytesVisitor {
+    type Value = &'de [u8];
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a borrowed string or byte string, use `serialize::Buffer` for other inputs")
+    }
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<&'de [u8], E> {
+        Ok(v.as_bytes())
+    }
+    fn visit_borrowed_bytes<E: de::Error>(self, v: &'de [u8]) -> Result<&'de [u8], E> {
+        Ok(v)
+    }
+}
+
+/// Owned copy of serialized data that the generated structs can borrow
+/// from.
+///
+/// ```ignore
+/// let buffer: Buffer = serde_json::from_str(json)?;
+/// let msg: Game = buffer.deserialize()?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct Buffer(Content);
+
+impl Buffer {
+    /// Deserializes a value borrowing its byte strings from the buffer.
+    pub fn deserialize<'a, T: Deserialize<'a>>(&'a self) -> Result<T, de::value::Error> {
+        T::deserialize(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Buffer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Buffer, D::Error> {
+        Content::deserialize(deserializer).map(Buffer)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Content {
+    Unit,
+    Bool(bool),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    Some(Box<Content>),
+    /// Sequences of small integers are kept as bytes, too, so that byte
+    /// strings serialized as arrays can be borrowed.
+    Seq(Vec<Content>, Option<Vec<u8>>),
+    Map(Vec<(Content, Content)>),
+}
+
+impl<'de> Deserialize<'de> for Content {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Content, D::Error> {
+        deserializer.deserialize_any(ContentVisitor)
+    }
+}
+
+struct ContentVisitor;
+
+impl<'de> de::Visitor<'de> for ContentVisitor {
+    type Value = Content;
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any value")
+    }
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Content, E> {
+        Ok(Content::Bool(v))
+    }
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Content, E> {
+        Ok(Content::I64(v))
+    }
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Content, E> {
+        Ok(Content::U64(v))
+    }
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Content, E> {
+        Ok(Content::F64(v))
+    }
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Content, E> {
+        Ok(Content::String(v.to_owned()))
+    }
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Content, E> {
+        Ok(Content::String(v))
+    }
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Content, E> {
+        Ok(Content::Bytes(v.to_owned()))
+    }
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Content, E> {
+        Ok(Content::Bytes(v))
+    }
+    fn visit_none<E: de::Error>(self) -> Result<Content, E> {
+        Ok(Content::Unit)
+    }
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Content, D::Error> {
+        Ok(Content::Some(Box::new(Content::deserialize(deserializer)?)))
+    }
+    fn visit_unit<E: de::Error>(self) -> Result<Content, E> {
+        Ok(Content::Unit)
+    }
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Content, A::Error> {
+        let mut elements = Vec::new();
+        while let Some(e) = seq.next_element()? {
+            elements.push(e);
+        }
+        let bytes = elements
+            .iter()
+            .map(|e| match *e {
+                Content::U64(b) if b <= 0xff => Some(b as u8),
+                _ => None,
+            })
+            .collect();
+        Ok(Content::Seq(elements, bytes))
+    }
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Content, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Content::Map(entries))
+    }
+}
+
+impl<'a> IntoDeserializer<'a, de::value::Error> for &'a Content {
+    type Deserializer = &'a Content;
+    fn into_deserializer(self) -> &'a Content {
+        self
+    }
+}
+
+impl<'a> Deserializer<'a> for &'a Content {
+    type Error = de::value::Error;
+    fn deserialize_any<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match *self {
+            Content::Unit => visitor.visit_unit(),
+            Content::Bool(v) => visitor.visit_bool(v),
+            Content::U64(v) => visitor.visit_u64(v),
+            Content::I64(v) => visitor.visit_i64(v),
+            Content::F64(v) => visitor.visit_f64(v),
+            Content::String(ref v) => visitor.visit_borrowed_str(v),
+            Content::Bytes(ref v) => visitor.visit_borrowed_bytes(v),
+            Content::Some(ref v) => visitor.visit_some(&**v),
+            Content::Seq(ref v, _) => {
+                let mut seq = SeqDeserializer::new(v.iter());
+                let result = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(result)
+            }
+            Content::Map(ref v) => {
+                let mut map = MapDeserializer::new(v.iter().map(|(k, v)| (k, v)));
+                let result = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(result)
+            }
+        }
+    }
+    fn deserialize_bytes<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match *self {
+            Content::Seq(_, Some(ref bytes)) => visitor.visit_borrowed_bytes(bytes),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+    fn deserialize_byte_buf<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_bytes(visitor)
+    }
+    fn deserialize_option<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match *self {
+            Content::Unit => visitor.visit_none(),
+            Content::Some(ref v) => visitor.visit_some(&**v),
+            _ => visitor.visit_some(self),
+        }
+    }
+    fn deserialize_enum<V: de::Visitor<'a>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match *self {
+            Content::String(ref variant) => {
+                visitor.visit_enum(variant.as_str().into_deserializer())
+            }
+            Content::Map(ref v) if v.len() == 1 => visitor.visit_enum(Enum {
+                variant: &v[0].0,
+                value: &v[0].1,
+            }),
+            _ => Err(de::Error::invalid_type(
+                de::Unexpected::Other("non-enum"),
+                &"a string or a map with a single entry",
+            )),
+        }
+    }
+    forward_to_deserialize_any! {
+        <W: Visitor<'a>>
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct newtype_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+/// Externally tagged enum variant with data, `{"variant": value}`.
+struct Enum<'a> {
+    variant: &'a Content,
+    value: &'a Content,
+}
+
+impl<'a> de::EnumAccess<'a> for Enum<'a> {
+    type Error = de::value::Error;
+    type Variant = &'a Content;
+    fn variant_seed<V: de::DeserializeSeed<'a>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, &'a Content), de::value::Error> {
+        Ok((seed.deserialize(self.variant)?, self.value))
+    }
+}
+
+impl<'a> de::VariantAccess<'a> for &'a Content {
+    type Error = de::value::Error;
+    fn unit_variant(self) -> Result<(), de::value::Error> {
+        de::Deserialize::deserialize(self)
+    }
+    fn newtype_variant_seed<T: de::DeserializeSeed<'a>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, de::value::Error> {
+        seed.deserialize(self)
+    }
+    fn tuple_variant<V: de::Visitor<'a>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, de::value::Error> {
+        self.deserialize_any(visitor)
+    }
+    fn struct_variant<V: de::Visitor<'a>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, de::value::Error> {
+        self.deserialize_any(visitor)
+    }
+}
+
+struct Bytes<'a>(&'a [u8]);
+
+impl<'a> Serialize for Bytes<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_bytes(self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Bytes<'de>, D::Error> {
+        deserialize_bytes(deserializer).map(Bytes)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct IntString<const N: usize>([i32; N]);
+
+impl<const N: usize> Serialize for IntString<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut bytes = Vec::with_capacity(N * mem::size_of::<i32>());
+        for &i in &self.0 {
+            bytes.extend(i.to_be_bytes().iter().map(|&b| b.wrapping_sub(0x80)));
+        }
+        // The last byte is always treated as the NUL terminator.
+        bytes.pop();
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        serialize_bytes(&bytes[..len], serializer)
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for IntString<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<IntString<N>, D::Error> {
+        deserializer.deserialize_byte_buf(IntStringVisitor)
+    }
+}
+
+struct IntStringVisitor<const N: usize>;
+
+impl<'de, const N: usize> de::Visitor<'de> for IntStringVisitor<N> {
+    type Value = IntString<N>;
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a string without NUL bytes, shorter than {} bytes",
+            N * mem::size_of::<i32>()
+        )
+    }
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<IntString<N>, E> {
+        self.visit_bytes(v.as_bytes())
+    }
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<IntString<N>, E> {
+        if v.len() >= N * mem::size_of::<i32>() {
+            return Err(E::invalid_length(v.len(), &self));
+        }
+        if v.contains(&0) {
+            return Err(E::invalid_value(de::Unexpected::Bytes(v), &self));
+        }
+        let mut result = [0; N];
+        string_to_ints(&mut result, v);
+        Ok(IntString(result))
+    }
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<IntString<N>, A::Error> {
+        let mut bytes = Vec::new();
+        while let Some(b) = seq.next_element()? {
+            bytes.push(b);
+        }
+        self.visit_bytes(&bytes)
+    }
+}
+
+fn serialize_array<S, T, const N: usize>(array: &[T; N], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Serialize,
+{
+    let mut tuple = serializer.serialize_tuple(N)?;
+    for e in array {
+        tuple.serialize_element(e)?;
+    }
+    tuple.end()
+}
+
+fn deserialize_array<'de, D, E, T, F, const N: usize>(
+    deserializer: D,
+    default: T,
+    convert: F,
+) -> Result<[T; N], D::Error>
+where
+    D: Deserializer<'de>,
+    E: Deserialize<'de>,
+    T: Copy,
+    F: Fn(E) -> T,
+{
+    deserializer.deserialize_tuple(
+        N,
+        ArrayVisitor {
+            default,
+            convert,
+            element: PhantomData,
+        },
+    )
+}
+
+struct ArrayVisitor<E, T, F, const N: usize> {
+    default: T,
+    convert: F,
+    element: PhantomData<E>,
+}
+
+impl<'de, E, T, F, const N: usize> de::Visitor<'de> for ArrayVisitor<E, T, F, N>
+where
+    E: Deserialize<'de>,
+    T: Copy,
+    F: Fn(E) -> T,
+{
+    type Value = [T; N];
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an array of length {}", N)
+    }
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<[T; N], A::Error> {
+        let mut result = [self.default; N];
+        for (i, r) in result.iter_mut().enumerate() {
+            let element = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+            *r = (self.convert)(element);
+        }
+        Ok(result)
+    }
+}
+
+/// Byte strings, `&'a [u8]`.
+pub mod bytes {
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    pub fn serialize<S: Serializer>(bytes: &&[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        super::serialize_bytes(bytes, serializer)
+    }
+    pub fn deserialize<'de: 'a, 'a, D>(deserializer: D) -> Result<&'a [u8], D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        super::deserialize_bytes(deserializer)
+    }
+}
+
+/// Optional byte strings, `Option<&'a [u8]>`.
+pub mod option_bytes {
+    use super::Bytes;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serialize;
+    use serde::Serializer;
+
+    pub fn serialize<S>(bytes: &Option<&[u8]>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        bytes.map(Bytes).serialize(serializer)
+    }
+    pub fn deserialize<'de: 'a, 'a, D>(deserializer: D) -> Result<Option<&'a [u8]>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Option::<Bytes>::deserialize(deserializer)?.map(|b| b.0))
+    }
+}
+
+/// Arrays of byte strings, `[&'a [u8]; N]`.
+pub mod bytes_array {
+    use super::Bytes;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    pub fn serialize<S, const N: usize>(
+        array: &[&[u8]; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        super::serialize_array(&array.map(Bytes), serializer)
+    }
+    pub fn deserialize<'de: 'a, 'a, D, const N: usize>(
+        deserializer: D,
+    ) -> Result<[&'a [u8]; N], D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        super::deserialize_array(deserializer, &[][..], |b: Bytes<'de>| b.0)
+    }
+}
+
+/// Arrays that are too long for serde's built-in implementations, `[T; N]`.
+pub mod array {
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serialize;
+    use serde::Serializer;
+
+    pub fn serialize<S, T, const N: usize>(array: &[T; N], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        super::serialize_array(array, serializer)
+    }
+    pub fn deserialize<'de, D, T, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Copy + Default + Deserialize<'de>,
+    {
+        super::deserialize_array(deserializer, T::default(), |e| e)
+    }
+}
+
+/// Strings stored as integers in snapshot objects, `[i32; N]`.
+pub mod int_string {
+    use super::IntString;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serialize;
+    use serde::Serializer;
+
+    pub fn serialize<S, const N: usize>(ints: &[i32; N], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        IntString(*ints).serialize(serializer)
+    }
+    pub fn deserialize<'de, D, const N: usize>(deserializer: D) -> Result<[i32; N], D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(IntString::deserialize(deserializer)?.0)
+    }
+}
+
+/// Packed server addresses, `&'a [AddrPacked]`.
+pub mod addr_packed_slice {
+    use super::AddrPacked;
+    use super::AddrPackedSliceExt;
+    use super::Ignore;
+    use serde::de;
+    use serde::Deserializer;
+    use serde::Serializer;
+    use std::mem;
+
+    pub fn serialize<S>(addrs: &&[AddrPacked], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(addrs.as_bytes())
+    }
+    pub fn deserialize<'de: 'a, 'a, D>(deserializer: D) -> Result<&'a [AddrPacked], D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bytes = super::deserialize_bytes(deserializer)?;
+        if bytes.len() % mem::size_of::<AddrPacked>() != 0 {
+            return Err(de::Error::invalid_length(
+                bytes.len(),
+                &"a multiple of the packed address size",
+            ));
+        }
+        Ok(AddrPackedSliceExt::from_bytes(&mut Ignore, bytes))
+    }
+}
+
+impl<'a> Serialize for ClientsData<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_bytes(self.as_bytes(), serializer)
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for ClientsData<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ClientsData<'a>, D::Error> {
+        deserialize_bytes(deserializer).map(ClientsData::from_bytes)
+    }
+}
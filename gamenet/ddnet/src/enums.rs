@@ -26,6 +26,8 @@ pub const EMOTE_BLINK: i32 = 5;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Emote {
     Normal,
     Pain,
@@ -46,6 +48,8 @@ pub const POWERUP_ARMOR_LASER: i32 = 7;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Powerup {
     Health,
     Armor,
@@ -76,6 +80,8 @@ pub const EMOTICON_QUESTION: i32 = 15;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Emoticon {
     Oop,
     Exclamation,
@@ -102,6 +108,8 @@ pub const AUTHED_ADMIN: i32 = 3;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Authed {
     No,
     Helper,
@@ -123,6 +131,8 @@ pub const ENTITYCLASS_PICKUP: i32 = 10;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Entityclass {
     Projectile,
     Door,
@@ -147,6 +157,8 @@ pub const LASERTYPE_PLASMA: i32 = 6;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Lasertype {
     Rifle,
     Shotgun,
@@ -166,6 +178,8 @@ pub const LASERDRAGGERTYPE_STRONG_NW: i32 = 5;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Laserdraggertype {
     Weak,
     WeakNw,
@@ -182,6 +196,8 @@ pub const LASERGUNTYPE_EXPFREEZE: i32 = 3;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Laserguntype {
     Unfreeze,
     Explosive,
@@ -198,6 +214,8 @@ pub const TEAM_WHISPER_RECV: i32 = 3;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Team {
     All = -2,
     Spectators,
@@ -215,6 +233,8 @@ pub const SAVESTATE_ERROR: i32 = 4;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Savestate {
     Pending,
     Done,
@@ -232,6 +252,8 @@ pub const WEAPON_NINJA: i32 = 5;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Weapon {
     Hammer,
     Pistol,
@@ -285,6 +307,8 @@ pub const SOUND_MENU: i32 = 40;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Sound {
     GunFire,
     ShotgunFire,
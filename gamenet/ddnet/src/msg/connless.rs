@@ -81,15 +81,17 @@ pub const FORWARD_OK: &'static [u8; 8] = b"\xff\xff\xff\xfffwok";
 pub const FORWARD_ERROR: &'static [u8; 8] = b"\xff\xff\xff\xfffwer";
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Connless<'a> {
     RequestList(RequestList),
-    List(List<'a>),
+    List(#[cfg_attr(feature = "serde", serde(borrow))] List<'a>),
     RequestCount(RequestCount),
     Count(Count),
     RequestInfo(RequestInfo),
-    Info(Info<'a>),
-    InfoExtended(InfoExtended<'a>),
-    InfoExtendedMore(InfoExtendedMore<'a>),
+    Info(#[cfg_attr(feature = "serde", serde(borrow))] Info<'a>),
+    InfoExtended(#[cfg_attr(feature = "serde", serde(borrow))] InfoExtended<'a>),
+    InfoExtendedMore(#[cfg_attr(feature = "serde", serde(borrow))] InfoExtendedMore<'a>),
     Heartbeat(Heartbeat),
     ForwardCheck(ForwardCheck),
     ForwardResponse(ForwardResponse),
@@ -250,82 +252,109 @@ impl<'a> From<ForwardError> for Connless<'a> {
     }
 }
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RequestList;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct List<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::addr_packed_slice"))]
     pub servers: &'a [AddrPacked],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RequestCount;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Count {
     pub count: u16,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RequestInfo {
     pub token: u8,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Info<'a> {
     pub token: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub version: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub map: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub game_type: &'a [u8],
     pub flags: i32,
     pub num_players: i32,
     pub max_players: i32,
     pub num_clients: i32,
     pub max_clients: i32,
+    #[cfg_attr(feature = "serde", serde(borrow))]
     pub clients: ClientsData<'a>,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct InfoExtended<'a> {
     pub token: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub version: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub map: &'a [u8],
     pub map_crc: i32,
     pub map_size: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub game_type: &'a [u8],
     pub flags: i32,
     pub num_players: i32,
     pub max_players: i32,
     pub num_clients: i32,
     pub max_clients: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub reserved: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow))]
     pub clients: ClientsData<'a>,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct InfoExtendedMore<'a> {
     pub token: i32,
     pub packet_no: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub reserved: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow))]
     pub clients: ClientsData<'a>,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Heartbeat {
     pub alt_port: u16,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ForwardCheck;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ForwardResponse;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ForwardOk;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ForwardError;
 
 impl RequestList {
@@ -96,10 +96,12 @@ pub const SV_MODERATOR_ALERT: Uuid = Uuid::from_u128(0xd7c55683_7983_32f0_8d9a_8
 pub const CL_ENABLE_SPECTATOR_COUNT: Uuid = Uuid::from_u128(0xe19b66e8_0646_351b_aa03_d4aba7b9545f);
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Game<'a> {
-    SvMotd(SvMotd<'a>),
-    SvBroadcast(SvBroadcast<'a>),
-    SvChat(SvChat<'a>),
+    SvMotd(#[cfg_attr(feature = "serde", serde(borrow))] SvMotd<'a>),
+    SvBroadcast(#[cfg_attr(feature = "serde", serde(borrow))] SvBroadcast<'a>),
+    SvChat(#[cfg_attr(feature = "serde", serde(borrow))] SvChat<'a>),
     SvKillMsg(SvKillMsg),
     SvSoundGlobal(SvSoundGlobal),
     SvTuneParams(SvTuneParams),
@@ -108,20 +110,20 @@ pub enum Game<'a> {
     SvWeaponPickup(SvWeaponPickup),
     SvEmoticon(SvEmoticon),
     SvVoteClearOptions(SvVoteClearOptions),
-    SvVoteOptionListAdd(SvVoteOptionListAdd<'a>),
-    SvVoteOptionAdd(SvVoteOptionAdd<'a>),
-    SvVoteOptionRemove(SvVoteOptionRemove<'a>),
-    SvVoteSet(SvVoteSet<'a>),
+    SvVoteOptionListAdd(#[cfg_attr(feature = "serde", serde(borrow))] SvVoteOptionListAdd<'a>),
+    SvVoteOptionAdd(#[cfg_attr(feature = "serde", serde(borrow))] SvVoteOptionAdd<'a>),
+    SvVoteOptionRemove(#[cfg_attr(feature = "serde", serde(borrow))] SvVoteOptionRemove<'a>),
+    SvVoteSet(#[cfg_attr(feature = "serde", serde(borrow))] SvVoteSet<'a>),
     SvVoteStatus(SvVoteStatus),
-    ClSay(ClSay<'a>),
+    ClSay(#[cfg_attr(feature = "serde", serde(borrow))] ClSay<'a>),
     ClSetTeam(ClSetTeam),
     ClSetSpectatorMode(ClSetSpectatorMode),
-    ClStartInfo(ClStartInfo<'a>),
-    ClChangeInfo(ClChangeInfo<'a>),
+    ClStartInfo(#[cfg_attr(feature = "serde", serde(borrow))] ClStartInfo<'a>),
+    ClChangeInfo(#[cfg_attr(feature = "serde", serde(borrow))] ClChangeInfo<'a>),
     ClKill(ClKill),
     ClEmoticon(ClEmoticon),
     ClVote(ClVote),
-    ClCallVote(ClCallVote<'a>),
+    ClCallVote(#[cfg_attr(feature = "serde", serde(borrow))] ClCallVote<'a>),
     ClIsDdnetLegacy(ClIsDdnetLegacy),
     SvDdraceTimeLegacy(SvDdraceTimeLegacy),
     SvRecordLegacy(SvRecordLegacy),
@@ -138,8 +140,8 @@ pub enum Game<'a> {
     SvKillMsgTeam(SvKillMsgTeam),
     SvYourVote(SvYourVote),
     SvRaceFinish(SvRaceFinish),
-    SvCommandInfo(SvCommandInfo<'a>),
-    SvCommandInfoRemove(SvCommandInfoRemove<'a>),
+    SvCommandInfo(#[cfg_attr(feature = "serde", serde(borrow))] SvCommandInfo<'a>),
+    SvCommandInfoRemove(#[cfg_attr(feature = "serde", serde(borrow))] SvCommandInfoRemove<'a>),
     SvVoteOptionGroupStart(SvVoteOptionGroupStart),
     SvVoteOptionGroupEnd(SvVoteOptionGroupEnd),
     SvCommandInfoGroupStart(SvCommandInfoGroupStart),
@@ -147,9 +149,9 @@ pub enum Game<'a> {
     SvChangeInfoCooldown(SvChangeInfoCooldown),
     SvMapSoundGlobal(SvMapSoundGlobal),
     SvPreInput(SvPreInput),
-    SvSaveCode(SvSaveCode<'a>),
-    SvServerAlert(SvServerAlert<'a>),
-    SvModeratorAlert(SvModeratorAlert<'a>),
+    SvSaveCode(#[cfg_attr(feature = "serde", serde(borrow))] SvSaveCode<'a>),
+    SvServerAlert(#[cfg_attr(feature = "serde", serde(borrow))] SvServerAlert<'a>),
+    SvModeratorAlert(#[cfg_attr(feature = "serde", serde(borrow))] SvModeratorAlert<'a>),
     ClEnableSpectatorCount(ClEnableSpectatorCount),
 }
 
@@ -717,23 +719,30 @@ impl<'a> From<ClEnableSpectatorCount> for Game<'a> {
     }
 }
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvMotd<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub message: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvBroadcast<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub message: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvChat<'a> {
     pub team: i32,
     pub client_id: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub message: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvKillMsg {
     pub killer: i32,
     pub victim: i32,
@@ -742,11 +751,13 @@ pub struct SvKillMsg {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvSoundGlobal {
     pub sound_id: enums::Sound,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvTuneParams {
     pub ground_control_speed: TuneParam,
     pub ground_control_accel: TuneParam,
@@ -798,49 +809,64 @@ pub struct SvTuneParams {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Unused;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvReadyToEnter;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvWeaponPickup {
     pub weapon: enums::Weapon,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvEmoticon {
     pub client_id: i32,
     pub emoticon: enums::Emoticon,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteClearOptions;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteOptionListAdd<'a> {
     pub num_options: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes_array"))]
     pub description: [&'a [u8]; 15],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteOptionAdd<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub description: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteOptionRemove<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub description: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteSet<'a> {
     pub timeout: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub description: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub reason: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteStatus {
     pub yes: i32,
     pub no: i32,
@@ -849,26 +875,34 @@ pub struct SvVoteStatus {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClSay<'a> {
     pub team: bool,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub message: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClSetTeam {
     pub team: enums::Team,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClSetSpectatorMode {
     pub spectator_id: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClStartInfo<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub clan: &'a [u8],
     pub country: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub skin: &'a [u8],
     pub use_custom_color: bool,
     pub color_body: i32,
@@ -876,10 +910,14 @@ pub struct ClStartInfo<'a> {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClChangeInfo<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub clan: &'a [u8],
     pub country: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub skin: &'a [u8],
     pub use_custom_color: bool,
     pub color_body: i32,
@@ -887,31 +925,40 @@ pub struct ClChangeInfo<'a> {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClKill;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClEmoticon {
     pub emoticon: enums::Emoticon,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClVote {
     pub vote: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClCallVote<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub type_: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub value: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub reason: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClIsDdnetLegacy {
     pub ddnet_version: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvDdraceTimeLegacy {
     pub time: i32,
     pub check: i32,
@@ -919,41 +966,50 @@ pub struct SvDdraceTimeLegacy {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvRecordLegacy {
     pub server_time_best: i32,
     pub player_time_best: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Unused2;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvTeamsStateLegacy {
+    #[cfg_attr(feature = "serde", serde(with = "libtw2_gamenet_common::serialize::array"))]
     pub teams: [i32; 128],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClShowOthersLegacy {
     pub show: bool,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvMyOwnMessage {
     pub test: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClShowDistance {
     pub x: i32,
     pub y: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClShowOthers {
     pub show: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClCameraInfo {
     pub zoom: i32,
     pub deadzone: i32,
@@ -961,11 +1017,14 @@ pub struct ClCameraInfo {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvTeamsState {
+    #[cfg_attr(feature = "serde", serde(with = "libtw2_gamenet_common::serialize::array"))]
     pub teams: [i32; 128],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvDdraceTime {
     pub time: i32,
     pub check: i32,
@@ -973,23 +1032,27 @@ pub struct SvDdraceTime {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvRecord {
     pub server_time_best: i32,
     pub player_time_best: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvKillMsgTeam {
     pub team: i32,
     pub first: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvYourVote {
     pub voted: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvRaceFinish {
     pub client_id: i32,
     pub time: i32,
@@ -999,40 +1062,53 @@ pub struct SvRaceFinish {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvCommandInfo<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub args_format: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub help_text: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvCommandInfoRemove<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteOptionGroupStart;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteOptionGroupEnd;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvCommandInfoGroupStart;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvCommandInfoGroupEnd;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvChangeInfoCooldown {
     pub wait_until: crate::snap_obj::Tick,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvMapSoundGlobal {
     pub sound_id: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvPreInput {
     pub direction: i32,
     pub target_x: i32,
@@ -1048,27 +1124,39 @@ pub struct SvPreInput {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvSaveCode<'a> {
     pub state: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub error: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub save_requester: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub server_name: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub generated_code: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub code: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub team_members: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvServerAlert<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub message: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvModeratorAlert<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub message: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClEnableSpectatorCount {
     pub enable: bool,
 }
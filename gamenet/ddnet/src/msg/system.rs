@@ -76,34 +76,36 @@ pub const MAPLIST_GROUP_START: Uuid = Uuid::from_u128(0xd2fafec0_5cd2_319a_a84d_
 pub const MAPLIST_GROUP_END: Uuid = Uuid::from_u128(0x43fd0a8b_8b23_350d_b3f6_0de549246a70);
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum System<'a> {
-    Info(Info<'a>),
-    MapChange(MapChange<'a>),
-    MapData(MapData<'a>),
+    Info(#[cfg_attr(feature = "serde", serde(borrow))] Info<'a>),
+    MapChange(#[cfg_attr(feature = "serde", serde(borrow))] MapChange<'a>),
+    MapData(#[cfg_attr(feature = "serde", serde(borrow))] MapData<'a>),
     ConReady(ConReady),
-    Snap(Snap<'a>),
+    Snap(#[cfg_attr(feature = "serde", serde(borrow))] Snap<'a>),
     SnapEmpty(SnapEmpty),
-    SnapSingle(SnapSingle<'a>),
+    SnapSingle(#[cfg_attr(feature = "serde", serde(borrow))] SnapSingle<'a>),
     InputTiming(InputTiming),
     RconAuthStatus(RconAuthStatus),
-    RconLine(RconLine<'a>),
+    RconLine(#[cfg_attr(feature = "serde", serde(borrow))] RconLine<'a>),
     Ready(Ready),
     EnterGame(EnterGame),
     Input(Input),
-    RconCmd(RconCmd<'a>),
-    RconAuth(RconAuth<'a>),
+    RconCmd(#[cfg_attr(feature = "serde", serde(borrow))] RconCmd<'a>),
+    RconAuth(#[cfg_attr(feature = "serde", serde(borrow))] RconAuth<'a>),
     RequestMapData(RequestMapData),
     Ping(Ping),
     PingReply(PingReply),
-    RconCmdAdd(RconCmdAdd<'a>),
-    RconCmdRemove(RconCmdRemove<'a>),
+    RconCmdAdd(#[cfg_attr(feature = "serde", serde(borrow))] RconCmdAdd<'a>),
+    RconCmdRemove(#[cfg_attr(feature = "serde", serde(borrow))] RconCmdRemove<'a>),
     WhatIs(WhatIs),
-    ItIs(ItIs<'a>),
+    ItIs(#[cfg_attr(feature = "serde", serde(borrow))] ItIs<'a>),
     IDontKnow(IDontKnow),
     RconType(RconType),
-    MapDetails(MapDetails<'a>),
+    MapDetails(#[cfg_attr(feature = "serde", serde(borrow))] MapDetails<'a>),
     Capabilities(Capabilities),
-    ClientVersion(ClientVersion<'a>),
+    ClientVersion(#[cfg_attr(feature = "serde", serde(borrow))] ClientVersion<'a>),
     PingEx(PingEx),
     PongEx(PongEx),
     ChecksumRequest(ChecksumRequest),
@@ -543,27 +545,35 @@ impl<'a> From<MaplistGroupEnd> for System<'a> {
     }
 }
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Info<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub version: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::option_bytes"))]
     pub password: Option<&'a [u8]>,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct MapChange<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
     pub crc: i32,
     pub size: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct MapData<'a> {
     pub last: i32,
     pub crc: i32,
     pub chunk: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub data: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ConReady;
 
 pub use libtw2_gamenet_snap::Snap;
@@ -573,29 +583,36 @@ pub use libtw2_gamenet_snap::SnapEmpty;
 pub use libtw2_gamenet_snap::SnapSingle;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct InputTiming {
     pub input_pred_tick: i32,
     pub time_left: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconAuthStatus {
     pub auth_level: Option<i32>,
     pub receive_commands: Option<i32>,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconLine<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub line: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Ready;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct EnterGame;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Input {
     pub ack_snapshot: i32,
     pub intended_tick: i32,
@@ -604,92 +621,122 @@ pub struct Input {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconCmd<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub cmd: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconAuth<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub _unused: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub password: &'a [u8],
     pub request_commands: Option<i32>,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RequestMapData {
     pub chunk: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Ping;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct PingReply;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconCmdAdd<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub help: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub params: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconCmdRemove<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct WhatIs {
     pub uuid: Uuid,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ItIs<'a> {
     pub uuid: Uuid,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct IDontKnow {
     pub uuid: Uuid,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconType {
     pub username_required: bool,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct MapDetails<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
     pub sha256: Sha256,
     pub crc: i32,
+    pub size: Option<i32>,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::option_bytes"))]
+    pub url: Option<&'a [u8]>,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Capabilities {
     pub version: i32,
     pub flags: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClientVersion<'a> {
     pub connection_id: Uuid,
     pub ddnet_version: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub ddnet_version_string: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct PingEx {
     pub id: Uuid,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct PongEx {
     pub id: Uuid,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ChecksumRequest {
     pub id: Uuid,
     pub start: i32,
@@ -697,45 +744,55 @@ pub struct ChecksumRequest {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ChecksumResponse {
     pub id: Uuid,
     pub sha256: Sha256,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ChecksumError {
     pub id: Uuid,
     pub error: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Redirect {
     pub port: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconCmdGroupStart {
     pub length: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconCmdGroupEnd;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct MapReload;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Reconnect;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct MaplistAdd;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct MaplistGroupStart {
     pub length: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct MaplistGroupEnd;
 
 impl<'a> Info<'a> {
@@ -1220,14 +1277,20 @@ impl<'a> MapDetails<'a> {
             name: _p.read_string()?,
             sha256: Sha256::from_slice(_p.read_raw(32)?).unwrap(),
             crc: _p.read_int(warn)?,
+            size: _p.read_int(warn).ok(),
+            url: _p.read_string().ok(),
         });
         _p.finish(wrap(warn));
         result
     }
     pub fn encode<'d, 's>(&self, mut _p: Packer<'d, 's>) -> Result<&'d [u8], CapacityError> {
+        assert!(self.size.is_some());
+        assert!(self.url.is_some());
         _p.write_string(self.name)?;
         _p.write_raw(&self.sha256.0)?;
         _p.write_int(self.crc)?;
+        _p.write_int(self.size.unwrap())?;
+        _p.write_string(self.url.unwrap())?;
         Ok(_p.written())
     }
 }
@@ -1237,6 +1300,8 @@ impl<'a> fmt::Debug for MapDetails<'a> {
             .field("name", &pretty::Bytes::new(&self.name))
             .field("sha256", &self.sha256)
             .field("crc", &self.crc)
+            .field("size", &self.size.as_ref().map(|v| v))
+            .field("url", &self.url.as_ref().map(|v| pretty::Bytes::new(&v)))
             .finish()
     }
 }
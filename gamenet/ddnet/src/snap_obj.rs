@@ -178,6 +178,8 @@ pub const ENTITY_EX: Uuid = Uuid::from_u128(0x2de9aec3_32e4_3986_8f7e_e7459da7f5
 pub const MAP_SOUND_WORLD: Uuid = Uuid::from_u128(0x54ecad2e_bfad_3be5_8903_621ba052458e);
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum SnapObj {
     PlayerInput(PlayerInput),
     Projectile(Projectile),
@@ -614,6 +616,7 @@ impl From<MapSoundWorld> for SnapObj {
 
 #[repr(C)]
 #[derive(Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct PlayerInput {
     pub direction: i32,
     pub target_x: i32,
@@ -629,6 +632,7 @@ pub struct PlayerInput {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Projectile {
     pub x: i32,
     pub y: i32,
@@ -640,6 +644,7 @@ pub struct Projectile {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Laser {
     pub x: i32,
     pub y: i32,
@@ -650,6 +655,7 @@ pub struct Laser {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Pickup {
     pub x: i32,
     pub y: i32,
@@ -659,6 +665,7 @@ pub struct Pickup {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Flag {
     pub x: i32,
     pub y: i32,
@@ -667,6 +674,7 @@ pub struct Flag {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct GameInfo {
     pub game_flags: i32,
     pub game_state_flags: i32,
@@ -680,6 +688,7 @@ pub struct GameInfo {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct GameData {
     pub teamscore_red: i32,
     pub teamscore_blue: i32,
@@ -689,6 +698,7 @@ pub struct GameData {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct CharacterCore {
     pub tick: i32,
     pub x: i32,
@@ -709,6 +719,7 @@ pub struct CharacterCore {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Character {
     pub character_core: CharacterCore,
     pub player_flags: i32,
@@ -722,6 +733,7 @@ pub struct Character {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct PlayerInfo {
     pub local: i32,
     pub client_id: i32,
@@ -732,10 +744,14 @@ pub struct PlayerInfo {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClientInfo {
+    #[cfg_attr(feature = "serde", serde(with = "libtw2_gamenet_common::serialize::int_string"))]
     pub name: [i32; 4],
+    #[cfg_attr(feature = "serde", serde(with = "libtw2_gamenet_common::serialize::int_string"))]
     pub clan: [i32; 3],
     pub country: i32,
+    #[cfg_attr(feature = "serde", serde(with = "libtw2_gamenet_common::serialize::int_string"))]
     pub skin: [i32; 6],
     pub use_custom_color: i32,
     pub color_body: i32,
@@ -744,6 +760,7 @@ pub struct ClientInfo {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SpectatorInfo {
     pub spectator_id: i32,
     pub x: i32,
@@ -752,12 +769,14 @@ pub struct SpectatorInfo {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct MyOwnObject {
     pub test: i32,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct DdnetCharacter {
     pub flags: i32,
     pub freeze_end: crate::snap_obj::Tick,
@@ -774,6 +793,7 @@ pub struct DdnetCharacter {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct DdnetPlayer {
     pub flags: i32,
     pub auth_level: i32,
@@ -781,6 +801,7 @@ pub struct DdnetPlayer {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct GameInfoEx {
     pub flags: i32,
     pub version: i32,
@@ -789,6 +810,7 @@ pub struct GameInfoEx {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct DdraceProjectile {
     pub x: i32,
     pub y: i32,
@@ -800,6 +822,7 @@ pub struct DdraceProjectile {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct DdnetLaser {
     pub to_x: i32,
     pub to_y: i32,
@@ -815,6 +838,7 @@ pub struct DdnetLaser {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct DdnetProjectile {
     pub x: i32,
     pub y: i32,
@@ -830,6 +854,7 @@ pub struct DdnetProjectile {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct DdnetPickup {
     pub x: i32,
     pub y: i32,
@@ -841,6 +866,7 @@ pub struct DdnetPickup {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct DdnetSpectatorInfo {
     pub has_camera_info: bool,
     pub zoom: i32,
@@ -851,12 +877,14 @@ pub struct DdnetSpectatorInfo {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SpectatorCount {
     pub num_spectators: i32,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Common {
     pub x: i32,
     pub y: i32,
@@ -864,24 +892,28 @@ pub struct Common {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Explosion {
     pub common: Common,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Spawn {
     pub common: Common,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct HammerHit {
     pub common: Common,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Death {
     pub common: Common,
     pub client_id: i32,
@@ -889,6 +921,7 @@ pub struct Death {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SoundGlobal {
     pub common: Common,
     pub sound_id: enums::Sound,
@@ -896,6 +929,7 @@ pub struct SoundGlobal {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SoundWorld {
     pub common: Common,
     pub sound_id: enums::Sound,
@@ -903,6 +937,7 @@ pub struct SoundWorld {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct DamageInd {
     pub common: Common,
     pub angle: i32,
@@ -910,24 +945,28 @@ pub struct DamageInd {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Birthday {
     pub common: Common,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Finish {
     pub common: Common,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct MyOwnEvent {
     pub test: i32,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SpecChar {
     pub x: i32,
     pub y: i32,
@@ -935,6 +974,7 @@ pub struct SpecChar {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SwitchState {
     pub highest_switch_number: i32,
     pub status: [i32; 8],
@@ -944,6 +984,7 @@ pub struct SwitchState {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct EntityEx {
     pub switch_number: i32,
     pub layer: i32,
@@ -952,6 +993,7 @@ pub struct EntityEx {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct MapSoundWorld {
     pub common: Common,
     pub sound_id: i32,
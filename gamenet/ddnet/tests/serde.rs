@@ -0,0 +1,205 @@
+use libtw2_gamenet_common::serialize::Buffer;
+use libtw2_gamenet_ddnet::enums;
+use libtw2_gamenet_ddnet::msg::game;
+use libtw2_gamenet_ddnet::msg::system;
+use libtw2_gamenet_ddnet::msg::Game;
+use libtw2_gamenet_ddnet::msg::System;
+use libtw2_gamenet_ddnet::snap_obj;
+use libtw2_gamenet_ddnet::SnapObj;
+use libtw2_packer::string_to_ints3;
+use libtw2_packer::string_to_ints4;
+use libtw2_packer::string_to_ints6;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+
+fn check<'de, T>(value: T, json: &'de str)
+where
+    T: fmt::Debug + Deserialize<'de> + Serialize,
+{
+    assert_eq!(serde_json::to_string(&value).unwrap(), json);
+    let decoded: T = serde_json::from_str(json).unwrap();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", value));
+}
+
+#[test]
+fn game() {
+    check(
+        Game::SvChat(game::SvChat {
+            team: 0,
+            client_id: -1,
+            message: b"hello",
+        }),
+        r#"{"sv_chat":{"team":0,"client_id":-1,"message":"hello"}}"#,
+    );
+    check(
+        Game::SvEmoticon(game::SvEmoticon {
+            client_id: 3,
+            emoticon: enums::Emoticon::Hearts,
+        }),
+        r#"{"sv_emoticon":{"client_id":3,"emoticon":"hearts"}}"#,
+    );
+    check(
+        Game::SvVoteClearOptions(game::SvVoteClearOptions),
+        r#"{"sv_vote_clear_options":null}"#,
+    );
+}
+
+#[test]
+fn game_arrays() {
+    let mut description = [&b""[..]; 15];
+    description[0] = b"kick nameless";
+    description[1] = b"change map";
+    let json = serde_json::to_string(&game::SvVoteOptionListAdd {
+        num_options: 2,
+        description,
+    })
+    .unwrap();
+    assert!(
+        json.starts_with(r#"{"num_options":2,"description":["kick nameless","change map","",""#)
+    );
+    let decoded: game::SvVoteOptionListAdd = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded.description, description);
+
+    let mut teams = [0; 128];
+    teams[5] = 3;
+    teams[127] = 64;
+    let json = serde_json::to_string(&game::SvTeamsState { teams }).unwrap();
+    let decoded: game::SvTeamsState = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded.teams, teams);
+    assert!(serde_json::from_str::<game::SvTeamsState>(r#"{"teams":[1,2,3]}"#).is_err());
+}
+
+#[test]
+fn system() {
+    check(
+        System::Info(system::Info {
+            version: b"0.6 626fce9a778df4d4",
+            password: Some(b"secret"),
+        }),
+        r#"{"info":{"version":"0.6 626fce9a778df4d4","password":"secret"}}"#,
+    );
+    check(
+        System::Info(system::Info {
+            version: b"0.6 626fce9a778df4d4",
+            password: None,
+        }),
+        r#"{"info":{"version":"0.6 626fce9a778df4d4","password":null}}"#,
+    );
+}
+
+fn check_buffered(value: Game, json: &str) {
+    assert_eq!(serde_json::to_string(&value).unwrap(), json);
+    // Can't be borrowed from the JSON directly.
+    assert!(serde_json::from_str::<Game>(json).is_err());
+    let buffer: Buffer = serde_json::from_str(json).unwrap();
+    let decoded: Game = buffer.deserialize().unwrap();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", value));
+}
+
+#[test]
+fn non_utf8() {
+    check_buffered(
+        Game::ClSay(game::ClSay {
+            team: false,
+            message: b"\xff",
+        }),
+        r#"{"cl_say":{"team":false,"message":[255]}}"#,
+    );
+}
+
+#[test]
+fn escaped() {
+    check_buffered(
+        Game::SvChat(game::SvChat {
+            team: 0,
+            client_id: -1,
+            message: b"\"quoted\"\n",
+        }),
+        r#"{"sv_chat":{"team":0,"client_id":-1,"message":"\"quoted\"\n"}}"#,
+    );
+}
+
+#[test]
+fn buffered() {
+    // Values that could be borrowed can be deserialized through a buffer,
+    // too.
+    let json = r#"{"info":{"version":"0.6 626fce9a778df4d4","password":null}}"#;
+    let buffer: Buffer = serde_json::from_str(json).unwrap();
+    let decoded: System = buffer.deserialize().unwrap();
+    assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+    let json = r#"{"sv_vote_clear_options":null}"#;
+    let buffer: Buffer = serde_json::from_str(json).unwrap();
+    let decoded: Game = buffer.deserialize().unwrap();
+    assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+
+    let json = concat!(
+        r#"{"client_info":{"name":"\u00e4","clan":"","country":-1,"#,
+        r#""skin":"default","use_custom_color":0,"color_body":0,"color_feet":0}}"#,
+    );
+    let buffer: Buffer = serde_json::from_str(json).unwrap();
+    let decoded: SnapObj = buffer.deserialize().unwrap();
+    match decoded {
+        SnapObj::ClientInfo(i) => assert_eq!(i.name, string_to_ints4("ä".as_bytes())),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn snap_obj() {
+    check(
+        SnapObj::ClientInfo(snap_obj::ClientInfo {
+            name: string_to_ints4(b"nameless tee"),
+            clan: string_to_ints3(b""),
+            country: -1,
+            skin: string_to_ints6(b"default"),
+            use_custom_color: 0,
+            color_body: 65408,
+            color_feet: 65408,
+        }),
+        concat!(
+            r#"{"client_info":{"name":"nameless tee","clan":"","country":-1,"#,
+            r#""skin":"default","use_custom_color":0,"color_body":65408,"color_feet":65408}}"#,
+        ),
+    );
+    // Too long for `name`, which can only hold 15 bytes.
+    assert!(serde_json::from_str::<snap_obj::ClientInfo>(
+        r#"{"name":"0123456789abcdef","clan":"","country":-1,"skin":"default","use_custom_color":0,"color_body":0,"color_feet":0}"#
+    )
+    .is_err());
+}
+
+#[test]
+fn snap_obj_super() {
+    let json = serde_json::to_string(&snap_obj::Character {
+        character_core: snap_obj::CharacterCore {
+            tick: 100,
+            x: 1584,
+            y: 304,
+            vel_x: 0,
+            vel_y: 128,
+            angle: 0,
+            direction: 0,
+            jumped: 0,
+            hooked_player: -1,
+            hook_state: 0,
+            hook_tick: 0,
+            hook_x: 1584,
+            hook_y: 304,
+            hook_dx: 0,
+            hook_dy: 0,
+        },
+        player_flags: 0,
+        health: 10,
+        armor: 0,
+        ammo_count: 10,
+        weapon: 1,
+        emote: enums::Emote::Happy,
+        attack_tick: 0,
+    })
+    .unwrap();
+    assert!(json.starts_with(r#"{"character_core":{"tick":100,"x":1584,"y":304,"#));
+    assert!(json.ends_with(r#""weapon":1,"emote":"happy","attack_tick":0}"#));
+    let decoded: snap_obj::Character = serde_json::from_str(&json).unwrap();
+    assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+}
@@ -351,12 +351,22 @@ impl<'a> fmt::Debug for Client<'a> {{
 pub const INFO_FLAG_PASSWORD: i32 = 1;
 """.format(l=lifetime))
 
+SERDE_DERIVE = '#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]'
+SERDE_RENAME_ALL = '#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]'
+def serde_attr(attr):
+    return '#[cfg_attr(feature = "serde", serde({}))]'.format(attr)
+def serde_with(module):
+    return 'with = "libtw2_gamenet_common::serialize::{}"'.format(module)
+
 def emit_enum_def(name, structs):
     lifetime = "<'a>" if any(s.lifetime() for s in structs) else ""
     print("#[derive(Clone, Copy)]")
+    print(SERDE_DERIVE)
+    print(SERDE_RENAME_ALL)
     print("pub enum {}{} {{".format(title(name), lifetime))
     for s in structs:
-        print("    {}({}{}),".format(title(s.name), title(s.name), s.lifetime()))
+        borrow = serde_attr("borrow") + " " if s.lifetime() else ""
+        print("    {}({}{}{}),".format(title(s.name), borrow, title(s.name), s.lifetime()))
     print("}")
 
 def emit_enum_from(name, structs):
@@ -617,7 +627,25 @@ libtw2-gamenet-common = {{ path = "../common/" }}
 libtw2-gamenet-snap = {{ path = "../snap/" }}
 libtw2-packer = {{ path = "../../packer/", features = ["uuid"] }}
 libtw2-warn = {{ path = "../../warn/" }}
-uuid = ">=0.8.1,<2.0.0"\
+serde = {{ version = "1.0.23", optional = true }}
+serde_derive = {{ version = "1.0.7", optional = true }}
+uuid = ">=0.8.1,<2.0.0"
+
+[dev-dependencies]
+serde_json = "1.0.0"
+
+[[test]]
+name = "serde"
+required-features = ["serde"]
+
+[features]
+serde = [
+    "dep:serde",
+    "dep:serde_derive",
+    "libtw2-common/serde",
+    "libtw2-gamenet-snap/serde",
+    "uuid/serde",
+]\
 """.format(name))
 
 def emit_main_lib():
@@ -798,6 +826,8 @@ class Enum(NameValues):
         print()
         print("#[repr(i32)]")
         print("#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]")
+        print(SERDE_DERIVE)
+        print(SERDE_RENAME_ALL)
         print("pub enum {} {{".format(title(self.name)))
         for i, name in enumerate(self.values):
             if self.offset == 0 or i != 0:
@@ -949,11 +979,17 @@ class Struct(NameValues):
             print("#[derive(Clone, Copy)]")
         else:
             print("#[derive(Clone, Copy, Default)]")
+        print(SERDE_DERIVE)
         if self.values or super:
             print("pub struct {}{} {{".format(title(self.name), self.lifetime()))
             if super:
+                if super.lifetime():
+                    print("    {}".format(serde_attr("borrow")))
                 print("    pub {}: {}{},".format(snake(super.name), title(super.name), super.lifetime()))
             for member in self.values:
+                attr = member.serde_attr()
+                if attr is not None:
+                    print("    {}".format(serde_attr(attr)))
                 print("    pub {},".format(member.definition()))
             print("}")
         else:
@@ -1153,6 +1189,8 @@ class Member:
         return "{}: {}".format(snake(self.name), self.type_)
     def contains_lifetime(self):
         return "'a" in self.type_
+    def serde_attr(self):
+        return None
     def update(self, parent, consts, enums, structs):
         return self
     def emit_decode(self):
@@ -1193,6 +1231,14 @@ class NetArray(Member):
         self.inner = inner
         self.count = count
         self.type_ = "[{}; {}]".format(inner.type_, count)
+    def serde_attr(self):
+        if self.inner.type_ == "&'a [u8]":
+            return "borrow, " + serde_with("bytes_array")
+        if self.inner.serde_attr() is not None:
+            raise ValueError("can't form an array of this type")
+        if self.count > 32:
+            return serde_with("array")
+        return None
     def decode_expr(self):
         return "[\n{}]".format("".join(
             "    {},\n".format(self.inner.decode_expr()) for _ in range(self.count)
@@ -1238,6 +1284,12 @@ class NetOptional(Member):
         super().__init__(name)
         self.inner = inner
         self.type_ = "Option<{}>".format(inner.type_)
+    def serde_attr(self):
+        if self.inner.type_ == "&'a [u8]":
+            return "borrow, " + serde_with("option_bytes")
+        if self.inner.serde_attr() is not None:
+            raise ValueError("can't form an optional of this type")
+        return None
     def decode_expr(self):
         END="?"
         inner_decode = self.inner.decode_expr()
@@ -1259,6 +1311,8 @@ class NetOptional(Member):
 class NetString(Member):
     kind = "string"
     type_ = "&'a [u8]"
+    def serde_attr(self):
+        return "borrow, " + serde_with("bytes")
     def decode_expr(self):
         return "_p.read_string()?"
     def encode_expr(self, self_expr):
@@ -1292,6 +1346,8 @@ NetStringHalfStrict = NetStringStrict
 class NetData(Member):
     kind = "data"
     type_ = "&'a [u8]"
+    def serde_attr(self):
+        return "borrow, " + serde_with("bytes")
     def decode_expr(self):
         return "_p.read_data(warn)?"
     def encode_expr(self, self_expr):
@@ -1308,6 +1364,8 @@ class NetData(Member):
 class NetDataRest(Member):
     kind = "rest"
     type_ = "&'a [u8]"
+    def serde_attr(self):
+        return "borrow, " + serde_with("bytes")
     def decode_expr(self):
         return "_p.read_rest()?"
     def encode_expr(self, self_expr):
@@ -1382,6 +1440,8 @@ class NetTwIntString(Member):
             raise ValueError("size must be divisible by 4, but isn't: {}".format(count))
         self.count = size // 4
         self.type_ = "[i32; {}]".format(self.count)
+    def serde_attr(self):
+        return serde_with("int_string")
     def decode_int_expr(self):
         return "[\n{}]".format("".join("    _p.read_int()?,\n" for _ in range(self.count)))
     def int_size(self):
@@ -1606,6 +1666,8 @@ class NetObjectMember(Member):
 class NetAddrs(Member):
     kind = "packed_addresses"
     type_ = "&'a [AddrPacked]"
+    def serde_attr(self):
+        return "borrow, " + serde_with("addr_packed_slice")
     def definition(self):
         import_("super::AddrPacked")
         return super().definition()
@@ -1652,6 +1714,8 @@ class NetU8(Member):
 class NetIntString(NetString):
     kind = "int32_string"
     type_ = "i32"
+    def serde_attr(self):
+        return None
     def decode_expr(self):
         import_("libtw2_gamenet_common::msg::int_from_string")
         return "int_from_string(_p.read_string()?)?"
@@ -1669,6 +1733,8 @@ class NetIntString(NetString):
 class NetClients(Member):
     kind = "serverinfo_client"
     type_ = "ClientsData<'a>"
+    def serde_attr(self):
+        return "borrow"
     def definition(self):
         import_("super::ClientsData")
         return super().definition()
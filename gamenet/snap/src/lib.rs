@@ -28,26 +28,46 @@ impl<'a> fmt::Debug for SnapMsg<'a> {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_derive::Deserialize, serde_derive::Serialize)
+)]
 pub struct Snap<'a> {
     pub tick: i32,
     pub delta_tick: i32,
     pub num_parts: i32,
     pub part: i32,
     pub crc: i32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(borrow, with = "libtw2_gamenet_common::serialize::bytes")
+    )]
     pub data: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_derive::Deserialize, serde_derive::Serialize)
+)]
 pub struct SnapEmpty {
     pub tick: i32,
     pub delta_tick: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_derive::Deserialize, serde_derive::Serialize)
+)]
 pub struct SnapSingle<'a> {
     pub tick: i32,
     pub delta_tick: i32,
     pub crc: i32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(borrow, with = "libtw2_gamenet_common::serialize::bytes")
+    )]
     pub data: &'a [u8],
 }
 
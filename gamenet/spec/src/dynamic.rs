@@ -0,0 +1,907 @@
+//! Decoding and encoding of messages and snapshot objects, driven by a
+//! [`Spec`] at runtime instead of generated code.
+//!
+//! Decoding follows the same rules as the generated `libtw2-gamenet-*`
+//! crates, so that a message that decodes with the generated code decodes
+//! with a [`Protocol`] created from the same spec, and vice versa.
+
+use crate::ConnlessMessage;
+use crate::Enumeration;
+use crate::Flags;
+use crate::Identifier;
+use crate::Member;
+use crate::Message;
+use crate::MessageId;
+use crate::Object;
+use crate::Spec;
+use crate::Type;
+use crate::TypeId;
+use libtw2_buffer::CapacityError;
+use libtw2_common::digest::Sha256;
+use libtw2_gamenet_common::error::Error;
+use libtw2_gamenet_common::msg::int_from_string;
+use libtw2_gamenet_common::msg::string_from_int;
+use libtw2_gamenet_common::msg::SystemOrGame;
+use libtw2_packer::sanitize;
+use libtw2_packer::string_to_ints;
+use libtw2_packer::with_packer;
+use libtw2_packer::ExcessData;
+use libtw2_packer::IntOutOfRange;
+use libtw2_packer::IntUnpacker;
+use libtw2_packer::Packer;
+use libtw2_packer::Unpacker;
+use libtw2_packer::Warning;
+use libtw2_warn::wrap;
+use libtw2_warn::Warn;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::mem;
+use uuid::Uuid;
+
+/// Size of one packed address in a `packed_addresses` member.
+const ADDR_PACKED_SIZE: usize = 18;
+
+/// A single decoded member value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Value {
+    /// `int32`, `int32_string` and `tick` members.
+    Int(i32),
+    /// `boolean` members.
+    Bool(bool),
+    /// `enum` members.
+    Enum(EnumValue),
+    /// `flags` members.
+    Flags(FlagsValue),
+    /// `string` and `int32_twstring` members.
+    String(Vec<u8>),
+    /// `data`, `rest`, `packed_addresses` and `serverinfo_client` members.
+    Data(Vec<u8>),
+    Array(Vec<Value>),
+    Optional(Option<Box<Value>>),
+    Sha256(Sha256),
+    Uuid(Uuid),
+    /// `tune_param` members, in hundredths.
+    TuneParam(i32),
+    Uint8(u8),
+    BeUint16(u16),
+    /// `snapshot_object` members and the super object of a snapshot object.
+    Object(Record),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EnumValue {
+    pub value: i32,
+    pub name: Identifier,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FlagsValue {
+    pub value: i32,
+    /// Names of the known flags that are set in `value`.
+    pub names: Vec<Identifier>,
+}
+
+/// A decoded message or snapshot object.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Record {
+    pub name: Identifier,
+    pub members: Vec<(Identifier, Value)>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SpecError {
+    DuplicateEnumeration(Identifier),
+    DuplicateFlags(Identifier),
+    DuplicateMessage(Identifier),
+    DuplicateConnlessMessage(Identifier),
+    DuplicateObject(Identifier),
+    UnknownEnumeration(Identifier),
+    UnknownFlags(Identifier),
+    UnknownObject(Identifier),
+    /// A snapshot object contains a member that cannot be represented as
+    /// integers.
+    InvalidObjectMember(Identifier),
+}
+
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum EncodeError {
+    Capacity,
+    UnknownName,
+    /// A value doesn't match its member type or is out of range.
+    InvalidValue,
+}
+
+impl From<CapacityError> for EncodeError {
+    fn from(_: CapacityError) -> EncodeError {
+        EncodeError::Capacity
+    }
+}
+
+impl Record {
+    pub fn new(name: Identifier) -> Record {
+        Record {
+            name,
+            members: Vec::new(),
+        }
+    }
+    pub fn with(mut self, name: &str, value: Value) -> Record {
+        self.members.push((Identifier::from_snake(name), value));
+        self
+    }
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.members
+            .iter()
+            .find(|(n, _)| n.snake() == name)
+            .map(|(_, v)| v)
+    }
+}
+
+impl fmt::Debug for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = f.debug_struct(&self.name.snake());
+        for (name, value) in &self.members {
+            s.field(&name.snake(), value);
+        }
+        s.finish()
+    }
+}
+
+struct Index<K> {
+    by_id: HashMap<K, usize>,
+    by_name: HashMap<Identifier, usize>,
+}
+
+impl<K: Eq + Hash> Index<K> {
+    fn new<T, FI, FN>(items: &[T], id: FI, name: FN) -> Result<Index<K>, Identifier>
+    where
+        FI: Fn(&T) -> K,
+        FN: Fn(&T) -> &Identifier,
+    {
+        let mut result = Index {
+            by_id: HashMap::new(),
+            by_name: HashMap::new(),
+        };
+        for (i, item) in items.iter().enumerate() {
+            if result.by_id.insert(id(item), i).is_some()
+                || result.by_name.insert(name(item).clone(), i).is_some()
+            {
+                return Err(name(item).clone());
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// A protocol described by a [`Spec`], able to decode and encode all of its
+/// messages and snapshot objects.
+pub struct Protocol {
+    spec: Spec,
+    enumerations: HashMap<Identifier, usize>,
+    flags: HashMap<Identifier, usize>,
+    system_messages: Index<MessageId>,
+    game_messages: Index<MessageId>,
+    connless_messages: Index<[u8; 8]>,
+    snapshot_objects: Index<TypeId>,
+}
+
+impl Protocol {
+    pub fn new(spec: Spec) -> Result<Protocol, SpecError> {
+        let mut enumerations = HashMap::new();
+        for (i, e) in spec.game_enumerations.iter().enumerate() {
+            if enumerations.insert(e.name.clone(), i).is_some() {
+                return Err(SpecError::DuplicateEnumeration(e.name.clone()));
+            }
+        }
+        let mut flags = HashMap::new();
+        for (i, f) in spec.game_flags.iter().enumerate() {
+            if flags.insert(f.name.clone(), i).is_some() {
+                return Err(SpecError::DuplicateFlags(f.name.clone()));
+            }
+        }
+        let result = Protocol {
+            system_messages: Index::new(&spec.system_messages, |m| m.id, |m| &m.name)
+                .map_err(SpecError::DuplicateMessage)?,
+            game_messages: Index::new(&spec.game_messages, |m| m.id, |m| &m.name)
+                .map_err(SpecError::DuplicateMessage)?,
+            connless_messages: Index::new(&spec.connless_messages, |m| m.id, |m| &m.name)
+                .map_err(SpecError::DuplicateConnlessMessage)?,
+            snapshot_objects: Index::new(&spec.snapshot_objects, |o| o.id, |o| &o.name)
+                .map_err(SpecError::DuplicateObject)?,
+            enumerations,
+            flags,
+            spec,
+        };
+        result.check()?;
+        Ok(result)
+    }
+    fn check(&self) -> Result<(), SpecError> {
+        let messages = self
+            .spec
+            .system_messages
+            .iter()
+            .chain(&self.spec.game_messages);
+        for m in messages {
+            self.check_members(&m.members, false)?;
+        }
+        for m in &self.spec.connless_messages {
+            self.check_members(&m.members, false)?;
+        }
+        for o in &self.spec.snapshot_objects {
+            if let Some(super_) = &o.super_ {
+                self.object_by_name(super_)
+                    .ok_or_else(|| SpecError::UnknownObject(super_.clone()))?;
+            }
+            self.check_members(&o.members, true)?;
+        }
+        Ok(())
+    }
+    fn check_members(&self, members: &[Member], int_only: bool) -> Result<(), SpecError> {
+        for m in members {
+            self.check_type(&m.name, &m.type_, int_only)?;
+        }
+        Ok(())
+    }
+    fn check_type(&self, name: &Identifier, type_: &Type, int_only: bool) -> Result<(), SpecError> {
+        match type_ {
+            Type::Array(i) => self.check_type(name, &i.member_type, int_only)?,
+            Type::Enum(i) => {
+                self.enumeration(&i.enum_)
+                    .ok_or_else(|| SpecError::UnknownEnumeration(i.enum_.clone()))?;
+            }
+            Type::Flags(i) => {
+                self.flags(&i.flags)
+                    .ok_or_else(|| SpecError::UnknownFlags(i.flags.clone()))?;
+            }
+            Type::SnapshotObject(i) => {
+                self.object_by_name(&i.name)
+                    .ok_or_else(|| SpecError::UnknownObject(i.name.clone()))?;
+            }
+            Type::Boolean | Type::Int32(_) | Type::Int32Twstring(_) | Type::Tick => {}
+            Type::TuneParam => {}
+            _ if int_only => return Err(SpecError::InvalidObjectMember(name.clone())),
+            Type::Optional(i) => self.check_type(name, &i.inner, int_only)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn spec(&self) -> &Spec {
+        &self.spec
+    }
+    pub fn enumeration(&self, name: &Identifier) -> Option<&Enumeration> {
+        self.enumerations
+            .get(name)
+            .map(|&i| &self.spec.game_enumerations[i])
+    }
+    pub fn flags(&self, name: &Identifier) -> Option<&Flags> {
+        self.flags.get(name).map(|&i| &self.spec.game_flags[i])
+    }
+    pub fn system_message(&self, id: MessageId) -> Option<&Message> {
+        let i = *self.system_messages.by_id.get(&id)?;
+        Some(&self.spec.system_messages[i])
+    }
+    pub fn game_message(&self, id: MessageId) -> Option<&Message> {
+        let i = *self.game_messages.by_id.get(&id)?;
+        Some(&self.spec.game_messages[i])
+    }
+    pub fn connless_message(&self, id: [u8; 8]) -> Option<&ConnlessMessage> {
+        let i = *self.connless_messages.by_id.get(&id)?;
+        Some(&self.spec.connless_messages[i])
+    }
+    pub fn object(&self, id: TypeId) -> Option<&Object> {
+        let i = *self.snapshot_objects.by_id.get(&id)?;
+        Some(&self.spec.snapshot_objects[i])
+    }
+    pub fn object_by_name(&self, name: &Identifier) -> Option<&Object> {
+        let i = *self.snapshot_objects.by_name.get(name)?;
+        Some(&self.spec.snapshot_objects[i])
+    }
+    /// Size of a snapshot object with an ordinal type ID, in `i32`s.
+    pub fn obj_size(&self, type_id: u16) -> Option<u32> {
+        self.object(TypeId::Ordinal(type_id))
+            .map(|o| self.object_int_size(o))
+    }
+    fn object_int_size(&self, o: &Object) -> u32 {
+        let super_size = o
+            .super_
+            .as_ref()
+            .map(|s| self.object_int_size(self.object_by_name(s).unwrap()))
+            .unwrap_or(0);
+        super_size + o.members.iter().map(|m| int_size(&m.type_)).sum::<u32>()
+    }
+
+    pub fn decode_msg<'a, W: Warn<Warning>>(
+        &self,
+        warn: &mut W,
+        p: &mut Unpacker<'a>,
+    ) -> Result<SystemOrGame<Record, Record>, Error> {
+        Ok(match SystemOrGame::decode_id(warn, p)? {
+            SystemOrGame::System(id) => SystemOrGame::System(self.decode_system(warn, id, p)?),
+            SystemOrGame::Game(id) => SystemOrGame::Game(self.decode_game(warn, id, p)?),
+        })
+    }
+    pub fn decode_system<'a, W: Warn<Warning>>(
+        &self,
+        warn: &mut W,
+        id: MessageId,
+        p: &mut Unpacker<'a>,
+    ) -> Result<Record, Error> {
+        let msg = self.system_message(id).ok_or(Error::UnknownId)?;
+        self.decode_message(warn, &msg.name, &msg.members, p)
+    }
+    pub fn decode_game<'a, W: Warn<Warning>>(
+        &self,
+        warn: &mut W,
+        id: MessageId,
+        p: &mut Unpacker<'a>,
+    ) -> Result<Record, Error> {
+        let msg = self.game_message(id).ok_or(Error::UnknownId)?;
+        self.decode_message(warn, &msg.name, &msg.members, p)
+    }
+    pub fn decode_connless<'a, W: Warn<Warning>>(
+        &self,
+        warn: &mut W,
+        p: &mut Unpacker<'a>,
+    ) -> Result<Record, Error> {
+        let id = p.read_raw(8)?;
+        let id = [id[0], id[1], id[2], id[3], id[4], id[5], id[6], id[7]];
+        let msg = self.connless_message(id).ok_or(Error::UnknownId)?;
+        self.decode_message(warn, &msg.name, &msg.members, p)
+    }
+    pub fn decode_obj<W: Warn<ExcessData>>(
+        &self,
+        warn: &mut W,
+        obj_type_id: TypeId,
+        p: &mut IntUnpacker,
+    ) -> Result<Record, Error> {
+        let obj = self.object(obj_type_id).ok_or(Error::UnknownId)?;
+        let result = self.decode_object(obj, &mut || Ok(p.read_int()?))?;
+        p.finish(warn);
+        Ok(result)
+    }
+
+    fn decode_message<'a, W: Warn<Warning>>(
+        &self,
+        warn: &mut W,
+        name: &Identifier,
+        members: &[Member],
+        p: &mut Unpacker<'a>,
+    ) -> Result<Record, Error> {
+        let mut result = Record::new(name.clone());
+        for m in members {
+            let value = self.decode_value(warn, &m.type_, p)?;
+            result.members.push((m.name.clone(), value));
+        }
+        p.finish(wrap(warn));
+        Ok(result)
+    }
+    fn decode_value<'a, W: Warn<Warning>>(
+        &self,
+        warn: &mut W,
+        type_: &Type,
+        p: &mut Unpacker<'a>,
+    ) -> Result<Value, Error> {
+        Ok(match type_ {
+            Type::Array(i) => Value::Array(
+                (0..i.count)
+                    .map(|_| self.decode_value(warn, &i.member_type, p))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Type::BeUint16 => {
+                let s = p.read_raw(2)?;
+                Value::BeUint16(u16::from_be_bytes([s[0], s[1]]))
+            }
+            Type::Data => Value::Data(p.read_data(warn)?.to_vec()),
+            Type::Int32String => Value::Int(int_from_string(p.read_string()?)?),
+            Type::Optional(i) => {
+                Value::Optional(self.decode_value(warn, &i.inner, p).ok().map(Box::new))
+            }
+            Type::PackedAddresses => {
+                let rest = p.read_rest()?;
+                if rest.len() % ADDR_PACKED_SIZE != 0 {
+                    warn.warn(Warning::ExcessData);
+                }
+                Value::Data(rest.to_vec())
+            }
+            Type::Rest | Type::ServerinfoClient => Value::Data(p.read_rest()?.to_vec()),
+            Type::Sha256 => Value::Sha256(Sha256::from_slice(p.read_raw(32)?).unwrap()),
+            Type::SnapshotObject(i) => {
+                let obj = self.object_by_name(&i.name).unwrap();
+                Value::Object(self.decode_object(obj, &mut || Ok(p.read_int(warn)?))?)
+            }
+            Type::String(i) => {
+                let s = p.read_string()?;
+                if i.disallow_cc {
+                    sanitize(warn, s)?;
+                }
+                Value::String(s.to_vec())
+            }
+            Type::Uint8 => Value::Uint8(p.read_raw(1)?[0]),
+            Type::Uuid => Value::Uuid(Uuid::from_slice(p.read_raw(16)?).unwrap()),
+            _ => self.decode_int_value(type_, &mut || Ok(p.read_int(warn)?))?,
+        })
+    }
+    fn decode_object<F>(&self, obj: &Object, read: &mut F) -> Result<Record, Error>
+    where
+        F: FnMut() -> Result<i32, Error>,
+    {
+        let mut result = Record::new(obj.name.clone());
+        if let Some(super_) = &obj.super_ {
+            let super_obj = self.object_by_name(super_).unwrap();
+            let value = Value::Object(self.decode_object(super_obj, read)?);
+            result.members.push((super_.clone(), value));
+        }
+        for m in &obj.members {
+            let value = self.decode_int_value(&m.type_, read)?;
+            result.members.push((m.name.clone(), value));
+        }
+        Ok(result)
+    }
+    fn decode_int_value<F>(&self, type_: &Type, read: &mut F) -> Result<Value, Error>
+    where
+        F: FnMut() -> Result<i32, Error>,
+    {
+        Ok(match type_ {
+            Type::Array(i) => Value::Array(
+                (0..i.count)
+                    .map(|_| self.decode_int_value(&i.member_type, read))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Type::Boolean => Value::Bool(match read()? {
+                0 => false,
+                1 => true,
+                _ => return Err(Error::IntOutOfRange),
+            }),
+            Type::Enum(i) => {
+                let value = read()?;
+                let name = self
+                    .enumeration(&i.enum_)
+                    .unwrap()
+                    .values
+                    .iter()
+                    .find(|v| v.value == value)
+                    .ok_or(Error::IntOutOfRange)?
+                    .name
+                    .clone();
+                Value::Enum(EnumValue { value, name })
+            }
+            Type::Flags(i) => {
+                let value = read()?;
+                let names = self
+                    .flags(&i.flags)
+                    .unwrap()
+                    .values
+                    .iter()
+                    .filter(|f| value as u32 & f.value != 0)
+                    .map(|f| f.name.clone())
+                    .collect();
+                Value::Flags(FlagsValue { value, names })
+            }
+            Type::Int32(i) => Value::Int(check_range(read()?, i.min, i.max)?),
+            Type::Int32Twstring(i) => {
+                let mut bytes = Vec::with_capacity(i.count as usize * mem::size_of::<i32>());
+                for _ in 0..i.count {
+                    bytes.extend(read()?.to_be_bytes().iter().map(|b| b.wrapping_sub(0x80)));
+                }
+                // The last byte is always treated as the NUL terminator.
+                bytes.pop();
+                let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                bytes.truncate(len);
+                Value::String(bytes)
+            }
+            Type::Tick => Value::Int(read()?),
+            Type::TuneParam => Value::TuneParam(read()?),
+            _ => unreachable!(),
+        })
+    }
+
+    pub fn encode_msg<'d, 's>(
+        &self,
+        msg: SystemOrGame<&Record, &Record>,
+        mut p: Packer<'d, 's>,
+    ) -> Result<&'d [u8], EncodeError> {
+        let (index, messages, record) = match msg {
+            SystemOrGame::System(r) => (&self.system_messages, &self.spec.system_messages, r),
+            SystemOrGame::Game(r) => (&self.game_messages, &self.spec.game_messages, r),
+        };
+        let &i = index
+            .by_name
+            .get(&record.name)
+            .ok_or(EncodeError::UnknownName)?;
+        let msg_def = &messages[i];
+        let id = match msg {
+            SystemOrGame::System(_) => SystemOrGame::System(msg_def.id),
+            SystemOrGame::Game(_) => SystemOrGame::Game(msg_def.id),
+        };
+        with_packer(&mut p, |p| id.encode_id(p))?;
+        self.encode_message(&msg_def.members, record, &mut p)?;
+        Ok(p.written())
+    }
+    pub fn encode_connless<'d, 's>(
+        &self,
+        record: &Record,
+        mut p: Packer<'d, 's>,
+    ) -> Result<&'d [u8], EncodeError> {
+        let &i = self
+            .connless_messages
+            .by_name
+            .get(&record.name)
+            .ok_or(EncodeError::UnknownName)?;
+        let msg_def = &self.spec.connless_messages[i];
+        p.write_raw(&msg_def.id)?;
+        self.encode_message(&msg_def.members, record, &mut p)?;
+        Ok(p.written())
+    }
+    pub fn encode_obj(&self, record: &Record) -> Result<(TypeId, Vec<i32>), EncodeError> {
+        let obj = self
+            .object_by_name(&record.name)
+            .ok_or(EncodeError::UnknownName)?;
+        let mut result = Vec::new();
+        self.encode_object(obj, record, &mut |i| {
+            result.push(i);
+            Ok(())
+        })?;
+        Ok((obj.id, result))
+    }
+
+    fn encode_message(
+        &self,
+        members: &[Member],
+        record: &Record,
+        p: &mut Packer,
+    ) -> Result<(), EncodeError> {
+        if members.len() != record.members.len() {
+            return Err(EncodeError::InvalidValue);
+        }
+        for (m, (name, value)) in members.iter().zip(&record.members) {
+            if m.name != *name {
+                return Err(EncodeError::InvalidValue);
+            }
+            self.encode_value(&m.type_, value, p)?;
+        }
+        Ok(())
+    }
+    fn encode_value(&self, type_: &Type, value: &Value, p: &mut Packer) -> Result<(), EncodeError> {
+        match (type_, value) {
+            (Type::Array(i), Value::Array(v)) => {
+                if v.len() != i.count as usize {
+                    return Err(EncodeError::InvalidValue);
+                }
+                for e in v {
+                    self.encode_value(&i.member_type, e, p)?;
+                }
+            }
+            (Type::BeUint16, &Value::BeUint16(v)) => p.write_raw(&v.to_be_bytes())?,
+            (Type::Data, Value::Data(v)) => p.write_data(v)?,
+            (Type::Int32String, &Value::Int(v)) => p.write_string(&string_from_int(v))?,
+            (Type::Optional(_), Value::Optional(None)) => {}
+            (Type::Optional(i), Value::Optional(Some(v))) => self.encode_value(&i.inner, v, p)?,
+            (Type::PackedAddresses, Value::Data(v)) => {
+                if v.len() % ADDR_PACKED_SIZE != 0 {
+                    return Err(EncodeError::InvalidValue);
+                }
+                p.write_rest(v)?;
+            }
+            (Type::Rest, Value::Data(v)) => p.write_rest(v)?,
+            (Type::ServerinfoClient, Value::Data(v)) => p.write_rest(v)?,
+            (Type::Sha256, Value::Sha256(v)) => p.write_raw(&v.0)?,
+            (Type::SnapshotObject(i), Value::Object(v)) => {
+                let obj = self.object_by_name(&i.name).unwrap();
+                if v.name != obj.name {
+                    return Err(EncodeError::InvalidValue);
+                }
+                self.encode_object(obj, v, &mut |i| Ok(p.write_int(i)?))?;
+            }
+            (Type::String(i), Value::String(v)) => {
+                if v.contains(&0) || (i.disallow_cc && v.iter().any(|&b| b < b' ')) {
+                    return Err(EncodeError::InvalidValue);
+                }
+                p.write_string(v)?;
+            }
+            (Type::Uint8, &Value::Uint8(v)) => p.write_raw(&[v])?,
+            (Type::Uuid, Value::Uuid(v)) => p.write_raw(v.as_bytes())?,
+            _ => self.encode_int_value(type_, value, &mut |i| Ok(p.write_int(i)?))?,
+        }
+        Ok(())
+    }
+    fn encode_object<F>(
+        &self,
+        obj: &Object,
+        record: &Record,
+        write: &mut F,
+    ) -> Result<(), EncodeError>
+    where
+        F: FnMut(i32) -> Result<(), EncodeError>,
+    {
+        let mut values = record.members.iter();
+        if let Some(super_) = &obj.super_ {
+            match values.next() {
+                Some((name, Value::Object(v))) if name == super_ && v.name == *super_ => {
+                    self.encode_object(self.object_by_name(super_).unwrap(), v, write)?;
+                }
+                _ => return Err(EncodeError::InvalidValue),
+            }
+        }
+        if obj.members.len() != values.len() {
+            return Err(EncodeError::InvalidValue);
+        }
+        for (m, (name, value)) in obj.members.iter().zip(values) {
+            if m.name != *name {
+                return Err(EncodeError::InvalidValue);
+            }
+            self.encode_int_value(&m.type_, value, write)?;
+        }
+        Ok(())
+    }
+    fn encode_int_value<F>(
+        &self,
+        type_: &Type,
+        value: &Value,
+        write: &mut F,
+    ) -> Result<(), EncodeError>
+    where
+        F: FnMut(i32) -> Result<(), EncodeError>,
+    {
+        match (type_, value) {
+            (Type::Array(i), Value::Array(v)) => {
+                if v.len() != i.count as usize {
+                    return Err(EncodeError::InvalidValue);
+                }
+                for e in v {
+                    self.encode_int_value(&i.member_type, e, write)?;
+                }
+            }
+            (Type::Boolean, &Value::Bool(v)) => write(v as i32)?,
+            (Type::Enum(i), Value::Enum(v)) => {
+                let known = self
+                    .enumeration(&i.enum_)
+                    .unwrap()
+                    .values
+                    .iter()
+                    .any(|e| e.value == v.value);
+                if !known {
+                    return Err(EncodeError::InvalidValue);
+                }
+                write(v.value)?;
+            }
+            (Type::Flags(_), Value::Flags(v)) => write(v.value)?,
+            (Type::Int32(i), &Value::Int(v)) => {
+                check_range(v, i.min, i.max).map_err(|_| EncodeError::InvalidValue)?;
+                write(v)?;
+            }
+            (Type::Int32Twstring(i), Value::String(v)) => {
+                let mut ints = vec![0; i.count as usize];
+                if v.contains(&0) || v.len() >= ints.len() * mem::size_of::<i32>() {
+                    return Err(EncodeError::InvalidValue);
+                }
+                string_to_ints(&mut ints, v);
+                for i in ints {
+                    write(i)?;
+                }
+            }
+            (Type::Tick, &Value::Int(v)) => write(v)?,
+            (Type::TuneParam, &Value::TuneParam(v)) => write(v)?,
+            _ => return Err(EncodeError::InvalidValue),
+        }
+        Ok(())
+    }
+}
+
+fn check_range(v: i32, min: Option<i32>, max: Option<i32>) -> Result<i32, IntOutOfRange> {
+    if min.map(|min| v < min).unwrap_or(false) || max.map(|max| v > max).unwrap_or(false) {
+        return Err(IntOutOfRange);
+    }
+    Ok(v)
+}
+
+fn int_size(type_: &Type) -> u32 {
+    match type_ {
+        Type::Array(i) => i.count as u32 * int_size(&i.member_type),
+        Type::Int32Twstring(i) => i.count as u32,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::EncodeError;
+    use super::Protocol;
+    use super::Record;
+    use super::Value;
+    use crate::Identifier;
+    use crate::Spec;
+    use libtw2_gamenet_common::msg::SystemOrGame;
+    use libtw2_gamenet_ddnet::msg::connless;
+    use libtw2_gamenet_ddnet::msg::game;
+    use libtw2_gamenet_ddnet::msg::ClientsData;
+    use libtw2_gamenet_ddnet::msg::Connless;
+    use libtw2_gamenet_ddnet::msg::Game;
+    use libtw2_gamenet_ddnet::snap_obj;
+    use libtw2_gamenet_ddnet::SnapObj;
+    use libtw2_packer::string_to_ints3;
+    use libtw2_packer::string_to_ints4;
+    use libtw2_packer::string_to_ints6;
+    use libtw2_packer::with_packer;
+    use libtw2_packer::IntUnpacker;
+    use libtw2_packer::Unpacker;
+    use libtw2_warn::Panic;
+
+    const DDNET_SPEC: &str = include_str!("../../generate/spec/ddnet-19.6.json");
+
+    fn protocol() -> Protocol {
+        let spec: Spec = serde_json::from_str(DDNET_SPEC).unwrap();
+        Protocol::new(spec).unwrap()
+    }
+
+    #[test]
+    fn all_specs_valid() {
+        let specs = [
+            include_str!("../../generate/spec/ddnet-15.2.5.json"),
+            include_str!("../../generate/spec/ddnet-16.2.json"),
+            include_str!("../../generate/spec/ddnet-16.7.2.json"),
+            include_str!("../../generate/spec/ddnet-17.2.1.json"),
+            include_str!("../../generate/spec/ddnet-19.1.json"),
+            include_str!("../../generate/spec/ddnet-19.6.json"),
+            include_str!("../../generate/spec/teeworlds-0.5.json"),
+            include_str!("../../generate/spec/teeworlds-0.6.json"),
+            include_str!("../../generate/spec/teeworlds-0.7-trunk.json"),
+            include_str!("../../generate/spec/teeworlds-0.7.5.json"),
+        ];
+        for spec in &specs {
+            Protocol::new(serde_json::from_str(spec).unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn game_roundtrip() {
+        let protocol = protocol();
+        let msg = Game::SvChat(game::SvChat {
+            team: 0,
+            client_id: 3,
+            message: b"hello",
+        });
+        let mut buf = Vec::with_capacity(1024);
+        let encoded = with_packer(&mut buf, |p| msg.encode(p).unwrap()).to_vec();
+
+        let decoded = protocol
+            .decode_msg(&mut Panic, &mut Unpacker::new(&encoded))
+            .unwrap();
+        let record = match decoded {
+            SystemOrGame::Game(r) => r,
+            SystemOrGame::System(_) => panic!("expected game message"),
+        };
+        assert_eq!(
+            record,
+            Record::new(Identifier::from_snake("sv_chat"))
+                .with("team", Value::Int(0))
+                .with("client_id", Value::Int(3))
+                .with("message", Value::String(b"hello".to_vec())),
+        );
+
+        let mut buf2 = Vec::with_capacity(1024);
+        let reencoded = with_packer(&mut buf2, |p| {
+            protocol.encode_msg(SystemOrGame::Game(&record), p).unwrap()
+        });
+        assert_eq!(reencoded, &encoded[..]);
+    }
+
+    #[test]
+    fn connless_roundtrip() {
+        let protocol = protocol();
+        let msg = Connless::Info(connless::Info {
+            token: 1234,
+            version: b"0.6.4",
+            name: b"server",
+            map: b"dm1",
+            game_type: b"DM",
+            flags: 0,
+            num_players: 1,
+            max_players: 16,
+            num_clients: 1,
+            max_clients: 16,
+            clients: ClientsData::from_bytes(b"nameless\0\0-1\x000\x001\0"),
+        });
+        let mut buf = Vec::with_capacity(1024);
+        let encoded = with_packer(&mut buf, |p| msg.encode(p).unwrap()).to_vec();
+
+        let record = protocol
+            .decode_connless(&mut Panic, &mut Unpacker::new(&encoded))
+            .unwrap();
+        assert_eq!(record.get("token"), Some(&Value::Int(1234)));
+        assert_eq!(record.get("map"), Some(&Value::String(b"dm1".to_vec())));
+
+        let mut buf2 = Vec::with_capacity(1024);
+        let reencoded = with_packer(&mut buf2, |p| protocol.encode_connless(&record, p).unwrap());
+        assert_eq!(reencoded, &encoded[..]);
+    }
+
+    #[test]
+    fn snap_obj_roundtrip() {
+        let protocol = protocol();
+        let obj = SnapObj::ClientInfo(snap_obj::ClientInfo {
+            name: string_to_ints4(b"nameless tee"),
+            clan: string_to_ints3(b"clan"),
+            country: -1,
+            skin: string_to_ints6(b"default"),
+            use_custom_color: 0,
+            color_body: 0,
+            color_feet: 0,
+        });
+        let record = protocol
+            .decode_obj(
+                &mut Panic,
+                obj.obj_type_id(),
+                &mut IntUnpacker::new(obj.encode()),
+            )
+            .unwrap();
+        assert_eq!(
+            record.get("name"),
+            Some(&Value::String(b"nameless tee".to_vec()))
+        );
+        assert_eq!(
+            record.get("skin"),
+            Some(&Value::String(b"default".to_vec()))
+        );
+        assert_eq!(
+            protocol.obj_size(snap_obj::CLIENT_INFO),
+            Some(obj.encode().len() as u32)
+        );
+
+        let (type_id, ints) = protocol.encode_obj(&record).unwrap();
+        assert_eq!(type_id, obj.obj_type_id());
+        assert_eq!(ints, obj.encode());
+    }
+
+    #[test]
+    fn int_string_max_length() {
+        let protocol = protocol();
+        let mut obj = snap_obj::ClientInfo {
+            name: string_to_ints4(b"123456789012345"),
+            clan: string_to_ints3(b"clan"),
+            country: -1,
+            skin: string_to_ints6(b"default"),
+            use_custom_color: 0,
+            color_body: 0,
+            color_feet: 0,
+        };
+        let decode = |obj: &snap_obj::ClientInfo| {
+            protocol
+                .decode_obj(
+                    &mut Panic,
+                    SnapObj::ClientInfo(*obj).obj_type_id(),
+                    &mut IntUnpacker::new(obj.encode()),
+                )
+                .unwrap()
+        };
+        let record = decode(&obj);
+        assert_eq!(
+            record.get("name"),
+            Some(&Value::String(b"123456789012345".to_vec()))
+        );
+        let (_, ints) = protocol.encode_obj(&record).unwrap();
+        assert_eq!(ints, obj.encode());
+
+        // Missing NUL terminator, the last byte is dropped.
+        obj.name = [i32::from_be_bytes([b'a'.wrapping_add(0x80); 4]); 4];
+        let record = decode(&obj);
+        assert_eq!(record.get("name"), Some(&Value::String(vec![b'a'; 15])));
+        protocol.encode_obj(&record).unwrap();
+    }
+
+    #[test]
+    fn encode_invalid() {
+        let protocol = protocol();
+        let record = Record::new(Identifier::from_snake("sv_chat"))
+            .with("team", Value::Int(7))
+            .with("client_id", Value::Int(3))
+            .with("message", Value::String(b"hello".to_vec()));
+        let mut buf = Vec::with_capacity(1024);
+        let result = with_packer(&mut buf, |p| {
+            protocol
+                .encode_msg(SystemOrGame::Game(&record), p)
+                .map(|_| ())
+        });
+        assert_eq!(result, Err(EncodeError::InvalidValue));
+    }
+}
@@ -1,10 +1,14 @@
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
 use std::collections::HashSet;
+use std::fmt;
 
 pub use libtw2_gamenet_common::msg::MessageId;
 pub use libtw2_gamenet_common::snap_obj::TypeId;
 
+pub mod dynamic;
+pub mod version;
+
 #[derive(Clone, Deserialize, Default, Eq, PartialEq, Serialize)]
 pub struct Spec {
     pub constants: Vec<Constant>,
@@ -47,6 +51,8 @@ pub struct Message {
 pub struct Object {
     pub id: TypeId,
     pub name: Identifier,
+    #[serde(rename = "super", default, skip_serializing_if = "Option::is_none")]
+    pub super_: Option<Identifier>,
     pub members: Vec<Member>,
     pub attributes: HashSet<String>,
 }
@@ -94,6 +100,7 @@ pub enum Type {
     Flags(FlagsType),
     Int32(Int32Type),
     Int32String,
+    Int32Twstring(Int32TwstringType),
     Optional(OptionalType),
     PackedAddresses,
     Rest,
@@ -130,6 +137,11 @@ pub struct Int32Type {
     pub max: Option<i32>,
 }
 
+#[derive(Clone, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Int32TwstringType {
+    pub count: i32,
+}
+
 #[derive(Clone, Deserialize, Eq, PartialEq, Serialize)]
 pub struct OptionalType {
     pub inner: Box<Type>,
@@ -169,6 +181,11 @@ fn capitalize_first(s: &str) -> String {
     result
 }
 impl Identifier {
+    pub fn from_snake(s: &str) -> Identifier {
+        Identifier {
+            parts: s.split('_').map(|p| p.to_owned()).collect(),
+        }
+    }
     pub fn snake(&self) -> String {
         self.parts.join("_")
     }
@@ -176,3 +193,9 @@ impl Identifier {
         capitalize_first(&self.parts.join(" "))
     }
 }
+
+impl fmt::Debug for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.snake())
+    }
+}
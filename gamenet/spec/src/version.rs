@@ -0,0 +1,252 @@
+//! Selection of the protocol spec matching a peer or a demo.
+//!
+//! The DDNet protocol changed in incompatible ways between releases, e.g.
+//! DDNet 19.1 raised the maximum client ID in several messages. Decoding old
+//! demos or talking to old servers with the newest spec thus mis-decodes some
+//! messages and snapshot objects. [`Version`] bundles all known specs and
+//! picks the right one from a demo's `net_version` or a client's
+//! `ClientVersion` message.
+
+use crate::dynamic::Protocol;
+use crate::Spec;
+
+/// A DDNet release for which a protocol spec is available.
+///
+/// Ordered by release.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum DdnetVersion {
+    V15_2_5,
+    V16_2,
+    V16_7_2,
+    V17_2_1,
+    V19_1,
+    V19_6,
+}
+
+impl DdnetVersion {
+    pub const ALL: &'static [DdnetVersion] = &[
+        DdnetVersion::V15_2_5,
+        DdnetVersion::V16_2,
+        DdnetVersion::V16_7_2,
+        DdnetVersion::V17_2_1,
+        DdnetVersion::V19_1,
+        DdnetVersion::V19_6,
+    ];
+    pub const LATEST: DdnetVersion = DdnetVersion::V19_6;
+
+    /// Picks the spec for a DDNet version number as sent in the
+    /// `ddnet_version` member of the `ClientVersion` message, e.g. `16072`
+    /// for DDNet 16.7.2.
+    ///
+    /// Returns the newest spec that is not newer than the given version.
+    /// Versions older than the oldest spec get the oldest spec.
+    pub fn from_version_number(ddnet_version: i32) -> DdnetVersion {
+        DdnetVersion::ALL
+            .iter()
+            .rev()
+            .copied()
+            .find(|v| v.version_number() <= ddnet_version)
+            .unwrap_or(DdnetVersion::ALL[0])
+    }
+    /// DDNet version number of the release, as sent in `ClientVersion`.
+    pub fn version_number(self) -> i32 {
+        use self::DdnetVersion::*;
+        match self {
+            V15_2_5 => 15025,
+            V16_2 => 16020,
+            V16_7_2 => 16072,
+            V17_2_1 => 17021,
+            V19_1 => 19010,
+            V19_6 => 19060,
+        }
+    }
+    /// Human-readable version of the release, e.g. `"16.7.2"`.
+    pub fn as_str(self) -> &'static str {
+        use self::DdnetVersion::*;
+        match self {
+            V15_2_5 => "15.2.5",
+            V16_2 => "16.2",
+            V16_7_2 => "16.7.2",
+            V17_2_1 => "17.2.1",
+            V19_1 => "19.1",
+            V19_6 => "19.6",
+        }
+    }
+    fn spec_json(self) -> &'static str {
+        use self::DdnetVersion::*;
+        match self {
+            V15_2_5 => include_str!("../../generate/spec/ddnet-15.2.5.json"),
+            V16_2 => include_str!("../../generate/spec/ddnet-16.2.json"),
+            V16_7_2 => include_str!("../../generate/spec/ddnet-16.7.2.json"),
+            V17_2_1 => include_str!("../../generate/spec/ddnet-17.2.1.json"),
+            V19_1 => include_str!("../../generate/spec/ddnet-19.1.json"),
+            V19_6 => include_str!("../../generate/spec/ddnet-19.6.json"),
+        }
+    }
+}
+
+/// A protocol version for which a spec is available.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Version {
+    Teeworlds05,
+    Teeworlds06,
+    Teeworlds07,
+    Ddnet(DdnetVersion),
+}
+
+pub const NET_VERSION_0_5: &[u8] = b"0.5 b67d1f1a1eea234e";
+pub const NET_VERSION_0_6: &[u8] = b"0.6 626fce9a778df4d4";
+pub const NET_VERSION_0_7: &[u8] = b"0.7 802f1be60a05665f";
+
+impl Version {
+    /// Picks the spec for a network version string as found in demo headers
+    /// and the `Info` system message.
+    ///
+    /// DDNet uses the same network version as Teeworlds 0.6 and extends its
+    /// protocol, so this returns the latest DDNet spec for it. Use
+    /// [`Version::with_ddnet_version`] to narrow it down once the
+    /// `ClientVersion` message is known.
+    pub fn from_net_version(net_version: &[u8]) -> Option<Version> {
+        Some(match net_version {
+            NET_VERSION_0_5 => Version::Teeworlds05,
+            NET_VERSION_0_6 => Version::Ddnet(DdnetVersion::LATEST),
+            NET_VERSION_0_7 => Version::Teeworlds07,
+            _ => return None,
+        })
+    }
+    /// Refines the version with the `ddnet_version` member of a
+    /// `ClientVersion` message.
+    ///
+    /// Versions other than Teeworlds 0.6 and DDNet are returned unchanged,
+    /// since they don't use that message.
+    pub fn with_ddnet_version(self, ddnet_version: i32) -> Version {
+        match self {
+            Version::Teeworlds06 | Version::Ddnet(_) => {
+                Version::Ddnet(DdnetVersion::from_version_number(ddnet_version))
+            }
+            v => v,
+        }
+    }
+    pub fn net_version(self) -> &'static [u8] {
+        match self {
+            Version::Teeworlds05 => NET_VERSION_0_5,
+            Version::Teeworlds06 | Version::Ddnet(_) => NET_VERSION_0_6,
+            Version::Teeworlds07 => NET_VERSION_0_7,
+        }
+    }
+    /// The JSON spec of the protocol version.
+    pub fn spec_json(self) -> &'static str {
+        match self {
+            Version::Teeworlds05 => include_str!("../../generate/spec/teeworlds-0.5.json"),
+            Version::Teeworlds06 => include_str!("../../generate/spec/teeworlds-0.6.json"),
+            Version::Teeworlds07 => include_str!("../../generate/spec/teeworlds-0.7.5.json"),
+            Version::Ddnet(v) => v.spec_json(),
+        }
+    }
+    pub fn spec(self) -> Spec {
+        serde_json::from_str(self.spec_json()).expect("invalid bundled spec")
+    }
+    /// Creates a decoder for the protocol version.
+    ///
+    /// This parses and validates the spec, so the result should be reused.
+    pub fn protocol(self) -> Protocol {
+        Protocol::new(self.spec()).expect("invalid bundled spec")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DdnetVersion;
+    use super::Version;
+    use libtw2_gamenet_common::error::Error;
+    use libtw2_gamenet_ddnet::msg::game;
+    use libtw2_gamenet_ddnet::msg::Game;
+    use libtw2_packer::with_packer;
+    use libtw2_packer::Unpacker;
+    use libtw2_warn::Panic;
+
+    #[test]
+    fn all_protocols() {
+        for &v in DdnetVersion::ALL {
+            Version::Ddnet(v).protocol();
+        }
+        Version::Teeworlds05.protocol();
+        Version::Teeworlds06.protocol();
+        Version::Teeworlds07.protocol();
+    }
+
+    #[test]
+    fn ddnet_version_order() {
+        for w in DdnetVersion::ALL.windows(2) {
+            assert!(w[0] < w[1]);
+            assert!(w[0].version_number() < w[1].version_number());
+        }
+        assert_eq!(*DdnetVersion::ALL.last().unwrap(), DdnetVersion::LATEST);
+    }
+
+    #[test]
+    fn from_version_number() {
+        use super::DdnetVersion::*;
+        assert_eq!(DdnetVersion::from_version_number(0), V15_2_5);
+        assert_eq!(DdnetVersion::from_version_number(15025), V15_2_5);
+        assert_eq!(DdnetVersion::from_version_number(16010), V15_2_5);
+        assert_eq!(DdnetVersion::from_version_number(16020), V16_2);
+        assert_eq!(DdnetVersion::from_version_number(18090), V17_2_1);
+        assert_eq!(DdnetVersion::from_version_number(19010), V19_1);
+        assert_eq!(DdnetVersion::from_version_number(19050), V19_1);
+        assert_eq!(DdnetVersion::from_version_number(19060), V19_6);
+        assert_eq!(DdnetVersion::from_version_number(20000), V19_6);
+    }
+
+    #[test]
+    fn from_net_version() {
+        assert_eq!(
+            Version::from_net_version(b"0.5 b67d1f1a1eea234e"),
+            Some(Version::Teeworlds05)
+        );
+        assert_eq!(
+            Version::from_net_version(b"0.6 626fce9a778df4d4"),
+            Some(Version::Ddnet(DdnetVersion::LATEST))
+        );
+        assert_eq!(
+            Version::from_net_version(b"0.7 802f1be60a05665f"),
+            Some(Version::Teeworlds07)
+        );
+        assert_eq!(Version::from_net_version(b"0.6"), None);
+        assert_eq!(
+            Version::Teeworlds06.with_ddnet_version(16072),
+            Version::Ddnet(DdnetVersion::V16_7_2)
+        );
+        assert_eq!(
+            Version::Teeworlds07.with_ddnet_version(16072),
+            Version::Teeworlds07
+        );
+        for &v in DdnetVersion::ALL {
+            assert_eq!(
+                Version::from_net_version(Version::Ddnet(v).net_version()),
+                Some(Version::Ddnet(DdnetVersion::LATEST))
+            );
+        }
+    }
+
+    #[test]
+    fn version_differences() {
+        // DDNet 19.1 raised the maximum client ID from 63 to 127.
+        let mut buf = Vec::with_capacity(1024);
+        let msg = Game::SvChat(game::SvChat {
+            team: 0,
+            client_id: 100,
+            message: b"hello",
+        });
+        let encoded = with_packer(&mut buf, |p| msg.encode(p)).unwrap();
+
+        let old = Version::Ddnet(DdnetVersion::V17_2_1).protocol();
+        let new = Version::Ddnet(DdnetVersion::V19_1).protocol();
+        assert!(matches!(
+            old.decode_msg(&mut Panic, &mut Unpacker::new(encoded)),
+            Err(Error::IntOutOfRange)
+        ));
+        new.decode_msg(&mut Panic, &mut Unpacker::new(encoded))
+            .unwrap();
+    }
+}
@@ -15,6 +15,8 @@ pub const PLAYERSTATE_CHATTING: i32 = 3;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Playerstate {
     Unknown,
     Playing,
@@ -31,6 +33,8 @@ pub const EMOTE_BLINK: i32 = 5;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Emote {
     Normal,
     Pain,
@@ -47,6 +51,8 @@ pub const POWERUP_NINJA: i32 = 3;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Powerup {
     Health,
     Armor,
@@ -72,6 +78,8 @@ pub const EMOTICON_V15: i32 = 14;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Emoticon {
     V1,
     V2,
@@ -99,6 +107,8 @@ pub const WEAPON_NINJA: i32 = 5;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Weapon {
     Hammer,
     Pistol,
@@ -114,6 +124,8 @@ pub const TEAM_BLUE: i32 = 1;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Team {
     Spectators = -1,
     Red,
@@ -162,6 +174,8 @@ pub const SOUND_CTF_CAPTURE: i32 = 38;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Sound {
     GunFire,
     ShotgunFire,
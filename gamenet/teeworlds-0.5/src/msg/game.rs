@@ -61,10 +61,12 @@ pub const CL_VOTE: i32 = 21;
 pub const CL_CALL_VOTE: i32 = 22;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Game<'a> {
-    SvMotd(SvMotd<'a>),
-    SvBroadcast(SvBroadcast<'a>),
-    SvChat(SvChat<'a>),
+    SvMotd(#[cfg_attr(feature = "serde", serde(borrow))] SvMotd<'a>),
+    SvBroadcast(#[cfg_attr(feature = "serde", serde(borrow))] SvBroadcast<'a>),
+    SvChat(#[cfg_attr(feature = "serde", serde(borrow))] SvChat<'a>),
     SvKillMsg(SvKillMsg),
     SvSoundGlobal(SvSoundGlobal),
     SvTuneParams(SvTuneParams),
@@ -73,17 +75,17 @@ pub enum Game<'a> {
     SvWeaponPickup(SvWeaponPickup),
     SvEmoticon(SvEmoticon),
     SvVoteClearOptions(SvVoteClearOptions),
-    SvVoteOption(SvVoteOption<'a>),
-    SvVoteSet(SvVoteSet<'a>),
+    SvVoteOption(#[cfg_attr(feature = "serde", serde(borrow))] SvVoteOption<'a>),
+    SvVoteSet(#[cfg_attr(feature = "serde", serde(borrow))] SvVoteSet<'a>),
     SvVoteStatus(SvVoteStatus),
-    ClSay(ClSay<'a>),
+    ClSay(#[cfg_attr(feature = "serde", serde(borrow))] ClSay<'a>),
     ClSetTeam(ClSetTeam),
-    ClStartInfo(ClStartInfo<'a>),
-    ClChangeInfo(ClChangeInfo<'a>),
+    ClStartInfo(#[cfg_attr(feature = "serde", serde(borrow))] ClStartInfo<'a>),
+    ClChangeInfo(#[cfg_attr(feature = "serde", serde(borrow))] ClChangeInfo<'a>),
     ClKill(ClKill),
     ClEmoticon(ClEmoticon),
     ClVote(ClVote),
-    ClCallVote(ClCallVote<'a>),
+    ClCallVote(#[cfg_attr(feature = "serde", serde(borrow))] ClCallVote<'a>),
 }
 
 impl<'a> Game<'a> {
@@ -330,23 +332,30 @@ impl<'a> From<ClCallVote<'a>> for Game<'a> {
     }
 }
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvMotd<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub message: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvBroadcast<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub message: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvChat<'a> {
     pub team: bool,
     pub client_id: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub message: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvKillMsg {
     pub killer: i32,
     pub victim: i32,
@@ -355,11 +364,13 @@ pub struct SvKillMsg {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvSoundGlobal {
     pub sound_id: enums::Sound,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvTuneParams {
     pub ground_control_speed: TuneParam,
     pub ground_control_accel: TuneParam,
@@ -397,40 +408,51 @@ pub struct SvTuneParams {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvExtraProjectile {
     pub projectile: crate::snap_obj::Projectile,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvReadyToEnter;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvWeaponPickup {
     pub weapon: enums::Weapon,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvEmoticon {
     pub client_id: i32,
     pub emoticon: enums::Emoticon,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteClearOptions;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteOption<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub command: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteSet<'a> {
     pub timeout: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub description: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub command: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteStatus {
     pub yes: i32,
     pub no: i32,
@@ -439,19 +461,25 @@ pub struct SvVoteStatus {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClSay<'a> {
     pub team: bool,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub message: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClSetTeam {
     pub team: enums::Team,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClStartInfo<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub skin: &'a [u8],
     pub use_custom_color: bool,
     pub color_body: i32,
@@ -459,8 +487,11 @@ pub struct ClStartInfo<'a> {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClChangeInfo<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub skin: &'a [u8],
     pub use_custom_color: bool,
     pub color_body: i32,
@@ -468,21 +499,27 @@ pub struct ClChangeInfo<'a> {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClKill;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClEmoticon {
     pub emoticon: enums::Emoticon,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClVote {
     pub vote: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClCallVote<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub type_: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub value: &'a [u8],
 }
 
@@ -49,21 +49,23 @@ pub const PING: i32 = 21;
 pub const PING_REPLY: i32 = 22;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum System<'a> {
-    Info(Info<'a>),
-    MapChange(MapChange<'a>),
-    MapData(MapData<'a>),
-    Snap(Snap<'a>),
+    Info(#[cfg_attr(feature = "serde", serde(borrow))] Info<'a>),
+    MapChange(#[cfg_attr(feature = "serde", serde(borrow))] MapChange<'a>),
+    MapData(#[cfg_attr(feature = "serde", serde(borrow))] MapData<'a>),
+    Snap(#[cfg_attr(feature = "serde", serde(borrow))] Snap<'a>),
     SnapEmpty(SnapEmpty),
-    SnapSingle(SnapSingle<'a>),
+    SnapSingle(#[cfg_attr(feature = "serde", serde(borrow))] SnapSingle<'a>),
     InputTiming(InputTiming),
     RconAuthStatus(RconAuthStatus),
-    RconLine(RconLine<'a>),
+    RconLine(#[cfg_attr(feature = "serde", serde(borrow))] RconLine<'a>),
     Ready(Ready),
     EnterGame(EnterGame),
     Input(Input),
-    RconCmd(RconCmd<'a>),
-    RconAuth(RconAuth<'a>),
+    RconCmd(#[cfg_attr(feature = "serde", serde(borrow))] RconCmd<'a>),
+    RconAuth(#[cfg_attr(feature = "serde", serde(borrow))] RconAuth<'a>),
     RequestMapData(RequestMapData),
     Ping(Ping),
     PingReply(PingReply),
@@ -263,73 +265,94 @@ impl<'a> From<PingReply> for System<'a> {
     }
 }
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Info<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub version: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub clan: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub password: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct MapChange<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
     pub crc: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct MapData<'a> {
     pub last: i32,
     pub total_size: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub data: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Snap<'a> {
     pub tick: i32,
     pub delta_tick: i32,
     pub num_parts: i32,
     pub part: i32,
     pub crc: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub data: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SnapEmpty {
     pub tick: i32,
     pub delta_tick: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SnapSingle<'a> {
     pub tick: i32,
     pub delta_tick: i32,
     pub crc: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub data: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct InputTiming {
     pub input_pred_tick: i32,
     pub time_left: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconAuthStatus {
     pub authed: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconLine<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub line: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Ready;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct EnterGame;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Input {
     pub ack_snapshot: i32,
     pub intended_tick: i32,
@@ -338,25 +361,33 @@ pub struct Input {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconCmd<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub cmd: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconAuth<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub _unused: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub password: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RequestMapData {
     pub chunk: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Ping;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct PingReply;
 
 impl<'a> Info<'a> {
@@ -40,6 +40,8 @@ pub const SOUND_WORLD: u16 = 17;
 pub const DAMAGE_IND: u16 = 18;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum SnapObj {
     PlayerInput(PlayerInput),
     Projectile(Projectile),
@@ -267,6 +269,7 @@ impl From<DamageInd> for SnapObj {
 
 #[repr(C)]
 #[derive(Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct PlayerInput {
     pub direction: i32,
     pub target_x: i32,
@@ -282,6 +285,7 @@ pub struct PlayerInput {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Projectile {
     pub x: i32,
     pub y: i32,
@@ -293,6 +297,7 @@ pub struct Projectile {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Laser {
     pub x: i32,
     pub y: i32,
@@ -303,6 +308,7 @@ pub struct Laser {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Pickup {
     pub x: i32,
     pub y: i32,
@@ -312,6 +318,7 @@ pub struct Pickup {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Flag {
     pub x: i32,
     pub y: i32,
@@ -321,6 +328,7 @@ pub struct Flag {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Game {
     pub flags: i32,
     pub round_start_tick: crate::snap_obj::Tick,
@@ -338,6 +346,7 @@ pub struct Game {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct CharacterCore {
     pub tick: i32,
     pub x: i32,
@@ -358,6 +367,7 @@ pub struct CharacterCore {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Character {
     pub character_core: CharacterCore,
     pub player_state: enums::Playerstate,
@@ -371,6 +381,7 @@ pub struct Character {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct PlayerInfo {
     pub local: i32,
     pub client_id: i32,
@@ -382,6 +393,7 @@ pub struct PlayerInfo {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClientInfo {
     pub name: [i32; 6],
     pub skin: [i32; 6],
@@ -392,6 +404,7 @@ pub struct ClientInfo {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Common {
     pub x: i32,
     pub y: i32,
@@ -399,24 +412,28 @@ pub struct Common {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Explosion {
     pub common: Common,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Spawn {
     pub common: Common,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct HammerHit {
     pub common: Common,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Death {
     pub common: Common,
     pub client_id: i32,
@@ -424,6 +441,7 @@ pub struct Death {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SoundGlobal {
     pub common: Common,
     pub sound_id: enums::Sound,
@@ -431,6 +449,7 @@ pub struct SoundGlobal {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SoundWorld {
     pub common: Common,
     pub sound_id: enums::Sound,
@@ -438,6 +457,7 @@ pub struct SoundWorld {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct DamageInd {
     pub common: Common,
     pub angle: i32,
@@ -0,0 +1,109 @@
+use libtw2_gamenet_common::serialize::Buffer;
+use libtw2_gamenet_teeworlds_0_5::enums;
+use libtw2_gamenet_teeworlds_0_5::msg::game;
+use libtw2_gamenet_teeworlds_0_5::msg::system;
+use libtw2_gamenet_teeworlds_0_5::msg::Game;
+use libtw2_gamenet_teeworlds_0_5::msg::System;
+use libtw2_gamenet_teeworlds_0_5::snap_obj;
+use libtw2_gamenet_teeworlds_0_5::SnapObj;
+use libtw2_packer::string_to_ints6;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+
+fn check<'de, T>(value: T, json: &'de str)
+where
+    T: fmt::Debug + Deserialize<'de> + Serialize,
+{
+    assert_eq!(serde_json::to_string(&value).unwrap(), json);
+    let decoded: T = serde_json::from_str(json).unwrap();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", value));
+}
+
+#[test]
+fn game() {
+    check(
+        Game::SvChat(game::SvChat {
+            team: true,
+            client_id: 4,
+            message: b"hello",
+        }),
+        r#"{"sv_chat":{"team":true,"client_id":4,"message":"hello"}}"#,
+    );
+    check(
+        Game::SvKillMsg(game::SvKillMsg {
+            killer: 1,
+            victim: 2,
+            weapon: -3,
+            mode_special: 0,
+        }),
+        r#"{"sv_kill_msg":{"killer":1,"victim":2,"weapon":-3,"mode_special":0}}"#,
+    );
+    check(
+        Game::SvVoteClearOptions(game::SvVoteClearOptions),
+        r#"{"sv_vote_clear_options":null}"#,
+    );
+}
+
+#[test]
+fn system() {
+    check(
+        System::Info(system::Info {
+            version: b"0.5 b67d1f1a1eea234e",
+            name: b"nameless tee",
+            clan: b"",
+            password: b"secret",
+        }),
+        concat!(
+            r#"{"info":{"version":"0.5 b67d1f1a1eea234e","name":"nameless tee","#,
+            r#""clan":"","password":"secret"}}"#,
+        ),
+    );
+}
+
+#[test]
+fn non_utf8() {
+    let value = Game::ClSay(game::ClSay {
+        team: false,
+        message: b"\xff",
+    });
+    let json = r#"{"cl_say":{"team":false,"message":[255]}}"#;
+    assert_eq!(serde_json::to_string(&value).unwrap(), json);
+    // Can't be borrowed from the JSON directly.
+    assert!(serde_json::from_str::<Game>(json).is_err());
+    let buffer: Buffer = serde_json::from_str(json).unwrap();
+    let decoded: Game = buffer.deserialize().unwrap();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", value));
+}
+
+#[test]
+fn snap_obj() {
+    // Unlike DDNet's, the spec doesn't mark the names as strings, they're
+    // serialized as plain integer arrays.
+    let value = SnapObj::ClientInfo(snap_obj::ClientInfo {
+        name: string_to_ints6(b"nameless tee"),
+        skin: string_to_ints6(b"default"),
+        use_custom_color: 0,
+        color_body: 65408,
+        color_feet: 65408,
+    });
+    let json = serde_json::to_string(&value).unwrap();
+    assert!(json.starts_with(r#"{"client_info":{"name":["#));
+    assert!(json.ends_with(r#""use_custom_color":0,"color_body":65408,"color_feet":65408}}"#));
+    let decoded: SnapObj = serde_json::from_str(&json).unwrap();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", value));
+    check(
+        SnapObj::PlayerInfo(snap_obj::PlayerInfo {
+            local: 0,
+            client_id: 7,
+            team: enums::Team::Blue,
+            score: 12,
+            latency: 30,
+            latency_flux: 4,
+        }),
+        concat!(
+            r#"{"player_info":{"local":0,"client_id":7,"team":"blue","score":12,"#,
+            r#""latency":30,"latency_flux":4}}"#,
+        ),
+    );
+}
@@ -22,6 +22,8 @@ pub const EMOTE_BLINK: i32 = 5;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Emote {
     Normal,
     Pain,
@@ -38,6 +40,8 @@ pub const POWERUP_NINJA: i32 = 3;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Powerup {
     Health,
     Armor,
@@ -64,6 +68,8 @@ pub const EMOTICON_QUESTION: i32 = 15;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Emoticon {
     Oop,
     Exclamation,
@@ -92,6 +98,8 @@ pub const WEAPON_NINJA: i32 = 5;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Weapon {
     Hammer,
     Pistol,
@@ -107,6 +115,8 @@ pub const TEAM_BLUE: i32 = 1;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Team {
     Spectators = -1,
     Red,
@@ -157,6 +167,8 @@ pub const SOUND_MENU: i32 = 40;
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Hash, Ord)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Sound {
     GunFire,
     ShotgunFire,
@@ -53,27 +53,29 @@ pub const RCON_CMD_ADD: i32 = 25;
 pub const RCON_CMD_REMOVE: i32 = 26;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum System<'a> {
-    Info(Info<'a>),
-    MapChange(MapChange<'a>),
-    MapData(MapData<'a>),
+    Info(#[cfg_attr(feature = "serde", serde(borrow))] Info<'a>),
+    MapChange(#[cfg_attr(feature = "serde", serde(borrow))] MapChange<'a>),
+    MapData(#[cfg_attr(feature = "serde", serde(borrow))] MapData<'a>),
     ConReady(ConReady),
-    Snap(Snap<'a>),
+    Snap(#[cfg_attr(feature = "serde", serde(borrow))] Snap<'a>),
     SnapEmpty(SnapEmpty),
-    SnapSingle(SnapSingle<'a>),
+    SnapSingle(#[cfg_attr(feature = "serde", serde(borrow))] SnapSingle<'a>),
     InputTiming(InputTiming),
     RconAuthStatus(RconAuthStatus),
-    RconLine(RconLine<'a>),
+    RconLine(#[cfg_attr(feature = "serde", serde(borrow))] RconLine<'a>),
     Ready(Ready),
     EnterGame(EnterGame),
     Input(Input),
-    RconCmd(RconCmd<'a>),
-    RconAuth(RconAuth<'a>),
+    RconCmd(#[cfg_attr(feature = "serde", serde(borrow))] RconCmd<'a>),
+    RconAuth(#[cfg_attr(feature = "serde", serde(borrow))] RconAuth<'a>),
     RequestMapData(RequestMapData),
     Ping(Ping),
     PingReply(PingReply),
-    RconCmdAdd(RconCmdAdd<'a>),
-    RconCmdRemove(RconCmdRemove<'a>),
+    RconCmdAdd(#[cfg_attr(feature = "serde", serde(borrow))] RconCmdAdd<'a>),
+    RconCmdRemove(#[cfg_attr(feature = "serde", serde(borrow))] RconCmdRemove<'a>),
 }
 
 impl<'a> System<'a> {
@@ -300,27 +302,35 @@ impl<'a> From<RconCmdRemove<'a>> for System<'a> {
     }
 }
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Info<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub version: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::option_bytes"))]
     pub password: Option<&'a [u8]>,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct MapChange<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
     pub crc: i32,
     pub size: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct MapData<'a> {
     pub last: i32,
     pub crc: i32,
     pub chunk: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub data: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ConReady;
 
 pub use libtw2_gamenet_snap::Snap;
@@ -330,29 +340,36 @@ pub use libtw2_gamenet_snap::SnapEmpty;
 pub use libtw2_gamenet_snap::SnapSingle;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct InputTiming {
     pub input_pred_tick: i32,
     pub time_left: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconAuthStatus {
     pub auth_level: Option<i32>,
     pub receive_commands: Option<i32>,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconLine<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub line: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Ready;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct EnterGame;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Input {
     pub ack_snapshot: i32,
     pub intended_tick: i32,
@@ -361,37 +378,51 @@ pub struct Input {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconCmd<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub cmd: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconAuth<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub _unused: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub password: &'a [u8],
     pub request_commands: Option<i32>,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RequestMapData {
     pub chunk: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Ping;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct PingReply;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconCmdAdd<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub help: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub params: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct RconCmdRemove<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
 }
 
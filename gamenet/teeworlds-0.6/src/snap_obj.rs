@@ -51,6 +51,8 @@ pub const SOUND_WORLD: u16 = 19;
 pub const DAMAGE_IND: u16 = 20;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum SnapObj {
     PlayerInput(PlayerInput),
     Projectile(Projectile),
@@ -300,6 +302,7 @@ impl From<DamageInd> for SnapObj {
 
 #[repr(C)]
 #[derive(Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct PlayerInput {
     pub direction: i32,
     pub target_x: i32,
@@ -315,6 +318,7 @@ pub struct PlayerInput {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Projectile {
     pub x: i32,
     pub y: i32,
@@ -326,6 +330,7 @@ pub struct Projectile {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Laser {
     pub x: i32,
     pub y: i32,
@@ -336,6 +341,7 @@ pub struct Laser {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Pickup {
     pub x: i32,
     pub y: i32,
@@ -345,6 +351,7 @@ pub struct Pickup {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Flag {
     pub x: i32,
     pub y: i32,
@@ -353,6 +360,7 @@ pub struct Flag {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct GameInfo {
     pub game_flags: i32,
     pub game_state_flags: i32,
@@ -366,6 +374,7 @@ pub struct GameInfo {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct GameData {
     pub teamscore_red: i32,
     pub teamscore_blue: i32,
@@ -375,6 +384,7 @@ pub struct GameData {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct CharacterCore {
     pub tick: i32,
     pub x: i32,
@@ -395,6 +405,7 @@ pub struct CharacterCore {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Character {
     pub character_core: CharacterCore,
     pub player_flags: i32,
@@ -408,6 +419,7 @@ pub struct Character {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct PlayerInfo {
     pub local: i32,
     pub client_id: i32,
@@ -418,6 +430,7 @@ pub struct PlayerInfo {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClientInfo {
     pub name: [i32; 4],
     pub clan: [i32; 3],
@@ -430,6 +443,7 @@ pub struct ClientInfo {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SpectatorInfo {
     pub spectator_id: i32,
     pub x: i32,
@@ -438,6 +452,7 @@ pub struct SpectatorInfo {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Common {
     pub x: i32,
     pub y: i32,
@@ -445,24 +460,28 @@ pub struct Common {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Explosion {
     pub common: Common,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Spawn {
     pub common: Common,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct HammerHit {
     pub common: Common,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Death {
     pub common: Common,
     pub client_id: i32,
@@ -470,6 +489,7 @@ pub struct Death {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SoundGlobal {
     pub common: Common,
     pub sound_id: enums::Sound,
@@ -477,6 +497,7 @@ pub struct SoundGlobal {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SoundWorld {
     pub common: Common,
     pub sound_id: enums::Sound,
@@ -484,6 +505,7 @@ pub struct SoundWorld {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct DamageInd {
     pub common: Common,
     pub angle: i32,
@@ -0,0 +1,129 @@
+use libtw2_gamenet_common::serialize::Buffer;
+use libtw2_gamenet_teeworlds_0_6::enums;
+use libtw2_gamenet_teeworlds_0_6::msg::game;
+use libtw2_gamenet_teeworlds_0_6::msg::system;
+use libtw2_gamenet_teeworlds_0_6::msg::Game;
+use libtw2_gamenet_teeworlds_0_6::msg::System;
+use libtw2_gamenet_teeworlds_0_6::snap_obj;
+use libtw2_gamenet_teeworlds_0_6::SnapObj;
+use libtw2_packer::string_to_ints3;
+use libtw2_packer::string_to_ints4;
+use libtw2_packer::string_to_ints6;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+
+fn check<'de, T>(value: T, json: &'de str)
+where
+    T: fmt::Debug + Deserialize<'de> + Serialize,
+{
+    assert_eq!(serde_json::to_string(&value).unwrap(), json);
+    let decoded: T = serde_json::from_str(json).unwrap();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", value));
+}
+
+#[test]
+fn game() {
+    check(
+        Game::SvChat(game::SvChat {
+            team: false,
+            client_id: -1,
+            message: b"hello",
+        }),
+        r#"{"sv_chat":{"team":false,"client_id":-1,"message":"hello"}}"#,
+    );
+    check(
+        Game::SvEmoticon(game::SvEmoticon {
+            client_id: 3,
+            emoticon: enums::Emoticon::Hearts,
+        }),
+        r#"{"sv_emoticon":{"client_id":3,"emoticon":"hearts"}}"#,
+    );
+    check(
+        Game::SvVoteClearOptions(game::SvVoteClearOptions),
+        r#"{"sv_vote_clear_options":null}"#,
+    );
+}
+
+#[test]
+fn game_arrays() {
+    let mut description = [&b""[..]; 15];
+    description[0] = b"kick nameless";
+    description[1] = b"change map";
+    let json = serde_json::to_string(&game::SvVoteOptionListAdd {
+        num_options: 2,
+        description,
+    })
+    .unwrap();
+    assert!(
+        json.starts_with(r#"{"num_options":2,"description":["kick nameless","change map","",""#)
+    );
+    let decoded: game::SvVoteOptionListAdd = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded.description, description);
+}
+
+#[test]
+fn system() {
+    check(
+        System::Info(system::Info {
+            version: b"0.6 626fce9a778df4d4",
+            password: Some(b"secret"),
+        }),
+        r#"{"info":{"version":"0.6 626fce9a778df4d4","password":"secret"}}"#,
+    );
+    check(
+        System::Info(system::Info {
+            version: b"0.6 626fce9a778df4d4",
+            password: None,
+        }),
+        r#"{"info":{"version":"0.6 626fce9a778df4d4","password":null}}"#,
+    );
+}
+
+#[test]
+fn non_utf8() {
+    let value = Game::ClSay(game::ClSay {
+        team: false,
+        message: b"\xff",
+    });
+    let json = r#"{"cl_say":{"team":false,"message":[255]}}"#;
+    assert_eq!(serde_json::to_string(&value).unwrap(), json);
+    // Can't be borrowed from the JSON directly.
+    assert!(serde_json::from_str::<Game>(json).is_err());
+    let buffer: Buffer = serde_json::from_str(json).unwrap();
+    let decoded: Game = buffer.deserialize().unwrap();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", value));
+}
+
+#[test]
+fn snap_obj() {
+    // Unlike DDNet's, the spec doesn't mark the names as strings, they're
+    // serialized as plain integer arrays.
+    let value = SnapObj::ClientInfo(snap_obj::ClientInfo {
+        name: string_to_ints4(b"nameless tee"),
+        clan: string_to_ints3(b""),
+        country: -1,
+        skin: string_to_ints6(b"default"),
+        use_custom_color: 0,
+        color_body: 65408,
+        color_feet: 65408,
+    });
+    let json = serde_json::to_string(&value).unwrap();
+    assert!(json.starts_with(r#"{"client_info":{"name":["#));
+    assert!(json.ends_with(r#""use_custom_color":0,"color_body":65408,"color_feet":65408}}"#));
+    let decoded: SnapObj = serde_json::from_str(&json).unwrap();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", value));
+    check(
+        SnapObj::PlayerInfo(snap_obj::PlayerInfo {
+            local: 1,
+            client_id: 0,
+            team: enums::Team::Spectators,
+            score: -2,
+            latency: 50,
+        }),
+        concat!(
+            r#"{"player_info":{"local":1,"client_id":0,"team":"spectators","#,
+            r#""score":-2,"latency":50}}"#,
+        ),
+    );
+}
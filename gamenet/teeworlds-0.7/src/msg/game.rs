@@ -81,10 +81,12 @@ pub const SV_COMMAND_INFO_REMOVE: i32 = 38;
 pub const CL_COMMAND: i32 = 39;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Game<'a> {
-    SvMotd(SvMotd<'a>),
-    SvBroadcast(SvBroadcast<'a>),
-    SvChat(SvChat<'a>),
+    SvMotd(#[cfg_attr(feature = "serde", serde(borrow))] SvMotd<'a>),
+    SvBroadcast(#[cfg_attr(feature = "serde", serde(borrow))] SvBroadcast<'a>),
+    SvChat(#[cfg_attr(feature = "serde", serde(borrow))] SvChat<'a>),
     SvTeam(SvTeam),
     SvKillMsg(SvKillMsg),
     SvTuneParams(SvTuneParams),
@@ -94,33 +96,33 @@ pub enum Game<'a> {
     SvEmoticon(SvEmoticon),
     SvVoteClearOptions(SvVoteClearOptions),
     SvVoteOptionListAdd(SvVoteOptionListAdd),
-    SvVoteOptionAdd(SvVoteOptionAdd<'a>),
-    SvVoteOptionRemove(SvVoteOptionRemove<'a>),
-    SvVoteSet(SvVoteSet<'a>),
+    SvVoteOptionAdd(#[cfg_attr(feature = "serde", serde(borrow))] SvVoteOptionAdd<'a>),
+    SvVoteOptionRemove(#[cfg_attr(feature = "serde", serde(borrow))] SvVoteOptionRemove<'a>),
+    SvVoteSet(#[cfg_attr(feature = "serde", serde(borrow))] SvVoteSet<'a>),
     SvVoteStatus(SvVoteStatus),
     SvServerSettings(SvServerSettings),
-    SvClientInfo(SvClientInfo<'a>),
+    SvClientInfo(#[cfg_attr(feature = "serde", serde(borrow))] SvClientInfo<'a>),
     SvGameInfo(SvGameInfo),
-    SvClientDrop(SvClientDrop<'a>),
+    SvClientDrop(#[cfg_attr(feature = "serde", serde(borrow))] SvClientDrop<'a>),
     SvGameMsg(SvGameMsg),
-    DeClientEnter(DeClientEnter<'a>),
-    DeClientLeave(DeClientLeave<'a>),
-    ClSay(ClSay<'a>),
+    DeClientEnter(#[cfg_attr(feature = "serde", serde(borrow))] DeClientEnter<'a>),
+    DeClientLeave(#[cfg_attr(feature = "serde", serde(borrow))] DeClientLeave<'a>),
+    ClSay(#[cfg_attr(feature = "serde", serde(borrow))] ClSay<'a>),
     ClSetTeam(ClSetTeam),
     ClSetSpectatorMode(ClSetSpectatorMode),
-    ClStartInfo(ClStartInfo<'a>),
+    ClStartInfo(#[cfg_attr(feature = "serde", serde(borrow))] ClStartInfo<'a>),
     ClKill(ClKill),
     ClReadyChange(ClReadyChange),
     ClEmoticon(ClEmoticon),
     ClVote(ClVote),
-    ClCallVote(ClCallVote<'a>),
-    SvSkinChange(SvSkinChange<'a>),
-    ClSkinChange(ClSkinChange<'a>),
+    ClCallVote(#[cfg_attr(feature = "serde", serde(borrow))] ClCallVote<'a>),
+    SvSkinChange(#[cfg_attr(feature = "serde", serde(borrow))] SvSkinChange<'a>),
+    ClSkinChange(#[cfg_attr(feature = "serde", serde(borrow))] ClSkinChange<'a>),
     SvRaceFinish(SvRaceFinish),
     SvCheckpoint(SvCheckpoint),
-    SvCommandInfo(SvCommandInfo<'a>),
-    SvCommandInfoRemove(SvCommandInfoRemove<'a>),
-    ClCommand(ClCommand<'a>),
+    SvCommandInfo(#[cfg_attr(feature = "serde", serde(borrow))] SvCommandInfo<'a>),
+    SvCommandInfoRemove(#[cfg_attr(feature = "serde", serde(borrow))] SvCommandInfoRemove<'a>),
+    ClCommand(#[cfg_attr(feature = "serde", serde(borrow))] ClCommand<'a>),
 }
 
 impl<'a> Game<'a> {
@@ -537,24 +539,31 @@ impl<'a> From<ClCommand<'a>> for Game<'a> {
     }
 }
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvMotd<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub message: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvBroadcast<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub message: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvChat<'a> {
     pub mode: enums::Chat,
     pub client_id: i32,
     pub target_id: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub message: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvTeam {
     pub client_id: i32,
     pub team: enums::Team,
@@ -563,6 +572,7 @@ pub struct SvTeam {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvKillMsg {
     pub killer: i32,
     pub victim: i32,
@@ -571,6 +581,7 @@ pub struct SvKillMsg {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvTuneParams {
     pub ground_control_speed: TuneParam,
     pub ground_control_accel: TuneParam,
@@ -607,50 +618,64 @@ pub struct SvTuneParams {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvExtraProjectile {
     pub projectile: crate::snap_obj::Projectile,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvReadyToEnter;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvWeaponPickup {
     pub weapon: enums::Weapon,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvEmoticon {
     pub client_id: i32,
     pub emoticon: enums::Emoticon,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteClearOptions;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteOptionListAdd;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteOptionAdd<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub description: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteOptionRemove<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub description: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteSet<'a> {
     pub client_id: i32,
     pub type_: enums::Vote,
     pub timeout: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub description: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub reason: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvVoteStatus {
     pub yes: i32,
     pub no: i32,
@@ -659,6 +684,7 @@ pub struct SvVoteStatus {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvServerSettings {
     pub kick_vote: bool,
     pub kick_min: i32,
@@ -669,13 +695,17 @@ pub struct SvServerSettings {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvClientInfo<'a> {
     pub client_id: i32,
     pub local: bool,
     pub team: enums::Team,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub clan: &'a [u8],
     pub country: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes_array"))]
     pub skin_part_names: [&'a [u8]; 6],
     pub use_custom_colors: [bool; 6],
     pub skin_part_colors: [i32; 6],
@@ -683,6 +713,7 @@ pub struct SvClientInfo<'a> {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvGameInfo {
     pub game_flags: i32,
     pub score_limit: i32,
@@ -692,97 +723,126 @@ pub struct SvGameInfo {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvClientDrop<'a> {
     pub client_id: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub reason: &'a [u8],
     pub silent: bool,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvGameMsg;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct DeClientEnter<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
     pub client_id: i32,
     pub team: enums::Team,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct DeClientLeave<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
     pub client_id: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub reason: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClSay<'a> {
     pub mode: enums::Chat,
     pub target: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub message: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClSetTeam {
     pub team: enums::Team,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClSetSpectatorMode {
     pub spec_mode: enums::Spec,
     pub spectator_id: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClStartInfo<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub clan: &'a [u8],
     pub country: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes_array"))]
     pub skin_part_names: [&'a [u8]; 6],
     pub use_custom_colors: [bool; 6],
     pub skin_part_colors: [i32; 6],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClKill;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClReadyChange;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClEmoticon {
     pub emoticon: enums::Emoticon,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClVote {
     pub vote: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClCallVote<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub type_: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub value: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub reason: &'a [u8],
     pub force: bool,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvSkinChange<'a> {
     pub client_id: i32,
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes_array"))]
     pub skin_part_names: [&'a [u8]; 6],
     pub use_custom_colors: [bool; 6],
     pub skin_part_colors: [i32; 6],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClSkinChange<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes_array"))]
     pub skin_part_names: [&'a [u8]; 6],
     pub use_custom_colors: [bool; 6],
     pub skin_part_colors: [i32; 6],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvRaceFinish {
     pub client_id: i32,
     pub time: i32,
@@ -792,25 +852,35 @@ pub struct SvRaceFinish {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvCheckpoint {
     pub diff: i32,
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvCommandInfo<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub args_format: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub help_text: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SvCommandInfoRemove<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct ClCommand<'a> {
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub name: &'a [u8],
+    #[cfg_attr(feature = "serde", serde(borrow, with = "libtw2_gamenet_common::serialize::bytes"))]
     pub arguments: &'a [u8],
 }
 
@@ -75,6 +75,8 @@ pub const PLAYER_INFO_RACE: u16 = 23;
 pub const GAME_DATA_RACE: u16 = 24;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum SnapObj {
     PlayerInput(PlayerInput),
     Projectile(Projectile),
@@ -368,6 +370,7 @@ impl From<GameDataRace> for SnapObj {
 
 #[repr(C)]
 #[derive(Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct PlayerInput {
     pub direction: i32,
     pub target_x: i32,
@@ -383,6 +386,7 @@ pub struct PlayerInput {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Projectile {
     pub x: i32,
     pub y: i32,
@@ -394,6 +398,7 @@ pub struct Projectile {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Laser {
     pub x: i32,
     pub y: i32,
@@ -404,6 +409,7 @@ pub struct Laser {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Pickup {
     pub x: i32,
     pub y: i32,
@@ -412,6 +418,7 @@ pub struct Pickup {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Flag {
     pub x: i32,
     pub y: i32,
@@ -420,6 +427,7 @@ pub struct Flag {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct GameData {
     pub game_start_tick: crate::snap_obj::Tick,
     pub game_state_flags: i32,
@@ -428,6 +436,7 @@ pub struct GameData {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct GameDataTeam {
     pub teamscore_red: i32,
     pub teamscore_blue: i32,
@@ -435,6 +444,7 @@ pub struct GameDataTeam {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct GameDataFlag {
     pub flag_carrier_red: i32,
     pub flag_carrier_blue: i32,
@@ -444,6 +454,7 @@ pub struct GameDataFlag {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct CharacterCore {
     pub tick: crate::snap_obj::Tick,
     pub x: i32,
@@ -464,6 +475,7 @@ pub struct CharacterCore {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Character {
     pub character_core: CharacterCore,
     pub health: i32,
@@ -477,6 +489,7 @@ pub struct Character {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct PlayerInfo {
     pub player_flags: i32,
     pub score: i32,
@@ -485,6 +498,7 @@ pub struct PlayerInfo {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SpectatorInfo {
     pub spec_mode: enums::Spec,
     pub spectator_id: i32,
@@ -494,6 +508,7 @@ pub struct SpectatorInfo {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct DeClientInfo {
     pub local: bool,
     pub team: enums::Team,
@@ -507,6 +522,7 @@ pub struct DeClientInfo {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct DeGameInfo {
     pub game_flags: i32,
     pub score_limit: i32,
@@ -517,12 +533,14 @@ pub struct DeGameInfo {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct DeTuneParams {
     pub tune_params: [i32; 32],
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Common {
     pub x: i32,
     pub y: i32,
@@ -530,24 +548,28 @@ pub struct Common {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Explosion {
     pub common: Common,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Spawn {
     pub common: Common,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct HammerHit {
     pub common: Common,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Death {
     pub common: Common,
     pub client_id: i32,
@@ -555,6 +577,7 @@ pub struct Death {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct SoundWorld {
     pub common: Common,
     pub sound_id: enums::Sound,
@@ -562,6 +585,7 @@ pub struct SoundWorld {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct Damage {
     pub common: Common,
     pub client_id: i32,
@@ -573,12 +597,14 @@ pub struct Damage {
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct PlayerInfoRace {
     pub race_start_tick: crate::snap_obj::Tick,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde_derive::Deserialize, serde_derive::Serialize))]
 pub struct GameDataRace {
     pub best_time: i32,
     pub precision: i32,
@@ -0,0 +1,55 @@
+use libtw2_gamenet_teeworlds_0_7::enums;
+use libtw2_gamenet_teeworlds_0_7::msg::game;
+use libtw2_gamenet_teeworlds_0_7::msg::system;
+use libtw2_gamenet_teeworlds_0_7::msg::Game;
+use libtw2_gamenet_teeworlds_0_7::msg::System;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+
+fn check<'de, T>(value: T, json: &'de str)
+where
+    T: fmt::Debug + Deserialize<'de> + Serialize,
+{
+    assert_eq!(serde_json::to_string(&value).unwrap(), json);
+    let decoded: T = serde_json::from_str(json).unwrap();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", value));
+}
+
+#[test]
+fn game() {
+    check(
+        Game::SvChat(game::SvChat {
+            mode: enums::Chat::Whisper,
+            client_id: 1,
+            target_id: 2,
+            message: b"hi",
+        }),
+        r#"{"sv_chat":{"mode":"whisper","client_id":1,"target_id":2,"message":"hi"}}"#,
+    );
+    check(
+        Game::ClSkinChange(game::ClSkinChange {
+            skin_part_names: [b"standard", b"", b"", b"standard", b"standard", b"standard"],
+            use_custom_colors: [false, true, false, false, false, false],
+            skin_part_colors: [0, 1, 2, 3, 4, 5],
+        }),
+        concat!(
+            r#"{"cl_skin_change":{"#,
+            r#""skin_part_names":["standard","","","standard","standard","standard"],"#,
+            r#""use_custom_colors":[false,true,false,false,false,false],"#,
+            r#""skin_part_colors":[0,1,2,3,4,5]}}"#,
+        ),
+    );
+}
+
+#[test]
+fn system() {
+    check(
+        System::Info(system::Info {
+            version: b"0.7 802f1be60a05665f",
+            password: Some(b""),
+            client_version: Some(0x0705),
+        }),
+        r#"{"info":{"version":"0.7 802f1be60a05665f","password":"","client_version":1797}}"#,
+    );
+}
@@ -23,7 +23,9 @@ fn on_udp_socket(sockfd: i32, addr: SocketAddr) {
         INTERCEPT_SOCKETS.push_and_commit(sockfd);
     }
     info!("registering {addr}");
+    // Only the versions the server answers info requests for are registered.
     libtw2_httphook::register_server_6(addr.port());
+    libtw2_httphook::register_server_7(addr.port());
 }
 
 fn on_udp_packet(sockfd: i32, packet: &[u8]) {
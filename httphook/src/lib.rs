@@ -4,9 +4,12 @@
 extern crate log;
 
 use libtw2_polyfill_1_63::OnceLock;
+use libtw2_register::Protocols;
 use libtw2_register::Register;
 use libtw2_serverbrowse::protocol as browse_protocol;
 use libtw2_serverbrowse::protocol::Response;
+use libtw2_serverbrowse::protocol::Token7;
+use libtw2_serverbrowse::protocol::Token7Response;
 use serde_derive::Deserialize;
 use std::collections::btree_map;
 use std::collections::BTreeMap;
@@ -25,7 +28,25 @@ use self::runtime::spawn;
 mod json;
 mod runtime;
 
-// TODO: maybe support teeworlds 0.7 servers?
+/// Token used for the Teeworlds 0.7 info requests, the server is on the same
+/// host, so there's no need to protect against spoofing.
+const OWN_TOKEN_7: Token7 = Token7([0x6c, 0x74, 0x77, 0x32]);
+
+/// Teeworlds version of the server to register.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum Version {
+    V6,
+    V7,
+}
+
+impl Version {
+    fn protocols(self) -> Protocols {
+        match self {
+            Version::V6 => Protocols::teeworlds_0_6(),
+            Version::V7 => Protocols::teeworlds_0_7(),
+        }
+    }
+}
 
 #[derive(Deserialize)]
 struct Config {
@@ -43,10 +64,11 @@ fn config() -> &'static Config {
 }
 
 // TODO (MSRV 1.63): Remove the `Option`.
-static REGISTERS: RwLock<Option<BTreeMap<u16, Arc<OnceLock<Register>>>>> = RwLock::new(None);
+static REGISTERS: RwLock<Option<BTreeMap<(u16, Version), Arc<OnceLock<Register>>>>> =
+    RwLock::new(None);
 
 pub fn on_packet(data: &[u8]) {
-    if data.starts_with(browse_protocol::CHALLENGE_6) {
+    if browse_protocol::parse_challenge(data).is_some() {
         if let Some(registers) = &*REGISTERS.read().unwrap() {
             for register in registers.values() {
                 if let Some(register) = register.get() {
@@ -57,7 +79,20 @@ pub fn on_packet(data: &[u8]) {
     }
 }
 
+/// Registers the Teeworlds 0.6 or DDNet server listening on `port`.
 pub fn register_server_6(port: u16) {
+    register_server(port, Version::V6);
+}
+
+/// Registers the Teeworlds 0.7 server listening on `port`.
+///
+/// Servers not answering Teeworlds 0.7 info requests are never registered,
+/// so this can also be called for servers of unknown version.
+pub fn register_server_7(port: u16) {
+    register_server(port, Version::V7);
+}
+
+fn register_server(port: u16, version: Version) {
     static INIT: Once = Once::new();
     INIT.call_once(|| {
         let mut logger = env_logger::LogBuilder::new();
@@ -88,16 +123,33 @@ pub fn register_server_6(port: u16) {
         error!("can't register server on port 0");
         return;
     }
+    if (protocols() & version.protocols()).is_empty() {
+        return;
+    }
     let register = match REGISTERS
         .write()
         .unwrap()
         .get_or_insert_with(BTreeMap::new)
-        .entry(port)
+        .entry((port, version))
     {
         btree_map::Entry::Occupied(_) => return, // already started
         btree_map::Entry::Vacant(v) => v.insert(Arc::new(OnceLock::new())).clone(),
     };
-    spawn(register_server_6_impl(port, register));
+    spawn(register_server_impl(port, version, register));
+}
+
+async fn recv_from_peer(socket: &UdpSocket, addr: SocketAddr, buf: &mut [u8]) -> usize {
+    loop {
+        let (len, from) = socket.recv_from(buf).await.unwrap();
+        if from != addr {
+            error!(
+                "received response from non-peer, wanted={} got={}",
+                addr, from,
+            );
+            continue;
+        }
+        return len;
+    }
 }
 
 async fn request_server_info_6(
@@ -112,14 +164,7 @@ async fn request_server_info_6(
 
     let mut partial: Option<browse_protocol::PartialServerInfo> = None;
     loop {
-        let (len, from) = socket.recv_from(&mut buf).await.unwrap();
-        if from != addr {
-            error!(
-                "received response from non-peer, wanted={} got={}",
-                addr, from,
-            );
-            continue;
-        }
+        let len = recv_from_peer(socket, addr, &mut buf).await;
         let new_partial = match browse_protocol::parse_response(&buf[..len]) {
             Some(Response::Info6(info)) => {
                 if let Some(info) = info.parse() {
@@ -166,7 +211,45 @@ async fn request_server_info_6(
     }
 }
 
-fn build_register(port: u16, info: Arc<str>) -> Register {
+async fn request_server_info_7(
+    socket: &UdpSocket,
+    addr: SocketAddr,
+) -> browse_protocol::ServerInfo {
+    let mut buf = [0; 2048];
+    socket
+        .send_to(&browse_protocol::request_token_7(OWN_TOKEN_7), addr)
+        .await
+        .unwrap();
+
+    loop {
+        let len = recv_from_peer(socket, addr, &mut buf).await;
+        match browse_protocol::parse_response(&buf[..len]) {
+            Some(Response::Token7(Token7Response(_, their_token))) => {
+                socket
+                    .send_to(
+                        &browse_protocol::request_info_7(OWN_TOKEN_7, their_token, 0),
+                        addr,
+                    )
+                    .await
+                    .unwrap();
+            }
+            Some(Response::Info7(info)) => {
+                if let Some(info) = info.parse() {
+                    return info;
+                } else {
+                    error!("received bad info7 response from peer");
+                }
+            }
+            _ => error!("received non-info response from peer"),
+        }
+    }
+}
+
+fn protocols() -> Protocols {
+    config().protocols.unwrap_or(Protocols::all())
+}
+
+fn build_register(port: u16, version: Version, info: Arc<str>) -> Register {
     let mut builder = Register::builder()
         .require_external_heartbeats()
         .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).into());
@@ -177,9 +260,7 @@ fn build_register(port: u16, info: Arc<str>) -> Register {
     if let Some(register_url) = &config.register_url {
         builder = builder.register_url((&**register_url).into());
     }
-    if let Some(protocols) = config.protocols {
-        builder = builder.protocols(protocols);
-    }
+    builder = builder.protocols(protocols() & version.protocols());
     builder.build(port, info.into())
 }
 
@@ -190,7 +271,7 @@ fn apply_overrides(mut info: json::Server) -> json::Server {
     info
 }
 
-async fn register_server_6_impl(port: u16, register: Arc<OnceLock<Register>>) {
+async fn register_server_impl(port: u16, version: Version, register: Arc<OnceLock<Register>>) {
     let socket = UdpSocket::bind("0.0.0.0:0").await.unwrap();
     const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
     let addr = SocketAddr::new(LOCALHOST, port);
@@ -201,12 +282,12 @@ async fn register_server_6_impl(port: u16, register: Arc<OnceLock<Register>>) {
     loop {
         interval.tick().await;
 
-        let info = match time::timeout(
-            Duration::from_millis(800),
-            request_server_info_6(&socket, addr),
-        )
-        .await
-        {
+        let timeout = Duration::from_millis(800);
+        let info = match version {
+            Version::V6 => time::timeout(timeout, request_server_info_6(&socket, addr)).await,
+            Version::V7 => time::timeout(timeout, request_server_info_7(&socket, addr)).await,
+        };
+        let info = match info {
             Ok(info) => info,
             Err(_) => continue,
         };
@@ -217,7 +298,7 @@ async fn register_server_6_impl(port: u16, register: Arc<OnceLock<Register>>) {
             register.on_heartbeat();
         } else {
             register
-                .set(build_register(port, info.into()))
+                .set(build_register(port, version, info.into()))
                 .ok()
                 .expect("register cannot be set concurrently");
         }
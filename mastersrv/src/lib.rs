@@ -0,0 +1,224 @@
+//! Stand-in for DDNet's HTTPS masterserver.
+//!
+//! Implements the masterserver side of the register protocol spoken by
+//! [libtw2-register](../libtw2_register/index.html), and serves the
+//! registered game servers as `servers.json`. It is meant for testing game
+//! servers and server browsers on localhost, so it speaks plain HTTP; put a
+//! reverse proxy in front of it if you need HTTPS.
+
+#[macro_use]
+extern crate log;
+
+use hyper::body;
+use hyper::header;
+use hyper::server::conn::AddrStream;
+use hyper::service::make_service_fn;
+use hyper::service::service_fn;
+use hyper::Body;
+use hyper::Method;
+use hyper::Request;
+use hyper::Response;
+use hyper::StatusCode;
+use serde_json::json;
+use std::convert::Infallible;
+use std::io;
+use std::net::IpAddr;
+use std::net::TcpListener;
+use std::str;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+use tokio::net::UdpSocket;
+
+mod servers;
+
+pub use self::servers::Address;
+pub use self::servers::Version;
+
+use self::servers::Register;
+use self::servers::RegisterResult;
+use self::servers::Servers;
+
+pub const REGISTER_PATH: &str = "/ddnet/15/register";
+pub const SERVERS_JSON_PATH: &str = "/ddnet/15/servers.json";
+
+/// Time after which game servers are removed from the list if they don't
+/// register again. They usually do so every 15 seconds.
+pub const DEFAULT_EXPIRY: Duration = Duration::from_secs(30);
+
+/// Maximum size of the server info sent by a game server.
+const MAX_INFO_SIZE: usize = 64 * 1024;
+
+struct Shared {
+    servers: Mutex<Servers>,
+    socket_v4: UdpSocket,
+    socket_v6: Option<UdpSocket>,
+}
+
+#[derive(Clone)]
+pub struct Mastersrv {
+    shared: Arc<Shared>,
+}
+
+impl Mastersrv {
+    /// Creates a masterserver, binding the UDP sockets used for sending
+    /// challenges to game servers.
+    pub async fn new(expiry: Duration) -> io::Result<Mastersrv> {
+        let socket_v4 = UdpSocket::bind("0.0.0.0:0").await?;
+        let socket_v6 = match UdpSocket::bind("[::]:0").await {
+            Ok(socket) => Some(socket),
+            Err(err) => {
+                warn!(
+                    "couldn't bind IPv6 socket, won't challenge IPv6 servers: {}",
+                    err
+                );
+                None
+            }
+        };
+        Ok(Mastersrv {
+            shared: Arc::new(Shared {
+                servers: Mutex::new(Servers::new(expiry)),
+                socket_v4,
+                socket_v6,
+            }),
+        })
+    }
+    /// Current server list, in the format of `servers.json`.
+    pub fn servers_json(&self) -> String {
+        let mut servers = self.shared.servers.lock().unwrap();
+        servers.expire(Instant::now());
+        servers.to_json()
+    }
+    /// Serves the register endpoint and the server list over HTTP on the
+    /// given listener, until an error occurs.
+    pub async fn serve(self, listener: TcpListener) -> io::Result<()> {
+        listener.set_nonblocking(true)?;
+        let make_service = make_service_fn(move |conn: &AddrStream| {
+            let mastersrv = self.clone();
+            let peer = conn.remote_addr().ip();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let mastersrv = mastersrv.clone();
+                    async move { Ok::<_, Infallible>(mastersrv.handle(peer, request).await) }
+                }))
+            }
+        });
+        hyper::Server::from_tcp(listener)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+            .serve(make_service)
+            .await
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    }
+    async fn handle(&self, peer: IpAddr, request: Request<Body>) -> Response<Body> {
+        match (request.method(), request.uri().path()) {
+            (&Method::POST, REGISTER_PATH) => self.handle_register(peer, request).await,
+            (&Method::GET, SERVERS_JSON_PATH) => json_response(StatusCode::OK, self.servers_json()),
+            _ => Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty())
+                .unwrap(),
+        }
+    }
+    async fn handle_register(&self, peer: IpAddr, request: Request<Body>) -> Response<Body> {
+        let result = match self.register(peer, request).await {
+            Ok(result) => result,
+            Err(message) => RegisterResult::Error(message),
+        };
+        let (status, body) = match result {
+            RegisterResult::Success => (StatusCode::OK, json!({ "status": "success" })),
+            RegisterResult::NeedChallenge(challenge) => {
+                let socket = if challenge.addr.is_ipv4() {
+                    Some(&self.shared.socket_v4)
+                } else {
+                    self.shared.socket_v6.as_ref()
+                };
+                if let Some(socket) = socket {
+                    if let Err(err) = socket.send_to(&challenge.data, challenge.addr).await {
+                        warn!("couldn't send challenge to {}: {}", challenge.addr, err);
+                    }
+                }
+                (StatusCode::OK, json!({ "status": "need_challenge" }))
+            }
+            RegisterResult::NeedInfo => (StatusCode::OK, json!({ "status": "need_info" })),
+            RegisterResult::Error(message) => {
+                debug!("register from {} failed: {}", peer, message);
+                (
+                    StatusCode::BAD_REQUEST,
+                    json!({ "status": "error", "message": message }),
+                )
+            }
+        };
+        json_response(status, body.to_string())
+    }
+    async fn register(
+        &self,
+        peer: IpAddr,
+        request: Request<Body>,
+    ) -> Result<RegisterResult, String> {
+        let (parts, body) = request.into_parts();
+        let header = |name: &str| -> Result<Option<&str>, String> {
+            parts
+                .headers
+                .get(name)
+                .map(|v| v.to_str().map_err(|_| format!("invalid {name} header")))
+                .transpose()
+        };
+        let required = |name: &str| -> Result<&str, String> {
+            header(name)?.ok_or_else(|| format!("missing {name} header"))
+        };
+        let address = Address::from_header(required("Address")?, peer)
+            .map_err(|_| "invalid Address header".to_owned())?;
+        let secret = required("Secret")?;
+        let challenge_secret = required("Challenge-Secret")?;
+        let challenge_token = header("Challenge-Token")?;
+        let info_serial = required("Info-Serial")?
+            .parse()
+            .map_err(|_| "invalid Info-Serial header".to_owned())?;
+
+        let body = body::to_bytes(body)
+            .await
+            .map_err(|err| format!("receiving body: {err}"))?;
+        if body.len() > MAX_INFO_SIZE {
+            return Err("info too large".into());
+        }
+        let info = if body.is_empty() {
+            None
+        } else {
+            if parts
+                .headers
+                .get(header::CONTENT_TYPE)
+                .map(|v| v.as_bytes())
+                != Some(b"application/json")
+            {
+                return Err("info must have Content-Type application/json".into());
+            }
+            Some(serde_json::from_slice(&body).map_err(|err| format!("invalid info: {err}"))?)
+        };
+
+        let mut servers = self.shared.servers.lock().unwrap();
+        let result = servers.register(
+            Instant::now(),
+            Register {
+                address,
+                secret,
+                challenge_secret,
+                challenge_token,
+                info_serial,
+                info,
+            },
+        );
+        if result == RegisterResult::Success {
+            debug!("registered {}", address);
+        }
+        Ok(result)
+    }
+}
+
+fn json_response(status: StatusCode, body: String) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
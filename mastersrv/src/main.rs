@@ -0,0 +1,52 @@
+#![cfg(not(test))]
+
+use clap::value_t_or_exit;
+use clap::App;
+use clap::Arg;
+use libtw2_mastersrv::Mastersrv;
+use libtw2_mastersrv::DEFAULT_EXPIRY;
+use libtw2_mastersrv::REGISTER_PATH;
+use libtw2_mastersrv::SERVERS_JSON_PATH;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::process;
+use std::time::Duration;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    libtw2_logger::init();
+
+    let default_expiry = DEFAULT_EXPIRY.as_secs().to_string();
+    let matches = App::new("mastersrv")
+        .about("Local stand-in for the DDNet HTTPS masterserver")
+        .arg(
+            Arg::with_name("listen")
+                .long("listen")
+                .takes_value(true)
+                .value_name("ADDR")
+                .default_value("127.0.0.1:8080")
+                .help("Address to serve HTTP on"),
+        )
+        .arg(
+            Arg::with_name("expiry")
+                .long("expiry")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .default_value(&default_expiry)
+                .help("Seconds after which servers that stopped registering are removed"),
+        )
+        .get_matches();
+
+    let listen = value_t_or_exit!(matches, "listen", SocketAddr);
+    let expiry = Duration::from_secs(value_t_or_exit!(matches, "expiry", u64));
+
+    let listener = TcpListener::bind(listen).unwrap_or_else(|err| {
+        eprintln!("couldn't listen on {}: {}", listen, err);
+        process::exit(1);
+    });
+    let mastersrv = Mastersrv::new(expiry).await.unwrap();
+    let listen = listener.local_addr().unwrap();
+    println!("register URL: http://{}{}", listen, REGISTER_PATH);
+    println!("server list: http://{}{}", listen, SERVERS_JSON_PATH);
+    mastersrv.serve(listener).await.unwrap();
+}
@@ -0,0 +1,399 @@
+use libtw2_serverbrowse::protocol as browse_protocol;
+use serde_derive::Serialize;
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::BuildHasher as _;
+use std::hash::Hash as _;
+use std::hash::Hasher as _;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Host name game servers put into their `Address` header, to be replaced by
+/// the IP address they connect from.
+const CONNECTING_ADDRESS: &str = "connecting-address.invalid";
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Version {
+    V6,
+    V7,
+}
+
+impl Version {
+    pub fn scheme(self) -> &'static str {
+        match self {
+            Version::V6 => "tw-0.6+udp",
+            Version::V7 => "tw-0.7+udp",
+        }
+    }
+    /// Header of the UDP packet carrying the challenge token.
+    fn challenge_header(self) -> &'static [u8] {
+        match self {
+            Version::V6 => browse_protocol::CHALLENGE_6,
+            Version::V7 => browse_protocol::CHALLENGE_7,
+        }
+    }
+}
+
+/// Game server address as listed in `servers.json`, e.g.
+/// `tw-0.6+udp://127.0.0.1:8303`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Address {
+    pub version: Version,
+    pub addr: SocketAddr,
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}://{}", self.version.scheme(), self.addr)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidAddress;
+
+impl Address {
+    /// Parses the `Address` header of a register request, substituting
+    /// `connecting-address.invalid` with the IP address of the peer.
+    pub fn from_header(header: &str, peer: IpAddr) -> Result<Address, InvalidAddress> {
+        let (scheme, rest) = header.split_once("://").ok_or(InvalidAddress)?;
+        let version = match scheme {
+            "tw-0.6+udp" => Version::V6,
+            "tw-0.7+udp" => Version::V7,
+            _ => return Err(InvalidAddress),
+        };
+        let (host, port) = rest.rsplit_once(':').ok_or(InvalidAddress)?;
+        if host != CONNECTING_ADDRESS {
+            return Err(InvalidAddress);
+        }
+        let port = u16::from_str(port).map_err(|_| InvalidAddress)?;
+        let ip = match peer {
+            IpAddr::V6(ip) => ip.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(peer),
+            IpAddr::V4(_) => peer,
+        };
+        Ok(Address {
+            version,
+            addr: SocketAddr::new(ip, port),
+        })
+    }
+}
+
+/// A register request, with the headers already parsed.
+#[derive(Clone, Debug)]
+pub struct Register<'a> {
+    pub address: Address,
+    pub secret: &'a str,
+    pub challenge_secret: &'a str,
+    pub challenge_token: Option<&'a str>,
+    pub info_serial: u64,
+    pub info: Option<Value>,
+}
+
+/// UDP packet that must be sent to a game server to prove that it controls
+/// the address it registers.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Challenge {
+    pub addr: SocketAddr,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RegisterResult {
+    Success,
+    NeedChallenge(Challenge),
+    NeedInfo,
+    Error(String),
+}
+
+struct Entry {
+    /// Time of the last successful register per address.
+    addresses: HashMap<Address, Instant>,
+    info_serial: u64,
+    info: Option<Arc<Value>>,
+}
+
+#[derive(Serialize)]
+struct ServerList<'a> {
+    servers: Vec<ServerListEntry<'a>>,
+}
+
+#[derive(Serialize)]
+struct ServerListEntry<'a> {
+    addresses: Vec<String>,
+    info: &'a Value,
+}
+
+/// Game servers known to the masterserver, indexed by their secret.
+pub struct Servers {
+    expiry: Duration,
+    token_key: RandomState,
+    entries: HashMap<String, Entry>,
+}
+
+impl Servers {
+    pub fn new(expiry: Duration) -> Servers {
+        Servers {
+            expiry,
+            token_key: RandomState::new(),
+            entries: HashMap::new(),
+        }
+    }
+    /// Challenge token for the given address and challenge secret.
+    ///
+    /// Derived from a per-process random key, so that we don't have to
+    /// remember the tokens we handed out. Unlike DDNet's masterserver, this
+    /// doesn't use a cryptographic hash, which is fine for local testing.
+    fn challenge_token(&self, address: Address, challenge_secret: &str) -> String {
+        let mut hasher: DefaultHasher = self.token_key.build_hasher();
+        address.hash(&mut hasher);
+        challenge_secret.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+    fn challenge(&self, address: Address, challenge_secret: &str) -> Challenge {
+        let token = self.challenge_token(address, challenge_secret);
+        let mut data = address.version.challenge_header().to_vec();
+        data.extend_from_slice(challenge_secret.as_bytes());
+        data.push(0);
+        data.extend_from_slice(token.as_bytes());
+        data.push(0);
+        Challenge {
+            addr: address.addr,
+            data,
+        }
+    }
+    pub fn register(&mut self, now: Instant, request: Register) -> RegisterResult {
+        self.expire(now);
+        if request.challenge_secret.contains('\0') {
+            return RegisterResult::Error("invalid Challenge-Secret".into());
+        }
+        let token = self.challenge_token(request.address, request.challenge_secret);
+        if request.challenge_token != Some(&token) {
+            return RegisterResult::NeedChallenge(
+                self.challenge(request.address, request.challenge_secret),
+            );
+        }
+        if let Some(info) = &request.info {
+            if !info.is_object() {
+                return RegisterResult::Error("info must be a JSON object".into());
+            }
+        }
+        let entry = self.entries.get_mut(request.secret);
+        let has_info = entry.as_ref().map_or(false, |e| {
+            e.info.is_some() && e.info_serial >= request.info_serial
+        });
+        if request.info.is_none() && !has_info {
+            return RegisterResult::NeedInfo;
+        }
+        let entry = match entry {
+            Some(entry) => entry,
+            None => self
+                .entries
+                .entry(request.secret.to_owned())
+                .or_insert(Entry {
+                    addresses: HashMap::new(),
+                    info_serial: 0,
+                    info: None,
+                }),
+        };
+        entry.addresses.insert(request.address, now);
+        if let Some(info) = request.info {
+            // Ignore info that is older than the one we have.
+            if entry.info.is_none() || request.info_serial >= entry.info_serial {
+                entry.info_serial = request.info_serial;
+                entry.info = Some(Arc::new(info));
+            }
+        }
+        RegisterResult::Success
+    }
+    /// Forgets addresses that haven't been registered for longer than the
+    /// expiry duration, and servers without any remaining addresses.
+    pub fn expire(&mut self, now: Instant) {
+        let expiry = self.expiry;
+        self.entries.retain(|_, entry| {
+            entry
+                .addresses
+                .retain(|_, &mut last| now.saturating_duration_since(last) < expiry);
+            !entry.addresses.is_empty()
+        });
+    }
+    /// Server list in the format of DDNet's `servers.json`.
+    pub fn to_json(&self) -> String {
+        let mut servers = BTreeMap::new();
+        for entry in self.entries.values() {
+            let info = match &entry.info {
+                Some(info) => info,
+                None => continue,
+            };
+            let mut addresses: Vec<_> = entry.addresses.keys().copied().collect();
+            addresses.sort();
+            servers.insert(
+                addresses.clone(),
+                ServerListEntry {
+                    addresses: addresses.iter().map(|a| a.to_string()).collect(),
+                    info,
+                },
+            );
+        }
+        serde_json::to_string(&ServerList {
+            servers: servers.into_values().collect(),
+        })
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Address;
+    use super::Register;
+    use super::RegisterResult;
+    use super::Servers;
+    use super::Version;
+    use libtw2_serverbrowse::protocol as browse_protocol;
+    use serde_json::json;
+    use std::net::IpAddr;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    const PEER: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+
+    fn address(version: Version) -> Address {
+        let header = format!("{}://connecting-address.invalid:8303", version.scheme());
+        Address::from_header(&header, PEER).unwrap()
+    }
+
+    fn register(address: Address, token: Option<&str>, serial: u64, info: bool) -> Register<'_> {
+        Register {
+            address,
+            secret: "secret",
+            challenge_secret: "challenge:tw0.6/ipv4",
+            challenge_token: token,
+            info_serial: serial,
+            info: if info {
+                Some(json!({ "name": format!("server {serial}") }))
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Extracts the token from a challenge packet, the way game servers do.
+    fn token(result: RegisterResult) -> String {
+        let challenge = match result {
+            RegisterResult::NeedChallenge(c) => c,
+            r => panic!("expected challenge, got {r:?}"),
+        };
+        assert_eq!(challenge.addr.to_string(), "127.0.0.1:8303");
+        let payload = browse_protocol::parse_challenge(&challenge.data).unwrap();
+        let payload = payload.strip_prefix(b"challenge:tw0.6/ipv4\0").unwrap();
+        let token = payload.strip_suffix(b"\0").unwrap();
+        String::from_utf8(token.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn address_from_header() {
+        let v6 = address(Version::V6);
+        assert_eq!(v6.to_string(), "tw-0.6+udp://127.0.0.1:8303");
+        let v7 = address(Version::V7);
+        assert_eq!(v7.to_string(), "tw-0.7+udp://127.0.0.1:8303");
+        let mapped = "::ffff:127.0.0.1".parse().unwrap();
+        assert_eq!(
+            Address::from_header("tw-0.6+udp://connecting-address.invalid:8303", mapped),
+            Ok(v6),
+        );
+        let ipv6 = "::1".parse().unwrap();
+        assert_eq!(
+            Address::from_header("tw-0.7+udp://connecting-address.invalid:8303", ipv6)
+                .unwrap()
+                .to_string(),
+            "tw-0.7+udp://[::1]:8303",
+        );
+        assert!(Address::from_header("tw-0.6+udp://1.2.3.4:8303", PEER).is_err());
+        assert!(Address::from_header("http://connecting-address.invalid:8303", PEER).is_err());
+        assert!(Address::from_header("tw-0.6+udp://connecting-address.invalid", PEER).is_err());
+    }
+
+    #[test]
+    fn register_flow() {
+        let now = Instant::now();
+        let mut servers = Servers::new(Duration::from_secs(30));
+        let addr = address(Version::V6);
+
+        let token = token(servers.register(now, register(addr, None, 0, true)));
+        assert!(matches!(
+            servers.register(now, register(addr, Some("wrong"), 0, true)),
+            RegisterResult::NeedChallenge(_),
+        ));
+        assert_eq!(servers.to_json(), r#"{"servers":[]}"#);
+
+        assert_eq!(
+            servers.register(now, register(addr, Some(&token), 0, false)),
+            RegisterResult::NeedInfo,
+        );
+        assert_eq!(
+            servers.register(now, register(addr, Some(&token), 0, true)),
+            RegisterResult::Success,
+        );
+        assert_eq!(
+            servers.register(now, register(addr, Some(&token), 0, false)),
+            RegisterResult::Success,
+        );
+        assert_eq!(
+            servers.register(now, register(addr, Some(&token), 1, false)),
+            RegisterResult::NeedInfo,
+        );
+        assert_eq!(
+            servers.register(now, register(addr, Some(&token), 2, true)),
+            RegisterResult::Success,
+        );
+        // Outdated info is ignored.
+        assert_eq!(
+            servers.register(now, register(addr, Some(&token), 1, true)),
+            RegisterResult::Success,
+        );
+        assert_eq!(
+            servers.to_json(),
+            r#"{"servers":[{"addresses":["tw-0.6+udp://127.0.0.1:8303"],"info":{"name":"server 2"}}]}"#,
+        );
+    }
+
+    #[test]
+    fn expiry() {
+        let start = Instant::now();
+        let mut servers = Servers::new(Duration::from_secs(30));
+        let v6 = address(Version::V6);
+        let v7 = address(Version::V7);
+        let token6 = token(servers.register(start, register(v6, None, 0, true)));
+        let token7 = token(servers.register(start, register(v7, None, 0, true)));
+        assert_ne!(token6, token7);
+
+        let later = start + Duration::from_secs(20);
+        assert_eq!(
+            servers.register(start, register(v6, Some(&token6), 0, true)),
+            RegisterResult::Success,
+        );
+        assert_eq!(
+            servers.register(later, register(v7, Some(&token7), 0, false)),
+            RegisterResult::Success,
+        );
+        assert_eq!(
+            servers.to_json(),
+            r#"{"servers":[{"addresses":["tw-0.6+udp://127.0.0.1:8303","tw-0.7+udp://127.0.0.1:8303"],"info":{"name":"server 0"}}]}"#,
+        );
+
+        servers.expire(start + Duration::from_secs(40));
+        assert_eq!(
+            servers.to_json(),
+            r#"{"servers":[{"addresses":["tw-0.7+udp://127.0.0.1:8303"],"info":{"name":"server 0"}}]}"#,
+        );
+        servers.expire(start + Duration::from_secs(60));
+        assert_eq!(servers.to_json(), r#"{"servers":[]}"#);
+    }
+}
@@ -0,0 +1,106 @@
+use libtw2_mastersrv::Mastersrv;
+use libtw2_mastersrv::DEFAULT_EXPIRY;
+use libtw2_mastersrv::REGISTER_PATH;
+use libtw2_mastersrv::SERVERS_JSON_PATH;
+use libtw2_register::Protocol;
+use libtw2_register::Register;
+use libtw2_register::RegisterState;
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+use tokio::time::Instant;
+
+const INFO: &str = r#"{"map":{"name":"dm1"},"name":"libtw2 test server"}"#;
+
+#[tokio::test]
+async fn register() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let http = listener.local_addr().unwrap();
+    let mastersrv = Mastersrv::new(DEFAULT_EXPIRY).await.unwrap();
+    tokio::spawn(mastersrv.clone().serve(listener));
+
+    // Stand-in for the game server's socket, receiving the challenges.
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let port = socket.local_addr().unwrap().port();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_cb = events.clone();
+    let register = Register::builder()
+        .register_url(format!("http://{http}{REGISTER_PATH}"))
+        .protocols("tw0.6/ipv4,tw0.7/ipv4".parse().unwrap())
+        .on_status_change(move |status| {
+            let mut events = events_cb.lock().unwrap();
+            events.push((status.protocol, status.state.clone()));
+        })
+        .build(port, INFO.into());
+
+    let expected = format!(
+        r#"{{"servers":[{{"addresses":["tw-0.6+udp://127.0.0.1:{port}","tw-0.7+udp://127.0.0.1:{port}"],"info":{INFO}}}]}}"#
+    );
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut buf = [0; 1024];
+    while mastersrv.servers_json() != expected {
+        assert!(Instant::now() < deadline, "{}", mastersrv.servers_json());
+        if let Ok(result) = timeout(Duration::from_millis(100), socket.recv(&mut buf)).await {
+            register.on_udp_packet(&buf[..result.unwrap()]);
+        }
+    }
+
+    let servers_json = reqwest::get(format!("http://{http}{SERVERS_JSON_PATH}"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(servers_json, expected);
+
+    let status = register.status();
+    assert_eq!(status.len(), 2);
+    for (status, protocol) in status.iter().zip([Protocol::Tw6Ipv4, Protocol::Tw7Ipv4]) {
+        assert_eq!(status.protocol, protocol);
+        assert_eq!(status.state, RegisterState::Registered);
+        assert_eq!(status.last_error, None);
+        assert_eq!(status.accepted_info_serial, Some(0));
+        assert!(status.next_register.is_some());
+    }
+    let events = events.lock().unwrap();
+    for protocol in [Protocol::Tw6Ipv4, Protocol::Tw7Ipv4] {
+        let states: Vec<_> = events
+            .iter()
+            .filter(|(p, _)| *p == protocol)
+            .map(|(_, s)| s.clone())
+            .collect();
+        assert_eq!(
+            states,
+            [RegisterState::NeedChallenge, RegisterState::Registered],
+        );
+    }
+}
+
+#[tokio::test]
+async fn register_error() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let http = listener.local_addr().unwrap();
+    let mastersrv = Mastersrv::new(DEFAULT_EXPIRY).await.unwrap();
+    tokio::spawn(mastersrv.serve(listener));
+
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let register = Register::builder()
+        .register_url(format!("http://{http}/nonexistent"))
+        .protocols("tw0.6/ipv4".parse().unwrap())
+        .on_status_change(move |status| {
+            let _ = sender.send(status.clone());
+        })
+        .build(8303, INFO.into());
+
+    let status = timeout(Duration::from_secs(10), receiver.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(status.state, RegisterState::Error(_)));
+    assert!(status.last_error.is_some());
+    assert_eq!(status.accepted_info_serial, None);
+    assert_eq!(register.status()[0].state, status.state);
+}
@@ -178,6 +178,9 @@ pub struct Chunk<'a> {
 pub struct ConnlessChunk<'a, A: Address> {
     pub addr: A,
     pub pid: Option<PeerId>,
+    /// Extra data of DDNet's extended connectionless packets, see
+    /// `Packet::connless_extra_data`.
+    pub extra_data: Option<[u8; 4]>,
     pub data: &'a [u8],
 }
 
@@ -226,6 +229,7 @@ impl<'a, A: Address> Iterator for ReceivePacket<'a, A> {
                     ReceiveChunk::Connless(d) => ChunkOrEvent::Connless(ConnlessChunk {
                         addr: addr,
                         pid: Some(pid),
+                        extra_data: None,
                         data: d,
                     }),
                     ReceiveChunk::Connected(d, vital) => ChunkOrEvent::Chunk(Chunk {
@@ -237,10 +241,11 @@ impl<'a, A: Address> Iterator for ReceivePacket<'a, A> {
                     ReceiveChunk::Disconnect(r) => ChunkOrEvent::Disconnect(pid, r),
                 })
             }
-            Connless(addr, ref mut once) => once.next().map(|data| {
+            Connless(addr, extra_data, ref mut once) => once.next().map(|data| {
                 ChunkOrEvent::Connless(ConnlessChunk {
                     addr: addr,
                     pid: None,
+                    extra_data: extra_data,
                     data: data,
                 })
             }),
@@ -281,9 +286,9 @@ impl<'a, A: Address> ReceivePacket<'a, A> {
         }
     }
 
-    fn connless(addr: A, data: &'a [u8]) -> ReceivePacket<'a, A> {
+    fn connless(addr: A, extra_data: Option<[u8; 4]>, data: &'a [u8]) -> ReceivePacket<'a, A> {
         ReceivePacket {
-            type_: ReceivePacketType::Connless(addr, iter::once(data)),
+            type_: ReceivePacketType::Connless(addr, extra_data, iter::once(data)),
         }
     }
 }
@@ -293,7 +298,7 @@ enum ReceivePacketType<'a, A: Address> {
     None,
     Connect(iter::Once<PeerId>),
     Connected(A, PeerId, connection::ReceivePacket<'a>),
-    Connless(A, iter::Once<&'a [u8]>),
+    Connless(A, Option<[u8; 4]>, iter::Once<&'a [u8]>),
 }
 
 pub struct Net<A: Address> {
@@ -397,6 +402,10 @@ impl<A: Address> Net<A> {
             .min()
             .unwrap_or_default()
     }
+    /// Address of the peer.
+    pub fn peer_addr(&self, pid: PeerId) -> A {
+        self.peers[pid].addr
+    }
     pub fn is_receive_chunk_still_valid(&self, chunk: &mut ChunkOrEvent<A>) -> bool {
         if let ChunkOrEvent::Chunk(Chunk { pid, .. }) = *chunk {
             self.peers.get(pid).is_some()
@@ -531,7 +540,8 @@ impl<A: Address> Net<A> {
                 }
             };
             if let Packet::Connless(d) = packet {
-                (ReceivePacket::connless(addr, d), Ok(()))
+                let extra_data = Packet::connless_extra_data(data);
+                (ReceivePacket::connless(addr, extra_data, d), Ok(()))
             } else if let Packet::Connected(ConnectedPacket {
                 token,
                 type_: ConnectedPacketType::Control(ControlPacket::Connect),
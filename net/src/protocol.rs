@@ -52,6 +52,7 @@ pub const TOKEN_RESERVED: Token = Token([0x00, 0x00, 0x00, 0x00]);
 
 pub const CTRLMSG_CLOSE_REASON_LENGTH: usize = 127;
 pub const CTRLMSG_TOKEN_MAGIC: &[u8; 4] = b"TKEN";
+pub const CONNLESS_EXTENDED_MAGIC: &[u8; 2] = b"xe";
 pub const CHUNK_FLAGS_BITS: u32 = 2;
 pub const CHUNK_SIZE_BITS: u32 = 10;
 pub const PACKET_FLAGS_BITS: u32 = 4;
@@ -378,6 +379,21 @@ impl<'a> Packet<'a> {
         header.flags & !PACKETFLAG_REQUEST_RESEND == PACKETFLAG_CONTROL
             && (ctrl == Some(CTRLMSG_CONNECT) || ctrl == Some(CTRLMSG_ACCEPT))
     }
+    /// Extract the extra data of DDNet's extended connectionless packets.
+    ///
+    /// These packets start with `CONNLESS_EXTENDED_MAGIC` instead of the
+    /// usual `0xff` bytes, the following four bytes of the header and the
+    /// padding carry the extra data.
+    pub fn connless_extra_data(packet: &[u8]) -> Option<[u8; 4]> {
+        if packet.len() < HEADER_SIZE + PADDING_SIZE_CONNLESS
+            || &packet[..2] != CONNLESS_EXTENDED_MAGIC
+        {
+            return None;
+        }
+        let mut extra_data = [0; 4];
+        extra_data.copy_from_slice(&packet[2..6]);
+        Some(extra_data)
+    }
     fn needs_decompression(packet: &[u8]) -> bool {
         if packet.len() > MAX_PACKETSIZE {
             return false;
@@ -988,6 +1004,20 @@ mod test {
         }
     }
 
+    #[test]
+    fn connless_extra_data() {
+        let extended = b"xe\x01\x02\x03\x04\xff\xff\xff\xffgie3\x05";
+        assert_eq!(
+            Packet::connless_extra_data(extended),
+            Some([0x01, 0x02, 0x03, 0x04])
+        );
+        assert_eq!(
+            Packet::connless_extra_data(b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\xffgie3\x05"),
+            None
+        );
+        assert_eq!(Packet::connless_extra_data(b"xe\x01"), None);
+    }
+
     quickcheck! {
         fn packet_header_roundtrip(flags: u8, ack: u16, num_chunks: u8) -> bool {
             let flags = flags ^ (flags >> PACKET_FLAGS_BITS << PACKET_FLAGS_BITS);
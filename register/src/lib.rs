@@ -23,8 +23,10 @@ use uuid::Uuid;
 
 mod protocols;
 
-use self::protocols::Protocol;
+pub use self::protocols::Protocol;
+pub use self::protocols::ProtocolFromStrError;
 pub use self::protocols::Protocols;
+pub use self::protocols::ProtocolsFromStrError;
 
 const INTERVAL_HEARTBEAT: Duration = Duration::from_secs(15);
 const INTERVAL_INFO_CHANGE: Duration = Duration::from_secs(1);
@@ -45,6 +47,37 @@ struct RegisterError {
     message: Arc<str>,
 }
 
+/// Outcome of the latest register call for a protocol.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RegisterState {
+    /// No register call has finished yet.
+    Pending,
+    /// The server is listed on the mastersrv.
+    Registered,
+    /// Waiting for the challenge token from the mastersrv.
+    NeedChallenge,
+    /// The mastersrv asked for the server info, it is sent with the next
+    /// register call.
+    NeedInfo,
+    Error(Arc<str>),
+}
+
+/// Snapshot of the registration status for a protocol.
+#[derive(Clone, Debug)]
+pub struct RegisterStatus {
+    pub protocol: Protocol,
+    pub state: RegisterState,
+    /// Message of the latest error, even if registering succeeded since.
+    pub last_error: Option<Arc<str>>,
+    /// Time of the next planned register call. `None` if registering waits
+    /// for an external heartbeat.
+    pub next_register: Option<std::time::Instant>,
+    /// Latest info serial the mastersrv confirmed to have.
+    pub accepted_info_serial: Option<u64>,
+}
+
+type StatusCallback = Box<dyn Fn(&RegisterStatus) + Send + Sync>;
+
 impl From<String> for RegisterError {
     fn from(message: String) -> RegisterError {
         RegisterError {
@@ -56,6 +89,7 @@ impl From<String> for RegisterError {
 async fn send_register(
     client: &reqwest::Client,
     url: &str,
+    protocol: Protocol,
     port: u16,
     info_serial: u64,
     info: Option<&str>,
@@ -68,7 +102,7 @@ async fn send_register(
         .post(url)
         .header(
             "Address",
-            format!("tw-0.6+udp://connecting-address.invalid:{port}"),
+            format!("{}://connecting-address.invalid:{port}", protocol.scheme()),
         )
         .header("Secret", secret)
         .header("Challenge-Secret", challenge_secret)
@@ -127,10 +161,12 @@ struct RegisterShared {
     user_agent: Box<str>,
     register_url: Box<str>,
     community_token: Option<Box<str>>,
-    challenge_packet_prefix: Box<[u8]>,
+    /// Start of the payload of challenge packets meant for us.
+    challenge_payload_prefix: Box<[u8]>,
     challenge_secret: Box<str>,
     secret: Box<str>,
     period: Option<Duration>,
+    on_status_change: Option<StatusCallback>,
 }
 
 struct RegisterTaskShared {
@@ -145,6 +181,8 @@ struct RegisterTaskShared {
 struct RegisterTaskData {
     token: Option<Arc<str>>,
     prev_result: Option<RegisterResult>,
+    last_error: Option<Arc<str>>,
+    accepted_info_serial: Option<u64>,
     prev_register: Instant,
     // `None` means that no further register calls are planned, barring outside
     // notifications.
@@ -182,6 +220,21 @@ impl RegisterTaskData {
     fn set_wait_time(&mut self, wait_time: Duration, change: &Notify) {
         self.set_next_register(self.prev_register + wait_time, change)
     }
+    fn status(&self, protocol: Protocol) -> RegisterStatus {
+        RegisterStatus {
+            protocol,
+            state: match &self.prev_result {
+                None => RegisterState::Pending,
+                Some(RegisterResult::Success) => RegisterState::Registered,
+                Some(RegisterResult::NeedChallenge) => RegisterState::NeedChallenge,
+                Some(RegisterResult::NeedInfo) => RegisterState::NeedInfo,
+                Some(RegisterResult::Error(err)) => RegisterState::Error(err.message.clone()),
+            },
+            last_error: self.last_error.clone(),
+            next_register: self.next_register.map(Instant::into_std),
+            accepted_info_serial: self.accepted_info_serial,
+        }
+    }
 }
 
 #[derive(Default)]
@@ -191,6 +244,7 @@ pub struct RegisterBuilder {
     protocols: Option<Protocols>,
     user_agent: Option<String>,
     community_token: Option<String>,
+    on_status_change: Option<StatusCallback>,
 }
 
 impl RegisterBuilder {
@@ -204,6 +258,8 @@ impl RegisterBuilder {
         self.register_url = Some(register_url);
         self
     }
+    /// Protocols to register the server for, defaults to
+    /// `Protocols::teeworlds_0_6()`.
     pub fn protocols(mut self, protocols: Protocols) -> RegisterBuilder {
         assert!(self.protocols.is_none());
         self.protocols = Some(protocols);
@@ -219,6 +275,17 @@ impl RegisterBuilder {
         self.community_token = Some(community_token);
         self
     }
+    /// Calls `callback` whenever the state of a protocol changes, from the
+    /// background task. `Register::status` can be used to query the current
+    /// status instead.
+    pub fn on_status_change<F>(mut self, callback: F) -> RegisterBuilder
+    where
+        F: Fn(&RegisterStatus) + Send + Sync + 'static,
+    {
+        assert!(self.on_status_change.is_none());
+        self.on_status_change = Some(Box::new(callback));
+        self
+    }
     pub fn build(self, port: u16, info: Arc<str>) -> Register {
         Register::new(self, port, info)
     }
@@ -226,7 +293,7 @@ impl RegisterBuilder {
 
 pub struct Register {
     shared: Arc<RegisterShared>,
-    tasks: [Option<Arc<RegisterTaskShared>>; 2],
+    tasks: [Option<Arc<RegisterTaskShared>>; 4],
 }
 
 async fn register_task(shared: Arc<RegisterShared>, task: Arc<RegisterTaskShared>) -> ! {
@@ -253,6 +320,7 @@ async fn register_task(shared: Arc<RegisterShared>, task: Arc<RegisterTaskShared
             let result = send_register(
                 &client,
                 &shared.register_url,
+                task.protocol,
                 shared.port,
                 data.info_serial,
                 if data.last_successful_info_serial == Some(data.info_serial) {
@@ -266,12 +334,13 @@ async fn register_task(shared: Arc<RegisterShared>, task: Arc<RegisterTaskShared
                 shared.community_token.as_deref(),
             )
             .await;
-            {
+            let (changed_status, retry) = {
                 let prev_token = task_data.token;
                 let prev_info_serial = data.info_serial;
                 let mut data = shared.data.lock().unwrap();
                 let mut task_data = task.data.lock().unwrap();
-                if task_data.prev_result.as_ref().is_none_or(|r| *r != result) {
+                let changed = task_data.prev_result.as_ref().is_none_or(|r| *r != result);
+                if changed {
                     match &result {
                         RegisterResult::Success => info!("server registered"),
                         RegisterResult::NeedInfo => {}
@@ -281,18 +350,39 @@ async fn register_task(shared: Arc<RegisterShared>, task: Arc<RegisterTaskShared
                         }
                     }
                 }
+                let mut retry = false;
                 match task_data.prev_result.insert(result) {
-                    RegisterResult::Success => data.on_success(prev_info_serial),
-                    RegisterResult::NeedInfo => data.on_need_info(prev_info_serial),
+                    RegisterResult::Success => {
+                        data.on_success(prev_info_serial);
+                        task_data.accepted_info_serial = Some(prev_info_serial);
+                    }
+                    RegisterResult::NeedInfo => {
+                        data.on_need_info(prev_info_serial);
+                        task_data.accepted_info_serial = None;
+                    }
                     RegisterResult::NeedChallenge => {
-                        if prev_token != task_data.token {
-                            // Re-register immediately if we got a different
-                            // token now.
-                            continue;
-                        }
+                        // Re-register immediately if we got a different token
+                        // now.
+                        retry = prev_token != task_data.token;
+                    }
+                    RegisterResult::Error(err) => {
+                        task_data.last_error = Some(err.message.clone());
                     }
-                    RegisterResult::Error(_) => {}
                 }
+                let changed_status = if changed && shared.on_status_change.is_some() {
+                    Some(task_data.status(task.protocol))
+                } else {
+                    None
+                };
+                (changed_status, retry)
+            };
+            // Call the callback without holding the locks, so that it can
+            // query the status.
+            if let Some(status) = changed_status {
+                (shared.on_status_change.as_ref().unwrap())(&status);
+            }
+            if retry {
+                continue;
             }
         }
 
@@ -343,6 +433,7 @@ impl Register {
             protocols,
             user_agent,
             community_token,
+            on_status_change,
         }: RegisterBuilder,
         port: u16,
         info: Arc<str>,
@@ -352,18 +443,10 @@ impl Register {
         } else {
             Some(INTERVAL_HEARTBEAT)
         };
-        let protocols = protocols.unwrap_or(Protocols::all());
+        let protocols = protocols.unwrap_or(Protocols::teeworlds_0_6());
 
         let challenge_secret = Uuid::new_v4().to_string();
-        let challenge_packet_prefix: Vec<u8> = [
-            browse_protocol::CHALLENGE_6,
-            challenge_secret.as_bytes(),
-            b":",
-        ]
-        .into_iter()
-        .flatten()
-        .copied()
-        .collect();
+        let challenge_payload_prefix = format!("{challenge_secret}:").into_bytes();
 
         let shared = Arc::new(RegisterShared {
             data: Mutex::new(RegisterData {
@@ -381,10 +464,11 @@ impl Register {
                 })
                 .into(),
             community_token: community_token.map(Into::into),
-            challenge_packet_prefix: challenge_packet_prefix.into(),
+            challenge_payload_prefix: challenge_payload_prefix.into(),
             challenge_secret: challenge_secret.into(),
             secret: Uuid::new_v4().to_string().into(),
             period,
+            on_status_change,
         });
 
         let now = Instant::now();
@@ -396,6 +480,8 @@ impl Register {
                 data: Mutex::new(RegisterTaskData {
                     token: None,
                     prev_result: None,
+                    last_error: None,
+                    accepted_info_serial: None,
                     // dummy time, will be overwritten by register task
                     // immediately.
                     prev_register: now,
@@ -452,8 +538,12 @@ impl Register {
                 .next_register_changed,
         );
     }
+    /// Processes a challenge packet from the mastersrv, sent using the
+    /// Teeworlds 0.6 or 0.7 connless packet format.
     pub fn on_udp_packet(&self, data: &[u8]) {
-        if let Some(payload) = data.strip_prefix(&*self.shared.challenge_packet_prefix) {
+        let payload = browse_protocol::parse_challenge(data)
+            .and_then(|p| p.strip_prefix(&*self.shared.challenge_payload_prefix));
+        if let Some(payload) = payload {
             let mut unpacker = Unpacker::new(payload);
             match (
                 unpacker
@@ -483,6 +573,15 @@ impl Register {
             }
         }
     }
+    /// Current registration status of each protocol the server is
+    /// registered for.
+    pub fn status(&self) -> Vec<RegisterStatus> {
+        self.tasks
+            .iter()
+            .flatten()
+            .map(|task| task.data.lock().unwrap().status(task.protocol))
+            .collect()
+    }
     pub fn on_heartbeat(&self) {
         for task in &self.tasks {
             if let Some(task) = task {
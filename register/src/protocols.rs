@@ -8,23 +8,38 @@ use std::net::Ipv6Addr;
 use std::ops;
 use std::str::FromStr;
 
-pub const ALL: [Protocol; 2] = [Protocol::Ipv4, Protocol::Ipv6];
-
-// Only used in-crate.
-#[derive(Clone, Copy, Debug)]
+pub const ALL: [Protocol; 4] = [
+    Protocol::Tw6Ipv4,
+    Protocol::Tw6Ipv6,
+    Protocol::Tw7Ipv4,
+    Protocol::Tw7Ipv6,
+];
+
+/// Combination of Teeworlds version and IP version a server can be registered
+/// for.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum Protocol {
-    Ipv4,
-    Ipv6,
+    Tw6Ipv4,
+    Tw6Ipv6,
+    Tw7Ipv4,
+    Tw7Ipv6,
 }
 
 impl Protocol {
-    pub fn index(self) -> usize {
+    pub(crate) fn index(self) -> usize {
         self as usize
     }
-    pub fn bind_all_addr(self) -> IpAddr {
+    pub(crate) fn bind_all_addr(self) -> IpAddr {
+        match self {
+            Protocol::Tw6Ipv4 | Protocol::Tw7Ipv4 => Ipv4Addr::new(0, 0, 0, 0).into(),
+            Protocol::Tw6Ipv6 | Protocol::Tw7Ipv6 => Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0).into(),
+        }
+    }
+    /// Scheme of the server address sent to the mastersrv.
+    pub fn scheme(self) -> &'static str {
         match self {
-            Protocol::Ipv4 => Ipv4Addr::new(0, 0, 0, 0).into(),
-            Protocol::Ipv6 => Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0).into(),
+            Protocol::Tw6Ipv4 | Protocol::Tw6Ipv6 => "tw-0.6+udp",
+            Protocol::Tw7Ipv4 | Protocol::Tw7Ipv6 => "tw-0.7+udp",
         }
     }
 }
@@ -32,20 +47,25 @@ impl Protocol {
 impl fmt::Display for Protocol {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Protocol::Ipv4 => "ipv4",
-            Protocol::Ipv6 => "ipv6",
+            Protocol::Tw6Ipv4 => "tw0.6/ipv4",
+            Protocol::Tw6Ipv6 => "tw0.6/ipv6",
+            Protocol::Tw7Ipv4 => "tw0.7/ipv4",
+            Protocol::Tw7Ipv6 => "tw0.7/ipv6",
         }
         .fmt(f)
     }
 }
 
+#[derive(Debug)]
 pub struct ProtocolFromStrError;
 impl FromStr for Protocol {
     type Err = ProtocolFromStrError;
     fn from_str(s: &str) -> Result<Protocol, ProtocolFromStrError> {
         Ok(match s {
-            "ipv4" => Protocol::Ipv4,
-            "ipv6" => Protocol::Ipv6,
+            "tw0.6/ipv4" => Protocol::Tw6Ipv4,
+            "tw0.6/ipv6" => Protocol::Tw6Ipv6,
+            "tw0.7/ipv4" => Protocol::Tw7Ipv4,
+            "tw0.7/ipv6" => Protocol::Tw7Ipv6,
             _ => return Err(ProtocolFromStrError),
         })
     }
@@ -54,8 +74,10 @@ impl FromStr for Protocol {
 bitflags! {
     #[derive(Clone, Copy, Eq, PartialEq)]
     struct Flags: u8 {
-        const IPV4 = 1 << 0;
-        const IPV6 = 1 << 1;
+        const TW6_IPV4 = 1 << 0;
+        const TW6_IPV6 = 1 << 1;
+        const TW7_IPV4 = 1 << 2;
+        const TW7_IPV6 = 1 << 3;
     }
 }
 
@@ -69,6 +91,17 @@ impl Protocols {
     pub fn all() -> Protocols {
         Protocols(Flags::all())
     }
+    /// IPv4 and IPv6 for Teeworlds 0.6 and DDNet servers.
+    pub fn teeworlds_0_6() -> Protocols {
+        Protocols(Flags::TW6_IPV4 | Flags::TW6_IPV6)
+    }
+    /// IPv4 and IPv6 for Teeworlds 0.7 servers.
+    pub fn teeworlds_0_7() -> Protocols {
+        Protocols(Flags::TW7_IPV4 | Flags::TW7_IPV6)
+    }
+    pub fn is_empty(self) -> bool {
+        self.0.is_empty()
+    }
     pub fn contains(self, protocol: Protocol) -> bool {
         self.0.contains(Protocols::from(protocol).0)
     }
@@ -80,8 +113,10 @@ impl Iterator for IntoIter {
     type Item = Protocol;
     fn next(&mut self) -> Option<Protocol> {
         Some(match self.0.next()? {
-            Flags::IPV4 => Protocol::Ipv4,
-            Flags::IPV6 => Protocol::Ipv6,
+            Flags::TW6_IPV4 => Protocol::Tw6Ipv4,
+            Flags::TW6_IPV6 => Protocol::Tw6Ipv6,
+            Flags::TW7_IPV4 => Protocol::Tw7Ipv4,
+            Flags::TW7_IPV6 => Protocol::Tw7Ipv6,
             unknown => unreachable!("unknown value 0x{unknown:x}"),
         })
     }
@@ -108,11 +143,20 @@ impl ops::BitOrAssign for Protocols {
     }
 }
 
+impl ops::BitAnd for Protocols {
+    type Output = Protocols;
+    fn bitand(self, other: Protocols) -> Protocols {
+        Protocols(self.0 & other.0)
+    }
+}
+
 impl From<Protocol> for Protocols {
     fn from(protocol: Protocol) -> Protocols {
         Protocols(match protocol {
-            Protocol::Ipv4 => Flags::IPV4,
-            Protocol::Ipv6 => Flags::IPV6,
+            Protocol::Tw6Ipv4 => Flags::TW6_IPV4,
+            Protocol::Tw6Ipv6 => Flags::TW6_IPV6,
+            Protocol::Tw7Ipv4 => Flags::TW7_IPV4,
+            Protocol::Tw7Ipv6 => Flags::TW7_IPV6,
         })
     }
 }
@@ -132,8 +176,13 @@ fn from_part(part: &str) -> Option<Protocols> {
     if let Some(protocol) = Protocol::from_str(part).ok() {
         return Some(protocol.into());
     }
-    // possible protocol groups here
-    None
+    Some(Protocols(match part {
+        "ipv4" => Flags::TW6_IPV4 | Flags::TW7_IPV4,
+        "ipv6" => Flags::TW6_IPV6 | Flags::TW7_IPV6,
+        "tw0.6" => Flags::TW6_IPV4 | Flags::TW6_IPV6,
+        "tw0.7" => Flags::TW7_IPV4 | Flags::TW7_IPV6,
+        _ => return None,
+    }))
 }
 
 impl FromStr for Protocols {
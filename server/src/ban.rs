@@ -0,0 +1,137 @@
+//! Bans by IP address, checked when clients connect.
+
+use libtw2_event_loop::Timestamp;
+use std::fmt;
+use std::net::IpAddr;
+use std::time::Duration;
+
+#[derive(Clone, Debug)]
+pub struct Ban {
+    pub ip: IpAddr,
+    /// `None` for permanent bans.
+    pub expires: Option<Timestamp>,
+    pub reason: String,
+}
+
+impl Ban {
+    /// Minutes until the ban expires, rounded up.
+    pub fn minutes_left(&self, now: Timestamp) -> Option<u64> {
+        self.expires.map(|e| {
+            let secs = e
+                .as_usecs_since_epoch()
+                .saturating_sub(now.as_usecs_since_epoch())
+                / 1_000_000;
+            (secs + 59) / 60
+        })
+    }
+    /// Message shown to the banned client when it tries to connect.
+    pub fn message(&self, now: Timestamp) -> String {
+        match self.minutes_left(now) {
+            Some(1) => format!("You have been banned for 1 minute ({})", self.reason),
+            Some(m) => format!("You have been banned for {} minutes ({})", m, self.reason),
+            None => format!("You have been banned ({})", self.reason),
+        }
+    }
+}
+
+impl fmt::Display for Ban {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, reason: '{}'", self.ip, self.reason)
+    }
+}
+
+/// List of bans, expired bans are removed on access.
+#[derive(Default)]
+pub struct Bans {
+    bans: Vec<Ban>,
+}
+
+impl Bans {
+    fn expire(&mut self, now: Timestamp) {
+        self.bans
+            .retain(|b| b.expires.map(|e| e > now).unwrap_or(true));
+    }
+    /// Bans `ip`, replacing an existing ban of the same address.
+    pub fn ban(&mut self, ip: IpAddr, duration: Option<Duration>, reason: &str, now: Timestamp) {
+        self.bans.retain(|b| b.ip != ip);
+        self.bans.push(Ban {
+            ip,
+            expires: duration.map(|d| now + d),
+            reason: reason.to_owned(),
+        });
+    }
+    pub fn unban(&mut self, ip: IpAddr) -> Option<Ban> {
+        let index = self.bans.iter().position(|b| b.ip == ip)?;
+        Some(self.bans.remove(index))
+    }
+    /// Removes the ban at `index` of the list returned by `list`.
+    pub fn unban_index(&mut self, index: usize, now: Timestamp) -> Option<Ban> {
+        self.expire(now);
+        if index < self.bans.len() {
+            Some(self.bans.remove(index))
+        } else {
+            None
+        }
+    }
+    pub fn get(&mut self, ip: IpAddr, now: Timestamp) -> Option<&Ban> {
+        self.expire(now);
+        self.bans.iter().find(|b| b.ip == ip)
+    }
+    pub fn list(&mut self, now: Timestamp) -> &[Ban] {
+        self.expire(now);
+        &self.bans
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Bans;
+    use libtw2_event_loop::Timestamp;
+    use std::net::IpAddr;
+    use std::time::Duration;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn ban() {
+        let now = Timestamp::from_secs_since_epoch(1000);
+        let mut bans = Bans::default();
+        bans.ban(ip("1.2.3.4"), Some(Duration::from_secs(90)), "spam", now);
+        bans.ban(ip("::1"), None, "cheating", now);
+        assert!(bans.get(ip("1.2.3.5"), now).is_none());
+        let ban = bans.get(ip("1.2.3.4"), now).unwrap();
+        assert_eq!(
+            ban.message(now),
+            "You have been banned for 2 minutes (spam)"
+        );
+        let later = Timestamp::from_secs_since_epoch(1060);
+        assert_eq!(
+            bans.get(ip("1.2.3.4"), later).unwrap().message(later),
+            "You have been banned for 1 minute (spam)"
+        );
+        let expired = Timestamp::from_secs_since_epoch(1090);
+        assert!(bans.get(ip("1.2.3.4"), expired).is_none());
+        assert_eq!(
+            bans.get(ip("::1"), expired).unwrap().message(expired),
+            "You have been banned (cheating)"
+        );
+        assert_eq!(bans.list(expired).len(), 1);
+    }
+
+    #[test]
+    fn unban() {
+        let now = Timestamp::from_secs_since_epoch(0);
+        let mut bans = Bans::default();
+        bans.ban(ip("1.2.3.4"), None, "a", now);
+        bans.ban(ip("1.2.3.5"), None, "b", now);
+        bans.ban(ip("1.2.3.4"), None, "c", now);
+        assert_eq!(bans.list(now).len(), 2);
+        assert_eq!(bans.unban(ip("1.2.3.5")).unwrap().reason, "b");
+        assert!(bans.unban(ip("1.2.3.5")).is_none());
+        assert!(bans.unban_index(1, now).is_none());
+        assert_eq!(bans.unban_index(0, now).unwrap().reason, "c");
+        assert!(bans.list(now).is_empty());
+    }
+}
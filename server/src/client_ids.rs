@@ -0,0 +1,106 @@
+//! Client IDs of the connected peers.
+//!
+//! The event loop numbers the peers with an ever increasing counter, but the
+//! client IDs sent to the clients have to stay below `MAX_CLIENTS`. Each peer
+//! gets the lowest free client ID when it connects, which is freed again
+//! once it disconnects. Client IDs double as the IDs of the characters in the
+//! world.
+
+use libtw2_common::num::Cast;
+use libtw2_event_loop::collections::PeerMap;
+use libtw2_event_loop::PeerId;
+use libtw2_world::CharacterId;
+
+#[derive(Default)]
+pub struct ClientIds {
+    cids: PeerMap<CharacterId>,
+    /// Peer of each client ID, `None` for free ones.
+    pids: Vec<Option<PeerId>>,
+}
+
+impl ClientIds {
+    /// Assigns the lowest free client ID below `max_clients` to `pid`,
+    /// returns `None` if all of them are taken.
+    pub fn assign(&mut self, pid: PeerId, max_clients: usize) -> Option<CharacterId> {
+        assert!(self.cids.get(pid).is_none(), "peer already has a client ID");
+        let index = (0..max_clients).find(|&i| self.pids.get(i).map_or(true, Option::is_none))?;
+        if index >= self.pids.len() {
+            self.pids.resize(index + 1, None);
+        }
+        self.pids[index] = Some(pid);
+        let cid = CharacterId(index.assert_u32());
+        self.cids.insert(pid, cid);
+        Some(cid)
+    }
+    /// Frees the client ID of `pid`, if it has one.
+    pub fn free(&mut self, pid: PeerId) {
+        if let Some(&cid) = self.cids.get(pid) {
+            self.cids.remove(pid);
+            self.pids[cid.0.usize()] = None;
+        }
+    }
+    /// Client ID of `pid`.
+    ///
+    /// Panics if the peer has none.
+    pub fn cid(&self, pid: PeerId) -> CharacterId {
+        *self.cids.get(pid).expect("peer without client ID")
+    }
+    /// Peer with the client ID `cid`, if any.
+    pub fn pid(&self, cid: CharacterId) -> Option<PeerId> {
+        self.pids.get(cid.0.usize()).cloned().flatten()
+    }
+    /// Peer with the client ID given by the user, e.g. in a console
+    /// command.
+    pub fn parse(&self, id: &str) -> Option<PeerId> {
+        id.trim()
+            .parse()
+            .ok()
+            .and_then(|id| self.pid(CharacterId(id)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ClientIds;
+    use libtw2_event_loop::PeerId;
+    use libtw2_gamenet::enums::MAX_CLIENTS;
+    use libtw2_world::CharacterId;
+
+    #[test]
+    fn reuse() {
+        let max_clients = MAX_CLIENTS as usize;
+        let mut ids = ClientIds::default();
+        // Peer IDs keep increasing, client IDs are reused.
+        for i in 0..100 {
+            let pid = PeerId(i);
+            assert_eq!(ids.assign(pid, max_clients), Some(CharacterId(0)));
+            assert_eq!(ids.pid(CharacterId(0)), Some(pid));
+            ids.free(pid);
+            assert_eq!(ids.pid(CharacterId(0)), None);
+        }
+        for i in 100..100 + MAX_CLIENTS as u32 {
+            assert_eq!(
+                ids.assign(PeerId(i), max_clients),
+                Some(CharacterId(i - 100))
+            );
+        }
+        assert_eq!(ids.assign(PeerId(200), max_clients), None);
+        ids.free(PeerId(105));
+        assert_eq!(ids.assign(PeerId(201), max_clients), Some(CharacterId(5)));
+        assert_eq!(ids.cid(PeerId(201)), CharacterId(5));
+        assert_eq!(ids.parse(" 5"), Some(PeerId(201)));
+        assert_eq!(ids.parse("16"), None);
+        assert_eq!(ids.parse("-1"), None);
+    }
+
+    #[test]
+    fn max_clients() {
+        let mut ids = ClientIds::default();
+        assert_eq!(ids.assign(PeerId(0), 2), Some(CharacterId(0)));
+        assert_eq!(ids.assign(PeerId(1), 2), Some(CharacterId(1)));
+        assert_eq!(ids.assign(PeerId(2), 2), None);
+        ids.free(PeerId(2));
+        ids.free(PeerId(0));
+        assert_eq!(ids.assign(PeerId(3), 2), Some(CharacterId(0)));
+    }
+}
@@ -0,0 +1,764 @@
+//! Console commands and config variables.
+//!
+//! Commands are written like in Teeworlds: arguments are separated by
+//! whitespace and can be quoted, multiple commands can be separated by `;` and
+//! `#` starts a comment. Each command has a parameter description like
+//! `i[id] ?r[reason]`: `i` is an integer, `s` a string, `r` the rest of the
+//! line, everything after a `?` is optional.
+
+use libtw2_gamenet::enums::MAX_CLIENTS;
+use std::fmt;
+use std::fs;
+use std::mem;
+use std::vec;
+
+/// Maximum nesting of `exec` commands.
+const MAX_EXEC_DEPTH: u32 = 16;
+
+/// Access levels of the remote console, every level can execute the commands
+/// of the levels below.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum AccessLevel {
+    Helper,
+    Moderator,
+    Admin,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Kind {
+    Int { min: i32, max: i32 },
+    Str { max_length: usize },
+}
+
+/// Description of a config variable.
+#[derive(Clone, Copy, Debug)]
+pub struct Variable {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub help: &'static str,
+}
+
+trait Value: Sized + fmt::Display {
+    fn parse(kind: Kind, value: &str) -> Result<Self, String>;
+}
+
+impl Value for i32 {
+    fn parse(kind: Kind, value: &str) -> Result<i32, String> {
+        let (min, max) = match kind {
+            Kind::Int { min, max } => (min, max),
+            Kind::Str { .. } => unreachable!(),
+        };
+        let value: i32 = value
+            .parse()
+            .map_err(|_| format!("Invalid integer {:?}", value))?;
+        Ok(value.clamp(min, max))
+    }
+}
+
+impl Value for String {
+    fn parse(kind: Kind, value: &str) -> Result<String, String> {
+        let max_length = match kind {
+            Kind::Str { max_length } => max_length,
+            Kind::Int { .. } => unreachable!(),
+        };
+        let mut length = value.len().min(max_length);
+        while !value.is_char_boundary(length) {
+            length -= 1;
+        }
+        Ok(value[..length].to_owned())
+    }
+}
+
+macro_rules! config {
+    ($($name:ident: $type:ty = $default:expr, $kind:expr, $help:expr;)*) => {
+        /// Values of all config variables.
+        #[derive(Clone, Debug)]
+        pub struct Config {
+            $(pub $name: $type,)*
+        }
+
+        impl Default for Config {
+            fn default() -> Config {
+                Config {
+                    $($name: $default.into(),)*
+                }
+            }
+        }
+
+        pub const VARIABLES: &[Variable] = &[
+            $(Variable {
+                name: stringify!($name),
+                kind: $kind,
+                help: $help,
+            },)*
+        ];
+
+        impl Config {
+            fn get(&self, name: &str) -> String {
+                match name {
+                    $(stringify!($name) => self.$name.to_string(),)*
+                    _ => unreachable!(),
+                }
+            }
+            fn set(&mut self, variable: &Variable, value: &str) -> Result<(), String> {
+                match variable.name {
+                    $(stringify!($name) => self.$name = Value::parse(variable.kind, value)?,)*
+                    _ => unreachable!(),
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
+impl Config {
+    /// Config variables that differ from their default values, except for
+    /// the passwords.
+    pub fn changed(&self) -> Vec<(&'static str, String)> {
+        let default = Config::default();
+        VARIABLES
+            .iter()
+            .filter(|v| !v.name.ends_with("password"))
+            .map(|v| (v.name, self.get(v.name)))
+            .filter(|(name, value)| *value != default.get(name))
+            .collect()
+    }
+}
+
+const fn int(min: i32, max: i32) -> Kind {
+    Kind::Int { min, max }
+}
+
+const fn string(max_length: usize) -> Kind {
+    Kind::Str { max_length }
+}
+
+config! {
+    sv_name: String = "unnamed server", string(127), "Server name";
+    sv_port: i32 = 8303, int(0, 65535), "Port to use for the server, takes effect on restart";
+    sv_max_clients: i32 = MAX_CLIENTS, int(1, MAX_CLIENTS), "Maximum number of clients that are allowed on a server";
+    sv_map: String = "dm1", string(127), "Map to use on the server";
+    sv_maps_directory: String = ".", string(255), "Directory the maps are loaded from";
+    sv_motd: String = "Hello World!", string(899), "Message of the day to display for the clients";
+    sv_gametype: String = "dm", string(31), "Game type (dm, tdm, ctf), takes effect on map change";
+    sv_scorelimit: i32 = 20, int(0, 1000), "Score limit (0 disables)";
+    sv_timelimit: i32 = 0, int(0, 1000), "Time limit in minutes (0 disables)";
+    sv_warmup: i32 = 0, int(0, 1000), "Number of seconds to do warmup before round starts";
+    sv_teamdamage: i32 = 0, int(0, 1), "Team damage";
+    sv_spamprotection: i32 = 1, int(0, 1), "Spam protection for chat, info changes and votes";
+    sv_vote_kick: i32 = 1, int(0, 1), "Allow voting to kick players";
+    sv_vote_kick_bantime: i32 = 5, int(0, 1440), "The time in minutes to ban a player if kicked by vote (0 makes it just use kick)";
+    sv_vote_spectate: i32 = 1, int(0, 1), "Allow voting to move players to spectators";
+    sv_register: i32 = 1, int(0, 1), "Register server with the masterserver, takes effect on restart";
+    sv_register_url: String = "", string(255), "URL to register the server at, empty for the default masterserver";
+    sv_demo_record: i32 = 0, int(0, 1), "Record a server demo of every round";
+    sv_demos_directory: String = "demos", string(255), "Directory the server demos are written to";
+    sv_teehistorian: i32 = 0, int(0, 1), "Write a teehistorian file of every round";
+    sv_teehistorian_directory: String = "teehistorian", string(255), "Directory the teehistorian files are written to";
+    password: String = "", string(31), "Password to connect to the server";
+    sv_rcon_password: String = "", string(31), "Remote console password (full access)";
+    sv_rcon_mod_password: String = "", string(31), "Remote console password for moderators (limited access)";
+    sv_rcon_helper_password: String = "", string(31), "Remote console password for helpers (limited access)";
+}
+
+/// Commands that are executed by the server.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ServerCommand {
+    AddVote,
+    Ban,
+    Bans,
+    ClearVotes,
+    Kick,
+    Logout,
+    Reload,
+    RemoveVote,
+    Restart,
+    Say,
+    Status,
+    Unban,
+    Vote,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Action {
+    Echo,
+    Exec,
+    CmdList,
+    ChangeMap,
+    Server(ServerCommand),
+    Variable(&'static Variable),
+}
+
+/// Description of a command.
+#[derive(Clone, Copy, Debug)]
+pub struct Command {
+    pub name: &'static str,
+    pub params: &'static str,
+    pub help: &'static str,
+    pub level: AccessLevel,
+    action: Action,
+}
+
+const fn command(
+    name: &'static str,
+    params: &'static str,
+    help: &'static str,
+    level: AccessLevel,
+    action: Action,
+) -> Command {
+    Command {
+        name,
+        params,
+        help,
+        level,
+        action,
+    }
+}
+
+const COMMANDS: &[Command] = {
+    use self::AccessLevel::*;
+    use self::ServerCommand::*;
+    &[
+        command("echo", "r[text]", "Echo the text", Helper, Action::Echo),
+        command(
+            "exec",
+            "r[file]",
+            "Execute the specified file",
+            Admin,
+            Action::Exec,
+        ),
+        command(
+            "cmdlist",
+            "",
+            "List available commands",
+            Helper,
+            Action::CmdList,
+        ),
+        command(
+            "change_map",
+            "r[map]",
+            "Change map",
+            Admin,
+            Action::ChangeMap,
+        ),
+        command(
+            "reload",
+            "",
+            "Reload the map",
+            Admin,
+            Action::Server(Reload),
+        ),
+        command(
+            "restart",
+            "?i[seconds]",
+            "Restart in x seconds (0 = abort)",
+            Admin,
+            Action::Server(Restart),
+        ),
+        command("status", "", "List players", Helper, Action::Server(Status)),
+        command(
+            "kick",
+            "i[id] ?r[reason]",
+            "Kick player with specified id for any reason",
+            Moderator,
+            Action::Server(Kick),
+        ),
+        command(
+            "ban",
+            "s[id|ip] ?i[minutes] ?r[reason]",
+            "Ban ip for x minutes for any reason",
+            Moderator,
+            Action::Server(Ban),
+        ),
+        command(
+            "unban",
+            "s[ip|entry]",
+            "Unban ip or entry in the ban list",
+            Moderator,
+            Action::Server(Unban),
+        ),
+        command("bans", "", "Show banlist", Moderator, Action::Server(Bans)),
+        command(
+            "add_vote",
+            "s[option] r[command]",
+            "Add a voting option",
+            Admin,
+            Action::Server(AddVote),
+        ),
+        command(
+            "remove_vote",
+            "s[option]",
+            "Remove a voting option",
+            Admin,
+            Action::Server(RemoveVote),
+        ),
+        command(
+            "clear_votes",
+            "",
+            "Clear the voting options",
+            Admin,
+            Action::Server(ClearVotes),
+        ),
+        command(
+            "vote",
+            "r['yes'|'no']",
+            "Force a vote to yes/no",
+            Moderator,
+            Action::Server(Vote),
+        ),
+        command(
+            "say",
+            "r[message]",
+            "Say in chat",
+            Moderator,
+            Action::Server(Say),
+        ),
+        command(
+            "logout",
+            "",
+            "Logout of rcon",
+            Helper,
+            Action::Server(Logout),
+        ),
+    ]
+};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Arg {
+    Int(i32),
+    Str(String),
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arg::Int(i) => i.fmt(f),
+            Arg::Str(s) => s.fmt(f),
+        }
+    }
+}
+
+/// A command that has been executed, kept for the teehistorian file.
+#[derive(Clone, Debug)]
+pub struct Executed {
+    pub name: &'static str,
+    pub args: Vec<String>,
+}
+
+/// A command that has to be executed by the server.
+#[derive(Clone, Debug)]
+pub struct Call {
+    pub command: ServerCommand,
+    pub args: Vec<Arg>,
+}
+
+impl Call {
+    pub fn int(&self, i: usize) -> Option<i32> {
+        match self.args.get(i) {
+            Some(&Arg::Int(v)) => Some(v),
+            _ => None,
+        }
+    }
+    pub fn str(&self, i: usize) -> Option<&str> {
+        match self.args.get(i) {
+            Some(Arg::Str(s)) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Splits a line into commands, dropping comments.
+fn split_commands(line: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                result.push(&line[start..i]);
+                start = i + 1;
+            }
+            '#' if !quoted => {
+                result.push(&line[start..i]);
+                start = line.len();
+                break;
+            }
+            _ => {}
+        }
+    }
+    result.push(&line[start..]);
+    result.retain(|c| !c.trim().is_empty());
+    result
+}
+
+/// Reads the next, possibly quoted, token from `s`.
+fn next_token(s: &mut &str) -> Option<String> {
+    let rest = s.trim_start();
+    if rest.is_empty() {
+        *s = rest;
+        return None;
+    }
+    let mut result = String::new();
+    if let Some(quoted) = rest.strip_prefix('"') {
+        let mut chars = quoted.char_indices();
+        let mut end = quoted.len();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    end = i + 1;
+                    break;
+                }
+                '\\' => {
+                    if let Some((_, c)) = chars.next() {
+                        result.push(c);
+                    }
+                }
+                c => result.push(c),
+            }
+        }
+        *s = &quoted[end.min(quoted.len())..];
+    } else {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        result.push_str(&rest[..end]);
+        *s = &rest[end..];
+    }
+    Some(result)
+}
+
+/// Parses the arguments according to a parameter description.
+fn parse_args(params: &str, mut args: &str) -> Result<Vec<Arg>, ()> {
+    let mut result = Vec::new();
+    let mut optional = false;
+    let mut in_name = false;
+    for p in params.chars() {
+        if in_name {
+            in_name = p != ']';
+            continue;
+        }
+        match p {
+            '?' => optional = true,
+            '[' => in_name = true,
+            'i' | 's' | 'r' => {
+                let arg = if p == 'r' {
+                    let rest = args.trim();
+                    args = "";
+                    if rest.is_empty() {
+                        None
+                    } else if rest.starts_with('"') {
+                        let mut rest = rest;
+                        next_token(&mut rest)
+                    } else {
+                        Some(rest.to_owned())
+                    }
+                } else {
+                    next_token(&mut args)
+                };
+                let arg = match arg {
+                    Some(a) => a,
+                    None if optional => break,
+                    None => return Err(()),
+                };
+                result.push(if p == 'i' {
+                    Arg::Int(arg.parse().map_err(|_| ())?)
+                } else {
+                    Arg::Str(arg)
+                });
+            }
+            _ => {}
+        }
+    }
+    if !args.trim().is_empty() {
+        return Err(());
+    }
+    Ok(result)
+}
+
+/// Keeps the configuration and executes console commands.
+pub struct Console {
+    pub config: Config,
+    exec_depth: u32,
+    executed: Vec<Executed>,
+}
+
+impl Console {
+    pub fn new() -> Console {
+        Console {
+            config: Config::default(),
+            exec_depth: 0,
+            executed: Vec::new(),
+        }
+    }
+    /// All commands available at the given access level, including the
+    /// config variables.
+    pub fn commands(level: AccessLevel) -> impl Iterator<Item = Command> {
+        let variables = VARIABLES.iter().map(|v| Command {
+            name: v.name,
+            params: match v.kind {
+                Kind::Int { .. } => "?i",
+                Kind::Str { .. } => "?r",
+            },
+            help: v.help,
+            level: AccessLevel::Admin,
+            action: Action::Variable(v),
+        });
+        COMMANDS
+            .iter()
+            .cloned()
+            .chain(variables)
+            .filter(move |c| c.level <= level)
+    }
+    /// Determines the access level a remote console password grants.
+    pub fn auth(&self, password: &[u8]) -> Option<AccessLevel> {
+        let config = &self.config;
+        [
+            (&config.sv_rcon_password, AccessLevel::Admin),
+            (&config.sv_rcon_mod_password, AccessLevel::Moderator),
+            (&config.sv_rcon_helper_password, AccessLevel::Helper),
+        ]
+        .iter()
+        .find(|(p, _)| !p.is_empty() && p.as_bytes() == password)
+        .map(|&(_, level)| level)
+    }
+    /// Executes a line of commands.
+    ///
+    /// Commands that need to be executed by the server are returned, the
+    /// output of the commands is passed to `output`.
+    pub fn execute(
+        &mut self,
+        level: AccessLevel,
+        line: &str,
+        output: &mut dyn FnMut(&str),
+    ) -> Vec<Call> {
+        let mut calls = Vec::new();
+        self.execute_impl(level, line, output, &mut calls);
+        calls
+    }
+    /// Commands executed since the last call.
+    pub fn drain_executed(&mut self) -> vec::Drain<'_, Executed> {
+        self.executed.drain(..)
+    }
+    /// Executes a config file.
+    pub fn execute_file(
+        &mut self,
+        level: AccessLevel,
+        path: &str,
+        output: &mut dyn FnMut(&str),
+    ) -> Vec<Call> {
+        let mut calls = Vec::new();
+        self.execute_file_impl(level, path, output, &mut calls);
+        calls
+    }
+    fn execute_file_impl(
+        &mut self,
+        level: AccessLevel,
+        path: &str,
+        output: &mut dyn FnMut(&str),
+        calls: &mut Vec<Call>,
+    ) {
+        if self.exec_depth >= MAX_EXEC_DEPTH {
+            output(&format!(
+                "Too many nested exec commands, not executing {}",
+                path
+            ));
+            return;
+        }
+        let contents = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => return output(&format!("Failed to open {}: {}", path, e)),
+        };
+        output(&format!("Executing {}", path));
+        self.exec_depth += 1;
+        for line in contents.lines() {
+            self.execute_impl(level, line, output, calls);
+        }
+        self.exec_depth -= 1;
+    }
+    fn execute_impl(
+        &mut self,
+        level: AccessLevel,
+        line: &str,
+        output: &mut dyn FnMut(&str),
+        calls: &mut Vec<Call>,
+    ) {
+        for mut command_line in split_commands(line) {
+            let name = match next_token(&mut command_line) {
+                Some(n) => n,
+                None => continue,
+            };
+            let command = match Console::commands(AccessLevel::Admin).find(|c| c.name == name) {
+                Some(c) => c,
+                None => {
+                    output(&format!("No such command: {}", name));
+                    continue;
+                }
+            };
+            if command.level > level {
+                output(&format!("Access for command {} denied", name));
+                continue;
+            }
+            let mut args = match parse_args(command.params, command_line) {
+                Ok(a) => a,
+                Err(()) => {
+                    output(&format!("Usage: {} {}", command.name, command.params));
+                    continue;
+                }
+            };
+            self.executed.push(Executed {
+                name: command.name,
+                args: args.iter().map(|a| a.to_string()).collect(),
+            });
+            match command.action {
+                Action::Echo => output(&str_arg(&mut args)),
+                Action::Exec => {
+                    let path = str_arg(&mut args);
+                    self.execute_file_impl(level, &path, output, calls);
+                }
+                Action::CmdList => {
+                    let names: Vec<&str> = Console::commands(level).map(|c| c.name).collect();
+                    output(&names.join(", "));
+                }
+                Action::ChangeMap => {
+                    let map = str_arg(&mut args);
+                    let sv_map = VARIABLES.iter().find(|v| v.name == "sv_map").unwrap();
+                    self.set(sv_map, &map, output);
+                }
+                Action::Variable(variable) => match args.pop() {
+                    None => output(&format!("Value: {}", self.config.get(variable.name))),
+                    Some(Arg::Int(i)) => self.set(variable, &i.to_string(), output),
+                    Some(Arg::Str(s)) => self.set(variable, &s, output),
+                },
+                Action::Server(command) => calls.push(Call {
+                    command,
+                    args: mem::take(&mut args),
+                }),
+            }
+        }
+    }
+    fn set(&mut self, variable: &Variable, value: &str, output: &mut dyn FnMut(&str)) {
+        if let Err(e) = self.config.set(variable, value) {
+            output(&e);
+        }
+    }
+}
+
+fn str_arg(args: &mut Vec<Arg>) -> String {
+    match args.pop() {
+        Some(Arg::Str(s)) => s,
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::split_commands;
+    use super::AccessLevel;
+    use super::Console;
+
+    fn execute(
+        console: &mut Console,
+        level: AccessLevel,
+        line: &str,
+    ) -> (Vec<String>, Vec<String>) {
+        let mut output = Vec::new();
+        let calls = console.execute(level, line, &mut |l| output.push(l.to_owned()));
+        let calls = calls
+            .iter()
+            .map(|c| format!("{:?} {:?}", c.command, c.args))
+            .collect();
+        (output, calls)
+    }
+
+    #[test]
+    fn split() {
+        assert_eq!(split_commands("a; b \"c;d\" # e; f"), ["a", " b \"c;d\" "]);
+        assert_eq!(split_commands("a \"\\\";\";"), ["a \"\\\";\""]);
+        assert!(split_commands("  # comment").is_empty());
+    }
+
+    #[test]
+    fn variables() {
+        let mut console = Console::new();
+        let (output, _) = execute(&mut console, AccessLevel::Admin, "sv_port");
+        assert_eq!(output, ["Value: 8303"]);
+        execute(
+            &mut console,
+            AccessLevel::Admin,
+            "sv_port 8304; sv_name \"my \\\"server\\\"\"",
+        );
+        assert_eq!(console.config.sv_port, 8304);
+        assert_eq!(console.config.sv_name, "my \"server\"");
+        execute(
+            &mut console,
+            AccessLevel::Admin,
+            "sv_max_clients 100; sv_motd hello  world",
+        );
+        assert_eq!(console.config.sv_max_clients, 16);
+        assert_eq!(console.config.sv_motd, "hello  world");
+        let (output, _) = execute(&mut console, AccessLevel::Admin, "sv_port abc");
+        assert_eq!(output, ["Usage: sv_port ?i"]);
+        execute(&mut console, AccessLevel::Admin, "change_map ctf1");
+        assert_eq!(console.config.sv_map, "ctf1");
+        assert_eq!(
+            console.config.changed(),
+            [
+                ("sv_name", "my \"server\"".to_owned()),
+                ("sv_port", "8304".to_owned()),
+                ("sv_map", "ctf1".to_owned()),
+                ("sv_motd", "hello  world".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn executed() {
+        let mut console = Console::new();
+        console.config.sv_rcon_password = "admin".into();
+        execute(
+            &mut console,
+            AccessLevel::Admin,
+            "kick 3; sv_port 1; sv_port x",
+        );
+        let executed: Vec<_> = console
+            .drain_executed()
+            .map(|e| format!("{} {:?}", e.name, e.args))
+            .collect();
+        assert_eq!(executed, [r#"kick ["3"]"#, r#"sv_port ["1"]"#]);
+        assert!(console
+            .config
+            .changed()
+            .iter()
+            .all(|&(n, _)| n == "sv_port"));
+    }
+
+    #[test]
+    fn access() {
+        let mut console = Console::new();
+        console.config.sv_rcon_password = "admin".into();
+        console.config.sv_rcon_mod_password = "mod".into();
+        assert_eq!(console.auth(b"admin"), Some(AccessLevel::Admin));
+        assert_eq!(console.auth(b"mod"), Some(AccessLevel::Moderator));
+        assert_eq!(console.auth(b""), None);
+
+        let (output, calls) = execute(
+            &mut console,
+            AccessLevel::Moderator,
+            "kick 3 spam; sv_port 1; unknown",
+        );
+        assert_eq!(
+            output,
+            [
+                "Access for command sv_port denied",
+                "No such command: unknown"
+            ]
+        );
+        assert_eq!(calls, [r#"Kick [Int(3), Str("spam")]"#]);
+        assert!(Console::commands(AccessLevel::Helper).all(|c| c.level == AccessLevel::Helper));
+    }
+}
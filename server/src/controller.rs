@@ -0,0 +1,724 @@
+//! Game modes: teams, scoring, rounds and respawns.
+//!
+//! Follows the game controllers of Teeworlds 0.6, deathmatch (DM), team
+//! deathmatch (TDM) and capture the flag (CTF). The controller keeps track of
+//! the players that are not spectating, the server reports the kills
+//! happening in the world and sends the game events to the clients.
+
+use crate::console::Config;
+use crate::map::Map;
+use crate::TICKS_PER_SECOND;
+use libtw2_common::num::Cast;
+use libtw2_gamenet::enums::Team;
+use libtw2_gamenet::enums::WEAPON_GAME;
+use libtw2_gamenet::enums::WEAPON_SELF;
+use libtw2_gamenet::snap_obj;
+use libtw2_gamenet::snap_obj::GameData;
+use libtw2_gamenet::snap_obj::GameInfo;
+use libtw2_gamenet::snap_obj::Tick;
+use libtw2_world::vec2;
+use libtw2_world::CharacterId;
+use libtw2_world::Collision;
+use libtw2_world::World;
+use libtw2_world::CHARACTER_SIZE;
+use std::vec;
+
+/// Ticks from the end of a round until the next one starts.
+const GAME_OVER_TICKS: u32 = TICKS_PER_SECOND * 10;
+/// Ticks until a dropped flag returns to its stand.
+const FLAG_RETURN_TICKS: u32 = TICKS_PER_SECOND * 30;
+const FLAG_SIZE: f32 = 14.0;
+
+// Values of `GameData::flag_carrier_*` if nobody carries the flag.
+const FLAG_MISSING: i32 = -3;
+const FLAG_AT_STAND: i32 = -2;
+const FLAG_TAKEN: i32 = -1;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GameType {
+    Dm,
+    Tdm,
+    Ctf,
+}
+
+impl GameType {
+    /// Parses the value of `sv_gametype`, ignoring case.
+    pub fn from_name(name: &str) -> Option<GameType> {
+        Some(match &*name.to_ascii_lowercase() {
+            "dm" => GameType::Dm,
+            "tdm" => GameType::Tdm,
+            "ctf" => GameType::Ctf,
+            _ => return None,
+        })
+    }
+    /// Name shown in the server browser.
+    pub fn name(self) -> &'static str {
+        match self {
+            GameType::Dm => "DM",
+            GameType::Tdm => "TDM",
+            GameType::Ctf => "CTF",
+        }
+    }
+    pub fn is_teamplay(self) -> bool {
+        self != GameType::Dm
+    }
+}
+
+/// Things happening to the flags in CTF that the clients are told about.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FlagEvent {
+    /// `cid` took the flag of `team`.
+    Grab { team: Team, cid: CharacterId },
+    /// `cid` brought the flag of `team` to their own flag, `ticks` after
+    /// taking it from its stand.
+    Capture {
+        team: Team,
+        cid: CharacterId,
+        ticks: u32,
+    },
+    /// The flag of `team` was dropped by its carrier.
+    Drop { team: Team },
+    /// The flag of `team` went back to its stand.
+    Return { team: Team },
+}
+
+/// A player that is not spectating, identified by their client ID.
+pub struct Player {
+    pub cid: CharacterId,
+    /// `Team::Red` for all players if the game type has no teams.
+    pub team: Team,
+    pub score: i32,
+    die_tick: u32,
+    respawn_tick: u32,
+    /// Whether the player wants to spawn once `respawn_tick` is reached.
+    spawning: bool,
+}
+
+impl Player {
+    fn new(cid: CharacterId, team: Team, game_tick: u32) -> Player {
+        Player {
+            cid,
+            team,
+            score: 0,
+            die_tick: game_tick,
+            respawn_tick: game_tick,
+            spawning: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Flag {
+    team: Team,
+    stand: vec2,
+    pos: vec2,
+    vel: vec2,
+    carrier: Option<CharacterId>,
+    at_stand: bool,
+    drop_tick: u32,
+    grab_tick: u32,
+}
+
+impl Flag {
+    fn new(team: Team, stand: vec2) -> Flag {
+        Flag {
+            team,
+            stand,
+            pos: stand,
+            vel: vec2::new(0.0, 0.0),
+            carrier: None,
+            at_stand: true,
+            drop_tick: 0,
+            grab_tick: 0,
+        }
+    }
+    fn reset(&mut self) {
+        *self = Flag::new(self.team, self.stand);
+    }
+    fn carrier_net(&self) -> i32 {
+        match self.carrier {
+            Some(cid) => cid.0.assert_i32(),
+            None if self.at_stand => FLAG_AT_STAND,
+            None => FLAG_TAKEN,
+        }
+    }
+}
+
+fn team_index(team: Team) -> usize {
+    match team {
+        Team::Red => 0,
+        Team::Blue => 1,
+        Team::Spectators => unreachable!(),
+    }
+}
+
+fn other_team(team: Team) -> Team {
+    match team {
+        Team::Red => Team::Blue,
+        Team::Blue => Team::Red,
+        Team::Spectators => unreachable!(),
+    }
+}
+
+pub struct Controller {
+    game_type: GameType,
+    players: Vec<Player>,
+    team_scores: [i32; 2],
+    flags: [Option<Flag>; 2],
+    round_start_tick: u32,
+    round_count: i32,
+    /// Remaining ticks of the warmup.
+    warmup: u32,
+    game_over_tick: Option<u32>,
+    sudden_death: bool,
+    events: Vec<FlagEvent>,
+}
+
+impl Controller {
+    /// Starts the first round on the map, preceded by `warmup` seconds of
+    /// warmup.
+    pub fn new(game_type: GameType, map: &Map, game_tick: u32, warmup: i32) -> Controller {
+        let mut flags = [None, None];
+        if game_type == GameType::Ctf {
+            for &team in &[Team::Red, Team::Blue] {
+                flags[team_index(team)] = map.flag_stand(team).map(|s| Flag::new(team, s));
+            }
+        }
+        let mut result = Controller {
+            game_type,
+            players: Vec::new(),
+            team_scores: [0, 0],
+            flags,
+            round_start_tick: game_tick,
+            round_count: 0,
+            warmup: 0,
+            game_over_tick: None,
+            sudden_death: false,
+            events: Vec::new(),
+        };
+        result.do_warmup(warmup);
+        result
+    }
+    pub fn game_type(&self) -> GameType {
+        self.game_type
+    }
+    pub fn player(&self, cid: CharacterId) -> Option<&Player> {
+        self.players.iter().find(|p| p.cid == cid)
+    }
+    fn player_mut(&mut self, cid: CharacterId) -> Option<&mut Player> {
+        self.players.iter_mut().find(|p| p.cid == cid)
+    }
+    /// Team of a character in the world, `None` if players can hurt
+    /// everyone.
+    fn world_team(&self, team: Team) -> Option<Team> {
+        if self.game_type.is_teamplay() {
+            Some(team)
+        } else {
+            None
+        }
+    }
+    /// Whether the round is over and the world stands still.
+    pub fn is_paused(&self) -> bool {
+        self.game_over_tick.is_some()
+    }
+    /// Returns the events that happened since the last call.
+    pub fn drain_events(&mut self) -> vec::Drain<'_, FlagEvent> {
+        self.events.drain(..)
+    }
+    /// How joining a team is called in chat messages.
+    pub fn team_name(&self, team: Team) -> &'static str {
+        match team {
+            Team::Spectators => "spectators",
+            _ if !self.game_type.is_teamplay() => "game",
+            Team::Red => "red team",
+            Team::Blue => "blue team",
+        }
+    }
+    fn team_sizes(&self) -> [i32; 2] {
+        let mut result = [0, 0];
+        for player in &self.players {
+            result[team_index(player.team)] += 1;
+        }
+        result
+    }
+    /// Moves a player to another team or to the spectators, killing their
+    /// character.
+    ///
+    /// Returns the team the player ended up in if it changed, or an error
+    /// message if the teams would become unbalanced.
+    pub fn set_team(
+        &mut self,
+        world: &mut World,
+        cid: CharacterId,
+        team: Team,
+        game_tick: u32,
+    ) -> Result<Option<Team>, &'static str> {
+        let team = if team != Team::Spectators && !self.game_type.is_teamplay() {
+            Team::Red
+        } else {
+            team
+        };
+        let current = self.player(cid).map(|p| p.team);
+        if current.unwrap_or(Team::Spectators) == team {
+            return Ok(None);
+        }
+        if team == Team::Spectators {
+            self.players.retain(|p| p.cid != cid);
+            world.kill(cid, WEAPON_GAME);
+            return Ok(Some(team));
+        }
+        if self.game_type.is_teamplay() {
+            // Like Teeworlds, allow changing teams if the difference
+            // between the team sizes stays below two or decreases.
+            let mut sizes = self.team_sizes();
+            sizes[team_index(team)] += 1;
+            if current.is_some() {
+                sizes[team_index(other_team(team))] -= 1;
+            }
+            if (sizes[0] - sizes[1]).abs() >= 2 && (sizes[0] > sizes[1]) == (team == Team::Red) {
+                return Err("Teams must be balanced, please join other team");
+            }
+        }
+        world.kill(cid, WEAPON_GAME);
+        match self.player_mut(cid) {
+            Some(player) => {
+                player.team = team;
+                player.die_tick = game_tick;
+                player.respawn_tick = game_tick + TICKS_PER_SECOND / 2;
+            }
+            None => self.players.push(Player::new(cid, team, game_tick)),
+        }
+        Ok(Some(team))
+    }
+    /// Lets a dead player respawn as soon as possible, e.g. after they
+    /// pressed fire.
+    pub fn request_spawn(&mut self, cid: CharacterId) {
+        if let Some(player) = self.player_mut(cid) {
+            player.spawning = true;
+        }
+    }
+    /// Updates the scores after a character died, returns the flags carried
+    /// by the killer (2) and the victim (1) for the kill message.
+    pub fn on_kill(
+        &mut self,
+        game_tick: u32,
+        killer: CharacterId,
+        victim: CharacterId,
+        weapon: i32,
+    ) -> i32 {
+        let killer_team = self.player(killer).map(|p| p.team);
+        let victim_team = self.player(victim).map(|p| p.team);
+        if let Some(player) = self.player_mut(victim) {
+            player.die_tick = game_tick;
+            player.respawn_tick = game_tick + TICKS_PER_SECOND / 2;
+        }
+        if let (Some(killer_team), Some(victim_team)) = (killer_team, victim_team) {
+            if weapon != WEAPON_GAME {
+                self.score_kill(killer, killer_team, victim, victim_team, game_tick, weapon);
+            }
+        }
+        let mut mode_special = 0;
+        for flag in self.flags.iter_mut().flatten() {
+            if flag.carrier == Some(killer) {
+                mode_special |= 2;
+            }
+            if flag.carrier == Some(victim) {
+                flag.carrier = None;
+                flag.drop_tick = game_tick;
+                flag.vel = vec2::new(0.0, 0.0);
+                self.events.push(FlagEvent::Drop { team: flag.team });
+                if killer_team.is_some() && killer_team != victim_team {
+                    let player = self.players.iter_mut().find(|p| p.cid == killer);
+                    player.unwrap().score += 1;
+                }
+                mode_special |= 1;
+            }
+        }
+        mode_special
+    }
+    fn score_kill(
+        &mut self,
+        killer: CharacterId,
+        killer_team: Team,
+        victim: CharacterId,
+        victim_team: Team,
+        game_tick: u32,
+        weapon: i32,
+    ) {
+        let teamplay = self.game_type.is_teamplay();
+        let friendly = killer == victim || (teamplay && killer_team == victim_team);
+        self.player_mut(killer).unwrap().score += if friendly { -1 } else { 1 };
+        if weapon == WEAPON_SELF {
+            self.player_mut(victim).unwrap().respawn_tick = game_tick + TICKS_PER_SECOND * 3;
+        }
+        if self.game_type == GameType::Tdm {
+            self.team_scores[team_index(killer_team)] += if friendly { -1 } else { 1 };
+        }
+    }
+    /// Starts a new round after `seconds` of warmup, or stops the warmup if
+    /// `seconds` is zero.
+    pub fn do_warmup(&mut self, seconds: i32) {
+        self.warmup = seconds.max(0).assert_u32() * TICKS_PER_SECOND;
+    }
+    /// Starts a new round, the world has to be reset along with it.
+    pub fn start_round(&mut self, game_tick: u32) {
+        self.round_start_tick = game_tick;
+        self.sudden_death = false;
+        self.game_over_tick = None;
+        self.team_scores = [0, 0];
+        for flag in self.flags.iter_mut().flatten() {
+            flag.reset();
+        }
+        for player in &mut self.players {
+            player.score = 0;
+            player.die_tick = game_tick;
+            player.respawn_tick = game_tick + TICKS_PER_SECOND / 2;
+            player.spawning = true;
+        }
+    }
+    fn end_round(&mut self, game_tick: u32) {
+        if self.warmup != 0 {
+            return;
+        }
+        self.game_over_tick = Some(game_tick);
+        self.sudden_death = false;
+    }
+    /// Advances the game by one tick, after the world has been ticked.
+    ///
+    /// Returns whether a new round has to be started using
+    /// [`Controller::start_round`].
+    pub fn tick(
+        &mut self,
+        config: &Config,
+        world: &mut World,
+        map: &mut Map,
+        game_tick: u32,
+    ) -> bool {
+        world.team_damage = config.sv_teamdamage != 0;
+        if self.warmup != 0 {
+            self.warmup -= 1;
+            if self.warmup == 0 {
+                self.round_count = 0;
+                return true;
+            }
+        }
+        if let Some(game_over_tick) = self.game_over_tick {
+            if game_tick > game_over_tick + GAME_OVER_TICKS {
+                self.round_count += 1;
+                return true;
+            }
+            return false;
+        }
+        self.check_win(config, game_tick);
+        if self.game_over_tick.is_none() {
+            self.tick_flags(world, map, game_tick);
+            self.respawn(world, map, game_tick);
+        }
+        false
+    }
+    fn check_win(&mut self, config: &Config, game_tick: u32) {
+        if self.warmup != 0 {
+            return;
+        }
+        let time_limit = config.sv_timelimit.assert_u32() * TICKS_PER_SECOND * 60;
+        let time_over = time_limit != 0 && game_tick - self.round_start_tick >= time_limit;
+        let score_limit = config.sv_scorelimit;
+        if self.game_type.is_teamplay() {
+            let [red, blue] = self.team_scores;
+            if !(score_limit > 0 && (red >= score_limit || blue >= score_limit) || time_over) {
+                return;
+            }
+            if self.game_type == GameType::Ctf && self.sudden_death {
+                // Only a capture ends the sudden death, not a flag grab.
+                if red / 100 != blue / 100 {
+                    self.end_round(game_tick);
+                }
+            } else if red != blue {
+                self.end_round(game_tick);
+            } else {
+                self.sudden_death = true;
+            }
+        } else {
+            let top_score = self.players.iter().map(|p| p.score).max().unwrap_or(0);
+            let top_score = top_score.max(0);
+            let num_top = self.players.iter().filter(|p| p.score == top_score).count();
+            if !(score_limit > 0 && top_score >= score_limit || time_over) {
+                return;
+            }
+            if num_top == 1 {
+                self.end_round(game_tick);
+            } else {
+                self.sudden_death = true;
+            }
+        }
+    }
+    fn tick_flags(&mut self, world: &World, map: &mut Map, game_tick: u32) {
+        for i in 0..self.flags.len() {
+            let mut flag = match self.flags[i] {
+                Some(f) => f,
+                None => continue,
+            };
+            if map.is_death(flag.pos) || map.is_clipped(flag.pos) {
+                self.events.push(FlagEvent::Return { team: flag.team });
+                flag.reset();
+            } else if let Some(carrier) = flag.carrier {
+                if let Some(character) = world.character(carrier) {
+                    flag.pos = character.pos();
+                }
+                let capture = match self.flags[1 - i] {
+                    Some(own) => {
+                        own.at_stand
+                            && vec2::distance(flag.pos, own.pos) < FLAG_SIZE + CHARACTER_SIZE
+                    }
+                    None => false,
+                };
+                if capture {
+                    self.team_scores[1 - i] += 100;
+                    self.player_mut(carrier).unwrap().score += 5;
+                    self.events.push(FlagEvent::Capture {
+                        team: flag.team,
+                        cid: carrier,
+                        ticks: game_tick - flag.grab_tick,
+                    });
+                    flag.reset();
+                    self.flags[1 - i].as_mut().unwrap().reset();
+                }
+            } else {
+                self.touch_flag(&mut flag, world, map, game_tick);
+                if flag.carrier.is_none() && !flag.at_stand {
+                    if game_tick > flag.drop_tick + FLAG_RETURN_TICKS {
+                        self.events.push(FlagEvent::Return { team: flag.team });
+                        flag.reset();
+                    } else {
+                        // Teeworlds lets the flag bounce off the ground a
+                        // little, here it just stops.
+                        flag.vel.y += world.tuning().gravity.to_float();
+                        let size = vec2::new(FLAG_SIZE, FLAG_SIZE);
+                        let (pos, vel) = map.move_box(flag.pos, flag.vel, size);
+                        flag.pos = pos;
+                        flag.vel = vel;
+                    }
+                }
+            }
+            self.flags[i] = Some(flag);
+        }
+    }
+    /// Lets the characters close to a flag that isn't carried return or
+    /// take it.
+    fn touch_flag(&mut self, flag: &mut Flag, world: &World, map: &mut Map, game_tick: u32) {
+        for (cid, character) in world.characters() {
+            let pos = character.pos();
+            if vec2::distance(pos, flag.pos) >= FLAG_SIZE + CHARACTER_SIZE
+                || map.intersect_line(flag.pos, pos).is_some()
+            {
+                continue;
+            }
+            let player = match self.players.iter_mut().find(|p| p.cid == cid) {
+                Some(p) => p,
+                None => continue,
+            };
+            if player.team == flag.team {
+                if !flag.at_stand {
+                    player.score += 1;
+                    self.events.push(FlagEvent::Return { team: flag.team });
+                    flag.reset();
+                }
+            } else {
+                if flag.at_stand {
+                    self.team_scores[team_index(player.team)] += 1;
+                    flag.grab_tick = game_tick;
+                }
+                flag.at_stand = false;
+                flag.carrier = Some(cid);
+                player.score += 1;
+                self.events.push(FlagEvent::Grab {
+                    team: flag.team,
+                    cid,
+                });
+                break;
+            }
+        }
+    }
+    fn respawn(&mut self, world: &mut World, map: &Map, game_tick: u32) {
+        for i in 0..self.players.len() {
+            let player = &mut self.players[i];
+            let cid = player.cid;
+            if world.character(cid).is_some() {
+                continue;
+            }
+            if player.die_tick + TICKS_PER_SECOND * 3 <= game_tick {
+                player.spawning = true;
+            }
+            if !(player.spawning && player.respawn_tick <= game_tick) {
+                continue;
+            }
+            player.spawning = false;
+            let team = self.world_team(self.players[i].team);
+            let characters: Vec<(vec2, Option<Team>)> = world
+                .characters()
+                .map(|(c, character)| {
+                    let team = self.player(c).map(|p| p.team);
+                    (character.pos(), team.and_then(|t| self.world_team(t)))
+                })
+                .collect();
+            let spawn = map.spawn_point(team, characters.iter().cloned());
+            world.spawn(cid, spawn);
+            world.set_team(cid, team);
+        }
+    }
+    pub fn game_info(&self, config: &Config) -> GameInfo {
+        let mut game_flags = 0;
+        if self.game_type.is_teamplay() {
+            game_flags |= snap_obj::GAMEFLAG_TEAMS;
+        }
+        if self.game_type == GameType::Ctf {
+            game_flags |= snap_obj::GAMEFLAG_FLAGS;
+        }
+        let mut game_state_flags = 0;
+        if self.game_over_tick.is_some() {
+            game_state_flags |= snap_obj::GAMESTATEFLAG_GAMEOVER;
+        }
+        if self.sudden_death {
+            game_state_flags |= snap_obj::GAMESTATEFLAG_SUDDENDEATH;
+        }
+        GameInfo {
+            game_flags,
+            game_state_flags,
+            round_start_tick: Tick(self.round_start_tick.assert_i32()),
+            warmup_timer: self.warmup.assert_i32(),
+            score_limit: config.sv_scorelimit,
+            time_limit: config.sv_timelimit,
+            round_num: 0,
+            round_current: self.round_count + 1,
+        }
+    }
+    /// Team scores and flag carriers, only for game types with teams.
+    pub fn game_data(&self) -> Option<GameData> {
+        if !self.game_type.is_teamplay() {
+            return None;
+        }
+        let carrier = |team| match self.flags[team_index(team)] {
+            Some(ref f) => f.carrier_net(),
+            None if self.game_type == GameType::Ctf => FLAG_MISSING,
+            None => 0,
+        };
+        Some(GameData {
+            teamscore_red: self.team_scores[0],
+            teamscore_blue: self.team_scores[1],
+            flag_carrier_red: carrier(Team::Red),
+            flag_carrier_blue: carrier(Team::Blue),
+        })
+    }
+    /// Flags in the snapshot along with their IDs.
+    pub fn flags(&self) -> impl Iterator<Item = (u16, snap_obj::Flag)> + '_ {
+        self.flags.iter().flatten().map(|f| {
+            (
+                team_index(f.team).assert_u16(),
+                snap_obj::Flag {
+                    x: f.pos.x as i32,
+                    y: f.pos.y as i32,
+                    team: f.team as i32,
+                },
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Controller;
+    use super::GameType;
+    use crate::console::Config;
+    use crate::map::Map;
+    use libtw2_gamenet::enums::Team;
+    use libtw2_gamenet::enums::Weapon;
+    use libtw2_gamenet::enums::WEAPON_SELF;
+    use libtw2_gamenet::msg::game::SV_TUNE_PARAMS_DEFAULT;
+    use libtw2_world::CharacterId;
+    use libtw2_world::World;
+    use std::path::Path;
+
+    fn map() -> Map {
+        Map::load(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/dm1.map"))).unwrap()
+    }
+
+    fn join(c: &mut Controller, world: &mut World, cid: u32, team: Team) -> Option<Team> {
+        c.set_team(world, CharacterId(cid), team, 0).unwrap()
+    }
+
+    #[test]
+    fn dm() {
+        let (mut map, mut world) = (map(), World::new(SV_TUNE_PARAMS_DEFAULT));
+        let config = Config {
+            sv_scorelimit: 2,
+            ..Config::default()
+        };
+        let mut c = Controller::new(GameType::Dm, &map, 0, 0);
+        assert_eq!(join(&mut c, &mut world, 0, Team::Blue), Some(Team::Red));
+        assert_eq!(join(&mut c, &mut world, 1, Team::Red), Some(Team::Red));
+        assert_eq!(join(&mut c, &mut world, 1, Team::Blue), None);
+        for tick in 1..=25 {
+            assert!(!c.tick(&config, &mut world, &mut map, tick));
+        }
+        assert_eq!(world.characters().count(), 2);
+
+        let weapon = Weapon::Shotgun as i32;
+        assert_eq!(c.on_kill(26, CharacterId(0), CharacterId(1), weapon), 0);
+        assert_eq!(
+            c.on_kill(26, CharacterId(0), CharacterId(0), WEAPON_SELF),
+            0
+        );
+        assert_eq!(c.player(CharacterId(0)).unwrap().score, 0);
+        c.on_kill(27, CharacterId(0), CharacterId(1), weapon);
+        c.on_kill(28, CharacterId(0), CharacterId(1), weapon);
+        assert!(!c.tick(&config, &mut world, &mut map, 28));
+        assert!(c.is_paused());
+        assert!(!c.tick(&config, &mut world, &mut map, 28 + 500));
+        assert!(c.tick(&config, &mut world, &mut map, 28 + 501));
+        c.start_round(529);
+        assert!(!c.is_paused());
+        assert_eq!(c.player(CharacterId(0)).unwrap().score, 0);
+    }
+
+    #[test]
+    fn tdm() {
+        let (mut map, mut world) = (map(), World::new(SV_TUNE_PARAMS_DEFAULT));
+        let config = Config::default();
+        let mut c = Controller::new(GameType::Tdm, &map, 0, 0);
+        join(&mut c, &mut world, 0, Team::Red);
+        assert!(c
+            .set_team(&mut world, CharacterId(1), Team::Red, 0)
+            .is_err());
+        join(&mut c, &mut world, 1, Team::Blue);
+        join(&mut c, &mut world, 2, Team::Red);
+        assert_eq!(c.team_name(Team::Blue), "blue team");
+
+        let weapon = Weapon::Hammer as i32;
+        c.on_kill(1, CharacterId(0), CharacterId(1), weapon);
+        c.on_kill(1, CharacterId(0), CharacterId(2), weapon);
+        c.on_kill(1, CharacterId(1), CharacterId(1), WEAPON_SELF);
+        assert_eq!(c.game_data().unwrap().teamscore_red, 0);
+        assert_eq!(c.game_data().unwrap().teamscore_blue, -1);
+        assert_eq!(c.player(CharacterId(0)).unwrap().score, 0);
+
+        assert_eq!(
+            join(&mut c, &mut world, 2, Team::Spectators),
+            Some(Team::Spectators)
+        );
+        assert!(c.player(CharacterId(2)).is_none());
+        assert!(!c.tick(&config, &mut world, &mut map, 2));
+    }
+
+    #[test]
+    fn warmup() {
+        let (mut map, mut world) = (map(), World::new(SV_TUNE_PARAMS_DEFAULT));
+        let config = Config::default();
+        let mut c = Controller::new(GameType::Ctf, &map, 0, 1);
+        assert_eq!(c.game_info(&config).warmup_timer, 50);
+        assert_eq!(c.flags().count(), 0);
+        for tick in 1..50 {
+            assert!(!c.tick(&config, &mut world, &mut map, tick));
+        }
+        assert!(c.tick(&config, &mut world, &mut map, 50));
+    }
+}
@@ -0,0 +1,42 @@
+//! DDNet extensions of the Teeworlds 0.6 protocol.
+//!
+//! DDNet clients identify extended messages by UUIDs and can ask the server
+//! which name belongs to a UUID using `WhatIs`. Vanilla clients ignore the
+//! extended messages sent by the server.
+
+use libtw2_gamenet_ddnet::msg::system;
+use uuid::Uuid;
+
+/// Version of the `Capabilities` message understood by current DDNet
+/// clients.
+const CAPABILITIES_VERSION: i32 = 5;
+
+/// The server answers `PingEx` with `PongEx`.
+const CAPABILITY_PINGEX: i32 = 1 << 3;
+
+/// Sent before the map so that DDNet clients know which extensions the
+/// server supports.
+pub const CAPABILITIES: system::Capabilities = system::Capabilities {
+    version: CAPABILITIES_VERSION,
+    flags: CAPABILITY_PINGEX,
+};
+
+/// Extended messages known to the server, as answered to `WhatIs`.
+const KNOWN_UUIDS: &[(Uuid, &str)] = &[
+    (system::WHAT_IS, "what-is@ddnet.tw"),
+    (system::IT_IS, "it-is@ddnet.tw"),
+    (system::I_DONT_KNOW, "i-dont-know@ddnet.tw"),
+    (system::MAP_DETAILS, "map-details@ddnet.tw"),
+    (system::CAPABILITIES, "capabilities@ddnet.tw"),
+    (system::CLIENT_VERSION, "clientver@ddnet.tw"),
+    (system::PING_EX, "ping@ddnet.tw"),
+    (system::PONG_EX, "pong@ddnet.tw"),
+];
+
+/// Name of an extended message known to the server.
+pub fn uuid_name(uuid: Uuid) -> Option<&'static str> {
+    KNOWN_UUIDS
+        .iter()
+        .find(|&&(u, _)| u == uuid)
+        .map(|&(_, name)| name)
+}
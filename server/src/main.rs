@@ -1,14 +1,41 @@
 #[macro_use]
 extern crate log;
 
+mod ban;
+mod client_ids;
+mod console;
+mod controller;
+mod ddnet;
+mod map;
+mod record;
+mod serverinfo;
+mod vote;
+
+use crate::ban::Bans;
+use crate::client_ids::ClientIds;
+use crate::console::AccessLevel;
+use crate::console::Call;
+use crate::console::Console;
+use crate::console::ServerCommand;
+use crate::controller::Controller;
+use crate::controller::FlagEvent;
+use crate::controller::GameType;
+use crate::map::Map;
+use crate::record::Recorder;
+use crate::serverinfo::Request;
+use crate::vote::Outcome;
+use crate::vote::Vote;
+use crate::vote::VoteOption;
+use crate::vote::VOTE_DELAY;
 use arrayvec::ArrayString;
 use arrayvec::ArrayVec;
+use clap::App;
+use clap::Arg;
 use hexdump::hexdump_iter;
 use itertools::Itertools;
 use libtw2_common::num::Cast;
 use libtw2_common::num::CastFloat;
 use libtw2_common::pretty::AlmostString;
-use libtw2_common::unwrap_or_return;
 use libtw2_common::Takeable;
 use libtw2_event_loop::collections::PeerMap;
 use libtw2_event_loop::collections::PeerSet;
@@ -18,50 +45,62 @@ use libtw2_event_loop::Chunk;
 use libtw2_event_loop::ConnlessChunk;
 use libtw2_event_loop::Loop;
 use libtw2_event_loop::PeerId;
+use libtw2_event_loop::ServerLoop;
 use libtw2_event_loop::SocketLoop;
 use libtw2_event_loop::Timeout;
 use libtw2_event_loop::Timestamp;
-use libtw2_gamenet::enums::Emote;
+use libtw2_gamenet::enums::Sound;
 use libtw2_gamenet::enums::Team;
-use libtw2_gamenet::enums::Weapon;
-use libtw2_gamenet::enums::MAX_CLIENTS;
 use libtw2_gamenet::enums::VERSION;
+use libtw2_gamenet::enums::WEAPON_SELF;
 use libtw2_gamenet::msg;
-use libtw2_gamenet::msg::connless;
 use libtw2_gamenet::msg::game;
 use libtw2_gamenet::msg::game::SV_TUNE_PARAMS_DEFAULT;
 use libtw2_gamenet::msg::system;
 use libtw2_gamenet::msg::Connless;
 use libtw2_gamenet::msg::Game;
+use libtw2_gamenet::msg::MessageId;
 use libtw2_gamenet::msg::System;
 use libtw2_gamenet::msg::SystemOrGame;
 use libtw2_gamenet::snap_obj;
 use libtw2_gamenet::snap_obj::obj_size;
-use libtw2_gamenet::snap_obj::Character;
 use libtw2_gamenet::snap_obj::ClientInfo;
-use libtw2_gamenet::snap_obj::GameInfo;
 use libtw2_gamenet::snap_obj::PlayerInfo;
-use libtw2_gamenet::snap_obj::Tick;
 use libtw2_gamenet::SnapObj;
 use libtw2_packer::string_to_ints3;
 use libtw2_packer::string_to_ints4;
 use libtw2_packer::string_to_ints6;
 use libtw2_packer::with_packer;
 use libtw2_packer::Unpacker;
+use libtw2_register::Register;
 use libtw2_snapshot::snap;
 use libtw2_warn as warn;
-use libtw2_world::vec2;
+use libtw2_world::CharacterId;
+use libtw2_world::Event;
+use libtw2_world::World;
 use log::LogLevel;
-use ndarray::Array2;
-use std::cell::Cell;
 use std::fmt;
 use std::fmt::Write;
-use std::fs;
+use std::mem;
+use std::net::IpAddr;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
 use std::time::Duration;
 
 const TICKS_PER_SECOND: u32 = 50;
+const DEFAULT_CONFIG: &str = "autoexec_server.cfg";
 const PLAYER_NAME_LENGTH: usize = 16 - 1; // -1 for null termination
-const MAPDOWNLOAD_CHUNK_SIZE: u64 = 1024 - 128;
+const PLAYER_CLAN_LENGTH: usize = 12 - 1;
+const PLAYER_SKIN_LENGTH: usize = 24 - 1;
+/// Minimum time between chat messages of a client with spam protection.
+const CHAT_DELAY: Duration = Duration::from_secs(1);
+/// Minimum time between info changes of a client with spam protection.
+const CHANGE_INFO_DELAY: Duration = Duration::from_secs(5);
+/// Minimum time between team changes of a client with spam protection.
+const SET_TEAM_DELAY: Duration = Duration::from_secs(3);
+const MAX_VOTE_DESCRIPTION_LENGTH: usize = 64;
+const DEFAULT_BAN_MINUTES: i32 = 30;
 
 fn hexdump(level: LogLevel, data: &[u8]) {
     if log_enabled!(level) {
@@ -88,6 +127,21 @@ fn sends_impl<L: Loop + ?Sized>(msg: System, pid: PeerId, vital: bool, loop_: &m
     })
 }
 
+fn sends_ddnet_impl<L: Loop + ?Sized>(
+    msg: libtw2_gamenet_ddnet::msg::System,
+    pid: PeerId,
+    vital: bool,
+    loop_: &mut L,
+) {
+    let mut buf: ArrayVec<[u8; 2048]> = ArrayVec::new();
+    with_packer(&mut buf, |p| msg.encode(p).unwrap());
+    loop_.send(Chunk {
+        pid: pid,
+        vital: vital,
+        data: &buf,
+    })
+}
+
 trait LoopExt: Loop {
     fn sends<'a, S: Into<System<'a>>>(&mut self, pid: PeerId, msg: S) {
         sends_impl(msg.into(), pid, true, self)
@@ -95,6 +149,20 @@ trait LoopExt: Loop {
     fn sends_nonvital<'a, S: Into<System<'a>>>(&mut self, pid: PeerId, msg: S) {
         sends_impl(msg.into(), pid, false, self)
     }
+    fn sends_ddnet<'a, S: Into<libtw2_gamenet_ddnet::msg::System<'a>>>(
+        &mut self,
+        pid: PeerId,
+        msg: S,
+    ) {
+        sends_ddnet_impl(msg.into(), pid, true, self)
+    }
+    fn sends_ddnet_nonvital<'a, S: Into<libtw2_gamenet_ddnet::msg::System<'a>>>(
+        &mut self,
+        pid: PeerId,
+        msg: S,
+    ) {
+        sends_ddnet_impl(msg.into(), pid, false, self)
+    }
     fn sendg<'a, G: Into<Game<'a>>>(&mut self, pid: PeerId, msg: G) {
         fn inner<L: Loop + ?Sized>(msg: Game, pid: PeerId, loop_: &mut L) {
             let mut buf: ArrayVec<[u8; 2048]> = ArrayVec::new();
@@ -107,14 +175,6 @@ trait LoopExt: Loop {
         }
         inner(msg.into(), pid, self)
     }
-    fn sendc<'a, C: Into<Connless<'a>>>(&mut self, addr: Addr, msg: C) {
-        fn inner<L: Loop + ?Sized>(msg: Connless, addr: Addr, loop_: &mut L) {
-            let mut buf: ArrayVec<[u8; 2048]> = ArrayVec::new();
-            with_packer(&mut buf, |p| msg.encode(p).unwrap());
-            loop_.send_connless(addr, &buf)
-        }
-        inner(msg.into(), addr, self)
-    }
 }
 impl<L: Loop> LoopExt for L {}
 
@@ -131,127 +191,363 @@ impl SnapBuilderExt for snap::Builder {
         inner(self, id, obj.into())
     }
 }
-
-struct MapContents {
-    // TODO: Implement an actual memory map. Is that possible in a safe way?
-    contents: Vec<u8>,
-}
-
-impl Default for MapContents {
-    fn default() -> MapContents {
-        MapContents {
-            contents: fs::read("dm1.map").unwrap(),
-        }
+impl SnapBuilderExt for Vec<(SnapObj, u16)> {
+    fn add<O: Into<SnapObj>>(&mut self, id: u16, obj: O) {
+        self.push((obj.into(), id));
     }
 }
 
-impl MapContents {
-    fn serve_request(&self, rmd: system::RequestMapData) -> Option<system::MapData<'_>> {
-        let chunk = unwrap_or_return!(rmd.chunk.try_u64());
-        let offset = chunk * MAPDOWNLOAD_CHUNK_SIZE;
-        if offset >= self.contents.len().u64() {
-            return None;
-        }
-        let last = offset + MAPDOWNLOAD_CHUNK_SIZE >= self.contents.len().u64();
-        let offset = offset.assert_usize();
-        let data;
-        if !last {
-            data = &self.contents[offset..offset + MAPDOWNLOAD_CHUNK_SIZE.assert_usize()];
-        } else {
-            data = &self.contents[offset..];
-        }
-        Some(system::MapData {
-            last: last as i32,
-            crc: 0xf2159e6e_u32 as i32,
-            chunk: rmd.chunk,
-            data: data,
-        })
+fn map_path(config: &console::Config) -> Result<PathBuf, map::Error> {
+    if !map::is_valid_name(&config.sv_map) {
+        return Err(map::Error::InvalidName);
     }
+    Ok(Path::new(&config.sv_maps_directory).join(format!("{}.map", config.sv_map)))
 }
 
-struct Map {
-    spawn: vec2,
-    collision: Array2<Option<libtw2_world::CollisionType>>,
-    data: MapContents,
-}
-
-impl Default for Map {
-    fn default() -> Map {
-        let map_contents = MapContents::default();
-        let reader = libtw2_datafile::Reader::open("dm1.map").unwrap();
-        let mut map = libtw2_map::Reader::from_datafile(reader);
-        map.check_version().unwrap();
-        let gamelayers = map.game_layers().unwrap();
-        let tiles = map.layer_tiles(gamelayers.game()).unwrap();
-        let result = Map {
-            spawn: vec2::new(160.0, 160.0),
-            collision: tiles.mapv(|t| match t.index {
-                1 => Some(libtw2_world::CollisionType::Normal),
-                3 => Some(libtw2_world::CollisionType::Unhookable),
-                _ => None,
-            }),
-            data: map_contents,
-        };
-        for y in 0..result.collision.dim().0 {
-            for x in 0..result.collision.dim().1 {
-                let c = match result.collision[(y, x)] {
-                    Some(libtw2_world::CollisionType::Normal) => '#',
-                    Some(libtw2_world::CollisionType::Unhookable) => '!',
-                    None => ' ',
-                };
-                print!("{}", c);
-            }
-            println!("");
-        }
-        result
+fn new_world(map: &Map) -> World {
+    let mut world = World::new(SV_TUNE_PARAMS_DEFAULT);
+    for &(kind, pos) in map.pickups() {
+        world.add_pickup(kind, pos);
     }
+    world
 }
 
-impl libtw2_world::Collision for Map {
-    fn check_point(&mut self, pos: vec2) -> Option<libtw2_world::CollisionType> {
-        let (x, y) = (pos.x.round_to_i32(), pos.y.round_to_i32());
-        let (mut tx, mut ty) = (
-            (x as f32 / 32.0).trunc_to_i32(),
-            (y as f32 / 32.0).trunc_to_i32(),
-        );
-        if tx < 0 {
-            tx = 0;
-        }
-        if tx > self.collision.dim().1.assert_i32() {
-            tx = self.collision.dim().1.assert_i32() - 1;
-        }
-        if ty < 0 {
-            ty = 0;
-        }
-        if ty > self.collision.dim().0.assert_i32() {
-            ty = self.collision.dim().0.assert_i32() - 1;
-        }
-        self.collision[(ty.assert_usize(), tx.assert_usize())]
-    }
+fn new_controller(config: &console::Config, map: &Map, game_tick: u32) -> Controller {
+    let game_type = GameType::from_name(&config.sv_gametype).unwrap_or_else(|| {
+        warn!("unknown game type {:?}, using DM", config.sv_gametype);
+        GameType::Dm
+    });
+    Controller::new(game_type, map, game_tick, config.sv_warmup)
 }
 
-#[derive(Default)]
 struct Server {
+    console: Console,
     peers: PeerMap<Peer>,
-    players: Vec<Player>,
+    client_ids: ClientIds,
+    controller: Controller,
     game_start: Timestamp,
     game_tick: u32,
     delta_buffer: Vec<u8>,
     map: Map,
+    world: World,
+    /// World events since the last snapshot.
+    events: Vec<Event>,
+    /// Registration with the masterserver, if enabled.
+    register: Option<Register>,
+    recorder: Recorder,
+    /// Number of `events` already recorded in the demo.
+    demo_events: usize,
+    bans: Bans,
+    vote_options: Vec<VoteOption>,
+    /// The vote currently in progress.
+    vote: Option<Vote>,
 
     send_snapshots_peer_set: Takeable<PeerSet>,
 }
 
 impl Server {
+    fn new(console: Console, map: Map) -> Server {
+        Server {
+            world: new_world(&map),
+            events: Default::default(),
+            controller: new_controller(&console.config, &map, 0),
+            console,
+            peers: Default::default(),
+            client_ids: Default::default(),
+            game_start: Default::default(),
+            game_tick: 0,
+            delta_buffer: Default::default(),
+            map,
+            register: None,
+            recorder: Default::default(),
+            demo_events: 0,
+            bans: Default::default(),
+            vote_options: Vec::new(),
+            vote: None,
+            send_snapshots_peer_set: Default::default(),
+        }
+    }
+    fn cid(&self, pid: PeerId) -> CharacterId {
+        self.client_ids.cid(pid)
+    }
+    fn is_player(&self, pid: PeerId) -> bool {
+        self.controller.player(self.cid(pid)).is_some()
+    }
+    fn info(&self) -> serverinfo::Info<'_> {
+        let config = &self.console.config;
+        serverinfo::Info {
+            version: VERSION.as_bytes(),
+            name: config.sv_name.as_bytes(),
+            map: self.map.name().as_bytes(),
+            map_crc: self.map.crc(),
+            map_size: self.map.size(),
+            map_sha256: self.map.sha256(),
+            game_type: self.controller.game_type().name().as_bytes(),
+            passworded: !config.password.is_empty(),
+            max_players: config.sv_max_clients,
+            max_clients: config.sv_max_clients,
+            clients: self
+                .peers
+                .iter()
+                .map(|(pid, peer)| {
+                    let player = self.controller.player(self.cid(pid));
+                    let info = peer.state.info();
+                    serverinfo::Client {
+                        name: peer.state.net_name(),
+                        clan: info.map(|i| &i.clan[..]).unwrap_or(b""),
+                        country: info.map(|i| i.country).unwrap_or(-1),
+                        score: player.map(|p| p.score).unwrap_or(0),
+                        is_player: player.is_some(),
+                    }
+                })
+                .collect(),
+        }
+    }
+    fn update_register(&self) {
+        if let Some(register) = &self.register {
+            register.on_new_info(self.info().json().into());
+        }
+    }
+    /// Peers that haven't been disconnected by the server.
+    fn pids(&self) -> Vec<PeerId> {
+        self.peers
+            .iter()
+            .filter(|(_, peer)| !peer.disconnecting)
+            .map(|(pid, _)| pid)
+            .collect()
+    }
+    fn ingame_pids(&self) -> Vec<PeerId> {
+        self.peers
+            .iter()
+            .filter(|(_, peer)| !peer.disconnecting)
+            .filter(|(_, peer)| matches!(peer.state, PeerState::Ingame(..)))
+            .map(|(pid, _)| pid)
+            .collect()
+    }
+    /// Trims the name and prefixes it with "(1)", "(2)", etc. if another
+    /// client already uses it.
+    fn unique_name(&self, pid: PeerId, name: &[u8]) -> ArrayVec<[u8; PLAYER_NAME_LENGTH]> {
+        let start = name.iter().position(|&b| b != b' ').unwrap_or(name.len());
+        let end = name
+            .iter()
+            .rposition(|&b| b != b' ')
+            .map_or(start, |i| i + 1);
+        let name = match &name[start..end] {
+            b"" => &b"nameless tee"[..],
+            n => n,
+        };
+        let taken = |name: &[u8]| {
+            self.peers
+                .iter()
+                .any(|(p, peer)| p != pid && peer.state.has_name() && peer.state.net_name() == name)
+        };
+        let mut result: ArrayVec<[u8; PLAYER_NAME_LENGTH]> = name.iter().cloned().collect();
+        let mut i = 1;
+        while taken(&result) {
+            let prefix = format!("({})", i);
+            result = prefix.bytes().chain(name.iter().cloned()).collect();
+            i += 1;
+        }
+        result
+    }
+    /// Adds, removes or clears vote options, returns the console output.
+    ///
+    /// Doesn't need a running server so that it can be used in config
+    /// files.
+    fn vote_options_command(&mut self, call: &Call) -> String {
+        match call.command {
+            ServerCommand::AddVote => {
+                let description = call.str(0).unwrap();
+                let command = call.str(1).unwrap();
+                if description.len() > MAX_VOTE_DESCRIPTION_LENGTH {
+                    return format!(
+                        "Vote option description too long, at most {} bytes are allowed",
+                        MAX_VOTE_DESCRIPTION_LENGTH,
+                    );
+                }
+                if self
+                    .vote_options
+                    .iter()
+                    .any(|o| o.description == description)
+                {
+                    return format!("Option '{}' already exists", description);
+                }
+                self.vote_options.push(VoteOption {
+                    description: description.to_owned(),
+                    command: command.to_owned(),
+                });
+                format!("Added option '{}' '{}'", description, command)
+            }
+            ServerCommand::RemoveVote => {
+                let description = call.str(0).unwrap();
+                match self
+                    .vote_options
+                    .iter()
+                    .position(|o| o.description == description)
+                {
+                    Some(i) => {
+                        let option = self.vote_options.remove(i);
+                        format!(
+                            "Removed option '{}' '{}'",
+                            option.description, option.command
+                        )
+                    }
+                    None => format!("Option '{}' does not exist", description),
+                }
+            }
+            ServerCommand::ClearVotes => {
+                self.vote_options.clear();
+                "Cleared the vote options".to_owned()
+            }
+            c => unreachable!("{:?}", c),
+        }
+    }
+    /// Forgets the world events, after sending them or when starting over.
+    fn clear_events(&mut self) {
+        self.events.clear();
+        self.demo_events = 0;
+    }
     fn game_tick_time(&self, tick: u32) -> Timestamp {
         let millis = tick.u64() * 1000 / TICKS_PER_SECOND.u64();
         self.game_start + Duration::from_millis(millis)
     }
+    /// Adds the snapshot objects for `snap_pid` to `builder`, or those for
+    /// the demo if it's `None`.
+    fn snap<B: SnapBuilderExt>(&self, builder: &mut B, snap_pid: Option<PeerId>, events: &[Event]) {
+        let controller = &self.controller;
+        builder.add(0, controller.game_info(&self.console.config));
+        if let Some(game_data) = controller.game_data() {
+            builder.add(0, game_data);
+        }
+        for (id, flag) in controller.flags() {
+            builder.add(id, flag);
+        }
+        for (pid, peer) in self.peers.iter() {
+            if let PeerState::Ingame(ref ingame) = peer.state {
+                let cid = self.cid(pid);
+                let player = controller.player(cid);
+                let info = &ingame.info;
+                builder.add(
+                    cid.0.assert_u16(),
+                    ClientInfo {
+                        name: string_to_ints4(&info.name),
+                        clan: string_to_ints3(&info.clan),
+                        country: info.country,
+                        skin: string_to_ints6(&info.skin),
+                        use_custom_color: info.use_custom_color as i32,
+                        color_body: info.color_body,
+                        color_feet: info.color_feet,
+                    },
+                );
+                builder.add(
+                    cid.0.assert_u16(),
+                    PlayerInfo {
+                        local: (snap_pid == Some(pid)) as i32,
+                        client_id: cid.0.assert_i32(),
+                        team: player.map(|p| p.team).unwrap_or(Team::Spectators),
+                        score: player.map(|p| p.score).unwrap_or(0),
+                        latency: ingame.latency,
+                    },
+                );
+            }
+        }
+        let game_tick = self.game_tick.assert_i32();
+        let world = &self.world;
+        for (cid, character) in world.characters() {
+            let own = snap_pid.map(|pid| self.cid(pid)) == Some(cid);
+            builder.add(cid.0.assert_u16(), character.to_net(game_tick, own));
+        }
+        for projectile in world.projectiles() {
+            builder.add(projectile.id(), projectile.to_net());
+        }
+        for laser in world.lasers() {
+            builder.add(laser.id(), laser.to_net());
+        }
+        for pickup in world.pickups() {
+            if let Some(p) = pickup.to_net() {
+                builder.add(pickup.id(), p);
+            }
+        }
+        for (i, &event) in events.iter().enumerate() {
+            let id = i.assert_u16();
+            let common = |pos: libtw2_world::vec2| snap_obj::Common {
+                x: pos.x.round_to_i32(),
+                y: pos.y.round_to_i32(),
+            };
+            match event {
+                Event::Sound {
+                    pos,
+                    sound,
+                    receiver,
+                } => {
+                    // Demos contain the sounds for all receivers.
+                    let hear = match (receiver, snap_pid) {
+                        (Some(r), Some(pid)) => r == self.cid(pid),
+                        _ => true,
+                    };
+                    if hear {
+                        builder.add(
+                            id,
+                            snap_obj::SoundWorld {
+                                common: common(pos),
+                                sound_id: sound,
+                            },
+                        );
+                    }
+                }
+                Event::Explosion(pos) => builder.add(
+                    id,
+                    snap_obj::Explosion {
+                        common: common(pos),
+                    },
+                ),
+                Event::HammerHit(pos) => builder.add(
+                    id,
+                    snap_obj::HammerHit {
+                        common: common(pos),
+                    },
+                ),
+                Event::Spawn(pos) => builder.add(
+                    id,
+                    snap_obj::Spawn {
+                        common: common(pos),
+                    },
+                ),
+                Event::Death { pos, cid } => builder.add(
+                    id,
+                    snap_obj::Death {
+                        common: common(pos),
+                        client_id: cid.0.assert_i32(),
+                    },
+                ),
+                Event::DamageIndicator { pos, angle } => builder.add(
+                    id,
+                    snap_obj::DamageInd {
+                        common: common(pos),
+                        angle,
+                    },
+                ),
+                Event::Kill { .. } | Event::WeaponPickup { .. } => {}
+            }
+        }
+    }
 }
 
 #[derive(Default)]
 struct Peer {
     state: PeerState,
+    rcon_level: Option<AccessLevel>,
+    /// Whether the client asked for the list of rcon commands.
+    rcon_commands: bool,
+    last_chat: Option<Timestamp>,
+    last_change_info: Option<Timestamp>,
+    last_set_team: Option<Timestamp>,
+    last_vote_call: Option<Timestamp>,
+    /// DDNet version sent by DDNet clients before `Info`.
+    ddnet_version: Option<i32>,
+    /// Set once the server disconnected the peer, nothing may be sent to it
+    /// until `on_disconnect` removes it.
+    disconnecting: bool,
 }
 
 impl Default for PeerState {
@@ -287,74 +583,95 @@ impl PeerState {
         use PeerState::*;
         match *self {
             SystemInfo | SystemReady | GameInfo => b"(connecting)",
-            SystemEnterGame(ref s) => &s.name,
-            Ingame(ref s) => &s.name,
+            SystemEnterGame(ref s) => &s.info.name,
+            Ingame(ref s) => &s.info.name,
         }
     }
-    fn is_player(&self) -> bool {
+    /// Whether the client has sent its name.
+    fn has_name(&self) -> bool {
+        self.info().is_some()
+    }
+    fn info(&self) -> Option<&PeerInfo> {
         use PeerState::*;
         match *self {
-            SystemInfo | SystemReady | GameInfo | SystemEnterGame(_) => false,
-            Ingame(ref s) => !s.spectator,
+            SystemInfo | SystemReady | GameInfo => None,
+            SystemEnterGame(ref s) => Some(&s.info),
+            Ingame(ref s) => Some(&s.info),
         }
     }
-    fn net_is_player(&self) -> i32 {
-        self.is_player() as i32
-    }
 }
 
+/// Name and appearance of a player, sent in `ClStartInfo` and
+/// `ClChangeInfo`.
 #[derive(Clone)]
-struct SystemEnterGameState {
+struct PeerInfo {
     name: ArrayVec<[u8; PLAYER_NAME_LENGTH]>,
+    clan: ArrayVec<[u8; PLAYER_CLAN_LENGTH]>,
+    country: i32,
+    skin: ArrayVec<[u8; PLAYER_SKIN_LENGTH]>,
+    use_custom_color: bool,
+    color_body: i32,
+    color_feet: i32,
 }
 
-impl SystemEnterGameState {
-    fn new(name: &[u8]) -> SystemEnterGameState {
+impl<'a> From<game::ClStartInfo<'a>> for PeerInfo {
+    fn from(info: game::ClStartInfo<'a>) -> PeerInfo {
         // TODO: Warn for overlong name.
-        SystemEnterGameState {
-            name: name.iter().cloned().collect(),
+        PeerInfo {
+            name: info.name.iter().cloned().collect(),
+            clan: info.clan.iter().cloned().collect(),
+            country: info.country,
+            skin: info.skin.iter().cloned().collect(),
+            use_custom_color: info.use_custom_color,
+            color_body: info.color_body,
+            color_feet: info.color_feet,
         }
     }
 }
 
+impl<'a> From<game::ClChangeInfo<'a>> for PeerInfo {
+    fn from(info: game::ClChangeInfo<'a>) -> PeerInfo {
+        PeerInfo::from(game::ClStartInfo {
+            name: info.name,
+            clan: info.clan,
+            country: info.country,
+            skin: info.skin,
+            use_custom_color: info.use_custom_color,
+            color_body: info.color_body,
+            color_feet: info.color_feet,
+        })
+    }
+}
+
+#[derive(Clone)]
+struct SystemEnterGameState {
+    info: PeerInfo,
+}
+
 struct IngameState {
-    name: ArrayVec<[u8; PLAYER_NAME_LENGTH]>,
+    info: PeerInfo,
     snaps: libtw2_snapshot::Storage,
-    spectator: bool,
-    input: snap_obj::PlayerInput,
+    /// Milliseconds between sending the last acknowledged snapshot and
+    /// receiving the acknowledgement.
+    latency: i32,
 }
 
 impl From<SystemEnterGameState> for IngameState {
     fn from(system_enter_game: SystemEnterGameState) -> IngameState {
         IngameState {
-            name: system_enter_game.name,
+            info: system_enter_game.info,
             snaps: Default::default(),
-            spectator: true,
-            input: Default::default(),
+            latency: 0,
         }
     }
 }
 
-struct Player {
-    character: Cell<libtw2_world::Character>,
-    pid: PeerId,
-}
-
-impl Player {
-    fn new(pid: PeerId, spawn: vec2) -> Player {
-        Player {
-            character: Cell::new(libtw2_world::Character::spawn(spawn)),
-            pid: pid,
-        }
-    }
-}
-
-struct ServerLoop<'a, L: Loop + 'a> {
+struct Context<'a, L: ServerLoop + 'a> {
     loop_: &'a mut L,
     server: &'a mut Server,
 }
 
-impl<L: Loop> Application<L> for Server {
+impl<L: ServerLoop> Application<L> for Server {
     fn needs_tick(&mut self) -> Timeout {
         if !self.peers.is_empty() {
             Timeout::active(self.game_tick_time(self.game_tick + 1))
@@ -372,7 +689,8 @@ impl<L: Loop> Application<L> for Server {
             .on_packet(chunk.pid, chunk.vital, chunk.data);
     }
     fn on_connless_packet(&mut self, loop_: &mut L, chunk: ConnlessChunk) {
-        self.loop_(loop_).on_connless_packet(chunk.addr, chunk.data);
+        self.loop_(loop_)
+            .on_connless_packet(chunk.addr, chunk.extra_data, chunk.data);
     }
     fn on_connect(&mut self, loop_: &mut L, pid: PeerId) {
         self.loop_(loop_).on_connect(pid);
@@ -386,20 +704,46 @@ impl<L: Loop> Application<L> for Server {
 }
 
 impl Server {
-    fn run<L: Loop>() {
-        L::accept_connections_on_port(8303).run(Server::default());
+    fn run<L: ServerLoop>(console: Console, map: Map, calls: Vec<Call>) {
+        let port = console.config.sv_port.assert_u16();
+        let mut server = Server::new(console, map);
+        for call in calls {
+            match call.command {
+                ServerCommand::AddVote | ServerCommand::RemoveVote | ServerCommand::ClearVotes => {
+                    info!("{}", server.vote_options_command(&call));
+                }
+                command => warn!(
+                    "{:?} can only be executed while the server is running",
+                    command
+                ),
+            }
+        }
+        // The config is part of the teehistorian header, the commands that
+        // set it don't need to be recorded.
+        server.console.drain_executed();
+        if server.console.config.sv_register != 0 {
+            let config = &server.console.config;
+            let register =
+                serverinfo::register(port, &config.sv_register_url, server.info().json());
+            server.register = Some(register);
+        }
+        L::accept_connections_on_port(port).run(server);
     }
-    fn loop_<'a, L: Loop + 'a>(&'a mut self, loop_: &'a mut L) -> ServerLoop<'a, L> {
-        ServerLoop {
+    fn loop_<'a, L: ServerLoop + 'a>(&'a mut self, loop_: &'a mut L) -> Context<'a, L> {
+        Context {
             server: self,
             loop_: loop_,
         }
     }
 }
-impl<'a, L: Loop> ServerLoop<'a, L> {
+impl<'a, L: ServerLoop> Context<'a, L> {
     fn on_packet(&mut self, pid: PeerId, vital: bool, data: &[u8]) {
         use PeerState::*;
 
+        let id = SystemOrGame::decode_id(&mut Warn(pid, data), &mut Unpacker::new(data));
+        if let Ok(SystemOrGame::System(MessageId::Uuid(_))) = id {
+            return self.on_ddnet_packet(pid, vital, data);
+        }
         let msg = match msg::decode(&mut Warn(pid, data), &mut Unpacker::new(data)) {
             Ok(m) => m,
             Err(err) => {
@@ -412,24 +756,24 @@ impl<'a, L: Loop> ServerLoop<'a, L> {
             warn!("non-vital message {:?}", msg);
             return;
         }
+        if let (PeerState::Ingame(..), SystemOrGame::Game(..)) =
+            (&self.server.peers[pid].state, msg)
+        {
+            let cid = self.server.cid(pid);
+            self.server.recorder.message(cid, data);
+        }
         let mut processed = false;
         let peer = &mut self.server.peers[pid];
         match (&peer.state, msg) {
             (&SystemInfo, SystemOrGame::System(System::Info(info))) => {
                 if info.version == VERSION.as_bytes() {
-                    if info.password == Some(b"foobar") {
-                        self.loop_.sends(
-                            pid,
-                            system::MapChange {
-                                name: b"dm1",
-                                crc: 0xf2159e6e_u32 as i32,
-                                size: 5805,
-                            },
-                        );
-                        self.loop_.flush(pid);
+                    let password = self.server.console.config.password.as_bytes();
+                    if password.is_empty() || info.password == Some(password) {
                         peer.state = SystemReady;
+                        self.loop_.sends_ddnet(pid, ddnet::CAPABILITIES);
+                        self.send_map(pid);
                     } else {
-                        self.loop_.disconnect(pid, b"Wrong password");
+                        self.disconnect(pid, b"Wrong password");
                     }
                 } else {
                     let mut buf: ArrayString<[u8; 128]> = ArrayString::new();
@@ -450,12 +794,12 @@ impl<'a, L: Loop> ServerLoop<'a, L> {
                         }
                         .unwrap()
                     });
-                    self.loop_.disconnect(pid, buf.as_bytes());
+                    self.disconnect(pid, buf.as_bytes());
                 }
                 processed = true;
             }
             (&SystemReady, SystemOrGame::System(System::RequestMapData(rmd))) => {
-                if let Some(md) = self.server.map.data.serve_request(rmd) {
+                if let Some(md) = self.server.map.serve_request(rmd) {
                     self.loop_.sends(pid, md);
                 }
             }
@@ -463,7 +807,7 @@ impl<'a, L: Loop> ServerLoop<'a, L> {
                 self.loop_.sendg(
                     pid,
                     game::SvMotd {
-                        message: b"Hello World!",
+                        message: self.server.console.config.sv_motd.as_bytes(),
                     },
                 );
                 self.loop_.sends(pid, system::ConReady);
@@ -473,25 +817,76 @@ impl<'a, L: Loop> ServerLoop<'a, L> {
             }
             (&GameInfo, SystemOrGame::Game(Game::ClStartInfo(info))) => {
                 info!("{}:{} enters the game", pid, AlmostString::new(info.name));
-                self.loop_.sendg(pid, game::SvVoteClearOptions);
+                let mut info = PeerInfo::from(info);
+                info.name = self.server.unique_name(pid, &info.name);
+                self.send_vote_options(pid);
                 self.loop_.sendg(pid, SV_TUNE_PARAMS_DEFAULT);
                 self.loop_.sendg(pid, game::SvReadyToEnter);
                 self.loop_.flush(pid);
-                peer.state = SystemEnterGame(SystemEnterGameState::new(info.name));
+                self.server.peers[pid].state = SystemEnterGame(SystemEnterGameState { info });
                 processed = true;
             }
             (&SystemEnterGame(..), SystemOrGame::System(System::EnterGame(system::EnterGame))) => {
                 let system_enter_game = peer.state.assert_system_enter_game().clone();
                 peer.state = Ingame(system_enter_game.into());
+                let cid = self.server.cid(pid);
+                self.server.recorder.join(cid);
+                if self.server.vote.is_some() {
+                    self.send_vote(pid);
+                }
                 processed = true;
             }
-            (_, SystemOrGame::System(System::RconAuth(..))) => {
+            (_, SystemOrGame::System(System::RconAuth(auth))) => {
+                processed = true;
+                if peer.rcon_level.is_some() {
+                    return;
+                }
+                let level = match self.server.console.auth(auth.password) {
+                    Some(l) => l,
+                    None => {
+                        info!("{} rcon authentication failed", pid);
+                        self.loop_.sends(
+                            pid,
+                            system::RconLine {
+                                line: b"Wrong password",
+                            },
+                        );
+                        return;
+                    }
+                };
+                info!("{} rcon authenticated ({:?})", pid, level);
+                peer.rcon_level = Some(level);
+                peer.rcon_commands = auth.request_commands == Some(1);
+                self.loop_.sends(
+                    pid,
+                    system::RconAuthStatus {
+                        auth_level: Some(1),
+                        receive_commands: Some(peer.rcon_commands as i32),
+                    },
+                );
                 self.loop_.sends(
                     pid,
                     system::RconLine {
-                        line: b"Wrong password",
+                        line: b"Authentication successful. Remote console access granted.",
                     },
                 );
+                if peer.rcon_commands {
+                    for command in Console::commands(level) {
+                        self.loop_.sends(
+                            pid,
+                            system::RconCmdAdd {
+                                name: command.name.as_bytes(),
+                                help: command.help.as_bytes(),
+                                params: command.params.as_bytes(),
+                            },
+                        );
+                    }
+                }
+                self.loop_.flush(pid);
+            }
+            (_, SystemOrGame::System(System::RconCmd(cmd))) if peer.rcon_level.is_some() => {
+                let level = peer.rcon_level.unwrap();
+                self.rcon_command(pid, level, cmd.cmd);
                 processed = true;
             }
             (&Ingame(..), SystemOrGame::System(System::Input(input))) => {
@@ -502,82 +897,800 @@ impl<'a, L: Loop> ServerLoop<'a, L> {
                 {
                     warn!("invalid input tick: {:?} ({})", e, input.ack_snapshot);
                 }
+                let server = &mut *self.server;
+                if let Some(tick) = input.ack_snapshot.try_u32() {
+                    if tick <= server.game_tick {
+                        let sent = server.game_tick_time(tick).as_usecs_since_epoch();
+                        let now = self.loop_.time().as_usecs_since_epoch();
+                        let latency = (now.saturating_sub(sent) / 1000).try_i32();
+                        server.peers[pid].state.assert_ingame().latency =
+                            latency.unwrap_or(i32::MAX);
+                    }
+                }
+                processed = true;
                 // TODO: Teeworlds never ignores old inputs?
-                ingame.input = input.input;
+                if !server.is_player(pid) || server.controller.is_paused() {
+                    return;
+                }
+                let cid = server.cid(pid);
+                server.recorder.input(cid, &input.input);
+                if server.world.character(cid).is_some() {
+                    server.world.on_input(&mut server.map, cid, input.input);
+                    self.process_world_events();
+                } else if input.input.fire & 1 != 0 {
+                    server.controller.request_spawn(cid);
+                }
+            }
+            (&Ingame(..), SystemOrGame::Game(Game::ClKill(game::ClKill))) => {
+                // TODO: Only allow one kill every three seconds.
+                let cid = self.server.cid(pid);
+                self.server.world.kill(cid, WEAPON_SELF);
+                self.process_world_events();
+                processed = true;
+            }
+            (&Ingame(..), SystemOrGame::Game(Game::ClSay(say))) => {
                 processed = true;
+                let now = self.loop_.time();
+                let spam_protection = self.server.console.config.sv_spamprotection != 0;
+                if spam_protection && peer.last_chat.map(|t| now < t + CHAT_DELAY) == Some(true) {
+                    return;
+                }
+                peer.last_chat = Some(now);
+                self.say(pid, say.team, say.message);
+            }
+            (&Ingame(..), SystemOrGame::Game(Game::ClChangeInfo(info))) => {
+                processed = true;
+                let now = self.loop_.time();
+                let spam_protection = self.server.console.config.sv_spamprotection != 0;
+                if spam_protection
+                    && peer.last_change_info.map(|t| now < t + CHANGE_INFO_DELAY) == Some(true)
+                {
+                    return;
+                }
+                peer.last_change_info = Some(now);
+                self.change_info(pid, info.into());
             }
             (&Ingame(..), SystemOrGame::Game(Game::ClCallVote(call_vote))) => {
-                let error: Option<&[u8]> = match call_vote.type_ {
-                    b"kick" => Some(b"Server does not allow voting to kick players"),
-                    b"spectate" => {
-                        Some(b"Server does not allow voting to move players to spectators")
-                    }
-                    _ => None,
+                processed = true;
+                self.call_vote(pid, call_vote);
+            }
+            (&Ingame(..), SystemOrGame::Game(Game::ClVote(vote))) => {
+                processed = true;
+                let voted = match &mut self.server.vote {
+                    Some(v) if vote.vote != 0 => v.vote(pid, vote.vote > 0),
+                    _ => false,
                 };
-                if let Some(msg) = error {
+                if voted {
+                    self.send_vote_status();
+                    self.update_vote();
+                }
+            }
+            (&Ingame(..), SystemOrGame::Game(Game::ClSetTeam(set_team))) => {
+                processed = true;
+                let now = self.loop_.time();
+                let spam_protection = self.server.console.config.sv_spamprotection != 0;
+                if spam_protection
+                    && peer.last_set_team.map(|t| now < t + SET_TEAM_DELAY) == Some(true)
+                {
+                    return;
+                }
+                peer.last_set_team = Some(now);
+                if let Err(e) = self.set_team(pid, set_team.team) {
                     self.loop_.sendg(
                         pid,
-                        game::SvChat {
-                            team: false,
-                            client_id: -1,
-                            message: msg,
+                        game::SvBroadcast {
+                            message: e.as_bytes(),
                         },
                     );
-                    processed = true;
                 }
             }
-            (&Ingame(..), SystemOrGame::Game(Game::ClSetTeam(set_team))) => {
-                let ingame = peer.state.assert_ingame();
-                // TODO: Spam filter
-                let join_spectators = set_team.team == Team::Spectators;
-                if ingame.spectator == join_spectators {
+            _ => {}
+        }
+        if !processed {
+            warn!("unprocessed message {:?}", msg);
+        }
+    }
+    /// Handles the extended system messages of DDNet clients.
+    fn on_ddnet_packet(&mut self, pid: PeerId, vital: bool, data: &[u8]) {
+        use libtw2_gamenet_ddnet::msg::system as ddnet_system;
+        use libtw2_gamenet_ddnet::msg::System as DdnetSystem;
+
+        let msg =
+            match libtw2_gamenet_ddnet::msg::decode(&mut Warn(pid, data), &mut Unpacker::new(data))
+            {
+                Ok(SystemOrGame::System(m)) => m,
+                Ok(SystemOrGame::Game(_)) => unreachable!(),
+                Err(err) => {
+                    warn!("decode error {:?}:", err);
+                    hexdump(LogLevel::Warn, data);
                     return;
                 }
-                ingame.spectator = join_spectators;
-
-                let mut msg: ArrayString<[u8; 64]> = ArrayString::new();
-                if ingame.spectator {
-                    let idx = self
-                        .server
-                        .players
-                        .iter()
-                        .position(|p| p.pid == pid)
-                        .unwrap();
-                    self.server.players.swap_remove(idx);
-                    // Fix usage of AlmostString, sometimes it quotes.
-                    write!(
-                        &mut msg,
-                        "'{}' joined the spectators",
-                        AlmostString::new(&ingame.name)
-                    )
-                    .unwrap();
+            };
+        if !vital && !matches!(msg, DdnetSystem::PingEx(..)) {
+            warn!("non-vital message {:?}", msg);
+            return;
+        }
+        let peer = &mut self.server.peers[pid];
+        match (&peer.state, msg) {
+            (_, DdnetSystem::WhatIs(what_is)) => {
+                let uuid = what_is.uuid;
+                match ddnet::uuid_name(uuid) {
+                    Some(name) => self.loop_.sends_ddnet(
+                        pid,
+                        ddnet_system::ItIs {
+                            uuid,
+                            name: name.as_bytes(),
+                        },
+                    ),
+                    None => self
+                        .loop_
+                        .sends_ddnet(pid, ddnet_system::IDontKnow { uuid }),
+                }
+                self.loop_.flush(pid);
+            }
+            (_, DdnetSystem::ItIs(it_is)) => {
+                info!(
+                    "{} knows {} as {}",
+                    pid,
+                    it_is.uuid,
+                    AlmostString::new(it_is.name)
+                );
+            }
+            (_, DdnetSystem::IDontKnow(i_dont_know)) => {
+                info!("{} doesn't know {}", pid, i_dont_know.uuid);
+            }
+            (&PeerState::SystemInfo, DdnetSystem::ClientVersion(version)) => {
+                if peer.ddnet_version.is_some() {
+                    return;
+                }
+                info!(
+                    "{} uses DDNet {} ({})",
+                    pid,
+                    AlmostString::new(version.ddnet_version_string),
+                    version.ddnet_version,
+                );
+                peer.ddnet_version = Some(version.ddnet_version);
+            }
+            (_, DdnetSystem::PingEx(ping)) => {
+                let pong = ddnet_system::PongEx { id: ping.id };
+                if vital {
+                    self.loop_.sends_ddnet(pid, pong);
                 } else {
-                    self.server
-                        .players
-                        .push(Player::new(pid, self.server.map.spawn));
-                    write!(
-                        &mut msg,
-                        "'{}' joined the game",
-                        AlmostString::new(&ingame.name)
-                    )
-                    .unwrap();
+                    self.loop_.sends_ddnet_nonvital(pid, pong);
                 }
-                self.loop_.sendg(
+                self.loop_.flush(pid);
+            }
+            _ => warn!("unprocessed message {:?}", msg),
+        }
+    }
+    fn send_map(&mut self, pid: PeerId) {
+        let map = &self.server.map;
+        // Lets DDNet clients identify the map by its SHA256, other clients
+        // ignore this message.
+        self.loop_.sends_ddnet(
+            pid,
+            libtw2_gamenet_ddnet::msg::system::MapDetails {
+                name: map.name().as_bytes(),
+                sha256: map.sha256(),
+                crc: map.crc(),
+                size: Some(map.size()),
+                url: Some(b""),
+            },
+        );
+        self.loop_.sends(pid, map.map_change());
+        self.loop_.flush(pid);
+    }
+    /// Loads the map configured in `sv_map` if it isn't the current one or
+    /// `force` is set, and sends it to all clients.
+    fn update_map(&mut self, force: bool) -> Result<(), map::Error> {
+        let config = &mut self.server.console.config;
+        if !force && config.sv_map == self.server.map.name() {
+            return Ok(());
+        }
+        let map = match map_path(config).and_then(|p| Map::load(&p)) {
+            Ok(m) => m,
+            Err(e) => {
+                config.sv_map = self.server.map.name().to_owned();
+                return Err(e);
+            }
+        };
+        info!("changed map to {}", map.name());
+        self.server.map = map;
+        self.reset_game();
+        let pids = self.server.pids();
+        for pid in pids {
+            let peer = &mut self.server.peers[pid];
+            if let PeerState::SystemInfo = peer.state {
+                continue;
+            }
+            peer.state = PeerState::SystemReady;
+            self.send_map(pid);
+        }
+        Ok(())
+    }
+    /// Sends a chat message from the server to all clients in the game.
+    fn chat(&mut self, message: &[u8]) {
+        let chat = game::SvChat {
+            team: false,
+            client_id: -1,
+            message,
+        };
+        let pids = self.server.pids();
+        for pid in pids {
+            if let PeerState::Ingame(..) = self.server.peers[pid].state {
+                self.loop_.sendg(pid, chat);
+                self.loop_.flush(pid);
+            }
+        }
+        self.server.recorder.demo_msg(&chat.into());
+    }
+    /// Sends a chat message from the server to a single client.
+    fn chat_to(&mut self, pid: PeerId, message: &[u8]) {
+        self.loop_.sendg(
+            pid,
+            game::SvChat {
+                team: false,
+                client_id: -1,
+                message,
+            },
+        );
+        self.loop_.flush(pid);
+    }
+    /// Relays a chat message of a client to everyone, its team or a single
+    /// client for `/whisper`.
+    fn say(&mut self, pid: PeerId, team: bool, message: &[u8]) {
+        let message: Vec<u8> = message
+            .iter()
+            .map(|&b| if b < 0x20 { b' ' } else { b })
+            .collect();
+        let start = message.iter().position(|&b| b != b' ');
+        let end = message.iter().rposition(|&b| b != b' ');
+        let message = match (start, end) {
+            (Some(start), Some(end)) => &message[start..end + 1],
+            _ => return,
+        };
+        let name = String::from_utf8_lossy(self.server.peers[pid].state.net_name()).into_owned();
+        info!(
+            "{}:{} says{} {}",
+            pid,
+            name,
+            if team { " to team" } else { "" },
+            AlmostString::new(message)
+        );
+        for command in [&b"/whisper "[..], &b"/w "[..]].iter() {
+            if let Some(rest) = message.strip_prefix(*command) {
+                return self.whisper(pid, rest);
+            }
+        }
+        let server = &*self.server;
+        let chat = game::SvChat {
+            team,
+            client_id: server.cid(pid).0.assert_i32(),
+            message,
+        };
+        let team_of = |pid| server.controller.player(server.cid(pid)).map(|p| p.team);
+        let sender_team = team_of(pid);
+        for to in server.ingame_pids() {
+            if team && team_of(to) != sender_team {
+                continue;
+            }
+            self.loop_.sendg(to, chat);
+            self.loop_.flush(to);
+        }
+        if !team {
+            self.server.recorder.demo_msg(&chat.into());
+        }
+    }
+    /// Sends a private message, `message` starts with the name of the
+    /// recipient.
+    fn whisper(&mut self, pid: PeerId, message: &[u8]) {
+        // Prefer the longest name so that names containing spaces work.
+        let recipient = self
+            .server
+            .ingame_pids()
+            .into_iter()
+            .filter_map(|to| {
+                let name = self.server.peers[to].state.net_name();
+                let rest = message.strip_prefix(name)?;
+                let rest = rest.strip_prefix(b" ")?;
+                Some((name.len(), to, rest))
+            })
+            .max_by_key(|&(len, _, _)| len);
+        let (to, message) = match recipient {
+            Some((_, to, rest)) if !rest.is_empty() => (to, rest),
+            _ => return self.chat_to(pid, b"No player with this name online"),
+        };
+        let client_id = self.server.cid(pid).0.assert_i32();
+        let mut received = b"(whisper) ".to_vec();
+        received.extend_from_slice(message);
+        self.loop_.sendg(
+            to,
+            game::SvChat {
+                team: false,
+                client_id,
+                message: &received,
+            },
+        );
+        self.loop_.flush(to);
+        let name = String::from_utf8_lossy(self.server.peers[to].state.net_name());
+        let mut sent = format!("(whisper to '{}') ", name).into_bytes();
+        sent.extend_from_slice(message);
+        self.loop_.sendg(
+            pid,
+            game::SvChat {
+                team: false,
+                client_id,
+                message: &sent,
+            },
+        );
+        self.loop_.flush(pid);
+    }
+    /// Changes the name, clan and skin of a client.
+    fn change_info(&mut self, pid: PeerId, mut info: PeerInfo) {
+        info.name = self.server.unique_name(pid, &info.name);
+        let ingame = self.server.peers[pid].state.assert_ingame();
+        let old = mem::replace(&mut ingame.info, info);
+        if old.name != ingame.info.name {
+            let msg = format!(
+                "'{}' changed name to '{}'",
+                String::from_utf8_lossy(&old.name),
+                String::from_utf8_lossy(&ingame.info.name),
+            );
+            self.chat(msg.as_bytes());
+        }
+    }
+    /// Moves a client to another team and announces it, returns an error
+    /// message if it isn't allowed.
+    fn set_team(&mut self, pid: PeerId, team: Team) -> Result<(), &'static str> {
+        let server = &mut *self.server;
+        let game_tick = server.game_tick;
+        let cid = server.cid(pid);
+        let team = match server
+            .controller
+            .set_team(&mut server.world, cid, team, game_tick)?
+        {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+        let msg = format!(
+            "'{}' joined the {}",
+            String::from_utf8_lossy(self.server.peers[pid].state.net_name()),
+            self.server.controller.team_name(team),
+        );
+        self.chat(msg.as_bytes());
+        self.process_world_events();
+        Ok(())
+    }
+    /// Sends the vote options to a client that just sent its info.
+    fn send_vote_options(&mut self, pid: PeerId) {
+        self.loop_.sendg(pid, game::SvVoteClearOptions);
+        for option in &self.server.vote_options {
+            self.loop_.sendg(
+                pid,
+                game::SvVoteOptionAdd {
+                    description: option.description.as_bytes(),
+                },
+            );
+        }
+    }
+    /// Sends the current vote to a client that just entered the game.
+    fn send_vote(&mut self, pid: PeerId) {
+        let now = self.loop_.time();
+        let total = self.server.ingame_pids().len();
+        let vote = self.server.vote.as_ref().unwrap();
+        self.loop_.sendg(
+            pid,
+            game::SvVoteSet {
+                timeout: vote.timeout(now),
+                description: vote.description.as_bytes(),
+                reason: vote.reason.as_bytes(),
+            },
+        );
+        self.loop_.sendg(pid, vote.status(total));
+        self.loop_.flush(pid);
+    }
+    fn send_vote_status(&mut self) {
+        let pids = self.server.ingame_pids();
+        let status = self.server.vote.as_ref().unwrap().status(pids.len());
+        for pid in pids {
+            self.loop_.sendg(pid, status);
+            self.loop_.flush(pid);
+        }
+    }
+    /// Checks whether the client may call the vote, returns the description
+    /// and action of the vote or an error message.
+    fn check_vote(
+        &mut self,
+        pid: PeerId,
+        call_vote: &game::ClCallVote,
+    ) -> Result<(String, vote::Action, String), String> {
+        let now = self.loop_.time();
+        let server = &*self.server;
+        if server.vote.is_some() {
+            return Err("Wait for current vote to end before calling a new one.".into());
+        }
+        if let Some(last) = server.peers[pid].last_vote_call {
+            let end = last + VOTE_DELAY;
+            if now < end {
+                let usecs = end.as_usecs_since_epoch() - now.as_usecs_since_epoch();
+                let seconds = (usecs + 999_999) / 1_000_000;
+                return Err(format!(
+                    "You must wait {} seconds before making another vote",
+                    seconds
+                ));
+            }
+        }
+        let name = String::from_utf8_lossy(server.peers[pid].state.net_name());
+        let value = String::from_utf8_lossy(call_vote.value);
+        let target = || server.client_ids.parse(&value);
+        let config = &server.console.config;
+        match call_vote.type_ {
+            b"option" => {
+                let option = server
+                    .vote_options
+                    .iter()
+                    .find(|o| o.description == value)
+                    .ok_or_else(|| format!("'{}' isn't an option on this server", value))?;
+                let chat = format!(
+                    "'{}' called vote to change server option '{}'",
+                    name, option.description
+                );
+                let action = vote::Action::Command(option.command.clone());
+                Ok((option.description.clone(), action, chat))
+            }
+            b"kick" => {
+                if config.sv_vote_kick == 0 {
+                    return Err("Server does not allow voting to kick players".into());
+                }
+                let target = target().ok_or("Invalid client id to kick")?;
+                if target == pid {
+                    return Err("You can't kick yourself".into());
+                }
+                if server.peers[target].rcon_level.is_some() {
+                    return Err("You can't kick admins".into());
+                }
+                let target_name = String::from_utf8_lossy(server.peers[target].state.net_name());
+                let chat = format!("'{}' called for vote to kick '{}'", name, target_name);
+                let description = format!("kick '{}'", target_name);
+                Ok((description, vote::Action::Kick(target), chat))
+            }
+            b"spectate" => {
+                if config.sv_vote_spectate == 0 {
+                    return Err("Server does not allow voting to move players to spectators".into());
+                }
+                let target = target()
+                    .filter(|&t| server.is_player(t))
+                    .ok_or("Invalid client id to move")?;
+                if target == pid {
+                    return Err("You can't move yourself".into());
+                }
+                let target_name = String::from_utf8_lossy(server.peers[target].state.net_name());
+                let chat = format!(
+                    "'{}' called for vote to move '{}' to spectators",
+                    name, target_name
+                );
+                let description = format!("move '{}' to spectators", target_name);
+                Ok((description, vote::Action::Spectate(target), chat))
+            }
+            _ => Err("Unknown vote type".into()),
+        }
+    }
+    fn call_vote(&mut self, pid: PeerId, call_vote: game::ClCallVote) {
+        let (description, action, chat) = match self.check_vote(pid, &call_vote) {
+            Ok(v) => v,
+            Err(e) => return self.chat_to(pid, e.as_bytes()),
+        };
+        let now = self.loop_.time();
+        let reason = match String::from_utf8_lossy(call_vote.reason).trim() {
+            "" => "No reason given".to_owned(),
+            r => r.to_owned(),
+        };
+        info!("{} called vote '{}' ({})", pid, description, reason);
+        self.server.peers[pid].last_vote_call = Some(now);
+        self.chat(format!("{} ({})", chat, reason).as_bytes());
+        let vote = Vote::new(pid, description, reason, action, now);
+        self.server.vote = Some(vote);
+        for to in self.server.ingame_pids() {
+            self.send_vote(to);
+        }
+        self.update_vote();
+    }
+    /// Ends the current vote with a chat message.
+    fn end_vote(&mut self, message: &[u8]) -> Vote {
+        let vote = self.server.vote.take().unwrap();
+        let vote_set = game::SvVoteSet {
+            timeout: 0,
+            description: b"",
+            reason: b"",
+        };
+        for pid in self.server.ingame_pids() {
+            self.loop_.sendg(pid, vote_set);
+        }
+        self.chat(message);
+        vote
+    }
+    /// Ends the current vote if it passed or failed.
+    fn update_vote(&mut self) {
+        let now = self.loop_.time();
+        let total = self.server.ingame_pids().len();
+        let outcome = match self
+            .server
+            .vote
+            .as_ref()
+            .and_then(|v| v.outcome(total, now))
+        {
+            Some(o) => o,
+            None => return,
+        };
+        if outcome == Outcome::Fail {
+            self.end_vote(b"Vote failed");
+            return;
+        }
+        let vote = self.end_vote(b"Vote passed");
+        info!("vote '{}' passed", vote.description);
+        match vote.action {
+            vote::Action::Command(command) => {
+                self.execute(None, AccessLevel::Admin, &command);
+            }
+            // The target might have been kicked in the meantime.
+            vote::Action::Kick(target) if self.server.peers[target].disconnecting => {}
+            vote::Action::Kick(target) => {
+                let minutes = self.server.console.config.sv_vote_kick_bantime;
+                if minutes > 0 {
+                    let ip = self.loop_.peer_addr(target).ip;
+                    let duration = Duration::from_secs(minutes.assert_u32().u64() * 60);
+                    self.ban(ip, Some(duration), "Kicked by vote");
+                } else {
+                    self.disconnect(target, b"Kicked by vote");
+                }
+            }
+            vote::Action::Spectate(target) => {
+                let _ = self.set_team(target, Team::Spectators);
+            }
+        }
+    }
+    /// Disconnects the peer, it stays in `Server::peers` until
+    /// `on_disconnect` is called.
+    fn disconnect(&mut self, pid: PeerId, reason: &[u8]) {
+        let peer = &mut self.server.peers[pid];
+        if !mem::replace(&mut peer.disconnecting, true) {
+            self.loop_.disconnect(pid, reason);
+        }
+    }
+    /// Bans the address and disconnects the clients connected from it.
+    fn ban(&mut self, ip: IpAddr, duration: Option<Duration>, reason: &str) {
+        let now = self.loop_.time();
+        self.server.bans.ban(ip, duration, reason, now);
+        let message = self.server.bans.get(ip, now).unwrap().message(now);
+        let pids = self.server.pids();
+        for pid in pids {
+            if self.loop_.peer_addr(pid).ip == ip {
+                self.disconnect(pid, message.as_bytes());
+            }
+        }
+    }
+    /// Sends a console line to the rcon client `pid`, or logs it if the
+    /// command didn't come from a client.
+    fn output(&mut self, pid: Option<PeerId>, line: &str) {
+        match pid {
+            Some(pid) if self.server.peers[pid].disconnecting => {}
+            Some(pid) => self.loop_.sends(
+                pid,
+                system::RconLine {
+                    line: line.as_bytes(),
+                },
+            ),
+            None => info!("{}", line),
+        }
+    }
+    fn rcon_command(&mut self, pid: PeerId, level: AccessLevel, cmd: &[u8]) {
+        info!("{} rcon {}", pid, AlmostString::new(cmd));
+        self.execute(Some(pid), level, &String::from_utf8_lossy(cmd));
+        // The command might have kicked the client itself.
+        if !self.server.peers[pid].disconnecting {
+            self.loop_.flush(pid);
+        }
+    }
+    /// Executes a line of console commands, from a rcon client or from the
+    /// server itself if `from` is `None`.
+    fn execute(&mut self, from: Option<PeerId>, level: AccessLevel, cmd: &str) {
+        let loop_ = &mut *self.loop_;
+        let calls = self
+            .server
+            .console
+            .execute(level, cmd, &mut |line| match from {
+                Some(pid) => loop_.sends(
+                    pid,
+                    system::RconLine {
+                        line: line.as_bytes(),
+                    },
+                ),
+                None => info!("{}", line),
+            });
+        let server = &mut *self.server;
+        let cid = from.map(|pid| server.cid(pid));
+        for executed in server.console.drain_executed() {
+            let args = &executed.args;
+            server.recorder.console_command(cid, executed.name, args);
+        }
+        for call in calls {
+            self.server_command(from, call);
+        }
+        if let Err(e) = self.update_map(false) {
+            self.output(from, &format!("Failed to change map: {}", e));
+        }
+    }
+    fn server_command(&mut self, from: Option<PeerId>, call: Call) {
+        match call.command {
+            ServerCommand::AddVote | ServerCommand::RemoveVote | ServerCommand::ClearVotes => {
+                let output = self.server.vote_options_command(&call);
+                self.output(from, &output);
+                let pids = self.server.pids();
+                for pid in pids {
+                    if self.server.peers[pid].state.has_name() {
+                        self.send_vote_options(pid);
+                        self.loop_.flush(pid);
+                    }
+                }
+            }
+            ServerCommand::Ban => {
+                let target = call.str(0).unwrap();
+                let ip = match target.parse::<u32>() {
+                    Ok(id) => match self.server.client_ids.parse(target) {
+                        Some(pid) => self.loop_.peer_addr(pid).ip,
+                        None => return self.output(from, &format!("Invalid client id {}", id)),
+                    },
+                    Err(_) => match target.parse::<IpAddr>() {
+                        Ok(ip) => ip,
+                        Err(_) => return self.output(from, "Invalid network address"),
+                    },
+                };
+                let minutes = call.int(1).unwrap_or(DEFAULT_BAN_MINUTES);
+                let reason = call.str(2).unwrap_or("No reason given");
+                let duration = match minutes {
+                    m if m < 0 => return self.output(from, "Invalid ban time"),
+                    0 => None,
+                    m => Some(Duration::from_secs(m.assert_u32().u64() * 60)),
+                };
+                let output = match duration {
+                    Some(_) => format!("Banned {} for {} minutes ({})", ip, minutes, reason),
+                    None => format!("Banned {} ({})", ip, reason),
+                };
+                // Answer first, the ban might disconnect the admin.
+                self.output(from, &output);
+                self.ban(ip, duration, reason);
+            }
+            ServerCommand::Unban => {
+                let target = call.str(0).unwrap();
+                let now = self.loop_.time();
+                let bans = &mut self.server.bans;
+                let ban = match (target.parse::<IpAddr>(), target.parse::<usize>()) {
+                    (Ok(ip), _) => bans.unban(ip),
+                    (_, Ok(index)) => bans.unban_index(index, now),
+                    _ => return self.output(from, "Invalid network address or entry"),
+                };
+                match ban {
+                    Some(ban) => self.output(from, &format!("Unbanned {}", ban)),
+                    None => self.output(from, &format!("No ban for {}", target)),
+                }
+            }
+            ServerCommand::Bans => {
+                let now = self.loop_.time();
+                let lines: Vec<String> = self
+                    .server
+                    .bans
+                    .list(now)
+                    .iter()
+                    .enumerate()
+                    .map(|(i, ban)| match ban.minutes_left(now) {
+                        Some(m) => format!("#{} {}, {} minutes left", i, ban, m),
+                        None => format!("#{} {}, permanent", i, ban),
+                    })
+                    .collect();
+                let count = lines.len();
+                for line in lines {
+                    self.output(from, &line);
+                }
+                self.output(from, &format!("{} ban(s)", count));
+            }
+            ServerCommand::Kick => {
+                let id = call.int(0).unwrap();
+                let reason = call.str(1).unwrap_or("Kicked by console");
+                let pid = id
+                    .try_u32()
+                    .and_then(|id| self.server.client_ids.pid(CharacterId(id)));
+                match pid {
+                    Some(pid) => self.disconnect(pid, reason.as_bytes()),
+                    None => self.output(from, &format!("Invalid client id {}", id)),
+                }
+            }
+            ServerCommand::Logout => {
+                let pid = match from {
+                    Some(pid) => pid,
+                    None => return self.output(from, "Not logged in"),
+                };
+                let peer = &mut self.server.peers[pid];
+                if peer.disconnecting {
+                    return;
+                }
+                let level = match peer.rcon_level.take() {
+                    Some(level) => level,
+                    None => return self.output(from, "Not logged in"),
+                };
+                if mem::replace(&mut peer.rcon_commands, false) {
+                    for command in Console::commands(level) {
+                        self.loop_.sends(
+                            pid,
+                            system::RconCmdRemove {
+                                name: command.name.as_bytes(),
+                            },
+                        );
+                    }
+                }
+                self.loop_.sends(
                     pid,
-                    game::SvChat {
-                        team: false,
-                        client_id: -1,
-                        message: msg.as_bytes(),
+                    system::RconAuthStatus {
+                        auth_level: Some(0),
+                        receive_commands: Some(0),
                     },
                 );
+                self.output(from, "Logout successful.");
+            }
+            ServerCommand::Reload => {
+                if let Err(e) = self.update_map(true) {
+                    self.output(from, &format!("Failed to reload map: {}", e));
+                }
+            }
+            ServerCommand::Restart => match call.int(0) {
+                Some(seconds) => self.server.controller.do_warmup(seconds),
+                None => self.start_round(),
+            },
+            ServerCommand::Say => self.chat(call.str(0).unwrap().as_bytes()),
+            ServerCommand::Vote => {
+                let outcome = match call.str(0).unwrap() {
+                    "yes" => Outcome::Pass,
+                    "no" => Outcome::Fail,
+                    _ => return self.output(from, "Invalid vote, use 'yes' or 'no'"),
+                };
+                match &mut self.server.vote {
+                    Some(vote) => vote.force(outcome),
+                    None => return self.output(from, "No vote in progress"),
+                }
+                self.update_vote();
+            }
+            ServerCommand::Status => {
+                let server = &*self.server;
+                let lines: Vec<String> = server
+                    .peers
+                    .iter()
+                    .map(|(pid, peer)| {
+                        format!(
+                            "id={} name={} player={}",
+                            server.cid(pid).0,
+                            AlmostString::new(peer.state.net_name()),
+                            server.is_player(pid),
+                        )
+                    })
+                    .collect();
+                for line in lines {
+                    self.output(from, &line);
+                }
             }
-            _ => {}
-        }
-        if !processed {
-            warn!("unprocessed message {:?}", msg);
         }
     }
-    fn on_connless_packet(&mut self, addr: Addr, data: &[u8]) {
+    fn on_connless_packet(&mut self, addr: Addr, extra_data: Option<[u8; 4]>, data: &[u8]) {
+        if serverinfo::is_register_packet(data) {
+            if let Some(register) = &self.server.register {
+                serverinfo::register_on_packet(register, data);
+            }
+            return;
+        }
+        if let Some(request) = Request::parse(extra_data, data) {
+            for packet in self.server.info().encode(request) {
+                self.loop_.send_connless(addr, &packet);
+            }
+            return;
+        }
         let msg = match Connless::decode(&mut Warn(addr, data), &mut Unpacker::new(data)) {
             Ok(m) => m,
             Err(err) => {
@@ -586,60 +1699,31 @@ impl<'a, L: Loop> ServerLoop<'a, L> {
                 return;
             }
         };
-        let mut processed = false;
-        match msg {
-            Connless::RequestInfo(request) => {
-                processed = true;
-                let mut clients_buf: ArrayVec<[u8; 1024]> = Default::default();
-                for (_, peer) in &self.server.peers {
-                    with_packer(&mut clients_buf, |p| {
-                        connless::Client {
-                            name: peer.state.net_name(),
-                            clan: b"",
-                            country: -1,
-                            score: 0,
-                            is_player: peer.state.net_is_player(),
-                        }
-                        .encode(p)
-                        .unwrap()
-                    });
-                }
-                // TODO: Send clients. :)
-                self.loop_.sendc(
-                    addr,
-                    connless::Info {
-                        token: request.token.i32(),
-                        version: VERSION.as_bytes(),
-                        name: b"Rust Teeworlds Server",
-                        game_type: b"DM",
-                        map: b"dm1",
-                        flags: connless::INFO_FLAG_PASSWORD,
-                        num_players: self.server.players.len().assert_i32(),
-                        max_players: MAX_CLIENTS,
-                        num_clients: self.server.peers.len().assert_i32(),
-                        max_clients: MAX_CLIENTS,
-                        clients: msg::ClientsData::from_bytes(&clients_buf),
-                    },
-                );
-            }
-            _ => {}
-        }
-        if !processed {
-            warn!("unprocessed message {:?}", msg);
-        }
+        warn!("unprocessed message {:?}", msg);
     }
     fn on_connect(&mut self, pid: PeerId) {
-        if self.server.peers.is_empty() {
-            self.server.game_start = self.loop_.time();
-            self.server.game_tick = 0;
-        }
-        if self.server.peers.len() == MAX_CLIENTS.assert_usize() {
-            self.loop_.reject(pid, b"This server is full");
+        let now = self.loop_.time();
+        let ip = self.loop_.peer_addr(pid).ip;
+        if let Some(ban) = self.server.bans.get(ip, now) {
+            let message = ban.message(now);
+            info!("{} rejected, {} is banned", pid, ip);
+            self.loop_.reject(pid, message.as_bytes());
             return;
         }
+        if self.server.peers.is_empty() {
+            self.reset_game();
+        }
+        let max_clients = self.server.console.config.sv_max_clients.assert_usize();
+        let cid = match self.server.client_ids.assign(pid, max_clients) {
+            Some(cid) => cid,
+            None => {
+                self.loop_.reject(pid, b"This server is full");
+                return;
+            }
+        };
         self.loop_.accept(pid);
         self.server.peers.insert(pid, Peer::default());
-        info!("{} starting to connect", pid);
+        info!("{} starting to connect with client id {}", pid, cid.0);
     }
     fn on_disconnect(&mut self, pid: PeerId, remote: bool, reason: &[u8]) {
         let _ = remote;
@@ -648,68 +1732,176 @@ impl<'a, L: Loop> ServerLoop<'a, L> {
         } else {
             info!("{} leaves the game", pid);
         }
+        let cid = self.server.cid(pid);
+        if let PeerState::Ingame(..) = self.server.peers[pid].state {
+            self.server.recorder.drop(cid, reason);
+        }
         self.server.peers.remove(pid);
-        for i in 0..self.server.players.len() {
-            if self.server.players[i].pid == pid {
-                self.server.players.swap_remove(i);
-                break;
+        if self.server.peers.is_empty() {
+            self.server.recorder.stop();
+        }
+        let server = &mut *self.server;
+        let game_tick = server.game_tick;
+        let left = server
+            .controller
+            .set_team(&mut server.world, cid, Team::Spectators, game_tick);
+        assert!(left.is_ok());
+        self.process_world_events();
+        self.server.client_ids.free(pid);
+        if let Some(vote) = &mut self.server.vote {
+            vote.remove(pid);
+            let target = match vote.action {
+                vote::Action::Kick(t) | vote::Action::Spectate(t) => Some(t),
+                vote::Action::Command(_) => None,
+            };
+            if target == Some(pid) {
+                self.end_vote(b"Vote aborted");
+            } else {
+                self.send_vote_status();
+                self.update_vote();
             }
         }
     }
-    fn game_tick(&mut self) {
-        use libtw2_world::Character;
-        use libtw2_world::CharacterId;
-        struct OtherCharacters<'a> {
-            own_cid: CharacterId,
-            players: &'a [Player],
+    /// Restarts the game on the current map, without any players.
+    fn reset_game(&mut self) {
+        // Like in Teeworlds, votes don't survive map changes. The players
+        // aren't ingame afterwards, the vote would pass without them.
+        if self.server.vote.is_some() {
+            self.end_vote(b"Vote aborted");
         }
-        impl<'a> libtw2_world::OtherCharacters for OtherCharacters<'a> {
-            type Iter = CharacterId;
-            fn is_self(&self, cid: CharacterId) -> bool {
-                cid == self.own_cid
-            }
-            fn get(&self, cid: CharacterId) -> Character {
-                assert!(!self.is_self(cid));
-                self.players[cid.0.usize()].character.get()
-            }
-            fn modify<F: FnOnce(&mut Character)>(&self, cid: CharacterId, f: F) {
-                let mut character = self.get(cid);
-                f(&mut character);
-                self.players[cid.0.usize()].character.set(character);
-            }
-            fn iter(&self) -> CharacterId {
-                CharacterId(0)
+        self.server.game_start = self.loop_.time();
+        self.server.game_tick = 0;
+        self.server.world = new_world(&self.server.map);
+        self.server.clear_events();
+        self.server.controller = new_controller(&self.server.console.config, &self.server.map, 0);
+        self.server.recorder.restart();
+    }
+    /// Starts a new round in a fresh world, keeping the players.
+    fn start_round(&mut self) {
+        let server = &mut *self.server;
+        server.controller.start_round(server.game_tick);
+        server.world = new_world(&server.map);
+        server.clear_events();
+        server.recorder.restart();
+    }
+    /// Sends the game messages for the events that happened in the world
+    /// and keeps the others for the next snapshot.
+    fn process_world_events(&mut self) {
+        let events: Vec<Event> = self.server.world.drain_events().collect();
+        for &event in &events {
+            match event {
+                Event::Kill {
+                    killer,
+                    victim,
+                    weapon,
+                } => {
+                    let game_tick = self.server.game_tick;
+                    let mode_special = self
+                        .server
+                        .controller
+                        .on_kill(game_tick, killer, victim, weapon);
+                    let kill_msg = game::SvKillMsg {
+                        killer: killer.0.assert_i32(),
+                        victim: victim.0.assert_i32(),
+                        weapon,
+                        mode_special,
+                    };
+                    let pids = self.server.pids();
+                    for pid in pids {
+                        if let PeerState::Ingame(..) = self.server.peers[pid].state {
+                            self.loop_.sendg(pid, kill_msg);
+                        }
+                    }
+                    self.server.recorder.demo_msg(&kill_msg.into());
+                }
+                Event::WeaponPickup { cid, weapon } => {
+                    if let Some(pid) = self.server.client_ids.pid(cid) {
+                        self.loop_.sendg(pid, game::SvWeaponPickup { weapon });
+                    }
+                }
+                _ => {}
             }
-            fn next(&self, iter: &mut CharacterId) -> Option<(CharacterId, Character)> {
-                if *iter == self.own_cid {
-                    *iter = CharacterId(iter.0 + 1);
+        }
+        self.server.events.extend(events);
+    }
+    /// Sends the sounds and chat messages for the CTF flag events.
+    fn process_flag_events(&mut self) {
+        let events: Vec<FlagEvent> = self.server.controller.drain_events().collect();
+        for event in events {
+            let mut grab_team = None;
+            let sound = match event {
+                FlagEvent::Grab { team, .. } => {
+                    grab_team = Some(team);
+                    Sound::CtfGrabPl
                 }
-                let cur = *iter;
-                if cur.0.usize() == self.players.len() {
-                    return None;
+                FlagEvent::Capture { team, cid, ticks } => {
+                    let pid = self.server.client_ids.pid(cid).unwrap();
+                    let name = AlmostString::new(self.server.peers[pid].state.net_name());
+                    let team = if team == Team::Red { "red" } else { "blue" };
+                    let mut msg = format!("The {} flag was captured by '{}'", team, name);
+                    if ticks <= TICKS_PER_SECOND * 60 {
+                        let hundredths = ticks * 100 / TICKS_PER_SECOND;
+                        write!(
+                            msg,
+                            " ({}.{:02} seconds)",
+                            hundredths / 100,
+                            hundredths % 100
+                        )
+                        .unwrap();
+                    }
+                    self.chat(msg.as_bytes());
+                    Sound::CtfCapture
+                }
+                FlagEvent::Drop { .. } => Sound::CtfDrop,
+                FlagEvent::Return { .. } => Sound::CtfReturn,
+            };
+            let pids = self.server.pids();
+            for pid in pids {
+                if let PeerState::Ingame(..) = self.server.peers[pid].state {
+                    let cid = self.server.cid(pid);
+                    let team = self.server.controller.player(cid).map(|p| p.team);
+                    // The team whose flag was taken hears a different sound.
+                    let sound_id = if grab_team.is_some() && team == grab_team {
+                        Sound::CtfGrabEn
+                    } else {
+                        sound
+                    };
+                    self.loop_.sendg(pid, game::SvSoundGlobal { sound_id });
                 }
-                *iter = CharacterId(iter.0 + 1);
-                Some((cur, self.get(cur)))
             }
+            let sound_global = game::SvSoundGlobal { sound_id: sound };
+            self.server.recorder.demo_msg(&sound_global.into());
         }
+    }
+    fn game_tick(&mut self) {
+        let server = &mut *self.server;
+        if !server.controller.is_paused() {
+            server.world.tick(&mut server.map);
+        }
+        self.process_world_events();
 
-        for (cid, p) in self.server.players.iter().enumerate() {
-            let input = self.server.peers[p.pid].state.assert_ingame().input;
-            let other = &mut OtherCharacters {
-                own_cid: CharacterId(cid.assert_u32()),
-                players: &self.server.players,
-            };
-            let mut character = p.character.get();
-            character.tick(&mut self.server.map, other, input, &SV_TUNE_PARAMS_DEFAULT);
-            character.move_(&mut self.server.map, other, &SV_TUNE_PARAMS_DEFAULT);
-            character.quantize();
-            p.character.set(character);
+        let server = &mut *self.server;
+        let start_round = server.controller.tick(
+            &server.console.config,
+            &mut server.world,
+            &mut server.map,
+            server.game_tick,
+        );
+        if start_round {
+            self.start_round();
+        }
+        self.process_world_events();
+        self.process_flag_events();
+        self.update_vote();
+
+        if self.server.game_tick % TICKS_PER_SECOND == 0 {
+            self.server.update_register();
         }
     }
     fn send_snapshots(&mut self) {
         let mut peer_set = self.server.send_snapshots_peer_set.take();
         peer_set.clear();
-        peer_set.extend(self.server.peers.keys());
+        peer_set.extend(self.server.pids());
         for snap_pid in &peer_set {
             let mut builder;
             let delta_tick;
@@ -719,68 +1911,11 @@ impl<'a, L: Loop> ServerLoop<'a, L> {
             } else {
                 continue;
             }
-            builder.add(
-                0,
-                GameInfo {
-                    game_flags: 0,
-                    game_state_flags: 0,
-                    round_start_tick: Tick(0),
-                    warmup_timer: 0,
-                    score_limit: 20,
-                    time_limit: 0,
-                    round_num: 1,
-                    round_current: 1,
-                },
-            );
-            for (pid, peer) in self.server.peers.iter() {
-                if let PeerState::Ingame(ref ingame) = peer.state {
-                    // TODO: Fix ID!
-                    builder.add(
-                        pid.0.assert_u16(),
-                        ClientInfo {
-                            name: string_to_ints4(&ingame.name),
-                            clan: string_to_ints3(b""),
-                            country: -1,
-                            skin: string_to_ints6(b"default"),
-                            use_custom_color: 0,
-                            color_body: 0,
-                            color_feet: 0,
-                        },
-                    );
-                    builder.add(
-                        pid.0.assert_u16(),
-                        PlayerInfo {
-                            local: (snap_pid == pid) as i32,
-                            client_id: pid.0.assert_i32(),
-                            team: if ingame.spectator {
-                                Team::Spectators
-                            } else {
-                                Team::Red
-                            },
-                            score: 0,
-                            latency: 20,
-                        },
-                    );
-                }
-            }
-            for player in &self.server.players {
-                builder.add(
-                    player.pid.0.assert_u16(),
-                    Character {
-                        character_core: player.character.get().to_net(),
-                        player_flags: snap_obj::PLAYERFLAG_PLAYING,
-                        health: 10,
-                        armor: 0,
-                        ammo_count: 10,
-                        weapon: Weapon::Pistol,
-                        emote: Emote::Normal,
-                        attack_tick: 0,
-                    },
-                );
-            }
+            let game_tick = self.server.game_tick.assert_i32();
+            self.server
+                .snap(&mut builder, Some(snap_pid), &self.server.events);
             let snap = builder.finish();
             let crc = snap.crc();
-            let game_tick = self.server.game_tick.assert_i32();
             let delta = self.server.peers[snap_pid]
                 .state
                 .assert_ingame()
@@ -797,11 +1932,28 @@ impl<'a, L: Loop> ServerLoop<'a, L> {
             }
         }
         self.server.send_snapshots_peer_set.restore(peer_set);
+        self.server.clear_events();
+    }
+    /// Records the character positions in the teehistorian file and the
+    /// snapshot in the demo.
+    fn record_tick(&mut self) {
+        let server = &mut *self.server;
+        let game_tick = server.game_tick;
+        let recorder = &mut server.recorder;
+        recorder.begin_tick(&server.console.config, &server.map, game_tick);
+        recorder.players(server.world.characters().map(|(cid, c)| (cid, c.pos())));
+        if recorder.is_recording_demo() {
+            let mut objects = Vec::new();
+            server.snap(&mut objects, None, &server.events[server.demo_events..]);
+            server.recorder.demo_snap(game_tick, &objects);
+        }
+        server.demo_events = server.events.len();
     }
     fn tick(&mut self) {
         while self.server.game_tick_time(self.server.game_tick + 1) <= self.loop_.time() {
             self.server.game_tick += 1;
             self.game_tick();
+            self.record_tick();
             if self.server.game_tick % 2 == 0 {
                 self.send_snapshots();
             }
@@ -811,5 +1963,224 @@ impl<'a, L: Loop> ServerLoop<'a, L> {
 
 fn main() {
     libtw2_logger::init();
-    Server::run::<SocketLoop>();
+
+    let matches = App::new("Teeworlds server")
+        .about("Proof-of-concept Teeworlds 0.6 server.")
+        .arg(
+            Arg::with_name("config")
+                .help(
+                    "Executes the config file before starting the server, \
+                     defaults to autoexec_server.cfg if it exists",
+                )
+                .short("f")
+                .long("config")
+                .takes_value(true)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("COMMAND")
+                .help("Console commands to execute after the config file")
+                .multiple(true),
+        )
+        .get_matches();
+
+    let mut console = Console::new();
+    let mut output = |line: &str| info!("{}", line);
+    let mut calls = Vec::new();
+    match matches.value_of("config") {
+        Some(path) => calls.extend(console.execute_file(AccessLevel::Admin, path, &mut output)),
+        None => {
+            if Path::new(DEFAULT_CONFIG).exists() {
+                calls.extend(console.execute_file(AccessLevel::Admin, DEFAULT_CONFIG, &mut output));
+            }
+        }
+    }
+    for command in matches.values_of("COMMAND").unwrap_or_default() {
+        calls.extend(console.execute(AccessLevel::Admin, command, &mut output));
+    }
+
+    let map = match map_path(&console.config).and_then(|p| Map::load(&p)) {
+        Ok(m) => m,
+        Err(err) => {
+            eprintln!("{}: {}", console.config.sv_map, err);
+            process::exit(1);
+        }
+    };
+    Server::run::<SocketLoop>(console, map, calls);
+}
+
+#[cfg(test)]
+mod test {
+    use super::Server;
+    use crate::console::AccessLevel;
+    use crate::console::Console;
+    use crate::map::Map;
+    use crate::vote::Action;
+    use crate::vote::Vote;
+    use libtw2_event_loop::collections::PeerSet;
+    use libtw2_event_loop::Addr;
+    use libtw2_event_loop::Application;
+    use libtw2_event_loop::Chunk;
+    use libtw2_event_loop::Loop;
+    use libtw2_event_loop::PeerId;
+    use libtw2_event_loop::ServerLoop;
+    use libtw2_event_loop::Timestamp;
+    use libtw2_gamenet::msg;
+    use libtw2_gamenet::msg::System;
+    use libtw2_gamenet::msg::SystemOrGame;
+    use libtw2_packer::Unpacker;
+    use libtw2_warn::Panic;
+    use std::path::Path;
+
+    /// Event loop that panics like `SocketLoop` when something is sent to a
+    /// peer that isn't connected.
+    #[derive(Default)]
+    struct MockLoop {
+        connected: PeerSet,
+        disconnected: Vec<PeerId>,
+        rcon_lines: Vec<(PeerId, String)>,
+    }
+
+    impl MockLoop {
+        fn assert_connected(&mut self, pid: PeerId) {
+            assert!(self.connected.contains(pid), "invalid pid {}", pid);
+        }
+    }
+
+    impl Loop for MockLoop {
+        fn client() -> MockLoop {
+            unimplemented!();
+        }
+        fn run<A: Application<MockLoop>>(self, _: A) {
+            unimplemented!();
+        }
+        fn time(&mut self) -> Timestamp {
+            Timestamp::from_secs_since_epoch(0)
+        }
+        fn connect(&mut self, _: Addr) -> PeerId {
+            unimplemented!();
+        }
+        fn peer_addr(&mut self, pid: PeerId) -> Addr {
+            // Also called for connecting peers.
+            assert!(!self.disconnected.contains(&pid), "invalid pid {}", pid);
+            "192.0.2.1:8303".parse().unwrap()
+        }
+        fn disconnect(&mut self, pid: PeerId, _: &[u8]) {
+            self.assert_connected(pid);
+            self.connected.remove(pid);
+            self.disconnected.push(pid);
+        }
+        fn send_connless(&mut self, _: Addr, _: &[u8]) {
+            unimplemented!();
+        }
+        fn send(&mut self, chunk: Chunk) {
+            self.assert_connected(chunk.pid);
+            let msg = msg::decode(&mut Panic, &mut Unpacker::new(chunk.data)).unwrap();
+            if let SystemOrGame::System(System::RconLine(l)) = msg {
+                let line = String::from_utf8(l.line.to_owned()).unwrap();
+                self.rcon_lines.push((chunk.pid, line));
+            }
+        }
+        fn force_flush(&mut self, pid: PeerId) {
+            self.assert_connected(pid);
+        }
+        fn flush(&mut self, pid: PeerId) {
+            self.assert_connected(pid);
+        }
+    }
+
+    impl ServerLoop for MockLoop {
+        fn accept_connections_on_port(_: u16) -> MockLoop {
+            unimplemented!();
+        }
+        fn ignore(&mut self, _: PeerId) {
+            unimplemented!();
+        }
+        fn accept(&mut self, pid: PeerId) {
+            self.connected.insert(pid);
+        }
+        fn reject(&mut self, _: PeerId, _: &[u8]) {
+            unimplemented!();
+        }
+    }
+
+    /// Server with two connected clients.
+    fn server() -> (Server, MockLoop) {
+        let mut console = Console::new();
+        console.config.sv_maps_directory = env!("CARGO_MANIFEST_DIR").into();
+        let map = Map::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("dm1.map")).unwrap();
+        let mut server = Server::new(console, map);
+        let mut loop_ = MockLoop::default();
+        for i in 0..2 {
+            server.loop_(&mut loop_).on_connect(PeerId(i));
+        }
+        (server, loop_)
+    }
+
+    fn rcon(server: &mut Server, loop_: &mut MockLoop, pid: PeerId, cmd: &str) {
+        server.peers[pid].rcon_level = Some(AccessLevel::Admin);
+        server
+            .loop_(loop_)
+            .rcon_command(pid, AccessLevel::Admin, cmd.as_bytes());
+    }
+
+    #[test]
+    fn kick_self() {
+        let (mut server, mut loop_) = server();
+        rcon(
+            &mut server,
+            &mut loop_,
+            PeerId(0),
+            "kick 0; say bye; status",
+        );
+        rcon(&mut server, &mut loop_, PeerId(1), "kick 0; kick 1");
+        assert_eq!(loop_.disconnected, [PeerId(0), PeerId(1)]);
+        assert!(loop_.rcon_lines.is_empty());
+        for i in 0..2 {
+            server
+                .loop_(&mut loop_)
+                .on_disconnect(PeerId(i), false, b"Kicked by console");
+        }
+        assert!(server.peers.is_empty());
+    }
+
+    #[test]
+    fn logout_twice() {
+        let (mut server, mut loop_) = server();
+        rcon(&mut server, &mut loop_, PeerId(1), "logout; logout");
+        assert_eq!(
+            loop_.rcon_lines,
+            [
+                (PeerId(1), "Logout successful.".to_owned()),
+                (PeerId(1), "Not logged in".to_owned()),
+            ]
+        );
+        assert!(server.peers[PeerId(1)].rcon_level.is_none());
+    }
+
+    #[test]
+    fn ban_self() {
+        let (mut server, mut loop_) = server();
+        rcon(&mut server, &mut loop_, PeerId(0), "ban 0 5; status");
+        assert_eq!(loop_.disconnected, [PeerId(0), PeerId(1)]);
+        assert_eq!(
+            loop_.rcon_lines,
+            [(
+                PeerId(0),
+                "Banned 192.0.2.1 for 5 minutes (No reason given)".to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn vote_map_change() {
+        let (mut server, mut loop_) = server();
+        let now = loop_.time();
+        let kick = Action::Kick(PeerId(1));
+        server.vote = Some(Vote::new(PeerId(0), "kick".into(), "".into(), kick, now));
+        rcon(&mut server, &mut loop_, PeerId(0), "reload");
+        assert!(server.vote.is_none());
+        server.loop_(&mut loop_).update_vote();
+        assert!(loop_.disconnected.is_empty());
+    }
 }
@@ -0,0 +1,346 @@
+use libtw2_common::digest::Sha256;
+use libtw2_common::num::Cast;
+use libtw2_common::num::CastFloat;
+use libtw2_common::unwrap_or_return;
+use libtw2_gamenet::enums::Team;
+use libtw2_gamenet::enums::Weapon;
+use libtw2_gamenet::msg::system;
+use libtw2_map::format::Tile;
+use libtw2_world::vec2;
+use libtw2_world::CollisionType;
+use libtw2_world::PickupKind;
+use log::LogLevel;
+use ndarray::Array2;
+use sha2::Digest;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const MAPDOWNLOAD_CHUNK_SIZE: u64 = 1024 - 128;
+
+// Tile indices in the game layer.
+const TILE_SOLID: u8 = 1;
+const TILE_DEATH: u8 = 2;
+const TILE_NOHOOK: u8 = 3;
+const ENTITY_OFFSET: u8 = 255 - 16 * 4;
+const ENTITY_SPAWN: u8 = ENTITY_OFFSET + 1;
+const ENTITY_SPAWN_RED: u8 = ENTITY_OFFSET + 2;
+const ENTITY_SPAWN_BLUE: u8 = ENTITY_OFFSET + 3;
+const ENTITY_FLAGSTAND_RED: u8 = ENTITY_OFFSET + 4;
+const ENTITY_FLAGSTAND_BLUE: u8 = ENTITY_OFFSET + 5;
+const ENTITY_ARMOR_1: u8 = ENTITY_OFFSET + 6;
+const ENTITY_HEALTH_1: u8 = ENTITY_OFFSET + 7;
+const ENTITY_WEAPON_SHOTGUN: u8 = ENTITY_OFFSET + 8;
+const ENTITY_WEAPON_GRENADE: u8 = ENTITY_OFFSET + 9;
+const ENTITY_POWERUP_NINJA: u8 = ENTITY_OFFSET + 10;
+const ENTITY_WEAPON_RIFLE: u8 = ENTITY_OFFSET + 11;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Map(libtw2_map::Error),
+    /// The file name cannot be sent to clients as a map name.
+    InvalidName,
+    NoSpawnPoints,
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<libtw2_map::Error> for Error {
+    fn from(err: libtw2_map::Error) -> Error {
+        Error::Map(err)
+    }
+}
+
+impl From<libtw2_map::format::Error> for Error {
+    fn from(err: libtw2_map::format::Error) -> Error {
+        Error::Map(err.into())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => e.fmt(f),
+            Error::Map(ref e) => write!(f, "invalid map: {:?}", e),
+            Error::InvalidName => f.write_str("invalid map name"),
+            Error::NoSpawnPoints => f.write_str("map has no spawn points"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Checks whether a map name can be used as a file name in the maps
+/// directory.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains(&['/', '\\', '\0'][..])
+}
+
+/// Map served to the clients, along with what the game needs to know about
+/// it.
+pub struct Map {
+    name: String,
+    crc: i32,
+    sha256: Sha256,
+    // TODO: Implement an actual memory map. Is that possible in a safe way?
+    contents: Vec<u8>,
+    spawns: [Vec<vec2>; 3],
+    pickups: Vec<(PickupKind, vec2)>,
+    /// Flag stands of the red and the blue team.
+    flag_stands: [Option<vec2>; 2],
+    /// Game layer tiles, without entities.
+    tiles: Array2<u8>,
+}
+
+impl Map {
+    /// Loads a map, its name is derived from the file name.
+    pub fn load(path: &Path) -> Result<Map, Error> {
+        let name = path
+            .file_stem()
+            .and_then(|n| n.to_str())
+            .filter(|n| is_valid_name(n))
+            .ok_or(Error::InvalidName)?
+            .to_owned();
+        let contents = fs::read(path)?;
+        let crc = crc32fast::hash(&contents) as i32;
+        let sha256 = Sha256(sha2::Sha256::digest(&contents).into());
+
+        let mut map = libtw2_map::Reader::open(path)?;
+        map.check_version()?;
+        let game_layers = map.game_layers()?;
+        let tiles = map.layer_tiles(game_layers.game())?;
+        let (spawns, pickups, flag_stands) = entities(&tiles);
+        let result = Map {
+            name,
+            crc,
+            sha256,
+            contents,
+            spawns,
+            pickups,
+            flag_stands,
+            tiles: tiles.mapv(|t| match t.index {
+                TILE_SOLID | TILE_DEATH | TILE_NOHOOK => t.index,
+                _ => 0,
+            }),
+        };
+        if result.spawns.iter().all(|s| s.is_empty()) {
+            return Err(Error::NoSpawnPoints);
+        }
+        if log_enabled!(LogLevel::Debug) {
+            for row in result.tiles.outer_iter() {
+                let line: String = row
+                    .iter()
+                    .map(|&t| match t {
+                        TILE_SOLID => '#',
+                        TILE_DEATH => 'x',
+                        TILE_NOHOOK => '!',
+                        _ => ' ',
+                    })
+                    .collect();
+                debug!("{}", line);
+            }
+        }
+        Ok(result)
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn crc(&self) -> i32 {
+        self.crc
+    }
+    pub fn sha256(&self) -> Sha256 {
+        self.sha256
+    }
+    pub fn size(&self) -> i32 {
+        self.contents.len().assert_i32()
+    }
+    pub fn contents(&self) -> &[u8] {
+        &self.contents
+    }
+    pub fn map_change(&self) -> system::MapChange<'_> {
+        system::MapChange {
+            name: self.name.as_bytes(),
+            crc: self.crc,
+            size: self.size(),
+        }
+    }
+    pub fn serve_request(&self, rmd: system::RequestMapData) -> Option<system::MapData<'_>> {
+        let chunk = unwrap_or_return!(rmd.chunk.try_u64());
+        let offset = chunk * MAPDOWNLOAD_CHUNK_SIZE;
+        if offset >= self.contents.len().u64() {
+            return None;
+        }
+        let last = offset + MAPDOWNLOAD_CHUNK_SIZE >= self.contents.len().u64();
+        let offset = offset.assert_usize();
+        let data = if !last {
+            &self.contents[offset..offset + MAPDOWNLOAD_CHUNK_SIZE.assert_usize()]
+        } else {
+            &self.contents[offset..]
+        };
+        Some(system::MapData {
+            last: last as i32,
+            crc: self.crc,
+            chunk: rmd.chunk,
+            data,
+        })
+    }
+    /// Pickups placed in the map, in the order Teeworlds creates them.
+    pub fn pickups(&self) -> &[(PickupKind, vec2)] {
+        &self.pickups
+    }
+    /// Position of the flag stand of a team, the first one if the map has
+    /// several.
+    pub fn flag_stand(&self, team: Team) -> Option<vec2> {
+        match team {
+            Team::Red => self.flag_stands[0],
+            Team::Blue => self.flag_stands[1],
+            Team::Spectators => None,
+        }
+    }
+    /// Picks the spawn point farthest away from the given characters, which
+    /// are passed along with their team.
+    ///
+    /// Like Teeworlds, players without a team prefer the neutral spawn points
+    /// over the red and then the blue ones. Players of a team prefer their
+    /// own team's spawn points, then the neutral ones and consider their
+    /// teammates only half as much in the way.
+    pub fn spawn_point<I>(&self, team: Option<Team>, characters: I) -> vec2
+    where
+        I: Iterator<Item = (vec2, Option<Team>)> + Clone,
+    {
+        let order = match team {
+            None | Some(Team::Spectators) => [0, 1, 2],
+            Some(Team::Red) => [1, 0, 2],
+            Some(Team::Blue) => [2, 0, 1],
+        };
+        let spawns = order
+            .iter()
+            .map(|&i| &self.spawns[i])
+            .find(|s| !s.is_empty())
+            .unwrap();
+        let score = |pos: vec2| -> f32 {
+            characters
+                .clone()
+                .map(|(c, c_team)| {
+                    let factor = if team.is_some() && c_team == team {
+                        0.5
+                    } else {
+                        1.0
+                    };
+                    let d = vec2::distance(pos, c);
+                    factor * if d == 0.0 { 1_000_000_000.0 } else { 1.0 / d }
+                })
+                .sum()
+        };
+        let mut result = spawns[0];
+        let mut result_score = score(result);
+        for &pos in &spawns[1..] {
+            let s = score(pos);
+            if s < result_score {
+                result = pos;
+                result_score = s;
+            }
+        }
+        result
+    }
+}
+
+impl Map {
+    fn tile_at(&self, pos: vec2) -> u8 {
+        let (x, y) = (pos.x.round_to_i32(), pos.y.round_to_i32());
+        let (height, width) = self.tiles.dim();
+        let tx = (x / 32).clamp(0, width.assert_i32() - 1);
+        let ty = (y / 32).clamp(0, height.assert_i32() - 1);
+        self.tiles[(ty.assert_usize(), tx.assert_usize())]
+    }
+}
+
+type Entities = ([Vec<vec2>; 3], Vec<(PickupKind, vec2)>, [Option<vec2>; 2]);
+
+fn entities(tiles: &Array2<Tile>) -> Entities {
+    let mut spawns = [Vec::new(), Vec::new(), Vec::new()];
+    let mut pickups = Vec::new();
+    let mut flag_stands = [None, None];
+    for ((y, x), tile) in tiles.indexed_iter() {
+        let pos = |t: usize| (t.assert_i32() * 32 + 16) as f32;
+        let pos = vec2::new(pos(x), pos(y));
+        let pickup = match tile.index {
+            ENTITY_SPAWN => {
+                spawns[0].push(pos);
+                continue;
+            }
+            ENTITY_SPAWN_RED => {
+                spawns[1].push(pos);
+                continue;
+            }
+            ENTITY_SPAWN_BLUE => {
+                spawns[2].push(pos);
+                continue;
+            }
+            ENTITY_FLAGSTAND_RED | ENTITY_FLAGSTAND_BLUE => {
+                let stand = &mut flag_stands[(tile.index - ENTITY_FLAGSTAND_RED).usize()];
+                if stand.is_none() {
+                    *stand = Some(pos);
+                }
+                continue;
+            }
+            ENTITY_ARMOR_1 => PickupKind::Armor,
+            ENTITY_HEALTH_1 => PickupKind::Health,
+            ENTITY_WEAPON_SHOTGUN => PickupKind::Weapon(Weapon::Shotgun),
+            ENTITY_WEAPON_GRENADE => PickupKind::Weapon(Weapon::Grenade),
+            ENTITY_POWERUP_NINJA => PickupKind::Ninja,
+            ENTITY_WEAPON_RIFLE => PickupKind::Weapon(Weapon::Rifle),
+            _ => continue,
+        };
+        pickups.push((pickup, pos));
+    }
+    (spawns, pickups, flag_stands)
+}
+
+impl libtw2_world::Collision for Map {
+    fn check_point(&mut self, pos: vec2) -> Option<CollisionType> {
+        match self.tile_at(pos) {
+            TILE_SOLID => Some(CollisionType::Normal),
+            TILE_NOHOOK => Some(CollisionType::Unhookable),
+            _ => None,
+        }
+    }
+    fn is_death(&mut self, pos: vec2) -> bool {
+        self.tile_at(pos) == TILE_DEATH
+    }
+    fn is_clipped(&mut self, pos: vec2) -> bool {
+        let (x, y) = (pos.x.round_to_i32() / 32, pos.y.round_to_i32() / 32);
+        let (height, width) = self.tiles.dim();
+        let (width, height) = (width.assert_i32(), height.assert_i32());
+        x < -200 || x > width + 200 || y < -200 || y > height + 200
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Map;
+    use libtw2_common::digest::Sha256;
+    use libtw2_gamenet::enums::Team;
+    use std::path::Path;
+
+    #[test]
+    fn dm1() {
+        let map = Map::load(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/dm1.map"))).unwrap();
+        assert_eq!(map.name(), "dm1");
+        assert_eq!(map.crc(), 0xf2159e6e_u32 as i32);
+        assert_eq!(map.size(), 5805);
+        assert_ne!(map.sha256(), Sha256([0; 32]));
+        assert!(!map.pickups().is_empty());
+        assert!(map.flag_stand(Team::Red).is_none());
+        let spawn = map.spawn_point(None, [].iter().cloned());
+        assert_eq!((spawn.x as i32 - 16) % 32, 0);
+        assert_eq!((spawn.y as i32 - 16) % 32, 0);
+    }
+}
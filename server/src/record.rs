@@ -0,0 +1,219 @@
+//! Recording of server demos and teehistorian files.
+//!
+//! A new demo and teehistorian file is started for every round, using the
+//! config at the start of the round. The files are switched at the start of
+//! the next tick so that everything happening until then still ends up in
+//! the old ones. Write errors are logged and stop the affected recording
+//! until the next round.
+
+use crate::console::Config;
+use crate::map::Map;
+use chrono::Local;
+use libtw2_common::num::Cast;
+use libtw2_common::num::CastFloat;
+use libtw2_demo::ddnet::DemoWriter;
+use libtw2_demo::DemoKind;
+use libtw2_gamenet::enums::VERSION;
+use libtw2_gamenet::msg::Game;
+use libtw2_gamenet::snap_obj::PlayerInput;
+use libtw2_gamenet::Protocol;
+use libtw2_gamenet::SnapObj;
+use libtw2_teehistorian as teehistorian;
+use libtw2_teehistorian::Pos;
+use libtw2_world::vec2;
+use libtw2_world::CharacterId;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::path::Path;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+type Teehistorian = teehistorian::Writer<BufWriter<File>>;
+
+fn create(directory: &str, name: &str) -> io::Result<(PathBuf, BufWriter<File>)> {
+    fs::create_dir_all(directory)?;
+    let path = Path::new(directory).join(name);
+    let file = BufWriter::new(File::create(&path)?);
+    Ok((path, file))
+}
+
+/// Server demo and teehistorian file of the current round.
+#[derive(Default)]
+pub struct Recorder {
+    demo: Option<DemoWriter<'static, Protocol>>,
+    teehistorian: Option<Teehistorian>,
+    /// Client IDs of the characters whose position is recorded in the
+    /// teehistorian file.
+    recorded: Vec<CharacterId>,
+    /// New files should be started at the beginning of the next tick.
+    restart: bool,
+}
+
+impl Recorder {
+    /// Finishes the current recordings at the start of the next tick and
+    /// starts new ones if enabled in the config.
+    pub fn restart(&mut self) {
+        self.restart = true;
+    }
+    fn start(&mut self, config: &Config, map: &Map) {
+        self.stop();
+        let now = Local::now();
+        if config.sv_demo_record != 0 {
+            let name = format!("{}_{}.demo", map.name(), now.format("%Y-%m-%d_%H-%M-%S"));
+            let result = create(&config.sv_demos_directory, &name).and_then(|(path, file)| {
+                let timestamp = now.format("%Y-%m-%d %H:%M:%S").to_string();
+                let demo = DemoWriter::new(
+                    file,
+                    VERSION.as_bytes(),
+                    map.name().as_bytes(),
+                    Some(map.sha256()),
+                    map.crc() as u32,
+                    DemoKind::Server,
+                    0,
+                    timestamp.as_bytes(),
+                    map.contents(),
+                )
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+                Ok((path, demo))
+            });
+            match result {
+                Ok((path, demo)) => {
+                    info!("recording demo {}", path.display());
+                    self.demo = Some(demo);
+                }
+                Err(e) => warn!("failed to start demo recording: {}", e),
+            }
+        }
+        if config.sv_teehistorian != 0 {
+            let header = teehistorian::Header {
+                version: 2,
+                game_uuid: Uuid::new_v4(),
+                timestamp: now.with_timezone(now.offset()),
+                server_port: config.sv_port.assert_u16(),
+                map_name: Cow::Borrowed(map.name()),
+                map_size: map.size().assert_u32(),
+                map_sha256: Some(map.sha256()),
+                map_crc: map.crc() as u32,
+                config: config
+                    .changed()
+                    .into_iter()
+                    .map(|(name, value)| (Cow::Borrowed(name), Cow::Owned(value)))
+                    .collect(),
+            };
+            let name = format!("{}.teehistorian", header.game_uuid);
+            let result = create(&config.sv_teehistorian_directory, &name)
+                .and_then(|(path, file)| Ok((path, teehistorian::Writer::new(file, &header)?)));
+            match result {
+                Ok((path, teehistorian)) => {
+                    info!("writing teehistorian file {}", path.display());
+                    self.teehistorian = Some(teehistorian);
+                }
+                Err(e) => warn!("failed to start teehistorian file: {}", e),
+            }
+        }
+    }
+    /// Finishes the current recordings.
+    pub fn stop(&mut self) {
+        self.restart = false;
+        self.demo = None;
+        self.recorded.clear();
+        if let Some(teehistorian) = self.teehistorian.take() {
+            if let Err(e) = teehistorian.finish() {
+                warn!("failed to finish teehistorian file: {}", e);
+            }
+        }
+    }
+    pub fn is_recording_demo(&self) -> bool {
+        self.demo.is_some()
+    }
+    fn demo<E, F>(&mut self, f: F)
+    where
+        E: fmt::Display,
+        F: FnOnce(&mut DemoWriter<'static, Protocol>) -> Result<(), E>,
+    {
+        if let Some(demo) = &mut self.demo {
+            if let Err(e) = f(demo) {
+                warn!("failed to write demo, stopping: {}", e);
+                self.demo = None;
+            }
+        }
+    }
+    pub fn demo_snap(&mut self, tick: u32, objects: &[(SnapObj, u16)]) {
+        self.demo(|d| d.write_snap(tick.assert_i32(), objects.iter().map(|(o, id)| (o, *id))));
+    }
+    /// Records a game message that was sent to all clients.
+    pub fn demo_msg(&mut self, msg: &Game) {
+        self.demo(|d| d.write_msg(msg));
+    }
+    fn teehistorian<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut Teehistorian) -> io::Result<()>,
+    {
+        if let Some(teehistorian) = &mut self.teehistorian {
+            if let Err(e) = f(teehistorian) {
+                warn!("failed to write teehistorian file, stopping: {}", e);
+                self.teehistorian = None;
+            }
+        }
+    }
+    /// Starts a new tick in the teehistorian file, must be followed by
+    /// [`Recorder::players`].
+    pub fn begin_tick(&mut self, config: &Config, map: &Map, tick: u32) {
+        if self.restart {
+            self.start(config, map);
+        }
+        self.teehistorian(|t| {
+            t.begin_tick(tick.assert_i32());
+            Ok(())
+        });
+    }
+    /// Records the positions of the living characters. Characters recorded
+    /// in an earlier tick that are missing are recorded as gone.
+    pub fn players<I: IntoIterator<Item = (CharacterId, vec2)>>(&mut self, characters: I) {
+        if self.teehistorian.is_none() {
+            return;
+        }
+        // The teehistorian file wants the players ordered by client ID.
+        let mut positions: BTreeMap<CharacterId, Option<vec2>> =
+            self.recorded.drain(..).map(|cid| (cid, None)).collect();
+        positions.extend(characters.into_iter().map(|(cid, pos)| (cid, Some(pos))));
+        for (cid, pos) in positions {
+            if pos.is_some() {
+                self.recorded.push(cid);
+            }
+            let pos = pos.map(|p| Pos {
+                x: p.x.round_to_i32(),
+                y: p.y.round_to_i32(),
+            });
+            self.teehistorian(|t| t.player(cid.0.assert_i32(), pos));
+        }
+    }
+    pub fn input(&mut self, cid: CharacterId, input: &PlayerInput) {
+        let input = input.encode().try_into().unwrap();
+        self.teehistorian(|t| t.input(cid.0.assert_i32(), input));
+    }
+    /// Records a game message sent by a client.
+    pub fn message(&mut self, cid: CharacterId, data: &[u8]) {
+        self.teehistorian(|t| t.message(cid.0.assert_i32(), data));
+    }
+    pub fn join(&mut self, cid: CharacterId) {
+        self.teehistorian(|t| t.join(cid.0.assert_i32()));
+    }
+    pub fn drop(&mut self, cid: CharacterId, reason: &[u8]) {
+        self.teehistorian(|t| t.drop(cid.0.assert_i32(), reason));
+    }
+    /// Records an executed console command, `cid` is `None` for commands
+    /// that didn't come from a client.
+    pub fn console_command(&mut self, cid: Option<CharacterId>, name: &str, args: &[String]) {
+        let cid = cid.map(|c| c.0.assert_i32()).unwrap_or(-1);
+        let args: Vec<&[u8]> = args.iter().map(|a| a.as_bytes()).collect();
+        // The console doesn't have command flags.
+        self.teehistorian(|t| t.console_command(cid, 0, name.as_bytes(), &args));
+    }
+}
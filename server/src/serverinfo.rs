@@ -0,0 +1,436 @@
+//! Server info responses for server browsers and the masterserver.
+//!
+//! Server browsers ask the server for its info directly, in one of three
+//! formats: the vanilla one, the 64-player one which spreads the clients over
+//! several packets, and DDNet's extended one, which is split into an `iext`
+//! packet and as many `iex+` packets as needed. The masterserver instead gets
+//! the info as JSON over HTTPS, through `libtw2_register`.
+
+use arrayvec::Array;
+use arrayvec::ArrayString;
+use libtw2_common::digest::Sha256;
+use libtw2_common::num::Cast;
+use libtw2_common::str::truncated_arraystring;
+use libtw2_register::Register;
+use libtw2_serverbrowse::protocol as browse_protocol;
+use libtw2_serverbrowse::protocol::ClientInfo;
+use libtw2_serverbrowse::protocol::ServerInfo;
+use libtw2_serverbrowse::protocol::ServerInfoVersion;
+use serde_derive::Serialize;
+use std::future;
+use std::sync::Arc;
+use std::thread;
+use tokio::runtime;
+
+/// Length of the packet header and padding of connectionless packets, which
+/// are included in the headers of `libtw2_serverbrowse::protocol`.
+const CONNLESS_HEADER_LEN: usize = 6;
+
+fn header(header: &'static [u8; 14]) -> &'static [u8] {
+    &header[CONNLESS_HEADER_LEN..]
+}
+
+/// Server info request of a server browser.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Request {
+    Vanilla { token: i32 },
+    Players64 { token: i32 },
+    Extended { token: i32 },
+}
+
+impl Request {
+    /// Parses the payload of a connectionless packet, `extra_data` is the
+    /// extra data of DDNet's extended connectionless packets.
+    pub fn parse(extra_data: Option<[u8; 4]>, data: &[u8]) -> Option<Request> {
+        let (&token, data) = data.split_last()?;
+        let token = token.i32();
+        if data == header(browse_protocol::REQUEST_INFO_6) {
+            Some(match extra_data {
+                // The extended request only differs from the vanilla one by
+                // its packet header, which carries the upper 16 bits of the
+                // token.
+                Some(extra) => Request::Extended {
+                    token: (extra[0].i32() << 16) | (extra[1].i32() << 8) | token,
+                },
+                None => Request::Vanilla { token },
+            })
+        } else if data == header(browse_protocol::REQUEST_INFO_6_64) {
+            Some(Request::Players64 { token })
+        } else {
+            None
+        }
+    }
+}
+
+/// A client as shown in the server browser.
+#[derive(Clone, Copy, Debug)]
+pub struct Client<'a> {
+    pub name: &'a [u8],
+    pub clan: &'a [u8],
+    pub country: i32,
+    pub score: i32,
+    pub is_player: bool,
+}
+
+/// Everything the server browsers and the masterserver are told about the
+/// server.
+#[derive(Clone, Debug)]
+pub struct Info<'a> {
+    pub version: &'a [u8],
+    pub name: &'a [u8],
+    pub map: &'a [u8],
+    pub map_crc: i32,
+    pub map_size: i32,
+    pub map_sha256: Sha256,
+    pub game_type: &'a [u8],
+    pub passworded: bool,
+    pub max_players: i32,
+    pub max_clients: i32,
+    pub clients: Vec<Client<'a>>,
+}
+
+fn arraystring<A: Array<Item = u8> + Copy>(bytes: &[u8]) -> ArrayString<A> {
+    truncated_arraystring(&String::from_utf8_lossy(bytes))
+}
+
+impl<'a> Info<'a> {
+    /// Converts the info to the representation of
+    /// `libtw2_serverbrowse::protocol`, only listing the clients that fit
+    /// into the given format.
+    fn server_info(&self, token: i32, version: ServerInfoVersion) -> ServerInfo {
+        let max_clients = version
+            .max_clients()
+            .map(|m| m.usize())
+            .unwrap_or(usize::MAX);
+        let clients = &self.clients[..self.clients.len().min(max_clients)];
+        let max_clients = self
+            .max_clients
+            .min(max_clients.try_i32().unwrap_or(i32::MAX));
+        ServerInfo {
+            info_version: version,
+            token,
+            version: arraystring(self.version),
+            name: arraystring(self.name),
+            hostname: None,
+            map: arraystring(self.map),
+            map_crc: Some(self.map_crc as u32),
+            map_size: Some(self.map_size.try_u32().unwrap_or(0)),
+            game_type: arraystring(self.game_type),
+            flags: if self.passworded {
+                browse_protocol::SERVERINFO_FLAG_PASSWORDED
+            } else {
+                0
+            },
+            progression: None,
+            skill_level: None,
+            num_players: clients.iter().filter(|c| c.is_player).count().assert_i32(),
+            max_players: self.max_players.min(max_clients),
+            num_clients: clients.len().assert_i32(),
+            max_clients,
+            clients: clients
+                .iter()
+                .map(|c| ClientInfo {
+                    name: arraystring(c.name),
+                    clan: arraystring(c.clan),
+                    country: c.country,
+                    score: c.score,
+                    flags: if c.is_player {
+                        0
+                    } else {
+                        browse_protocol::CLIENTINFO_FLAG_SPECTATOR
+                    },
+                })
+                .collect(),
+        }
+    }
+    /// Encodes the response to a server info request, as the payloads of
+    /// connectionless packets.
+    pub fn encode(&self, request: Request) -> Vec<Vec<u8>> {
+        let packets = match request {
+            Request::Vanilla { token } => vec![browse_protocol::info_6(
+                &self.server_info(token, ServerInfoVersion::V6),
+            )],
+            Request::Players64 { token } => {
+                browse_protocol::info_6_64(&self.server_info(token, ServerInfoVersion::V664))
+            }
+            Request::Extended { token } => {
+                browse_protocol::info_6_ex(&self.server_info(token, ServerInfoVersion::V6Ex))
+            }
+        };
+        packets
+            .into_iter()
+            .map(|p| p[CONNLESS_HEADER_LEN..].to_vec())
+            .collect()
+    }
+    /// Encodes the info in the JSON format expected by the masterserver.
+    pub fn json(&self) -> String {
+        #[derive(Serialize)]
+        struct Json<'a> {
+            max_clients: i32,
+            max_players: i32,
+            passworded: bool,
+            game_type: &'a str,
+            name: &'a str,
+            map: JsonMap<'a>,
+            version: &'a str,
+            clients: Vec<JsonClient<'a>>,
+        }
+        #[derive(Serialize)]
+        struct JsonMap<'a> {
+            name: &'a str,
+            sha256: String,
+            size: i32,
+        }
+        #[derive(Serialize)]
+        struct JsonClient<'a> {
+            name: &'a str,
+            clan: &'a str,
+            country: i32,
+            score: i32,
+            is_player: bool,
+        }
+        let names: Vec<_> = self
+            .clients
+            .iter()
+            .map(|c| {
+                (
+                    String::from_utf8_lossy(c.name),
+                    String::from_utf8_lossy(c.clan),
+                )
+            })
+            .collect();
+        let game_type = String::from_utf8_lossy(self.game_type);
+        let name = String::from_utf8_lossy(self.name);
+        let map = String::from_utf8_lossy(self.map);
+        let version = String::from_utf8_lossy(self.version);
+        serde_json::to_string(&Json {
+            max_clients: self.max_clients,
+            max_players: self.max_players,
+            passworded: self.passworded,
+            game_type: &game_type,
+            name: &name,
+            map: JsonMap {
+                name: &map,
+                sha256: self.map_sha256.to_string(),
+                size: self.map_size,
+            },
+            version: &version,
+            clients: self
+                .clients
+                .iter()
+                .zip(&names)
+                .map(|(c, (name, clan))| JsonClient {
+                    name,
+                    clan,
+                    country: c.country,
+                    score: c.score,
+                    is_player: c.is_player,
+                })
+                .collect(),
+        })
+        .unwrap()
+    }
+}
+
+/// Checks whether a connectionless packet is meant for `Register`.
+pub fn is_register_packet(data: &[u8]) -> bool {
+    data.starts_with(header(browse_protocol::CHALLENGE_6))
+}
+
+/// Passes a connectionless packet for which `is_register_packet` returned
+/// `true` to `register`.
+pub fn register_on_packet(register: &Register, data: &[u8]) {
+    let mut packet = browse_protocol::CHALLENGE_6[..CONNLESS_HEADER_LEN].to_vec();
+    packet.extend_from_slice(data);
+    register.on_udp_packet(&packet);
+}
+
+/// Starts registering the server with the masterserver.
+///
+/// The registration runs on a Tokio runtime in a thread of its own, the
+/// returned `Register` must be kept up to date with `Register::on_new_info`.
+pub fn register(port: u16, register_url: &str, info: String) -> Register {
+    let runtime = runtime::Builder::new_current_thread()
+        .enable_io()
+        .enable_time()
+        .build()
+        .unwrap();
+    let mut builder = Register::builder()
+        .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).into());
+    if !register_url.is_empty() {
+        builder = builder.register_url(register_url.into());
+    }
+    let register = {
+        let _guard = runtime.enter();
+        builder.build(port, Arc::from(info))
+    };
+    thread::spawn(move || runtime.block_on(future::pending::<()>()));
+    register
+}
+
+#[cfg(test)]
+mod test {
+    use super::header;
+    use super::Client;
+    use super::Info;
+    use super::Request;
+    use libtw2_common::digest::Sha256;
+    use libtw2_serverbrowse::protocol as browse_protocol;
+    use libtw2_serverbrowse::protocol::PartialServerInfo;
+    use libtw2_serverbrowse::protocol::Response;
+    use libtw2_serverbrowse::protocol::ServerInfo;
+
+    const NAMES: [&[u8]; 4] = [b"nameless tee", b"brainless tee", b"(1)tee", b"tee"];
+
+    fn info(num_clients: usize) -> Info<'static> {
+        Info {
+            version: b"0.6.4",
+            name: b"test server",
+            map: b"dm1",
+            map_crc: 0x12345678,
+            map_size: 5805,
+            map_sha256: Sha256([0; 32]),
+            game_type: b"DM",
+            passworded: true,
+            max_players: 64,
+            max_clients: 64,
+            clients: (0..num_clients)
+                .map(|i| Client {
+                    name: NAMES[i % NAMES.len()],
+                    clan: b"clan",
+                    country: i as i32,
+                    score: 10 * i as i32,
+                    is_player: i % 3 != 0,
+                })
+                .collect(),
+        }
+    }
+
+    fn packet(payload: &[u8]) -> Vec<u8> {
+        let mut result = vec![0xff; 6];
+        result.extend_from_slice(payload);
+        result
+    }
+
+    fn merge(packets: &[Vec<u8>]) -> ServerInfo {
+        let mut partial: Option<PartialServerInfo> = None;
+        for p in packets {
+            let p = packet(p);
+            let new = match browse_protocol::parse_response(&p).unwrap() {
+                Response::Info664(r) => r.parse().unwrap(),
+                Response::Info6Ex(r) => r.parse().unwrap(),
+                Response::Info6ExMore(r) => r.parse().unwrap(),
+                _ => panic!("unexpected response"),
+            };
+            match partial {
+                Some(ref mut partial) => partial.merge(new).unwrap(),
+                None => partial = Some(new),
+            }
+        }
+        partial.unwrap().take_info().unwrap()
+    }
+
+    fn check(info: &Info, parsed: &ServerInfo, max_clients: usize) {
+        let clients = &info.clients[..info.clients.len().min(max_clients)];
+        assert_eq!(parsed.version.as_bytes(), info.version);
+        assert_eq!(parsed.name.as_bytes(), info.name);
+        assert_eq!(parsed.map.as_bytes(), info.map);
+        assert_eq!(parsed.game_type.as_bytes(), info.game_type);
+        assert_eq!(parsed.flags, browse_protocol::SERVERINFO_FLAG_PASSWORDED);
+        assert_eq!(parsed.num_clients, clients.len() as i32);
+        assert_eq!(
+            parsed.num_players,
+            clients.iter().filter(|c| c.is_player).count() as i32
+        );
+        assert_eq!(
+            parsed.max_clients as usize,
+            (info.max_clients as usize).min(max_clients)
+        );
+        let mut expected: Vec<_> = clients
+            .iter()
+            .map(|c| (c.name.to_vec(), c.country, c.score, c.is_player))
+            .collect();
+        let mut got: Vec<_> = parsed
+            .clients
+            .iter()
+            .map(|c| {
+                assert_eq!(c.clan.as_bytes(), b"clan");
+                (
+                    c.name.as_bytes().to_vec(),
+                    c.country,
+                    c.score,
+                    c.flags & browse_protocol::CLIENTINFO_FLAG_SPECTATOR == 0,
+                )
+            })
+            .collect();
+        expected.sort();
+        got.sort();
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn vanilla() {
+        for &num_clients in &[0, 5, 16, 20] {
+            let info = info(num_clients);
+            let packets = info.encode(Request::Vanilla { token: 42 });
+            assert_eq!(packets.len(), 1);
+            let p = packet(&packets[0]);
+            let parsed = match browse_protocol::parse_response(&p).unwrap() {
+                Response::Info6(r) => r.parse().unwrap(),
+                _ => panic!("unexpected response"),
+            };
+            assert_eq!(parsed.token, 42);
+            check(&info, &parsed, 16);
+        }
+    }
+
+    #[test]
+    fn players_64() {
+        for &(num_clients, num_packets) in &[(0, 1), (24, 1), (25, 2), (64, 3), (70, 3)] {
+            let info = info(num_clients);
+            let packets = info.encode(Request::Players64 { token: 42 });
+            assert_eq!(packets.len(), num_packets);
+            let parsed = merge(&packets);
+            assert_eq!(parsed.token, 42);
+            check(&info, &parsed, 64);
+        }
+    }
+
+    #[test]
+    fn extended() {
+        for &(num_clients, num_packets) in &[(0, 1), (64, 2), (256, 5)] {
+            let info = Info {
+                max_clients: 256,
+                ..info(num_clients)
+            };
+            let packets = info.encode(Request::Extended { token: 0x123456 });
+            assert_eq!(packets.len(), num_packets);
+            assert!(packets
+                .iter()
+                .all(|p| super::CONNLESS_HEADER_LEN + p.len() <= 1400));
+            let parsed = merge(&packets);
+            assert_eq!(parsed.token, 0x123456);
+            assert_eq!(parsed.map_crc, Some(0x12345678));
+            assert_eq!(parsed.map_size, Some(5805));
+            check(&info, &parsed, usize::MAX);
+        }
+    }
+
+    #[test]
+    fn parse_request() {
+        assert_eq!(
+            Request::parse(None, &browse_protocol::request_info_6(7)[6..]),
+            Some(Request::Vanilla { token: 7 })
+        );
+        assert_eq!(
+            Request::parse(None, &browse_protocol::request_info_6_64(7)[6..]),
+            Some(Request::Players64 { token: 7 })
+        );
+        let extended = browse_protocol::request_info_6_ex(0x123456);
+        assert_eq!(
+            Request::parse(Some([extended[2], extended[3], 0, 0]), &extended[6..]),
+            Some(Request::Extended { token: 0x123456 })
+        );
+        assert_eq!(Request::parse(None, header(browse_protocol::INFO_6)), None);
+    }
+}
@@ -0,0 +1,179 @@
+//! Votes called by the players, like in Teeworlds.
+//!
+//! A vote passes once more than half of the clients voted yes and fails once
+//! at least half of them voted no or when it times out.
+
+use libtw2_common::num::Cast;
+use libtw2_event_loop::collections::PeerMap;
+use libtw2_event_loop::PeerId;
+use libtw2_event_loop::Timestamp;
+use libtw2_gamenet::msg::game;
+use std::time::Duration;
+
+/// Time the players have to vote.
+pub const VOTE_TIME: Duration = Duration::from_secs(25);
+/// Time a player has to wait before calling another vote.
+pub const VOTE_DELAY: Duration = Duration::from_secs(60);
+
+/// Option that can be voted for, added by the server admin.
+#[derive(Clone, Debug)]
+pub struct VoteOption {
+    pub description: String,
+    pub command: String,
+}
+
+/// What happens if a vote passes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Action {
+    /// Execute a console command.
+    Command(String),
+    Kick(PeerId),
+    Spectate(PeerId),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    Pass,
+    Fail,
+}
+
+pub struct Vote {
+    pub description: String,
+    pub reason: String,
+    pub action: Action,
+    end: Timestamp,
+    /// `true` for yes, `false` for no.
+    votes: PeerMap<bool>,
+    /// Set by an admin, overrides the votes of the players.
+    forced: Option<Outcome>,
+}
+
+impl Vote {
+    /// Starts a vote, the caller automatically votes yes.
+    pub fn new(
+        caller: PeerId,
+        description: String,
+        reason: String,
+        action: Action,
+        now: Timestamp,
+    ) -> Vote {
+        let mut votes = PeerMap::new();
+        votes.insert(caller, true);
+        Vote {
+            description,
+            reason,
+            action,
+            end: now + VOTE_TIME,
+            votes,
+            forced: None,
+        }
+    }
+    /// Records the vote of a player, players can't change their vote.
+    pub fn vote(&mut self, pid: PeerId, yes: bool) -> bool {
+        if self.votes.get(pid).is_some() {
+            return false;
+        }
+        self.votes.insert(pid, yes);
+        true
+    }
+    /// Removes the vote of a player that left.
+    pub fn remove(&mut self, pid: PeerId) {
+        if self.votes.get(pid).is_some() {
+            self.votes.remove(pid);
+        }
+    }
+    pub fn force(&mut self, outcome: Outcome) {
+        self.forced = Some(outcome);
+    }
+    fn count(&self) -> (usize, usize) {
+        let yes = self.votes.values().filter(|&&v| v).count();
+        (yes, self.votes.len() - yes)
+    }
+    /// Seconds left to vote.
+    pub fn timeout(&self, now: Timestamp) -> i32 {
+        let usecs = self
+            .end
+            .as_usecs_since_epoch()
+            .saturating_sub(now.as_usecs_since_epoch());
+        ((usecs + 999_999) / 1_000_000)
+            .try_i32()
+            .unwrap_or(i32::MAX)
+    }
+    /// Current votes, `total` is the number of clients that can vote.
+    pub fn status(&self, total: usize) -> game::SvVoteStatus {
+        let (yes, no) = self.count();
+        game::SvVoteStatus {
+            yes: yes.assert_i32(),
+            no: no.assert_i32(),
+            // Players that voted might have left already.
+            pass: total.saturating_sub(yes + no).assert_i32(),
+            total: total.assert_i32(),
+        }
+    }
+    /// Whether the vote has ended, `total` is the number of clients that can
+    /// vote.
+    pub fn outcome(&self, total: usize, now: Timestamp) -> Option<Outcome> {
+        if let Some(forced) = self.forced {
+            return Some(forced);
+        }
+        let (yes, no) = self.count();
+        if yes > total / 2 {
+            Some(Outcome::Pass)
+        } else if no >= (total + 1) / 2 || now >= self.end {
+            Some(Outcome::Fail)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Action;
+    use super::Outcome;
+    use super::Vote;
+    use libtw2_event_loop::PeerId;
+    use libtw2_event_loop::Timestamp;
+    use std::time::Duration;
+
+    fn vote(now: Timestamp) -> Vote {
+        let action = Action::Command("restart".into());
+        Vote::new(PeerId(0), "restart".into(), "".into(), action, now)
+    }
+
+    #[test]
+    fn pass() {
+        let now = Timestamp::from_secs_since_epoch(0);
+        let mut v = vote(now);
+        assert_eq!(v.outcome(1, now), Some(Outcome::Pass));
+        assert_eq!(v.outcome(3, now), None);
+        assert!(v.vote(PeerId(1), true));
+        assert!(!v.vote(PeerId(1), false));
+        assert_eq!(v.outcome(3, now), Some(Outcome::Pass));
+        assert_eq!(v.outcome(4, now), None);
+        assert_eq!(v.status(4).pass, 2);
+        v.remove(PeerId(1));
+        assert_eq!(v.status(4).yes, 1);
+        v.vote(PeerId(2), false);
+        assert_eq!(v.status(1).pass, 0);
+    }
+
+    #[test]
+    fn fail() {
+        let now = Timestamp::from_secs_since_epoch(0);
+        let mut v = vote(now);
+        assert_eq!(v.timeout(now), 25);
+        v.vote(PeerId(1), false);
+        assert_eq!(v.outcome(3, now), None);
+        assert_eq!(v.outcome(2, now), Some(Outcome::Fail));
+        let later = now + Duration::from_millis(24_500);
+        assert_eq!(v.timeout(later), 1);
+        assert_eq!(v.outcome(3, later), None);
+        let end = now + Duration::from_secs(25);
+        assert_eq!(v.outcome(3, end), Some(Outcome::Fail));
+
+        let mut v = vote(now);
+        v.force(Outcome::Fail);
+        assert_eq!(v.outcome(1, now), Some(Outcome::Fail));
+    }
+}
@@ -0,0 +1,960 @@
+//! Server browser: queries masterservers for server lists and the listed
+//! servers for their info.
+//!
+//! [`Browser`] doesn't do any I/O by itself. Feed it received packets using
+//! [`Browser::on_packet`], send the packets returned by
+//! [`Browser::poll_transmit`] until it returns `None` and call it again at
+//! [`Browser::next_timeout`] at the latest. Changes to the server table are
+//! reported by [`Browser::poll_event`].
+//!
+//! With the `browser` feature, [`run`] drives a `Browser` using tokio.
+
+use crate::protocol;
+use crate::protocol::Addr;
+use crate::protocol::PartialServerInfo;
+use crate::protocol::Response;
+use crate::protocol::ServerInfo;
+use crate::protocol::Token7;
+use crate::protocol::MASTERSERVER_7_PORT;
+use libtw2_common::num::Cast;
+use rand::rngs::StdRng;
+use rand::Rng as _;
+use rand::SeedableRng as _;
+use std::cmp;
+use std::collections::hash_map;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fmt;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::time::Duration;
+use std::time::Instant;
+
+#[cfg(feature = "browser")]
+mod driver;
+
+#[cfg(feature = "browser")]
+pub use self::driver::run;
+#[cfg(feature = "browser")]
+pub use self::driver::Source;
+
+/// Protocol version used to query a server for its info.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ProtocolVersion {
+    /// `SERVERBROWSE_GETINFO_5`.
+    V5,
+    /// `SERVERBROWSE_GETINFO_6`, and its extended variant.
+    V6,
+    /// `SERVERBROWSE_GETINFO_7`.
+    V7,
+}
+
+/// Server address including the protocol version to query it with.
+#[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ServerAddr {
+    pub version: ProtocolVersion,
+    pub addr: Addr,
+}
+
+impl ServerAddr {
+    pub fn new(version: ProtocolVersion, addr: Addr) -> ServerAddr {
+        ServerAddr { version, addr }
+    }
+}
+
+impl fmt::Debug for ServerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}_{}", self.version, self.addr)
+    }
+}
+
+impl fmt::Display for ServerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Converts a socket address to an `Addr`, unmapping IPv4-mapped IPv6
+/// addresses.
+pub fn addr_from_socket_addr(addr: SocketAddr) -> Addr {
+    let ip_address = match addr.ip() {
+        IpAddr::V6(ip) => ip.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(addr.ip()),
+        ip => ip,
+    };
+    Addr {
+        ip_address,
+        port: addr.port(),
+    }
+}
+
+pub fn addr_to_socket_addr(addr: Addr) -> SocketAddr {
+    SocketAddr::new(addr.ip_address, addr.port)
+}
+
+/// Timing and rate limit configuration of the browser.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Maximum number of list requests per `lists_period`.
+    pub max_lists: u32,
+    pub lists_period: Duration,
+    /// Maximum number of info requests per `infos_period`.
+    pub max_infos: u32,
+    pub infos_period: Duration,
+    /// Time span in which list responses are expected.
+    pub list_expect: Duration,
+    /// Time span after which a successful list request is repeated.
+    pub list_repeat: Duration,
+    /// Time span in which info responses are expected.
+    pub info_expect: Duration,
+    /// Time span after which a successful info request is repeated.
+    pub info_repeat: Duration,
+    /// Number of consecutive missing info responses after which a server is
+    /// removed.
+    pub max_missing_responses: usize,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            max_lists: 1,
+            lists_period: Duration::from_millis(1_000),
+            max_infos: 10,
+            infos_period: Duration::from_millis(25),
+            list_expect: Duration::from_millis(5_000),
+            list_repeat: Duration::from_millis(30_000),
+            info_expect: Duration::from_millis(1_000),
+            info_repeat: Duration::from_millis(5_000),
+            max_missing_responses: 10,
+        }
+    }
+}
+
+/// Change of the server table.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// First info received from a server.
+    New(ServerAddr, ServerInfo),
+    /// Info received from a server, with the previous info. Reported for
+    /// every response, even if the info didn't change.
+    Change(ServerAddr, ServerInfo, ServerInfo),
+    /// Server stopped responding, with its last info.
+    Remove(ServerAddr, ServerInfo),
+}
+
+/// Packet to be sent.
+#[derive(Clone, Debug)]
+pub struct Transmit {
+    pub to: Addr,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct MasterId(usize);
+
+/// Throttles an action to `max` times per `period`.
+struct Limit {
+    remaining: u32,
+    reset: Instant,
+    max: u32,
+    period: Duration,
+}
+
+impl Limit {
+    fn new(max: u32, period: Duration, now: Instant) -> Limit {
+        Limit {
+            remaining: max,
+            reset: now,
+            max,
+            period,
+        }
+    }
+    fn acquire(&mut self, now: Instant) -> bool {
+        if now >= self.reset {
+            self.remaining = self.max;
+            self.reset = now + self.period;
+        }
+        if self.remaining == 0 {
+            return false;
+        }
+        self.remaining -= 1;
+        true
+    }
+}
+
+struct Master {
+    name: String,
+    nobackcompat: bool,
+    addr: Option<Addr>,
+    addr_7: Option<Addr>,
+    own_token: Option<Token7>,
+    count: Option<u16>,
+    count_7: Option<u16>,
+    list: HashSet<ServerAddr>,
+    list_7: HashSet<ServerAddr>,
+}
+
+struct Server {
+    info: Option<ServerInfo>,
+    /// Tokens of the info requests without response since the last
+    /// successful one.
+    missing_responses: Vec<u32>,
+    /// Incomplete info responses spanning multiple packets.
+    partial_responses: Vec<PartialServerInfo>,
+}
+
+enum Work {
+    RequestList(MasterId),
+    RequestList7(MasterId),
+    ExpectList(MasterId),
+    ExpectList7(MasterId),
+    RequestInfo(ServerAddr),
+    ExpectInfo(ServerAddr),
+}
+
+struct Scheduled {
+    time: Instant,
+    seq: u64,
+    work: Work,
+}
+
+impl Ord for Scheduled {
+    fn cmp(&self, other: &Scheduled) -> cmp::Ordering {
+        // Reversed, `BinaryHeap` is a max-heap.
+        (other.time, other.seq).cmp(&(self.time, self.seq))
+    }
+}
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Scheduled) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Scheduled) -> bool {
+        (self.time, self.seq) == (other.time, other.seq)
+    }
+}
+impl Eq for Scheduled {}
+
+fn token7(token: u32) -> Token7 {
+    Token7(token.to_be_bytes())
+}
+
+pub struct Browser {
+    config: Config,
+    masters: Vec<Master>,
+    servers: HashMap<ServerAddr, Server>,
+    list_limit: Limit,
+    info_limit: Limit,
+    queue: BinaryHeap<Scheduled>,
+    next_seq: u64,
+    transmits: VecDeque<Transmit>,
+    events: VecDeque<Event>,
+    rng: StdRng,
+}
+
+impl Browser {
+    pub fn new(config: Config, now: Instant) -> Browser {
+        Browser {
+            list_limit: Limit::new(config.max_lists, config.lists_period, now),
+            info_limit: Limit::new(config.max_infos, config.infos_period, now),
+            config,
+            masters: Vec::new(),
+            servers: HashMap::new(),
+            queue: BinaryHeap::new(),
+            next_seq: 0,
+            transmits: VecDeque::new(),
+            events: VecDeque::new(),
+            rng: StdRng::from_entropy(),
+        }
+    }
+    fn schedule(&mut self, time: Instant, work: Work) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.queue.push(Scheduled { time, seq, work });
+    }
+    fn send(&mut self, to: Addr, data: &[u8]) {
+        self.transmits.push_back(Transmit {
+            to,
+            data: data.to_vec(),
+        });
+    }
+    /// Adds a UDP masterserver. Its server list is requested once its
+    /// address is known, see `set_master_addr`.
+    ///
+    /// `nobackcompat` requests only servers that didn't register via the
+    /// HTTPS masterserver, for masterservers that support it.
+    pub fn add_master(&mut self, name: String, nobackcompat: bool) -> MasterId {
+        self.masters.push(Master {
+            name,
+            nobackcompat,
+            addr: None,
+            addr_7: None,
+            own_token: None,
+            count: None,
+            count_7: None,
+            list: HashSet::new(),
+            list_7: HashSet::new(),
+        });
+        MasterId(self.masters.len() - 1)
+    }
+    /// Sets the address of a masterserver, e.g. after resolving its domain.
+    /// The Teeworlds 0.7 masterserver is expected on the same IP address.
+    pub fn set_master_addr(&mut self, now: Instant, id: MasterId, addr: Addr) {
+        let master = &mut self.masters[id.0];
+        let first = master.addr.is_none();
+        master.addr = Some(addr);
+        master.addr_7 = Some(Addr {
+            ip_address: addr.ip_address,
+            port: MASTERSERVER_7_PORT,
+        });
+        if first {
+            self.schedule(now, Work::RequestList(id));
+            self.schedule(now, Work::RequestList7(id));
+        }
+    }
+    /// Adds a server to query, e.g. from an HTTPS server list. Returns
+    /// whether the server was new.
+    pub fn add_server(&mut self, now: Instant, addr: ServerAddr) -> bool {
+        match self.servers.entry(addr) {
+            hash_map::Entry::Occupied(_) => false,
+            hash_map::Entry::Vacant(v) => {
+                v.insert(Server {
+                    info: None,
+                    missing_responses: Vec::new(),
+                    partial_responses: Vec::new(),
+                });
+                self.schedule(now, Work::RequestInfo(addr));
+                true
+            }
+        }
+    }
+    /// Adds a server known from a previous run, with its last info and the
+    /// tokens of its unanswered info requests, see `server_states`.
+    ///
+    /// Unlike for servers added by `add_server`, its first response is
+    /// reported as `Event::Change`, as the server is already known. Returns
+    /// whether the server was new.
+    pub fn restore_server(
+        &mut self,
+        now: Instant,
+        addr: ServerAddr,
+        info: Option<ServerInfo>,
+        missing_responses: Vec<u32>,
+    ) -> bool {
+        match self.servers.entry(addr) {
+            hash_map::Entry::Occupied(_) => false,
+            hash_map::Entry::Vacant(v) => {
+                v.insert(Server {
+                    info,
+                    missing_responses,
+                    partial_responses: Vec::new(),
+                });
+                self.schedule(now, Work::RequestInfo(addr));
+                true
+            }
+        }
+    }
+    /// All known servers, with their info if they responded already and the
+    /// tokens of their unanswered info requests. Pass them to
+    /// `restore_server` to continue after a restart.
+    pub fn server_states(&self) -> impl Iterator<Item = (ServerAddr, Option<&ServerInfo>, &[u32])> {
+        self.servers
+            .iter()
+            .map(|(&addr, s)| (addr, s.info.as_ref(), &s.missing_responses[..]))
+    }
+    /// Info of the server, if it responded already.
+    pub fn server(&self, addr: ServerAddr) -> Option<&ServerInfo> {
+        self.servers.get(&addr).and_then(|s| s.info.as_ref())
+    }
+    /// All servers that responded with their info.
+    pub fn servers(&self) -> impl Iterator<Item = (ServerAddr, &ServerInfo)> {
+        self.servers
+            .iter()
+            .filter_map(|(&addr, s)| s.info.as_ref().map(|i| (addr, i)))
+    }
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+    /// Number of known servers, including those that didn't respond yet.
+    pub fn num_known_servers(&self) -> usize {
+        self.servers.len()
+    }
+    pub fn poll_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+    /// Time at which `poll_transmit` needs to be called next.
+    pub fn next_timeout(&self) -> Option<Instant> {
+        self.queue.peek().map(|s| s.time)
+    }
+    /// Returns the next packet to send, if any.
+    pub fn poll_transmit(&mut self, now: Instant) -> Option<Transmit> {
+        loop {
+            if let Some(transmit) = self.transmits.pop_front() {
+                return Some(transmit);
+            }
+            match self.queue.peek() {
+                Some(s) if s.time <= now => {}
+                _ => return None,
+            }
+            let Scheduled { time, seq, work } = self.queue.pop().unwrap();
+            if let Some(retry) = self.do_work(now, &work) {
+                // Rate limited, keep the order of the work items.
+                self.queue.push(Scheduled {
+                    time: cmp::max(time, retry),
+                    seq,
+                    work,
+                });
+            }
+        }
+    }
+    /// Does the work, returns the time at which to retry if rate limited.
+    fn do_work(&mut self, now: Instant, work: &Work) -> Option<Instant> {
+        match *work {
+            Work::RequestList(id) | Work::RequestList7(id) => {
+                if !self.list_limit.acquire(now) {
+                    return Some(self.list_limit.reset);
+                }
+                if let Work::RequestList(_) = work {
+                    self.request_list(now, id);
+                } else {
+                    self.request_list_7(now, id);
+                }
+            }
+            Work::ExpectList(id) => {
+                let master = &self.masters[id.0];
+                let complete = is_complete_list(master.count, &master.list);
+                self.expect_list(now, id, complete, Work::RequestList(id));
+            }
+            Work::ExpectList7(id) => {
+                let master = &self.masters[id.0];
+                let complete = is_complete_list(master.count_7, &master.list_7);
+                self.expect_list(now, id, complete, Work::RequestList7(id));
+            }
+            Work::RequestInfo(addr) => {
+                if !self.servers.contains_key(&addr) {
+                    return None;
+                }
+                if !self.info_limit.acquire(now) {
+                    return Some(self.info_limit.reset);
+                }
+                self.request_info(now, addr);
+            }
+            Work::ExpectInfo(addr) => self.expect_info(now, addr),
+        }
+        None
+    }
+    fn request_list(&mut self, now: Instant, id: MasterId) {
+        let master = &mut self.masters[id.0];
+        master.count = None;
+        master.list.clear();
+        let addr = master.addr.unwrap();
+        debug!("requesting count and list from {}", master.name);
+        if !master.nobackcompat {
+            self.send(addr, &protocol::request_count());
+            self.send(addr, &protocol::request_list_5());
+            self.send(addr, &protocol::request_list_6());
+        } else {
+            self.send(addr, &protocol::request_count_nobackcompat());
+            self.send(addr, &protocol::request_list_5_nobackcompat());
+            self.send(addr, &protocol::request_list_6_nobackcompat());
+        }
+        self.schedule(now + self.config.list_expect, Work::ExpectList(id));
+    }
+    fn request_list_7(&mut self, now: Instant, id: MasterId) {
+        let own_token = token7(self.rng.gen());
+        let master = &mut self.masters[id.0];
+        master.own_token = Some(own_token);
+        master.count_7 = None;
+        master.list_7.clear();
+        let addr = master.addr_7.unwrap();
+        debug!("requesting 0.7 token from {}", master.name);
+        self.send(addr, &protocol::request_token_7(own_token));
+        self.schedule(now + self.config.list_expect, Work::ExpectList7(id));
+    }
+    fn expect_list(&mut self, now: Instant, id: MasterId, complete: bool, request: Work) {
+        if complete {
+            self.schedule(now + self.config.list_repeat, request);
+        } else {
+            info!("re-requesting list from {}", self.masters[id.0].name);
+            self.schedule(now, request);
+        }
+    }
+    fn request_info(&mut self, now: Instant, addr: ServerAddr) {
+        let server = self.servers.get_mut(&addr).unwrap();
+        let mut token: u32 = self.rng.gen();
+        while server
+            .missing_responses
+            .iter()
+            .any(|&t| t as u8 == token as u8)
+        {
+            token = self.rng.gen();
+        }
+        server.missing_responses.push(token);
+        debug!("requesting info from {}", addr);
+        match addr.version {
+            ProtocolVersion::V5 => self.send(addr.addr, &protocol::request_info_5(token as u8)),
+            ProtocolVersion::V6 => {
+                self.send(addr.addr, &protocol::request_info_6_ex(token & 0x00ff_ffff))
+            }
+            ProtocolVersion::V7 => self.send(addr.addr, &protocol::request_token_7(token7(token))),
+        }
+        self.schedule(now + self.config.info_expect, Work::ExpectInfo(addr));
+    }
+    fn expect_info(&mut self, now: Instant, addr: ServerAddr) {
+        let server = match self.servers.get(&addr) {
+            Some(s) => s,
+            None => return,
+        };
+        if server.missing_responses.is_empty() {
+            self.schedule(now + self.config.info_repeat, Work::RequestInfo(addr));
+        } else if server.missing_responses.len() >= self.config.max_missing_responses {
+            info!("missing responses from {}, removing", addr);
+            if let Some(info) = self.servers.remove(&addr).unwrap().info {
+                self.events.push_back(Event::Remove(addr, info));
+            }
+        } else {
+            debug!("re-requesting info from {}", addr);
+            self.schedule(now, Work::RequestInfo(addr));
+        }
+    }
+    fn master_id(&self, addr: Addr) -> Option<MasterId> {
+        self.masters
+            .iter()
+            .position(|m| m.addr == Some(addr) || m.addr_7 == Some(addr))
+            .map(MasterId)
+    }
+    fn master_id_7(&self, addr: Addr, own_token: Token7) -> Option<MasterId> {
+        let id = self.master_id(addr)?;
+        if self.masters[id.0].own_token.map(|t| t.0) != Some(own_token.0) {
+            warn!(
+                "received 0.7 message with invalid token from master {}",
+                self.masters[id.0].name
+            );
+            return None;
+        }
+        Some(id)
+    }
+    fn on_list<I>(&mut self, now: Instant, id: MasterId, version: ProtocolVersion, servers: I)
+    where
+        I: Iterator<Item = Addr>,
+    {
+        for addr in servers {
+            let addr = ServerAddr::new(version, addr);
+            let master = &mut self.masters[id.0];
+            let list = if version == ProtocolVersion::V7 {
+                &mut master.list_7
+            } else {
+                &mut master.list
+            };
+            if !list.insert(addr) {
+                debug!("double-received {}", addr);
+            }
+            self.add_server(now, addr);
+        }
+    }
+    fn on_info(&mut self, addr: ServerAddr, token_matches: impl Fn(u32) -> bool, info: ServerInfo) {
+        let server = match self.servers.get_mut(&addr) {
+            Some(s) => s,
+            None => {
+                debug!("received info from unknown server {}", addr);
+                return;
+            }
+        };
+        if !server.missing_responses.iter().any(|&t| token_matches(t)) {
+            debug!("received info with wrong token from {}", addr);
+            return;
+        }
+        server.missing_responses.clear();
+        server.partial_responses.clear();
+        debug!("received info from {}, {:?}", addr, info);
+        let event = match server.info.replace(info.clone()) {
+            Some(old) => Event::Change(addr, old, info),
+            None => Event::New(addr, info),
+        };
+        self.events.push_back(event);
+    }
+    fn on_partial_info(&mut self, addr: ServerAddr, partial: PartialServerInfo) {
+        let server = match self.servers.get_mut(&addr) {
+            Some(s) => s,
+            None => {
+                debug!("received partial info from unknown server {}", addr);
+                return;
+            }
+        };
+        let token = partial.token();
+        if !server
+            .missing_responses
+            .iter()
+            .any(|&t| (t & 0x00ff_ffff).assert_i32() == token)
+        {
+            debug!("received partial info with wrong token from {}", addr);
+            return;
+        }
+        let responses = &mut server.partial_responses;
+        let index = match responses.iter().position(|r| r.token() == token) {
+            Some(i) => {
+                if let Err(err) = responses[i].merge(partial) {
+                    warn!("incompatible partial info from {}: {:?}", addr, err);
+                    return;
+                }
+                i
+            }
+            None => {
+                responses.push(partial);
+                responses.len() - 1
+            }
+        };
+        if let Some(info) = responses[index].take_info() {
+            self.on_info(addr, |_| true, info);
+        }
+    }
+    /// Processes a received packet.
+    pub fn on_packet(&mut self, now: Instant, from: Addr, data: &[u8]) {
+        use self::ProtocolVersion::*;
+        let response = match protocol::parse_response(data) {
+            Some(r) => r,
+            None => {
+                debug!("received unknown packet from {}", from);
+                return;
+            }
+        };
+        match response {
+            Response::Token7(protocol::Token7Response(own_token, their_token)) => {
+                if let Some(id) = self.master_id_7(from, own_token) {
+                    let master = &self.masters[id.0];
+                    let (count, list) = if !master.nobackcompat {
+                        (
+                            protocol::request_count_7(own_token, their_token).to_vec(),
+                            protocol::request_list_7(own_token, their_token).to_vec(),
+                        )
+                    } else {
+                        (
+                            protocol::request_count_7_nobackcompat(own_token, their_token).to_vec(),
+                            protocol::request_list_7_nobackcompat(own_token, their_token).to_vec(),
+                        )
+                    };
+                    let addr = master.addr_7.unwrap();
+                    self.send(addr, &count);
+                    self.send(addr, &list);
+                    return;
+                }
+                let addr = ServerAddr::new(V7, from);
+                let expected = self.servers.get(&addr).map_or(false, |s| {
+                    s.missing_responses
+                        .iter()
+                        .any(|&t| token7(t).0 == own_token.0)
+                });
+                if expected {
+                    self.send(from, &protocol::request_info_7(own_token, their_token, 0));
+                } else {
+                    debug!("received unexpected 0.7 token from {}", addr);
+                }
+            }
+            Response::Count(protocol::CountResponse(count)) => {
+                if let Some(id) = self.master_id(from) {
+                    self.masters[id.0].count = Some(count);
+                }
+            }
+            Response::Count7(protocol::Count7Response(own_token, _, count)) => {
+                if let Some(id) = self.master_id_7(from, own_token) {
+                    self.masters[id.0].count_7 = Some(count);
+                }
+            }
+            Response::List5(protocol::List5Response(servers)) => {
+                if let Some(id) = self.master_id(from) {
+                    self.on_list(now, id, V5, servers.iter().map(|a| a.unpack()));
+                }
+            }
+            Response::List6(protocol::List6Response(servers)) => {
+                if let Some(id) = self.master_id(from) {
+                    self.on_list(now, id, V6, servers.iter().map(|a| a.unpack()));
+                }
+            }
+            Response::List7(protocol::List7Response(own_token, _, servers)) => {
+                if let Some(id) = self.master_id_7(from, own_token) {
+                    self.on_list(now, id, V7, servers.iter().map(|a| a.unpack()));
+                }
+            }
+            Response::Info5(info) => {
+                if let Some(info) = info.parse() {
+                    let token = info.token;
+                    self.on_info(
+                        ServerAddr::new(V5, from),
+                        |t| (t as u8).i32() == token,
+                        info,
+                    );
+                }
+            }
+            Response::Info6(info) => {
+                if let Some(info) = info.parse() {
+                    let token = info.token;
+                    self.on_info(
+                        ServerAddr::new(V6, from),
+                        |t| (t as u8).i32() == token,
+                        info,
+                    );
+                }
+            }
+            Response::Info6Ddper(info) => {
+                if let Some(info) = info.parse() {
+                    let token = info.token;
+                    self.on_info(
+                        ServerAddr::new(V6, from),
+                        |t| (t as u8).i32() == token,
+                        info,
+                    );
+                }
+            }
+            Response::Info664(_) => {
+                // Not requested, its token is only 8 bits wide.
+                debug!("received unexpected 64-player info from {}", from);
+            }
+            Response::Info6Ex(info) => {
+                if let Some(info) = info.parse() {
+                    self.on_partial_info(ServerAddr::new(V6, from), info);
+                }
+            }
+            Response::Info6ExMore(info) => {
+                if let Some(info) = info.parse() {
+                    self.on_partial_info(ServerAddr::new(V6, from), info);
+                }
+            }
+            Response::Info7(info) => {
+                let protocol::Info7Response(own_token, _, _) = info;
+                if let Some(info) = info.parse() {
+                    self.on_info(
+                        ServerAddr::new(V7, from),
+                        |t| token7(t).0 == own_token.0,
+                        info,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Whether the received server list of a masterserver roughly matches the
+/// count it reported.
+fn is_complete_list(count: Option<u16>, list: &HashSet<ServerAddr>) -> bool {
+    count.map_or(false, |c| c.usize().abs_diff(list.len()) <= 5)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Browser;
+    use super::Config;
+    use super::Event;
+    use super::MasterId;
+    use super::ProtocolVersion;
+    use super::ServerAddr;
+    use super::Transmit;
+    use crate::protocol;
+    use crate::protocol::Addr;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    fn addr(s: &str) -> Addr {
+        super::addr_from_socket_addr(s.parse().unwrap())
+    }
+
+    fn transmits(browser: &mut Browser, now: Instant) -> Vec<Transmit> {
+        std::iter::from_fn(|| browser.poll_transmit(now)).collect()
+    }
+
+    fn info_6(token: u8) -> Vec<u8> {
+        let mut response = protocol::INFO_6.to_vec();
+        response.extend_from_slice(format!("{}\0", token).as_bytes());
+        response.extend_from_slice(b"0.6.4\0name\0map\0DM\0");
+        response.extend_from_slice(b"0\x000\x0016\x000\x0016\x00");
+        response
+    }
+
+    #[test]
+    fn master_list() {
+        let now = Instant::now();
+        let master = addr("192.0.2.1:8300");
+        let server = ServerAddr::new(ProtocolVersion::V6, addr("198.51.100.1:8303"));
+        let mut browser = Browser::new(Config::default(), now);
+        let id = browser.add_master("master".into(), false);
+        assert_eq!(id, MasterId(0));
+        assert!(transmits(&mut browser, now).is_empty());
+        browser.set_master_addr(now, id, master);
+
+        let requests = transmits(&mut browser, now);
+        assert_eq!(requests.len(), 3);
+        assert!(requests.iter().all(|t| t.to == master));
+        assert_eq!(requests[0].data, protocol::request_count());
+        assert_eq!(requests[2].data, protocol::request_list_6());
+
+        let mut count = protocol::COUNT.to_vec();
+        count.extend_from_slice(&[0, 1]);
+        browser.on_packet(now, master, &count);
+        let mut list = protocol::LIST_6.to_vec();
+        list.extend_from_slice(&protocol::IPV4_MAPPING);
+        list.extend_from_slice(&[198, 51, 100, 1, 0x20, 0x6f]);
+        browser.on_packet(now, master, &list);
+        assert_eq!(browser.num_known_servers(), 1);
+        assert_eq!(browser.server(server), None);
+
+        let requests = transmits(&mut browser, now);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].to, server.addr);
+        let request = &requests[0].data;
+        assert_eq!(request.len(), 15);
+        browser.on_packet(now, server.addr, &info_6(request[14]));
+        match browser.poll_event() {
+            Some(Event::New(a, info)) => {
+                assert_eq!(a, server);
+                assert_eq!(info.name.as_str(), "name");
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+        assert!(browser.poll_event().is_none());
+        assert!(browser.server(server).is_some());
+
+        // The next info request is due after `info_repeat`.
+        let to_server = |t: &Transmit| t.to == server.addr;
+        let later = now + Duration::from_secs(1);
+        assert!(!transmits(&mut browser, later).iter().any(to_server));
+        let later = later + Config::default().info_repeat;
+        let requests: Vec<_> = transmits(&mut browser, later)
+            .into_iter()
+            .filter(to_server)
+            .collect();
+        assert_eq!(requests.len(), 1);
+        browser.on_packet(later, server.addr, &info_6(requests[0].data[14]));
+        assert!(matches!(browser.poll_event(), Some(Event::Change(a, _, _)) if a == server));
+    }
+
+    #[test]
+    fn wrong_token() {
+        let now = Instant::now();
+        let server = ServerAddr::new(ProtocolVersion::V6, addr("198.51.100.1:8303"));
+        let mut browser = Browser::new(Config::default(), now);
+        assert!(browser.add_server(now, server));
+        assert!(!browser.add_server(now, server));
+        let requests = transmits(&mut browser, now);
+        assert_eq!(requests.len(), 1);
+        browser.on_packet(now, server.addr, &info_6(requests[0].data[14] ^ 1));
+        assert!(browser.poll_event().is_none());
+        assert_eq!(browser.server(server), None);
+    }
+
+    #[test]
+    fn rate_limit() {
+        let now = Instant::now();
+        let config = Config::default();
+        let mut browser = Browser::new(config.clone(), now);
+        for i in 0..15 {
+            let server =
+                ServerAddr::new(ProtocolVersion::V5, addr(&format!("198.51.100.{}:8303", i)));
+            browser.add_server(now, server);
+        }
+        assert_eq!(transmits(&mut browser, now).len(), 10);
+        assert_eq!(browser.next_timeout(), Some(now + config.infos_period));
+        assert_eq!(transmits(&mut browser, now + config.infos_period).len(), 5);
+    }
+
+    #[test]
+    fn remove() {
+        let now = Instant::now();
+        let config = Config {
+            max_missing_responses: 2,
+            ..Config::default()
+        };
+        let server = ServerAddr::new(ProtocolVersion::V6, addr("198.51.100.1:8303"));
+        let mut browser = Browser::new(config.clone(), now);
+        browser.add_server(now, server);
+        let requests = transmits(&mut browser, now);
+        browser.on_packet(now, server.addr, &info_6(requests[0].data[14]));
+        assert!(matches!(browser.poll_event(), Some(Event::New(..))));
+
+        let mut num_requests = 0;
+        while let Some(now) = browser.next_timeout() {
+            num_requests += transmits(&mut browser, now).len();
+        }
+        assert_eq!(num_requests, config.max_missing_responses);
+        assert!(matches!(browser.poll_event(), Some(Event::Remove(a, _)) if a == server));
+        assert_eq!(browser.num_known_servers(), 0);
+        assert_eq!(browser.next_timeout(), None);
+    }
+
+    #[test]
+    fn restore() {
+        let now = Instant::now();
+        let server = ServerAddr::new(ProtocolVersion::V6, addr("198.51.100.1:8303"));
+        let mut browser = Browser::new(Config::default(), now);
+        browser.add_server(now, server);
+        let requests = transmits(&mut browser, now);
+        browser.on_packet(now, server.addr, &info_6(requests[0].data[14]));
+        assert!(matches!(browser.poll_event(), Some(Event::New(..))));
+        let mut later = now;
+        while transmits(&mut browser, later).is_empty() {
+            later = browser.next_timeout().unwrap();
+        }
+
+        let states: Vec<_> = browser
+            .server_states()
+            .map(|(a, i, t)| (a, i.cloned(), t.to_vec()))
+            .collect();
+        assert_eq!(states.len(), 1);
+        let (addr, info, tokens) = states.into_iter().next().unwrap();
+        assert_eq!(addr, server);
+        assert!(info.is_some());
+        assert_eq!(tokens.len(), 1);
+
+        // Responses to requests of the previous run are still accepted, and
+        // reported as changes.
+        let token = tokens[0] as u8;
+        let mut browser = Browser::new(Config::default(), later);
+        assert!(browser.restore_server(later, addr, info, tokens));
+        assert!(!browser.add_server(later, server));
+        browser.on_packet(later, server.addr, &info_6(token));
+        assert!(matches!(browser.poll_event(), Some(Event::Change(a, _, _)) if a == server));
+        assert!(browser.poll_event().is_none());
+    }
+
+    #[test]
+    fn token_7() {
+        let now = Instant::now();
+        let server = ServerAddr::new(ProtocolVersion::V7, addr("[2001:db8::1]:8303"));
+        let mut browser = Browser::new(Config::default(), now);
+        browser.add_server(now, server);
+        let requests = transmits(&mut browser, now);
+        assert_eq!(requests.len(), 1);
+        let mut own_token = [0; 4];
+        own_token.copy_from_slice(&requests[0].data[8..12]);
+        let their_token = [1, 2, 3, 4];
+
+        let mut token = protocol::TOKEN_7.to_vec();
+        token[3..7].copy_from_slice(&own_token);
+        token.extend_from_slice(&their_token);
+        browser.on_packet(now, server.addr, &token);
+        let requests = transmits(&mut browser, now);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].data,
+            protocol::request_info_7(
+                protocol::Token7(own_token),
+                protocol::Token7(their_token),
+                0
+            ),
+        );
+
+        let mut info = protocol::INFO_7.to_vec();
+        info[1..5].copy_from_slice(&own_token);
+        info[5..9].copy_from_slice(&their_token);
+        info.extend_from_slice(b"\x00version\0name\0hostname\0map\0DM\0\x00\x00\x00\x00\x00\x10");
+        browser.on_packet(now, server.addr, &info);
+        match browser.poll_event() {
+            Some(Event::New(a, info)) => {
+                assert_eq!(a, server);
+                assert_eq!(info.map.as_str(), "map");
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+    }
+}
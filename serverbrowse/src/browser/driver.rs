@@ -0,0 +1,176 @@
+use super::addr_from_socket_addr;
+use super::addr_to_socket_addr;
+use super::Browser;
+use super::Event;
+use super::MasterId;
+use super::ProtocolVersion;
+use super::ServerAddr;
+use crate::json;
+use crate::protocol::MASTERSERVER_PORT;
+use std::future;
+use std::io;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+use tokio::net::lookup_host;
+use tokio::net::UdpSocket;
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tokio::time::sleep_until;
+
+/// Time span for re-resolving masterservers.
+const RESOLVE_REPEAT: Duration = Duration::from_secs(120);
+
+/// Source of server addresses.
+#[derive(Clone, Debug)]
+pub enum Source {
+    /// UDP masterserver, e.g. `master1.teeworlds.com`.
+    Master { domain: String, nobackcompat: bool },
+    /// HTTPS server list, e.g. [`json::DEFAULT_URL`].
+    Https(String),
+}
+
+struct AbortOnDrop(Vec<JoinHandle<()>>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        for task in &self.0 {
+            task.abort();
+        }
+    }
+}
+
+async fn resolve_master(
+    browser: Arc<Mutex<Browser>>,
+    changed: Arc<Notify>,
+    id: MasterId,
+    domain: String,
+) {
+    loop {
+        match lookup_host((&*domain, MASTERSERVER_PORT)).await {
+            Ok(mut addrs) => match addrs.next() {
+                Some(addr) => {
+                    info!("resolved {} to {}", domain, addr);
+                    let mut browser = browser.lock().unwrap();
+                    browser.set_master_addr(Instant::now(), id, addr_from_socket_addr(addr));
+                    changed.notify_one();
+                }
+                None => info!("resolved {}, no address found", domain),
+            },
+            Err(err) => warn!("error while resolving {}: {}", domain, err),
+        }
+        sleep(RESOLVE_REPEAT).await;
+    }
+}
+
+async fn fetch_list(browser: Arc<Mutex<Browser>>, changed: Arc<Notify>, url: String) {
+    let client = reqwest::Client::new();
+    let repeat = browser.lock().unwrap().config().list_repeat;
+    loop {
+        match json::fetch(&client, &url).await {
+            Ok(list) => {
+                debug!("fetched {} servers from {}", list.servers.len(), url);
+                let now = Instant::now();
+                let mut browser = browser.lock().unwrap();
+                for address in list.servers.iter().flat_map(|s| &s.addresses) {
+                    let version = match address.version {
+                        json::Version::V6 => ProtocolVersion::V6,
+                        json::Version::V7 => ProtocolVersion::V7,
+                    };
+                    let addr = addr_from_socket_addr(address.addr);
+                    browser.add_server(now, ServerAddr::new(version, addr));
+                }
+                changed.notify_one();
+            }
+            Err(err) => warn!("{}: {}", url, err),
+        }
+        sleep(repeat).await;
+    }
+}
+
+/// Drives `browser` on a new UDP socket until a socket error occurs, adding
+/// servers from `sources`.
+///
+/// Lock `browser` to query the current server table. Its events are sent to
+/// `events` if given, and discarded otherwise.
+pub async fn run(
+    browser: Arc<Mutex<Browser>>,
+    sources: Vec<Source>,
+    events: Option<mpsc::UnboundedSender<Event>>,
+) -> io::Result<()> {
+    // Prefer a dual-stack socket, fall back to IPv4 only.
+    let (socket, dual_stack) = match UdpSocket::bind("[::]:0").await {
+        Ok(socket) => (socket, true),
+        Err(_) => (UdpSocket::bind("0.0.0.0:0").await?, false),
+    };
+
+    let changed = Arc::new(Notify::new());
+    let mut tasks = Vec::new();
+    for source in sources {
+        let task = match source {
+            Source::Master {
+                domain,
+                nobackcompat,
+            } => {
+                let id = browser
+                    .lock()
+                    .unwrap()
+                    .add_master(domain.clone(), nobackcompat);
+                tokio::spawn(resolve_master(browser.clone(), changed.clone(), id, domain))
+            }
+            Source::Https(url) => tokio::spawn(fetch_list(browser.clone(), changed.clone(), url)),
+        };
+        tasks.push(task);
+    }
+    let _tasks = AbortOnDrop(tasks);
+
+    let mut buf = [0; 2048];
+    loop {
+        let (transmits, next_timeout) = {
+            let now = Instant::now();
+            let mut browser = browser.lock().unwrap();
+            let transmits: Vec<_> = std::iter::from_fn(|| browser.poll_transmit(now)).collect();
+            while let Some(event) = browser.poll_event() {
+                if let Some(events) = &events {
+                    let _ = events.send(event);
+                }
+            }
+            (transmits, browser.next_timeout())
+        };
+        for transmit in transmits {
+            let mut to = addr_to_socket_addr(transmit.to);
+            match to.ip() {
+                IpAddr::V4(ip) if dual_stack => {
+                    to = SocketAddr::new(ip.to_ipv6_mapped().into(), to.port());
+                }
+                IpAddr::V6(_) if !dual_stack => continue,
+                _ => {}
+            }
+            // Unreachable destinations shouldn't stop the browser.
+            if let Err(err) = socket.send_to(&transmit.data, to).await {
+                debug!("error sending to {}: {}", to, err);
+            }
+        }
+        let timeout = async {
+            match next_timeout {
+                Some(time) => sleep_until(time.into()).await,
+                None => future::pending().await,
+            }
+        };
+        select! {
+            result = socket.recv_from(&mut buf) => {
+                let (len, from) = result?;
+                let mut browser = browser.lock().unwrap();
+                browser.on_packet(Instant::now(), addr_from_socket_addr(from), &buf[..len]);
+            }
+            () = timeout => {}
+            () = changed.notified() => {}
+        }
+    }
+}
@@ -0,0 +1,408 @@
+//! Server list of the DDNet HTTPS masterserver, `servers.json`.
+//!
+//! The list is available at [`DEFAULT_URL`]. Game servers register their info
+//! with the masterserver using the `libtw2-register` crate.
+
+use crate::protocol::ClientInfo;
+use crate::protocol::ServerInfo;
+use crate::protocol::ServerInfoVersion;
+use crate::protocol::CLIENTINFO_FLAG_SPECTATOR;
+use crate::protocol::SERVERINFO_FLAG_PASSWORDED;
+use libtw2_common::num::Cast;
+use libtw2_common::str::truncated_arraystring;
+use serde::de;
+use serde::Deserialize as _;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+pub const DEFAULT_URL: &str = "https://master1.ddnet.org/ddnet/15/servers.json";
+
+/// Teeworlds version of a server address.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Version {
+    V6,
+    V7,
+}
+
+impl Version {
+    pub fn scheme(self) -> &'static str {
+        match self {
+            Version::V6 => "tw-0.6+udp",
+            Version::V7 => "tw-0.7+udp",
+        }
+    }
+}
+
+/// Server address, e.g. `tw-0.6+udp://127.0.0.1:8303`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Address {
+    pub version: Version,
+    pub addr: SocketAddr,
+}
+
+#[derive(Debug)]
+pub struct AddressFromStrError(Box<str>);
+
+impl Error for AddressFromStrError {}
+
+impl fmt::Display for AddressFromStrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid server address {:?}", self.0)
+    }
+}
+
+impl FromStr for Address {
+    type Err = AddressFromStrError;
+    fn from_str(s: &str) -> Result<Address, AddressFromStrError> {
+        let err = || AddressFromStrError(s.into());
+        let (scheme, addr) = s.split_once("://").ok_or_else(err)?;
+        let version = match scheme {
+            "tw-0.6+udp" => Version::V6,
+            "tw-0.7+udp" => Version::V7,
+            _ => return Err(err()),
+        };
+        Ok(Address {
+            version,
+            addr: addr.parse().map_err(|_| err())?,
+        })
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}://{}", self.version.scheme(), self.addr)
+    }
+}
+
+impl serde::Serialize for Address {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Address {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Address, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Skips addresses we don't understand, e.g. those of future protocols.
+fn known_addresses<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Address>, D::Error> {
+    let addresses = Vec::<String>::deserialize(deserializer)?;
+    Ok(addresses
+        .iter()
+        .filter_map(|a| match a.parse() {
+            Ok(address) => Some(address),
+            Err(err) => {
+                debug!("{}", err);
+                None
+            }
+        })
+        .collect())
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ServerList {
+    pub servers: Vec<Server>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Server {
+    #[serde(deserialize_with = "known_addresses")]
+    pub addresses: Vec<Address>,
+    /// Continent and country of the server, e.g. `eu:de`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// Community (server group) the server registered with, e.g. `ddnet`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub community: Option<String>,
+    pub info: Info,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Info {
+    pub max_clients: i32,
+    pub max_players: i32,
+    pub passworded: bool,
+    pub game_type: String,
+    pub name: String,
+    pub map: Map,
+    pub version: String,
+    /// ISO 3166-1 numeric country code set by the server operator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<i32>,
+    /// Either `points` or `time`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_score_kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requires_login: Option<bool>,
+    #[serde(default)]
+    pub clients: Vec<Client>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Map {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// CRC32 of the map file as used by Teeworlds, in hexadecimal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tw_crc: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u32>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Client {
+    pub name: String,
+    pub clan: String,
+    pub country: i32,
+    pub score: i32,
+    pub is_player: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub afk: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<i32>,
+}
+
+#[derive(Deserialize)]
+struct RawServerList {
+    servers: Vec<serde_json::Value>,
+}
+
+/// Parses a `servers.json` server list.
+///
+/// Entries that can't be parsed or don't have any known address are skipped,
+/// so that a single misbehaving game server doesn't invalidate the whole
+/// list.
+pub fn parse(data: &[u8]) -> Result<ServerList, serde_json::Error> {
+    let raw: RawServerList = serde_json::from_slice(data)?;
+    let servers = raw
+        .servers
+        .into_iter()
+        .filter_map(|server| match serde_json::from_value::<Server>(server) {
+            Ok(server) if server.addresses.is_empty() => {
+                debug!("skipping server without known addresses");
+                None
+            }
+            Ok(server) => Some(server),
+            Err(err) => {
+                warn!("skipping invalid server: {}", err);
+                None
+            }
+        })
+        .collect();
+    Ok(ServerList { servers })
+}
+
+impl Server {
+    /// Converts the server info to the format of the UDP server info
+    /// protocol, using `ServerInfoVersion::V7` for servers only reachable via
+    /// Teeworlds 0.7, and `ServerInfoVersion::V6Ex` otherwise.
+    pub fn to_server_info(&self) -> ServerInfo {
+        let only_0_7 = self.addresses.iter().all(|a| a.version == Version::V7);
+        let info_version = if only_0_7 {
+            ServerInfoVersion::V7
+        } else {
+            ServerInfoVersion::V6Ex
+        };
+        self.info.to_server_info(info_version)
+    }
+}
+
+impl Info {
+    /// Converts the server info to the format of the UDP server info
+    /// protocol. Strings that are too long are truncated.
+    pub fn to_server_info(&self, info_version: ServerInfoVersion) -> ServerInfo {
+        let mut flags = 0;
+        if self.passworded {
+            flags |= SERVERINFO_FLAG_PASSWORDED;
+        }
+        ServerInfo {
+            info_version,
+            token: 0,
+            version: truncated_arraystring(&self.version),
+            name: truncated_arraystring(&self.name),
+            hostname: None,
+            map: truncated_arraystring(&self.map.name),
+            map_crc: self
+                .map
+                .tw_crc
+                .as_ref()
+                .and_then(|crc| u32::from_str_radix(crc, 16).ok()),
+            map_size: self.map.size,
+            game_type: truncated_arraystring(&self.game_type),
+            flags,
+            progression: None,
+            skill_level: None,
+            num_players: self
+                .clients
+                .iter()
+                .filter(|c| c.is_player)
+                .count()
+                .assert_i32(),
+            max_players: self.max_players,
+            num_clients: self.clients.len().assert_i32(),
+            max_clients: self.max_clients,
+            clients: self.clients.iter().map(Client::to_client_info).collect(),
+        }
+    }
+}
+
+impl Client {
+    pub fn to_client_info(&self) -> ClientInfo {
+        ClientInfo {
+            name: truncated_arraystring(&self.name),
+            clan: truncated_arraystring(&self.clan),
+            country: self.country,
+            score: self.score,
+            flags: if self.is_player {
+                0
+            } else {
+                CLIENTINFO_FLAG_SPECTATOR
+            },
+        }
+    }
+}
+
+#[cfg(feature = "fetch")]
+#[derive(Debug)]
+pub enum FetchError {
+    Http(reqwest::Error),
+    Parse(serde_json::Error),
+}
+
+#[cfg(feature = "fetch")]
+impl Error for FetchError {}
+
+#[cfg(feature = "fetch")]
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FetchError::Http(err) => write!(f, "fetching server list: {}", err),
+            FetchError::Parse(err) => write!(f, "parsing server list: {}", err),
+        }
+    }
+}
+
+/// Downloads and parses the server list from the given URL, usually
+/// [`DEFAULT_URL`].
+#[cfg(feature = "fetch")]
+pub async fn fetch(client: &reqwest::Client, url: &str) -> Result<ServerList, FetchError> {
+    let body = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(FetchError::Http)?
+        .bytes()
+        .await
+        .map_err(FetchError::Http)?;
+    parse(&body).map_err(FetchError::Parse)
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse;
+    use super::Address;
+    use super::Version;
+    use crate::protocol::ServerInfoVersion;
+    use crate::protocol::CLIENTINFO_FLAG_SPECTATOR;
+    use crate::protocol::SERVERINFO_FLAG_PASSWORDED;
+
+    const DDNET: &str = include_str!("../tests/servers_json/ddnet.json");
+    const MASTERSRV: &str = include_str!("../tests/servers_json/mastersrv.json");
+
+    #[test]
+    fn address() {
+        let v6: Address = "tw-0.6+udp://127.0.0.1:8303".parse().unwrap();
+        assert_eq!(v6.version, Version::V6);
+        assert_eq!(v6.addr, "127.0.0.1:8303".parse().unwrap());
+        let v7: Address = "tw-0.7+udp://[2001:db8::1]:8304".parse().unwrap();
+        assert_eq!(v7.version, Version::V7);
+        assert_eq!(v7.to_string(), "tw-0.7+udp://[2001:db8::1]:8304");
+        assert!("tw-0.6+udp://127.0.0.1".parse::<Address>().is_err());
+        assert!("tw-0.6+tcp://127.0.0.1:8303".parse::<Address>().is_err());
+        assert!("127.0.0.1:8303".parse::<Address>().is_err());
+    }
+
+    #[test]
+    fn ddnet() {
+        let list = parse(DDNET.as_bytes()).unwrap();
+        // The entry with invalid info and the one with only unknown
+        // addresses are skipped.
+        assert_eq!(list.servers.len(), 3);
+
+        let ddnet = &list.servers[0];
+        assert_eq!(
+            ddnet.addresses,
+            [
+                "tw-0.6+udp://192.0.2.1:8303".parse().unwrap(),
+                "tw-0.7+udp://192.0.2.1:8303".parse().unwrap(),
+            ],
+        );
+        assert_eq!(ddnet.location.as_deref(), Some("eu:de"));
+        assert_eq!(ddnet.community.as_deref(), Some("ddnet"));
+        assert_eq!(ddnet.info.client_score_kind.as_deref(), Some("time"));
+        assert_eq!(ddnet.info.clients.len(), 3);
+        assert_eq!(ddnet.info.clients[0].afk, Some(false));
+        assert_eq!(ddnet.info.clients[2].team, Some(-1));
+
+        let info = ddnet.to_server_info();
+        assert_eq!(info.info_version, ServerInfoVersion::V6Ex);
+        assert_eq!(&*info.name, "DDNet GER10 - Novice");
+        assert_eq!(&*info.map, "Multeasymap");
+        assert_eq!(info.map_size, Some(72466));
+        assert_eq!(info.num_clients, 3);
+        assert_eq!(info.num_players, 2);
+        assert_eq!(info.max_clients, 64);
+        assert_eq!(info.flags, 0);
+        assert_eq!(&*info.clients[0].name, "nameless tee");
+        assert_eq!(info.clients[0].flags, 0);
+        assert_eq!(info.clients[2].flags, CLIENTINFO_FLAG_SPECTATOR);
+        // Truncated to the maximum name length of the UDP protocol.
+        assert_eq!(&*info.clients[1].name, "a very long nam");
+
+        let vanilla = &list.servers[1];
+        assert_eq!(vanilla.location, None);
+        assert_eq!(vanilla.community, None);
+        let info = vanilla.to_server_info();
+        assert_eq!(info.info_version, ServerInfoVersion::V7);
+        assert_eq!(info.flags, SERVERINFO_FLAG_PASSWORDED);
+        assert_eq!(info.num_clients, 0);
+        assert_eq!(info.map_crc, None);
+
+        // Unknown addresses are dropped, known ones kept.
+        let ipv6 = &list.servers[2];
+        assert_eq!(
+            ipv6.addresses,
+            ["tw-0.6+udp://[2001:db8::1]:8303".parse().unwrap()],
+        );
+    }
+
+    #[test]
+    fn mastersrv() {
+        let list = parse(MASTERSRV.as_bytes()).unwrap();
+        assert_eq!(list.servers.len(), 1);
+        let info = list.servers[0].to_server_info();
+        assert_eq!(&*info.name, "libtw2 test server");
+        assert_eq!(info.map_crc, Some(0xf2159e6e));
+        assert_eq!(info.map_size, Some(5805));
+        assert_eq!(info.clients[0].country, -1);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(parse(b"").is_err());
+        assert!(parse(b"{}").is_err());
+        assert!(parse(br#"{"servers":{}}"#).is_err());
+        assert_eq!(parse(br#"{"servers":[]}"#).unwrap().servers.len(), 0);
+    }
+}
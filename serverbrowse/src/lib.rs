@@ -1,4 +1,6 @@
 #[macro_use]
 extern crate log;
 
+pub mod browser;
+pub mod json;
 pub mod protocol;
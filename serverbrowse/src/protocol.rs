@@ -1,10 +1,13 @@
 use arrayvec::ArrayString;
+use arrayvec::ArrayVec;
 use libtw2_common::num::Cast;
 use libtw2_common::slice;
 use libtw2_common::str::truncated_arraystring;
 use libtw2_common::unwrap_or_return;
+use libtw2_packer::with_packer;
 use libtw2_packer::Unpacker;
 use libtw2_warn::Ignore;
+use std::cmp;
 use std::default::Default;
 use std::fmt;
 use std::mem;
@@ -24,6 +27,10 @@ const PLAYER_MAX_CLAN_LENGTH: usize = 12 - 1;
 const MAX_CLIENTS_5: u32 = 16;
 const MAX_CLIENTS_6_64: u32 = 64;
 const MAX_CLIENTS_7: u32 = 64;
+/// Maximum size of a packet, `NET_MAX_PACKETSIZE`.
+const MAX_PACKET_SIZE: usize = 1400;
+/// Maximum number of packets of DDNet's extended server info.
+const MAX_PACKETS_6_EX: i32 = 64;
 
 pub const MASTERSERVER_PORT: u16 = 8300;
 pub const MASTERSERVER_7_PORT: u16 = 8283;
@@ -58,6 +65,8 @@ pub const COUNT_7: &'static [u8; 17] = b"\x21\xff\xff\xff\xff\xff\xff\xff\xff\xf
 pub const REQUEST_INFO_7: &'static [u8; 17] =
     b"\x21\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xffgie3";
 pub const INFO_7: &'static [u8; 17] = b"\x21\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xffinf3";
+pub const CHALLENGE_7: &'static [u8; 17] =
+    b"\x21\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xffchal";
 
 pub const PACKETFLAG_CONNLESS: u8 = 1 << 6;
 pub const SERVERINFO_FLAG_PASSWORDED: i32 = 1 << 0;
@@ -728,6 +737,189 @@ impl<'a> Info7Response<'a> {
     }
 }
 
+/// Writes server info fields, integers are sent as decimal strings in all
+/// formats but the Teeworlds 0.7 one.
+struct InfoPacker {
+    buf: Vec<u8>,
+    version: ServerInfoVersion,
+}
+
+impl InfoPacker {
+    fn new(header: &[u8], version: ServerInfoVersion) -> InfoPacker {
+        InfoPacker {
+            buf: header.to_vec(),
+            version,
+        }
+    }
+    fn str(&mut self, string: &str) {
+        self.buf.extend(string.bytes().filter(|&b| b != 0));
+        self.buf.push(0);
+    }
+    fn int(&mut self, int: i32) {
+        if self.version != ServerInfoVersion::V7 {
+            self.str(&int.to_string());
+            return;
+        }
+        let mut buf: ArrayVec<[u8; 5]> = ArrayVec::new();
+        with_packer(&mut buf, |mut p| p.write_int(int).unwrap());
+        self.buf.extend_from_slice(&buf);
+    }
+    /// Writes the part of the info up to and including the client counts.
+    fn info_start(&mut self, info: &ServerInfo) {
+        let version = self.version;
+        self.int(info.token);
+        self.str(&info.version);
+        self.str(&info.name);
+        if version.has_hostname() {
+            self.str(info.hostname.as_ref().map(|h| &h[..]).unwrap_or(""));
+        }
+        self.str(&info.map);
+        if version.has_extended_map_info() {
+            self.int(info.map_crc.unwrap_or(0) as i32);
+            self.int(info.map_size.unwrap_or(0).try_i32().unwrap_or(i32::MAX));
+        }
+        self.str(&info.game_type);
+        self.int(info.flags);
+        if version.has_progression() {
+            self.int(info.progression.unwrap_or(0));
+        }
+        if version.has_skill_level() {
+            self.int(info.skill_level.unwrap_or(0));
+        }
+        self.int(info.num_players);
+        self.int(info.max_players);
+        if version.has_extended_player_info() {
+            self.int(info.num_clients);
+            self.int(info.max_clients);
+        }
+    }
+    fn client(&mut self, client: &ClientInfo) {
+        let version = self.version;
+        self.str(&client.name);
+        if version.has_extended_player_info() {
+            self.str(&client.clan);
+            self.int(client.country);
+        }
+        self.int(client.score);
+        if version.has_extended_player_info() {
+            if version.has_full_client_flags() {
+                self.int(client.flags);
+            } else {
+                let is_player = client.flags & CLIENTINFO_FLAG_SPECTATOR == 0;
+                self.int(is_player as i32);
+            }
+        }
+        if version.has_extra_info() {
+            self.str("");
+        }
+    }
+}
+
+/// Encodes the single packet of the formats that don't support splitting the
+/// info. Clients beyond `clients_per_packet` are dropped.
+fn info_single(header: &[u8], version: ServerInfoVersion, info: &ServerInfo) -> Vec<u8> {
+    let max = version.clients_per_packet().unwrap().usize();
+    let mut p = InfoPacker::new(header, version);
+    p.info_start(info);
+    for client in info.clients.iter().take(max) {
+        p.client(client);
+    }
+    p.buf
+}
+
+/// Encodes `info` as `SERVERBROWSE_INFO_5` response, the inverse of
+/// `Info5Response::parse`.
+///
+/// The counts are sent as they are in `info`, only the first
+/// `ServerInfoVersion::V5.clients_per_packet()` clients are included.
+pub fn info_5(info: &ServerInfo) -> Vec<u8> {
+    info_single(INFO_5, ServerInfoVersion::V5, info)
+}
+/// Encodes `info` as `SERVERBROWSE_INFO_6` response, see `info_5`.
+pub fn info_6(info: &ServerInfo) -> Vec<u8> {
+    info_single(INFO_6, ServerInfoVersion::V6, info)
+}
+/// Encodes `info` as DDPer's `SERVERBROWSE_INFO_6` response, see `info_5`.
+pub fn info_6_ddper(info: &ServerInfo) -> Vec<u8> {
+    info_single(INFO_6_DDPER, ServerInfoVersion::V6Ddper, info)
+}
+/// Encodes `info` as `SERVERBROWSE_INFO_64` responses, the inverse of
+/// `Info664Response::parse` and `PartialServerInfo::merge`.
+///
+/// The clients are split across packets according to
+/// `ServerInfoVersion::V664.clients_per_packet()`, clients beyond
+/// `ServerInfoVersion::V664.max_clients()` are dropped. At least one packet
+/// is returned.
+pub fn info_6_64(info: &ServerInfo) -> Vec<Vec<u8>> {
+    let version = ServerInfoVersion::V664;
+    let per_packet = version.clients_per_packet().unwrap().usize();
+    let max = version.max_clients().unwrap().usize();
+    let clients = &info.clients[..cmp::min(info.clients.len(), max)];
+    let mut result = Vec::new();
+    let mut offset = 0;
+    loop {
+        let mut p = InfoPacker::new(INFO_6_64, version);
+        p.info_start(info);
+        p.int(offset.assert_i32());
+        let end = cmp::min(clients.len(), offset + per_packet);
+        for client in &clients[offset..end] {
+            p.client(client);
+        }
+        result.push(p.buf);
+        offset = end;
+        if offset >= clients.len() {
+            break;
+        }
+    }
+    result
+}
+/// Encodes `info` as DDNet's extended info responses, one
+/// `SERVERBROWSE_INFO_EXTENDED` packet, followed by as many
+/// `SERVERBROWSE_INFO_EXTENDED_MORE` packets as needed. The inverse of
+/// `Info6ExResponse::parse`, `Info6ExMoreResponse::parse` and
+/// `PartialServerInfo::merge`.
+///
+/// The clients are split across packets by size. Clients that don't fit into
+/// 64 packets are dropped.
+pub fn info_6_ex(info: &ServerInfo) -> Vec<Vec<u8>> {
+    let version = ServerInfoVersion::V6Ex;
+    let mut result = Vec::new();
+    let mut p = InfoPacker::new(INFO_6_EX, version);
+    p.info_start(info);
+    p.str(""); // extra info
+    let mut packet_no = 0;
+    for client in &info.clients {
+        let start = p.buf.len();
+        p.client(client);
+        if p.buf.len() <= MAX_PACKET_SIZE {
+            continue;
+        }
+        let client = p.buf.split_off(start);
+        packet_no += 1;
+        if packet_no >= MAX_PACKETS_6_EX {
+            warn!("server info doesn't fit into {} packets", MAX_PACKETS_6_EX);
+            p.buf.truncate(start);
+            break;
+        }
+        result.push(mem::replace(&mut p, InfoPacker::new(INFO_6_EX_MORE, version)).buf);
+        p.int(info.token);
+        p.int(packet_no);
+        p.str(""); // extra info
+        p.buf.extend_from_slice(&client);
+    }
+    result.push(p.buf);
+    result
+}
+/// Encodes `info` as `SERVERBROWSE_INFO` response of Teeworlds 0.7, the
+/// inverse of `Info7Response::parse`. The tokens are in the same order as in
+/// `Info7Response`, see `info_5` for the clients.
+pub fn info_7(own_token: Token7, their_token: Token7, info: &ServerInfo) -> Vec<u8> {
+    let mut header = *INFO_7;
+    header[1..5].copy_from_slice(&own_token.0);
+    header[5..9].copy_from_slice(&their_token.0);
+    info_single(&header, ServerInfoVersion::V7, info)
+}
+
 #[derive(Copy, Clone)]
 pub struct Info5Response<'a>(pub &'a [u8]);
 #[derive(Copy, Clone)]
@@ -807,6 +999,28 @@ fn parse_count(data: &[u8]) -> Option<u16> {
     Some(((data[0] as u16) << 8) | (data[1] as u16))
 }
 
+/// Returns the payload of a challenge sent by the HTTPS masterserver to a
+/// registering server, using either the Teeworlds 0.6 or 0.7 packet format.
+pub fn parse_challenge(data: &[u8]) -> Option<&[u8]> {
+    if let Some(payload) = data.strip_prefix(CHALLENGE_6) {
+        return Some(payload);
+    }
+    if data.len() < CHALLENGE_7.len() {
+        return None;
+    }
+    let (header, payload) = data.split_at(CHALLENGE_7.len());
+    let mut header: [u8; 17] = header.try_into().unwrap();
+    // Ignore the tokens.
+    for b in &mut header[1..9] {
+        *b = 0xff;
+    }
+    if &header == CHALLENGE_7 {
+        Some(payload)
+    } else {
+        None
+    }
+}
+
 pub fn parse_response(data: &[u8]) -> Option<Response<'_>> {
     match data.first() {
         Some(0x04) => {
@@ -987,16 +1201,41 @@ impl Addr6Packed {
 
 #[cfg(test)]
 mod test {
+    use super::parse_response;
     use super::ClientInfo;
     use super::Info6ExMoreResponse;
     use super::Info6ExResponse;
     use super::Info6Response;
     use super::Info7Response;
+    use super::PartialServerInfo;
+    use super::Response;
     use super::ServerInfo;
     use super::ServerInfoVersion;
     use super::Token7;
     use super::CLIENTINFO_FLAG_SPECTATOR;
+    use arrayvec::Array;
+    use arrayvec::ArrayString;
+    use libtw2_common::num::Cast;
     use libtw2_common::str::truncated_arraystring as b;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn parse_challenge() {
+        use super::parse_challenge;
+        assert_eq!(
+            parse_challenge(b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\xffchalsecret\0"),
+            Some(&b"secret\0"[..])
+        );
+        assert_eq!(
+            parse_challenge(b"\x21\x01\x02\x03\x04\x05\x06\x07\x08\xff\xff\xff\xffchalsecret\0"),
+            Some(&b"secret\0"[..])
+        );
+        assert_eq!(
+            parse_challenge(b"\x21\x01\x02\x03\x04\x05\x06\x07\x08\xff\xff\xff\xffinf3"),
+            None
+        );
+        assert_eq!(parse_challenge(b"\x21chal"), None);
+    }
 
     #[test]
     fn parse_info_v6_real_world() {
@@ -1208,4 +1447,176 @@ mod test {
             Some(info),
         );
     }
+
+    type Strings = (String, String, String, String, String);
+    type Ints = (i32, i32, i32, u32, u32, u32);
+    type Clients = Vec<(String, String, i32, i32, i32)>;
+
+    fn s<A: Array<Item = u8> + Copy>(s: &str) -> ArrayString<A> {
+        b(&s.replace('\0', ""))
+    }
+
+    /// Builds a server info that survives a round trip through the given
+    /// format.
+    fn info(
+        version: ServerInfoVersion,
+        token: i32,
+        strings: Strings,
+        ints: Ints,
+        clients: Clients,
+    ) -> ServerInfo {
+        let (flags, progression, skill_level, map_crc, map_size, extra) = ints;
+        let max_clients = version.max_clients().map(|m| m.usize());
+        let per_packet = version.clients_per_packet().map(|m| m.usize());
+        let limit = per_packet.or(max_clients).unwrap_or(usize::MAX);
+        let mut clients: Vec<_> = clients
+            .into_iter()
+            .take(limit)
+            .map(|(name, clan, country, score, flags)| {
+                if !version.has_extended_player_info() {
+                    return ClientInfo {
+                        name: s(&name),
+                        country: -1,
+                        score,
+                        ..ClientInfo::default()
+                    };
+                }
+                ClientInfo {
+                    name: s(&name),
+                    clan: s(&clan),
+                    country,
+                    score,
+                    flags: if version.has_full_client_flags() {
+                        flags
+                    } else {
+                        flags & CLIENTINFO_FLAG_SPECTATOR
+                    },
+                }
+            })
+            .collect();
+        clients.sort();
+        let num_clients = clients.len();
+        let num_players = clients
+            .iter()
+            .filter(|c| c.flags & CLIENTINFO_FLAG_SPECTATOR == 0)
+            .count();
+        let max_clients = num_clients
+            + extra.usize() % (max_clients.unwrap_or(num_clients + 100) - num_clients + 1);
+        let max_players = if version.has_extended_player_info() {
+            num_players + extra.usize() % (max_clients - num_players + 1)
+        } else {
+            max_clients
+        };
+        let (version_, name, hostname, map, game_type) = strings;
+        ServerInfo {
+            info_version: version,
+            token,
+            version: s(&version_),
+            name: s(&name),
+            hostname: Some(s(&hostname)).filter(|_| version.has_hostname()),
+            map: s(&map),
+            map_crc: Some(map_crc).filter(|_| version.has_extended_map_info()),
+            map_size: Some(map_size & 0x7fff_ffff).filter(|_| version.has_extended_map_info()),
+            game_type: s(&game_type),
+            flags,
+            progression: Some(progression).filter(|_| version.has_progression()),
+            skill_level: Some(skill_level).filter(|_| version.has_skill_level()),
+            num_players: num_players.assert_i32(),
+            max_players: max_players.assert_i32(),
+            num_clients: num_clients.assert_i32(),
+            max_clients: max_clients.assert_i32(),
+            clients,
+        }
+    }
+
+    /// Parses the packets of a server info, merging them if necessary.
+    fn parse(packets: &[Vec<u8>]) -> Option<ServerInfo> {
+        let mut result: Option<PartialServerInfo> = None;
+        for packet in packets {
+            let partial = match parse_response(packet)? {
+                Response::Info5(r) if packets.len() == 1 => return r.parse(),
+                Response::Info6(r) if packets.len() == 1 => return r.parse(),
+                Response::Info6Ddper(r) if packets.len() == 1 => return r.parse(),
+                Response::Info7(r) if packets.len() == 1 => return r.parse(),
+                Response::Info664(r) => r.parse()?,
+                Response::Info6Ex(r) => r.parse()?,
+                Response::Info6ExMore(r) => r.parse()?,
+                _ => return None,
+            };
+            match &mut result {
+                Some(r) => r.merge(partial).ok()?,
+                None => result = Some(partial),
+            }
+        }
+        result?.take_info()
+    }
+
+    fn roundtrip<F>(
+        version: ServerInfoVersion,
+        encode: F,
+        token: i32,
+        strings: Strings,
+        ints: Ints,
+        clients: Clients,
+    ) -> bool
+    where
+        F: FnOnce(&ServerInfo) -> Vec<Vec<u8>>,
+    {
+        let info = info(version, token, strings, ints, clients);
+        let packets = encode(&info);
+        packets.iter().all(|p| p.len() <= super::MAX_PACKET_SIZE)
+            && parse(&packets).as_ref() == Some(&info)
+    }
+
+    quickcheck! {
+        fn info_5_roundtrip(token: i32, strings: Strings, ints: Ints, clients: Clients) -> bool {
+            let encode = |i: &_| vec![super::info_5(i)];
+            roundtrip(ServerInfoVersion::V5, encode, token, strings, ints, clients)
+        }
+        fn info_6_roundtrip(token: i32, strings: Strings, ints: Ints, clients: Clients) -> bool {
+            let encode = |i: &_| vec![super::info_6(i)];
+            roundtrip(ServerInfoVersion::V6, encode, token, strings, ints, clients)
+        }
+        fn info_6_ddper_roundtrip(token: i32, strings: Strings, ints: Ints, clients: Clients) -> bool {
+            let encode = |i: &_| vec![super::info_6_ddper(i)];
+            roundtrip(ServerInfoVersion::V6Ddper, encode, token, strings, ints, clients)
+        }
+        fn info_6_64_roundtrip(token: i32, strings: Strings, ints: Ints, clients: Clients) -> bool {
+            roundtrip(ServerInfoVersion::V664, super::info_6_64, token, strings, ints, clients)
+        }
+        fn info_6_ex_roundtrip(token: i32, strings: Strings, ints: Ints, clients: Clients) -> bool {
+            roundtrip(ServerInfoVersion::V6Ex, super::info_6_ex, token, strings, ints, clients)
+        }
+        fn info_7_roundtrip(token: i32, strings: Strings, ints: Ints, clients: Clients) -> bool {
+            let encode = |i: &_| vec![super::info_7(Token7([1, 2, 3, 4]), Token7([5, 6, 7, 8]), i)];
+            roundtrip(ServerInfoVersion::V7, encode, token, strings, ints, clients)
+        }
+    }
+
+    #[test]
+    fn info_split() {
+        let client = ClientInfo {
+            name: b("fifteen letters"),
+            clan: b("eleven clan"),
+            country: -1,
+            score: 0,
+            flags: 0,
+        };
+        let info = ServerInfo {
+            num_players: 64,
+            max_players: 64,
+            num_clients: 64,
+            max_clients: 64,
+            clients: vec![client; 64],
+            ..ServerInfo::default()
+        };
+        assert_eq!(super::info_6_64(&info).len(), 3);
+        assert_eq!(super::info_6_64(&ServerInfo::default()).len(), 1);
+        assert_eq!(super::info_6_ex(&info).len(), 2);
+        assert_eq!(super::info_6_ex(&ServerInfo::default()).len(), 1);
+        let mut info_6 = info.clone();
+        info_6.clients.truncate(16);
+        info_6.info_version = ServerInfoVersion::V6;
+        assert_eq!(super::info_6(&info), super::info_6(&info_6));
+    }
 }
@@ -0,0 +1,58 @@
+#![cfg(feature = "browser")]
+
+use libtw2_serverbrowse::browser;
+use libtw2_serverbrowse::browser::Browser;
+use libtw2_serverbrowse::browser::Config;
+use libtw2_serverbrowse::browser::Event;
+use libtw2_serverbrowse::browser::ProtocolVersion;
+use libtw2_serverbrowse::browser::ServerAddr;
+use libtw2_serverbrowse::protocol;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn run() {
+    // Stand-in for a Teeworlds 0.6 server answering info requests.
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let server = ServerAddr::new(
+        ProtocolVersion::V6,
+        browser::addr_from_socket_addr(socket.local_addr().unwrap()),
+    );
+    tokio::spawn(async move {
+        let mut buf = [0; 1024];
+        loop {
+            let (len, from) = socket.recv_from(&mut buf).await.unwrap();
+            assert_eq!(len, 15);
+            let mut response = protocol::INFO_6.to_vec();
+            response.extend_from_slice(format!("{}\0", buf[14]).as_bytes());
+            response.extend_from_slice(b"0.6.4\0libtw2 test server\0dm1\0DM\0");
+            response.extend_from_slice(b"0\x000\x0016\x000\x0016\x00");
+            socket.send_to(&response, from).await.unwrap();
+        }
+    });
+
+    let mut browser = Browser::new(Config::default(), Instant::now());
+    browser.add_server(Instant::now(), server);
+    let browser = Arc::new(Mutex::new(browser));
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    tokio::spawn(browser::run(browser.clone(), Vec::new(), Some(sender)));
+
+    let event = timeout(Duration::from_secs(10), receiver.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    match event {
+        Event::New(addr, info) => {
+            assert_eq!(addr, server);
+            assert_eq!(info.name.as_str(), "libtw2 test server");
+        }
+        e => panic!("unexpected event {:?}", e),
+    }
+    let browser = browser.lock().unwrap();
+    assert_eq!(browser.server(server).unwrap().map.as_str(), "dm1");
+}
@@ -1,11 +1,13 @@
 use libtw2_serverbrowse::protocol;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
 
 use std::fmt;
 use std::net;
 use std::net::IpAddr;
 
 /// Protocol version of the `SERVERBROWSE_GETINFO` packet.
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub enum ProtocolVersion {
     /// `SERVERBROWSE_GETINFO_5`.
     V5,
@@ -28,5 +28,7 @@ pub const LIST_EXPECT_MS: Duration = Duration(5_000);
 pub const LIST_REPEAT_MS: Duration = Duration(30_000);
 /// Time span for re-resolving master servers.
 pub const RESOLVE_REPEAT_MS: Duration = Duration(120_000);
+/// Time span after which the state is saved, if enabled.
+pub const STATE_SAVE_MS: Duration = Duration(60_000);
 /// Sleep time in the main loop.
 pub const SLEEP_MS: Duration = Duration(5);
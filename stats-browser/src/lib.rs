@@ -1,5 +1,3 @@
-#![cfg(not(test))]
-
 #[macro_use]
 extern crate log;
 
@@ -9,13 +7,11 @@ pub use self::stats_browser::StatsBrowserCb;
 pub mod addr;
 pub mod base64;
 pub mod config;
-pub mod entry;
-pub mod hashmap_ext;
 pub mod lookup;
 pub mod socket;
+pub mod state;
 pub mod stats_browser;
 pub mod time;
 pub mod tracker_fstd;
 pub mod tracker_json;
-pub mod vec_map;
-pub mod work_queue;
+pub mod tracker_sessions;
@@ -6,12 +6,17 @@ use clap::Arg;
 use clap::Values;
 use libtw2_stats_browser::tracker_fstd;
 use libtw2_stats_browser::tracker_json;
+use libtw2_stats_browser::tracker_sessions;
 use libtw2_stats_browser::StatsBrowser;
 use libtw2_stats_browser::StatsBrowserCb;
 use std::collections::HashSet;
 use uuid::Uuid;
 
-fn run_browser<T: StatsBrowserCb>(tracker: &mut T, masters: Vec<(String, bool)>) {
+fn run_browser<T: StatsBrowserCb>(
+    tracker: &mut T,
+    masters: Vec<(String, bool)>,
+    state: Option<String>,
+) {
     let browser = if masters.is_empty() {
         StatsBrowser::new(tracker)
     } else {
@@ -23,6 +28,11 @@ fn run_browser<T: StatsBrowserCb>(tracker: &mut T, masters: Vec<(String, bool)>)
         })
     };
     if let Some(mut browser) = browser {
+        if let Some(state) = state {
+            if let Err(e) = browser.persist_state(state.clone()) {
+                panic!("Failed to restore state from {}: {}", state, e);
+            }
+        }
         browser.run();
     } else {
         panic!("Failed to bind socket.");
@@ -44,6 +54,7 @@ fn main() {
             .default_value("fstd")
             .possible_value("fstd")
             .possible_value("json")
+            .possible_value("sessions")
             .help("Output format")
         )
         .arg(Arg::with_name("filename")
@@ -53,6 +64,13 @@ fn main() {
             .default_value("dump.json")
             .help("Output filename (only used for json tracker)")
         )
+        .arg(Arg::with_name("prefix")
+            .long("prefix")
+            .takes_value(true)
+            .value_name("PREFIX")
+            .default_value("sessions")
+            .help("Output filename prefix (only used for sessions tracker), finished player sessions are appended to PREFIX-YYYY-MM-DD.csv, one file per day (UTC)")
+        )
         .arg(Arg::with_name("locations")
             .long("locations")
             .takes_value(true)
@@ -65,6 +83,12 @@ fn main() {
             .value_name("SEED")
             .help("UUID seed to use for fake secrets of the reported servers (only used for json tracker, useful if you want to merge output of multiple stats_browser instances)")
         )
+        .arg(Arg::with_name("state")
+            .long("state")
+            .takes_value(true)
+            .value_name("STATE")
+            .help("State filename, the server table is restored from it on startup and saved to it periodically, so restarts don't show up as all servers and players leaving and joining")
+        )
         .arg(Arg::with_name("master")
             .long("master")
             .takes_value(true)
@@ -110,11 +134,13 @@ fn main() {
         );
     }
 
+    let state = matches.value_of("state").map(String::from);
+
     match matches.value_of("format").unwrap() {
         "fstd" => {
             let mut tracker = tracker_fstd::Tracker::new();
             tracker.start();
-            run_browser(&mut tracker, masters);
+            run_browser(&mut tracker, masters, state);
         }
         "json" => {
             let filename = String::from(matches.value_of("filename").unwrap());
@@ -126,7 +152,13 @@ fn main() {
             };
             let mut tracker = tracker_json::Tracker::new(filename, locations, seed);
             tracker.start();
-            run_browser(&mut tracker, masters);
+            run_browser(&mut tracker, masters, state);
+        }
+        "sessions" => {
+            let prefix = String::from(matches.value_of("prefix").unwrap());
+            let mut tracker = tracker_sessions::Tracker::new(prefix);
+            tracker.start();
+            run_browser(&mut tracker, masters, state);
         }
         _ => unreachable!(),
     }
@@ -0,0 +1,348 @@
+//! State of the stats browser that is kept across restarts.
+//!
+//! The state is saved as JSON lines, one line per server, sorted by address,
+//! so that consecutive snapshots can be diffed. Clients are stored as compact
+//! arrays instead of objects.
+
+use crate::addr::Addr;
+use crate::addr::ProtocolVersion;
+use crate::addr::ServerAddr;
+use arrayvec::ArrayString;
+use libtw2_serverbrowse::protocol::ClientInfo;
+use libtw2_serverbrowse::protocol::ServerInfo;
+use libtw2_serverbrowse::protocol::ServerInfoVersion;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::process;
+
+#[derive(Deserialize, Serialize)]
+#[serde(remote = "ServerInfoVersion")]
+enum ServerInfoVersionDef {
+    V5,
+    V6,
+    V6Ddper,
+    V664,
+    V6Ex,
+    V7,
+}
+
+/// Saved state of a server.
+#[derive(Deserialize, Serialize)]
+pub struct Server {
+    pub version: ProtocolVersion,
+    pub addr: SocketAddr,
+    /// Tokens of the unanswered info requests.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<Info>,
+}
+
+/// Last info of a server.
+#[derive(Deserialize, Serialize)]
+pub struct Info {
+    #[serde(with = "ServerInfoVersionDef")]
+    pub info_version: ServerInfoVersion,
+    pub version: ArrayString<[u8; 32]>,
+    pub name: ArrayString<[u8; 64]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<ArrayString<[u8; 64]>>,
+    pub map: ArrayString<[u8; 32]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map_crc: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map_size: Option<u32>,
+    pub game_type: ArrayString<[u8; 32]>,
+    pub flags: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progression: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skill_level: Option<i32>,
+    pub num_players: i32,
+    pub max_players: i32,
+    pub num_clients: i32,
+    pub max_clients: i32,
+    /// Sorted like `ServerInfo::clients`.
+    pub clients: Vec<Client>,
+}
+
+/// Name, clan, country, score and flags of a client.
+#[derive(Deserialize, Serialize)]
+pub struct Client(
+    pub ArrayString<[u8; 15]>,
+    pub ArrayString<[u8; 11]>,
+    pub i32,
+    pub i32,
+    pub i32,
+);
+
+impl Server {
+    pub fn new(addr: ServerAddr, info: Option<&ServerInfo>, tokens: &[u32]) -> Server {
+        Server {
+            version: addr.version,
+            addr: addr.addr.to_socket_addr(),
+            tokens: tokens.to_vec(),
+            info: info.map(Info::from),
+        }
+    }
+    pub fn server_addr(&self) -> ServerAddr {
+        ServerAddr::new(self.version, Addr::from_socket_addr(self.addr))
+    }
+}
+
+impl<'a> From<&'a ServerInfo> for Info {
+    fn from(i: &'a ServerInfo) -> Info {
+        Info {
+            info_version: i.info_version,
+            version: i.version,
+            name: i.name,
+            hostname: i.hostname,
+            map: i.map,
+            map_crc: i.map_crc,
+            map_size: i.map_size,
+            game_type: i.game_type,
+            flags: i.flags,
+            progression: i.progression,
+            skill_level: i.skill_level,
+            num_players: i.num_players,
+            max_players: i.max_players,
+            num_clients: i.num_clients,
+            max_clients: i.max_clients,
+            clients: i
+                .clients
+                .iter()
+                .map(|c| Client(c.name, c.clan, c.country, c.score, c.flags))
+                .collect(),
+        }
+    }
+}
+
+impl From<Info> for ServerInfo {
+    fn from(i: Info) -> ServerInfo {
+        ServerInfo {
+            info_version: i.info_version,
+            token: 0,
+            version: i.version,
+            name: i.name,
+            hostname: i.hostname,
+            map: i.map,
+            map_crc: i.map_crc,
+            map_size: i.map_size,
+            game_type: i.game_type,
+            flags: i.flags,
+            progression: i.progression,
+            skill_level: i.skill_level,
+            num_players: i.num_players,
+            max_players: i.max_players,
+            num_clients: i.num_clients,
+            max_clients: i.max_clients,
+            clients: i
+                .clients
+                .into_iter()
+                .map(|Client(name, clan, country, score, flags)| ClientInfo {
+                    name,
+                    clan,
+                    country,
+                    score,
+                    flags,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Reads the state saved by `save`. Returns an empty state if the file
+/// doesn't exist.
+pub fn load(filename: &str) -> io::Result<Vec<Server>> {
+    let file = match File::open(filename) {
+        Ok(f) => f,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut result = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let server = serde_json::from_str(&line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        result.push(server);
+    }
+    Ok(result)
+}
+
+/// Atomically replaces the state saved in `filename`.
+pub fn save(filename: &str, servers: &mut [Server]) -> io::Result<()> {
+    servers.sort_unstable_by_key(|s| s.server_addr());
+    let temp_filename = format!("{}.tmp.{}", filename, process::id());
+    {
+        let mut temp_file = BufWriter::new(File::create(&temp_filename)?);
+        for server in &*servers {
+            serde_json::to_writer(&mut temp_file, server)?;
+            temp_file.write_all(b"\n")?;
+        }
+        temp_file.flush()?;
+        // Drop the temporary file.
+    }
+    fs::rename(&temp_filename, filename)
+}
+
+#[cfg(test)]
+mod test {
+    use super::load;
+    use super::save;
+    use super::Server;
+    use crate::addr::Addr;
+    use crate::addr::ProtocolVersion;
+    use crate::addr::ServerAddr;
+    use arrayvec::ArrayString;
+    use libtw2_serverbrowse::browser;
+    use libtw2_serverbrowse::browser::Browser;
+    use libtw2_serverbrowse::browser::Event;
+    use libtw2_serverbrowse::protocol;
+    use libtw2_serverbrowse::protocol::ClientInfo;
+    use libtw2_serverbrowse::protocol::ServerInfo;
+    use libtw2_serverbrowse::protocol::ServerInfoVersion;
+    use std::fs;
+    use std::time::Instant;
+
+    fn addr(version: ProtocolVersion, addr: &str) -> ServerAddr {
+        ServerAddr::new(version, Addr::from_socket_addr(addr.parse().unwrap()))
+    }
+
+    fn client(name: &str, score: i32, flags: i32) -> ClientInfo {
+        ClientInfo {
+            name: ArrayString::from(name).unwrap(),
+            clan: ArrayString::from("clan").unwrap(),
+            country: -1,
+            score,
+            flags,
+        }
+    }
+
+    fn info(info_version: ServerInfoVersion, clients: Vec<ClientInfo>) -> ServerInfo {
+        let mut info = ServerInfo {
+            info_version,
+            version: ArrayString::from("0.6.4").unwrap(),
+            name: ArrayString::from("test server").unwrap(),
+            map: ArrayString::from("dm1").unwrap(),
+            game_type: ArrayString::from("DM").unwrap(),
+            num_players: clients.len() as i32,
+            max_players: 16,
+            num_clients: clients.len() as i32,
+            max_clients: 16,
+            clients,
+            ..ServerInfo::default()
+        };
+        info.sort_clients();
+        info
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let filename = dir.path().join("state.json");
+        let filename = filename.to_str().unwrap();
+        assert!(load(filename).unwrap().is_empty());
+
+        let v7 = ServerInfo {
+            hostname: Some(ArrayString::from("example.com").unwrap()),
+            skill_level: Some(1),
+            ..info(
+                ServerInfoVersion::V7,
+                vec![client("nameless tee", 3, protocol::CLIENTINFO_FLAG_BOT)],
+            )
+        };
+        let ex = ServerInfo {
+            map_crc: Some(0xf2159e6e),
+            map_size: Some(5805),
+            ..info(
+                ServerInfoVersion::V6Ex,
+                vec![client("brainless tee", 0, 0), client("(1)tee", -5, 1)],
+            )
+        };
+        let servers = vec![
+            (
+                addr(ProtocolVersion::V7, "[2001:db8::1]:8303"),
+                Some(v7),
+                vec![],
+            ),
+            (
+                addr(ProtocolVersion::V6, "198.51.100.1:8303"),
+                Some(ex),
+                vec![0x42],
+            ),
+            (
+                addr(ProtocolVersion::V5, "192.0.2.1:8303"),
+                None,
+                vec![1, 2],
+            ),
+        ];
+        let mut saved: Vec<_> = servers
+            .iter()
+            .map(|(addr, info, tokens)| Server::new(*addr, info.as_ref(), tokens))
+            .collect();
+        save(filename, &mut saved).unwrap();
+        // One line per server, and no leftover temporary file.
+        assert_eq!(fs::read_to_string(filename).unwrap().lines().count(), 3);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let loaded = load(filename).unwrap();
+        let loaded: Vec<_> = loaded
+            .into_iter()
+            .map(|s| (s.server_addr(), s.info.map(ServerInfo::from), s.tokens))
+            .collect();
+        let mut expected = servers;
+        expected.sort_by_key(|&(addr, _, _)| addr);
+        assert_eq!(loaded, expected);
+    }
+
+    #[test]
+    fn restored_server_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let filename = dir.path().join("state.json");
+        let filename = filename.to_str().unwrap();
+        let server = addr(ProtocolVersion::V6, "198.51.100.1:8303");
+        let old = info(
+            ServerInfoVersion::V6,
+            vec![client("nameless tee", 1, 0), client("brainless tee", 2, 0)],
+        );
+        save(filename, &mut [Server::new(server, Some(&old), &[0x42])]).unwrap();
+
+        let now = Instant::now();
+        let mut browser = Browser::new(browser::Config::default(), now);
+        for s in load(filename).unwrap() {
+            let addr = browser::ServerAddr::new(
+                browser::ProtocolVersion::V6,
+                s.server_addr().addr.to_srvbrowse_addr(),
+            );
+            assert!(browser.restore_server(now, addr, s.info.map(ServerInfo::from), s.tokens));
+        }
+
+        // Answer to a request of the previous run.
+        let new = ServerInfo {
+            token: 0x42,
+            ..info(ServerInfoVersion::V6, vec![client("nameless tee", 5, 0)])
+        };
+        let response = protocol::info_6(&new);
+        browser.on_packet(now, server.addr.to_srvbrowse_addr(), &response);
+        match browser.poll_event() {
+            Some(Event::Change(addr, o, n)) => {
+                assert_eq!(addr.addr, server.addr.to_srvbrowse_addr());
+                assert_eq!(o, old);
+                assert_eq!(n, new);
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert!(browser.poll_event().is_none());
+    }
+}
@@ -164,6 +164,18 @@ impl StatsBrowserCb for Tracker {
         print_server_remove(addr, last);
         self.server_count -= 1;
     }
+
+    fn on_server_restore(&mut self, addr: ServerAddr, info: &ServerInfo) {
+        // The server and its players were already reported by the previous
+        // run, only count them.
+        let addr = LogAddr::new(addr, info);
+        if Tracker::server_ignore(addr) {
+            return;
+        }
+        let players = info.clients.iter().filter(|c| !player_ignore(addr, c));
+        self.player_count += players.count() as u32;
+        self.server_count += 1;
+    }
 }
 
 fn print_iter<'a, I: Iterator<Item = &'a (dyn fmt::Display + 'a)>>(command: &str, args: I) {
@@ -362,4 +362,7 @@ impl StatsBrowserCb for Tracker {
         let mut servers = self.servers.lock().unwrap();
         assert!(servers.remove(&addr).is_some());
     }
+    fn on_server_restore(&mut self, addr: ServerAddr, info: &ServerInfo) {
+        self.on_server_new(addr, info);
+    }
 }
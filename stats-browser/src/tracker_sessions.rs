@@ -0,0 +1,450 @@
+//! Tracker aggregating the player list changes into player sessions.
+//!
+//! A session starts when a player is first seen on a server and ends when the
+//! player is missing from a server response, or the server goes away. Since
+//! servers are only polled periodically, the join and leave times are only
+//! known up to the refresh interval, so both bounds are recorded: a player
+//! joined after the previous response of the server and before the response
+//! they appeared in.
+//!
+//! Finished sessions are appended to CSV files, one per UTC day, named
+//! `<prefix>-<YYYY-MM-DD>.csv`.
+
+use crate::addr::ServerAddr;
+use crate::StatsBrowserCb;
+use arrayvec::ArrayString;
+use libtw2_serverbrowse::protocol::ClientInfo;
+use libtw2_serverbrowse::protocol::ServerInfo;
+use serde_derive::Serialize;
+use std::collections::hash_map;
+use std::collections::HashMap;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::mem;
+
+/// Finished player session, one row of the CSV output.
+///
+/// Times are in seconds since the Unix epoch. `join_earliest` is empty if the
+/// player was already there when the server was first seen.
+#[derive(Serialize)]
+struct Session {
+    addr: String,
+    name: ArrayString<[u8; 15]>,
+    clan: ArrayString<[u8; 11]>,
+    country: i32,
+    join_earliest: Option<i64>,
+    join_latest: i64,
+    leave_earliest: i64,
+    leave_latest: i64,
+    /// Lower bound of the time spent on the server,
+    /// `leave_earliest - join_latest`.
+    playtime: i64,
+    server_name: ArrayString<[u8; 64]>,
+    game_type: ArrayString<[u8; 32]>,
+    /// Maps played during the session, separated by spaces.
+    maps: String,
+    /// Previous clans of the player during the session, separated by spaces.
+    clan_changes: String,
+    score_start: i32,
+    score_end: i32,
+    score_max: i32,
+}
+
+struct Player {
+    clan: ArrayString<[u8; 11]>,
+    country: i32,
+    join_earliest: Option<i64>,
+    join_latest: i64,
+    maps: Vec<ArrayString<[u8; 32]>>,
+    clans: Vec<ArrayString<[u8; 11]>>,
+    score_start: i32,
+    score_end: i32,
+    score_max: i32,
+}
+
+struct Server {
+    /// Time of the last response of the server.
+    last_response: i64,
+    players: HashMap<ArrayString<[u8; 15]>, Player>,
+}
+
+struct Output {
+    date: String,
+    writer: csv::Writer<File>,
+}
+
+pub struct Tracker {
+    prefix: String,
+    output: Option<Output>,
+    servers: HashMap<ServerAddr, Server>,
+}
+
+impl Player {
+    fn new(
+        join_earliest: Option<i64>,
+        now: i64,
+        map: ArrayString<[u8; 32]>,
+        info: &ClientInfo,
+    ) -> Player {
+        Player {
+            clan: info.clan,
+            country: info.country,
+            join_earliest,
+            join_latest: now,
+            maps: vec![map],
+            clans: Vec::new(),
+            score_start: info.score,
+            score_end: info.score,
+            score_max: info.score,
+        }
+    }
+    fn update(&mut self, map: ArrayString<[u8; 32]>, info: &ClientInfo) {
+        if self.maps.last() != Some(&map) {
+            self.maps.push(map);
+        }
+        if self.clan != info.clan {
+            self.clans.push(mem::replace(&mut self.clan, info.clan));
+        }
+        self.country = info.country;
+        self.score_end = info.score;
+        self.score_max = self.score_max.max(info.score);
+    }
+    fn finish(
+        self,
+        addr: ServerAddr,
+        name: ArrayString<[u8; 15]>,
+        leave_earliest: i64,
+        leave_latest: i64,
+        info: &ServerInfo,
+    ) -> Session {
+        fn join<T: AsRef<str>>(strings: &[T]) -> String {
+            let strings: Vec<&str> = strings.iter().map(|s| s.as_ref()).collect();
+            strings.join(" ")
+        }
+        Session {
+            addr: addr.to_string(),
+            name,
+            clan: self.clan,
+            country: self.country,
+            join_earliest: self.join_earliest,
+            join_latest: self.join_latest,
+            leave_earliest,
+            leave_latest,
+            playtime: leave_earliest - self.join_latest,
+            server_name: info.name,
+            game_type: info.game_type,
+            maps: join(&self.maps),
+            clan_changes: join(&self.clans),
+            score_start: self.score_start,
+            score_end: self.score_end,
+            score_max: self.score_max,
+        }
+    }
+}
+
+fn now() -> i64 {
+    time::get_time().sec
+}
+
+fn date(timestamp: i64) -> String {
+    let tm = time::at_utc(time::Timespec::new(timestamp, 0));
+    time::strftime("%Y-%m-%d", &tm).unwrap()
+}
+
+fn player_ignore(info: &ClientInfo) -> bool {
+    &*info.name == "(connecting)"
+}
+
+impl Tracker {
+    pub fn new(prefix: String) -> Tracker {
+        Tracker {
+            prefix,
+            output: None,
+            servers: HashMap::new(),
+        }
+    }
+    pub fn start(&mut self) {}
+    fn open(&self, date: String) -> io::Result<Output> {
+        let filename = format!("{}-{}.csv", self.prefix, date);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&filename)?;
+        // Only write the header if we start a new file.
+        let is_new = file.metadata()?.len() == 0;
+        info!("Writing sessions to {}", filename);
+        Ok(Output {
+            date,
+            writer: csv::WriterBuilder::new()
+                .has_headers(is_new)
+                .from_writer(file),
+        })
+    }
+    fn write(&mut self, session: &Session) -> io::Result<()> {
+        let date = date(session.leave_latest);
+        if self.output.as_ref().map(|o| &o.date) != Some(&date) {
+            self.output = None;
+            self.output = Some(self.open(date)?);
+        }
+        let writer = &mut self.output.as_mut().unwrap().writer;
+        writer.serialize(session).map_err(io::Error::from)?;
+        writer.flush()
+    }
+    fn finish_sessions<I>(
+        &mut self,
+        addr: ServerAddr,
+        players: I,
+        info: &ServerInfo,
+        now: i64,
+        last_response: i64,
+    ) where
+        I: IntoIterator<Item = (ArrayString<[u8; 15]>, Player)>,
+    {
+        for (name, player) in players {
+            let session = player.finish(addr, name, last_response, now, info);
+            if let Err(e) = self.write(&session) {
+                warn!("Failed to write session of {:?} on {}, {}", &*name, addr, e);
+            }
+        }
+    }
+    fn on_response(&mut self, addr: ServerAddr, info: &ServerInfo, now: i64) {
+        let (join_earliest, server) = match self.servers.entry(addr) {
+            hash_map::Entry::Occupied(o) => (Some(o.get().last_response), o.into_mut()),
+            // We don't know since when the players have been there.
+            hash_map::Entry::Vacant(v) => (
+                None,
+                v.insert(Server {
+                    last_response: now,
+                    players: HashMap::new(),
+                }),
+            ),
+        };
+        let last_response = mem::replace(&mut server.last_response, now);
+        let mut players = mem::take(&mut server.players);
+        for client in info.clients.iter().filter(|c| !player_ignore(c)) {
+            let player = match players.remove(&client.name) {
+                Some(mut p) => {
+                    p.update(info.map, client);
+                    p
+                }
+                None => Player::new(join_earliest, now, info.map, client),
+            };
+            server.players.insert(client.name, player);
+        }
+        // Players that are no longer there left after the last response.
+        self.finish_sessions(addr, players, info, now, last_response);
+    }
+    fn on_remove(&mut self, addr: ServerAddr, last: &ServerInfo, now: i64) {
+        let server = self.servers.remove(&addr).unwrap();
+        self.finish_sessions(addr, server.players, last, now, server.last_response);
+    }
+}
+
+impl StatsBrowserCb for Tracker {
+    fn on_server_new(&mut self, addr: ServerAddr, info: &ServerInfo) {
+        self.on_response(addr, info, now());
+    }
+
+    fn on_server_change(&mut self, addr: ServerAddr, _old: &ServerInfo, new: &ServerInfo) {
+        self.on_response(addr, new, now());
+    }
+
+    fn on_server_remove(&mut self, addr: ServerAddr, last: &ServerInfo) {
+        self.on_remove(addr, last, now());
+    }
+
+    fn on_server_restore(&mut self, addr: ServerAddr, info: &ServerInfo) {
+        // The sessions that were running when the previous run stopped are
+        // lost, treat the players as if the server was new.
+        self.on_server_new(addr, info);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Tracker;
+    use crate::addr::Addr;
+    use crate::addr::ProtocolVersion;
+    use crate::addr::ServerAddr;
+    use arrayvec::ArrayString;
+    use libtw2_serverbrowse::protocol::ClientInfo;
+    use libtw2_serverbrowse::protocol::ServerInfo;
+    use std::fs;
+    use std::path::Path;
+
+    /// 2020-09-13 12:26:40 UTC.
+    const T: i64 = 1_600_000_000;
+
+    fn addr() -> ServerAddr {
+        let addr = "198.51.100.1:8303".parse().unwrap();
+        ServerAddr::new(ProtocolVersion::V6, Addr::from_socket_addr(addr))
+    }
+
+    fn client(name: &str, clan: &str, score: i32) -> ClientInfo {
+        ClientInfo {
+            name: ArrayString::from(name).unwrap(),
+            clan: ArrayString::from(clan).unwrap(),
+            country: -1,
+            score,
+            flags: 0,
+        }
+    }
+
+    fn info(map: &str, clients: Vec<ClientInfo>) -> ServerInfo {
+        ServerInfo {
+            name: ArrayString::from("test server").unwrap(),
+            map: ArrayString::from(map).unwrap(),
+            game_type: ArrayString::from("DM").unwrap(),
+            clients,
+            ..ServerInfo::default()
+        }
+    }
+
+    fn tracker(dir: &Path) -> Tracker {
+        Tracker::new(dir.join("sessions").to_str().unwrap().to_owned())
+    }
+
+    /// Rows written for the given day, without the header.
+    fn rows(dir: &Path, date: &str) -> Vec<String> {
+        let filename = dir.join(format!("sessions-{}.csv", date));
+        match fs::read_to_string(filename) {
+            Ok(contents) => contents.lines().skip(1).map(str::to_owned).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn join_leave() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tracker = tracker(dir.path());
+        let connecting = client("(connecting)", "", 0);
+        tracker.on_response(
+            addr(),
+            &info(
+                "dm1",
+                vec![client("nameless tee", "", 0), connecting.clone()],
+            ),
+            T,
+        );
+        tracker.on_response(
+            addr(),
+            &info(
+                "dm1",
+                vec![
+                    client("nameless tee", "", 3),
+                    client("brainless tee", "", 0),
+                    connecting,
+                ],
+            ),
+            T + 60,
+        );
+        assert!(rows(dir.path(), "2020-09-13").is_empty());
+        tracker.on_response(
+            addr(),
+            &info("dm1", vec![client("brainless tee", "", 1)]),
+            T + 120,
+        );
+        assert_eq!(
+            rows(dir.path(), "2020-09-13"),
+            [
+                "V6_198.51.100.1:8303,nameless tee,,-1,,1600000000,1600000060,1600000120,60,\
+                 test server,DM,dm1,,0,3,3"
+            ]
+        );
+        tracker.on_response(addr(), &info("dm1", vec![]), T + 180);
+        assert_eq!(
+            rows(dir.path(), "2020-09-13")[1],
+            "V6_198.51.100.1:8303,brainless tee,,-1,1600000000,1600000060,1600000120,\
+             1600000180,60,test server,DM,dm1,,0,1,1"
+        );
+    }
+
+    #[test]
+    fn server_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tracker = tracker(dir.path());
+        let players = info("dm1", vec![client("nameless tee", "", 2)]);
+        tracker.on_response(addr(), &players, T);
+        tracker.on_response(addr(), &players, T + 60);
+        tracker.on_remove(addr(), &players, T + 300);
+        assert_eq!(
+            rows(dir.path(), "2020-09-13"),
+            [
+                "V6_198.51.100.1:8303,nameless tee,,-1,,1600000000,1600000060,1600000300,60,\
+                 test server,DM,dm1,,2,2,2"
+            ]
+        );
+        // The server is forgotten, its players start new sessions if it
+        // comes back.
+        tracker.on_response(addr(), &players, T + 400);
+        tracker.on_remove(addr(), &players, T + 500);
+        assert_eq!(
+            rows(dir.path(), "2020-09-13")[1],
+            "V6_198.51.100.1:8303,nameless tee,,-1,,1600000400,1600000400,1600000500,0,\
+             test server,DM,dm1,,2,2,2"
+        );
+    }
+
+    #[test]
+    fn nick_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tracker = tracker(dir.path());
+        tracker.on_response(
+            addr(),
+            &info("dm1", vec![client("nameless tee", "a", 0)]),
+            T,
+        );
+        // Clan changes keep the session.
+        tracker.on_response(
+            addr(),
+            &info("dm1", vec![client("nameless tee", "b", 1)]),
+            T + 60,
+        );
+        // Name changes are indistinguishable from a leave and a join.
+        tracker.on_response(
+            addr(),
+            &info("dm1", vec![client("renamed tee", "b", 1)]),
+            T + 120,
+        );
+        tracker.on_response(addr(), &info("dm1", vec![]), T + 180);
+        assert_eq!(
+            rows(dir.path(), "2020-09-13"),
+            [
+                "V6_198.51.100.1:8303,nameless tee,b,-1,,1600000000,1600000060,1600000120,60,\
+                 test server,DM,dm1,a,0,1,1",
+                "V6_198.51.100.1:8303,renamed tee,b,-1,1600000060,1600000120,1600000120,\
+                 1600000180,0,test server,DM,dm1,,1,1,1",
+            ]
+        );
+    }
+
+    #[test]
+    fn csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tracker = tracker(dir.path());
+        let server = |map, score| ServerInfo {
+            name: ArrayString::from("\"quoted\", server").unwrap(),
+            ..info(map, vec![client("a,b", "c d", score)])
+        };
+        tracker.on_response(addr(), &server("dm1", 5), T);
+        tracker.on_response(addr(), &server("dm2", -1), T + 60);
+        // Sessions are written to the file of the day they ended on.
+        tracker.on_remove(addr(), &server("dm2", -1), T + 86400);
+        assert!(rows(dir.path(), "2020-09-13").is_empty());
+        let contents = fs::read_to_string(dir.path().join("sessions-2020-09-14.csv")).unwrap();
+        assert_eq!(
+            contents,
+            "addr,name,clan,country,join_earliest,join_latest,leave_earliest,leave_latest,\
+             playtime,server_name,game_type,maps,clan_changes,score_start,score_end,score_max\n\
+             V6_198.51.100.1:8303,\"a,b\",c d,-1,,1600000000,1600000060,1600086400,60,\
+             \"\"\"quoted\"\", server\",DM,dm1 dm2,,5,-1,5\n"
+        );
+
+        // The header is only written once per file.
+        let mut tracker = self::tracker(dir.path());
+        tracker.on_response(addr(), &server("dm1", 0), T + 86400);
+        tracker.on_remove(addr(), &server("dm1", 0), T + 86400);
+        assert_eq!(rows(dir.path(), "2020-09-14").len(), 2);
+    }
+}
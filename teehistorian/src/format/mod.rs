@@ -4,6 +4,7 @@ use libtw2_common::digest::Sha256;
 use libtw2_packer::UnexpectedEnd;
 use libtw2_packer::Unpacker;
 use serde_derive::Deserialize;
+use serde_derive::Serialize;
 use std::borrow::Cow;
 use std::collections::HashMap;
 use uuid::Uuid;
@@ -97,7 +98,7 @@ pub fn read_magic(p: &mut Unpacker) -> Result<(), MaybeEnd<WrongMagic>> {
     Ok(())
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 struct JsonHeader<'a> {
     version: Cow<'a, str>,
     game_uuid: Cow<'a, str>,
@@ -105,6 +106,7 @@ struct JsonHeader<'a> {
     server_port: Cow<'a, str>,
     map_name: Cow<'a, str>,
     map_size: Cow<'a, str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     map_sha256: Option<Sha256>,
     map_crc: Cow<'a, str>,
     config: HashMap<Cow<'a, str>, Cow<'a, str>>,
@@ -146,6 +148,35 @@ pub fn read_header<'a>(p: &mut Unpacker<'a>) -> Result<Header<'a>, MaybeEnd<Head
     Ok(header)
 }
 
+/// Writes the magic and the JSON header, the counterpart of `read_magic`
+/// and `read_header`.
+pub fn write_header(header: &Header) -> Vec<u8> {
+    let json_header = JsonHeader {
+        version: header.version.to_string().into(),
+        game_uuid: header.game_uuid.to_string().into(),
+        start_time: (if header.version == 1 {
+            header.timestamp.format("%Y-%m-%d %H:%M:%S %z").to_string()
+        } else {
+            header.timestamp.to_rfc3339()
+        })
+        .into(),
+        server_port: header.server_port.to_string().into(),
+        map_name: Cow::Borrowed(&header.map_name),
+        map_size: header.map_size.to_string().into(),
+        map_sha256: header.map_sha256,
+        map_crc: format!("{:08x}", header.map_crc).into(),
+        config: header
+            .config
+            .iter()
+            .map(|(k, v)| (Cow::Borrowed(&**k), Cow::Borrowed(&**v)))
+            .collect(),
+    };
+    let mut result = UUID.to_vec();
+    serde_json::to_writer(&mut result, &json_header).unwrap();
+    result.push(0);
+    result
+}
+
 impl From<HeaderError> for Error {
     fn from(e: HeaderError) -> Error {
         Error::Header(e)
@@ -2,6 +2,7 @@ mod bitmagic;
 mod file;
 pub mod format;
 mod raw;
+mod writer;
 
 pub use self::file::Buffer;
 pub use self::file::Error;
@@ -12,3 +13,4 @@ pub use self::raw::Input;
 pub use self::raw::Player;
 pub use self::raw::PlayerChange;
 pub use self::raw::Pos;
+pub use self::writer::Writer;
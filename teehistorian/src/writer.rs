@@ -0,0 +1,340 @@
+use crate::format;
+use crate::format::item;
+use crate::format::item::INPUT_LEN;
+use crate::format::Header;
+use crate::raw::Pos;
+use libtw2_common::num::Cast;
+use libtw2_packer::with_packer;
+use libtw2_packer::Packer;
+use std::io;
+use vec_map::VecMap;
+
+/// Upper bound for the encoded size of an item, apart from its strings and
+/// data.
+const ITEM_OVERHEAD: usize = 128;
+
+/// Strings are nul-terminated in teehistorian files, cut them at the first
+/// nul byte.
+fn c_str(string: &[u8]) -> &[u8] {
+    let nul = string.iter().position(|&b| b == 0).unwrap_or(string.len());
+    &string[..nul]
+}
+
+/// Teehistorian writer, the counterpart of `Reader`.
+///
+/// Each tick must be started with `begin_tick`, followed by the positions of
+/// the players in ascending client ID order, followed by the other items of
+/// the tick. Items that don't change anything, e.g. unchanged player
+/// positions, aren't written at all.
+pub struct Writer<W: io::Write> {
+    file: W,
+    buf: Vec<u8>,
+    tick: i32,
+    last_written_tick: i32,
+    tick_written: bool,
+    /// Maximum client ID of the player items written in this tick.
+    max_cid: i32,
+    /// Maximum client ID of the player items written in the previous tick.
+    prev_max_cid: i32,
+    players: VecMap<Pos>,
+    inputs: VecMap<[i32; INPUT_LEN]>,
+}
+
+impl<W: io::Write> Writer<W> {
+    /// Writes the header, only version 2 of the format is supported.
+    pub fn new(mut file: W, header: &Header) -> io::Result<Writer<W>> {
+        assert!(header.version == 2, "only version 2 can be written");
+        file.write_all(&format::write_header(header))?;
+        Ok(Writer {
+            file,
+            buf: Vec::new(),
+            tick: 0,
+            last_written_tick: 0,
+            tick_written: false,
+            max_cid: -1,
+            prev_max_cid: -1,
+            players: VecMap::new(),
+            inputs: VecMap::new(),
+        })
+    }
+    fn write<F>(&mut self, extra_len: usize, f: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut Packer),
+    {
+        self.buf.clear();
+        self.buf.reserve(ITEM_OVERHEAD + extra_len);
+        with_packer(&mut self.buf, |mut p| f(&mut p));
+        self.file.write_all(&self.buf)
+    }
+    fn write_tick(&mut self) -> io::Result<()> {
+        let dt = self.tick - self.last_written_tick - 1;
+        self.write(0, |p| {
+            p.write_int(item::TICK_SKIP).unwrap();
+            p.write_int(dt).unwrap();
+        })?;
+        self.last_written_tick = self.tick;
+        self.tick_written = true;
+        Ok(())
+    }
+    fn ensure_tick_written(&mut self) -> io::Result<()> {
+        if !self.tick_written {
+            self.write_tick()?;
+        }
+        Ok(())
+    }
+    fn ensure_tick_written_player(&mut self, cid: i32) -> io::Result<()> {
+        assert!(
+            cid > self.max_cid,
+            "player items must be in ascending order"
+        );
+        self.max_cid = cid;
+        if !self.tick_written {
+            // Player items implicitly start a new tick if their client ID
+            // isn't larger than the ones of the previous tick.
+            if cid > self.prev_max_cid || self.last_written_tick + 1 != self.tick {
+                self.write_tick()?;
+            } else {
+                self.last_written_tick = self.tick;
+                self.tick_written = true;
+            }
+        }
+        Ok(())
+    }
+    /// Starts a new tick, ticks must be strictly increasing.
+    pub fn begin_tick(&mut self, tick: i32) {
+        assert!(tick > self.last_written_tick, "ticks must be increasing");
+        self.tick = tick;
+        self.tick_written = false;
+        self.prev_max_cid = self.max_cid;
+        self.max_cid = -1;
+    }
+    /// Records the position of a player's character, `None` if it doesn't
+    /// exist.
+    pub fn player(&mut self, cid: i32, pos: Option<Pos>) -> io::Result<()> {
+        let old = self.players.get(cid.assert_usize()).copied();
+        match (old, pos) {
+            (None, None) => {}
+            (Some(old), Some(pos)) if old.x == pos.x && old.y == pos.y => {}
+            (None, Some(pos)) => {
+                self.ensure_tick_written_player(cid)?;
+                self.write(0, |p| {
+                    p.write_int(item::PLAYER_NEW).unwrap();
+                    p.write_int(cid).unwrap();
+                    p.write_int(pos.x).unwrap();
+                    p.write_int(pos.y).unwrap();
+                })?;
+                self.players.insert(cid.assert_usize(), pos);
+            }
+            (Some(old), Some(pos)) => {
+                self.ensure_tick_written_player(cid)?;
+                self.write(0, |p| {
+                    p.write_int(cid).unwrap();
+                    p.write_int(pos.x.wrapping_sub(old.x)).unwrap();
+                    p.write_int(pos.y.wrapping_sub(old.y)).unwrap();
+                })?;
+                self.players.insert(cid.assert_usize(), pos);
+            }
+            (Some(_), None) => {
+                self.ensure_tick_written_player(cid)?;
+                self.write(0, |p| {
+                    p.write_int(item::PLAYER_OLD).unwrap();
+                    p.write_int(cid).unwrap();
+                })?;
+                self.players.remove(cid.assert_usize());
+            }
+        }
+        Ok(())
+    }
+    /// Records the input of a player.
+    pub fn input(&mut self, cid: i32, input: [i32; INPUT_LEN]) -> io::Result<()> {
+        self.ensure_tick_written()?;
+        let old = self.inputs.insert(cid.assert_usize(), input);
+        self.write(0, |p| match old {
+            None => {
+                p.write_int(item::INPUT_NEW).unwrap();
+                p.write_int(cid).unwrap();
+                for &i in &input {
+                    p.write_int(i).unwrap();
+                }
+            }
+            Some(old) => {
+                p.write_int(item::INPUT_DIFF).unwrap();
+                p.write_int(cid).unwrap();
+                for (&i, &o) in input.iter().zip(old.iter()) {
+                    p.write_int(i.wrapping_sub(o)).unwrap();
+                }
+            }
+        })
+    }
+    /// Records a message sent by a client, including its message ID.
+    pub fn message(&mut self, cid: i32, msg: &[u8]) -> io::Result<()> {
+        self.ensure_tick_written()?;
+        self.write(msg.len(), |p| {
+            p.write_int(item::MESSAGE).unwrap();
+            p.write_int(cid).unwrap();
+            p.write_data(msg).unwrap();
+        })
+    }
+    pub fn join(&mut self, cid: i32) -> io::Result<()> {
+        self.ensure_tick_written()?;
+        self.write(0, |p| {
+            p.write_int(item::JOIN).unwrap();
+            p.write_int(cid).unwrap();
+        })
+    }
+    /// Records a client leaving, a client joining with the same client ID
+    /// afterwards starts with a fresh input.
+    pub fn drop(&mut self, cid: i32, reason: &[u8]) -> io::Result<()> {
+        self.ensure_tick_written()?;
+        self.inputs.remove(cid.assert_usize());
+        let reason = c_str(reason);
+        self.write(reason.len(), |p| {
+            p.write_int(item::DROP).unwrap();
+            p.write_int(cid).unwrap();
+            p.write_string(reason).unwrap();
+        })
+    }
+    /// Records an executed console command, `cid` is `-1` for commands that
+    /// weren't executed by a client.
+    pub fn console_command(
+        &mut self,
+        cid: i32,
+        flag_mask: u32,
+        cmd: &[u8],
+        args: &[&[u8]],
+    ) -> io::Result<()> {
+        assert!(args.len() <= item::CONSOLE_COMMAND_MAX_ARGS);
+        self.ensure_tick_written()?;
+        let cmd = c_str(cmd);
+        let len = cmd.len() + args.iter().map(|a| a.len() + 1).sum::<usize>();
+        self.write(len, |p| {
+            p.write_int(item::CONSOLE_COMMAND).unwrap();
+            p.write_int(cid).unwrap();
+            p.write_int(flag_mask as i32).unwrap();
+            p.write_string(cmd).unwrap();
+            p.write_int(args.len().assert_i32()).unwrap();
+            for &arg in args {
+                p.write_string(c_str(arg)).unwrap();
+            }
+        })
+    }
+    /// Marks the end of the file and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write(0, |p| p.write_int(item::FINISH).unwrap())?;
+        self.file.flush()?;
+        Ok(self.file)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Writer;
+    use crate::format::Header;
+    use crate::raw;
+    use crate::raw::Buffer;
+    use crate::raw::Pos;
+    use chrono::DateTime;
+    use std::borrow::Cow;
+    use std::collections::HashMap;
+    use std::io;
+    use uuid::Uuid;
+
+    struct Slice<'a>(&'a [u8]);
+
+    impl<'a> raw::Callback for Slice<'a> {
+        type Error = ();
+        fn read_at_most(&mut self, buffer: &mut [u8]) -> Result<Option<usize>, ()> {
+            if self.0.is_empty() {
+                return Ok(None);
+            }
+            let len = buffer.len().min(self.0.len());
+            buffer[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(Some(len))
+        }
+    }
+
+    fn header() -> Header<'static> {
+        let mut config = HashMap::new();
+        config.insert(Cow::Borrowed("sv_name"), Cow::Borrowed("\"test\""));
+        Header {
+            version: 2,
+            game_uuid: Uuid::from_bytes([1; 16]),
+            timestamp: DateTime::parse_from_rfc3339("2018-04-01T12:34:56+02:00").unwrap(),
+            server_port: 8303,
+            map_name: Cow::Borrowed("dm1"),
+            map_size: 5805,
+            map_sha256: None,
+            map_crc: 0xf2159e6e,
+            config,
+        }
+    }
+
+    fn pos(x: i32, y: i32) -> Option<Pos> {
+        Some(Pos { x, y })
+    }
+
+    fn read(data: &[u8]) -> Vec<String> {
+        let mut cb = Slice(data);
+        let mut buffer = Buffer::new();
+        let mut reader = {
+            let (header, reader) = raw::Reader::new(&mut cb, &mut buffer).unwrap();
+            assert_eq!(header.map_name, "dm1");
+            assert_eq!(header.map_crc, 0xf2159e6e);
+            assert_eq!(header.server_port, 8303);
+            assert_eq!(header.config["sv_name"], "\"test\"");
+            reader
+        };
+        let mut result = Vec::new();
+        while let Some(item) = reader.read(&mut cb, &mut buffer).unwrap() {
+            result.push(format!("{:?}", item));
+        }
+        result
+    }
+
+    #[test]
+    fn roundtrip() -> io::Result<()> {
+        let mut w = Writer::new(Vec::new(), &header())?;
+        w.begin_tick(10);
+        w.player(0, pos(1, 2))?;
+        w.player(3, pos(3, 4))?;
+        w.join(5)?;
+        w.begin_tick(11);
+        w.player(0, pos(1, 3))?;
+        w.input(0, [1; 10])?;
+        w.begin_tick(12);
+        w.player(5, pos(5, 6))?;
+        w.input(0, [2; 10])?;
+        w.message(0, b"\x11hello")?;
+        w.begin_tick(13);
+        w.begin_tick(15);
+        w.player(3, None)?;
+        w.console_command(-1, 1, b"echo", &[b"hi"])?;
+        w.drop(5, b"bye")?;
+        let data = w.finish()?;
+
+        let expected = [
+            "TickStart(10)",
+            "PlayerNew { cid: 0, pos: (1, 2) }",
+            "PlayerNew { cid: 3, pos: (3, 4) }",
+            "Join { cid: 5 }",
+            "TickEnd(10)",
+            "TickStart(11)",
+            "PlayerChange { cid: 0, pos: (1, 3), old_pos: (1, 2) }",
+            "Input { cid: 0, input: [1, 1, 1, 1, 1, 1, 1, 1, 1, 1] }",
+            "TickEnd(11)",
+            "TickStart(12)",
+            "PlayerNew { cid: 5, pos: (5, 6) }",
+            "Input { cid: 0, input: [2, 2, 2, 2, 2, 2, 2, 2, 2, 2] }",
+            "Message { cid: 0, msg: b\"\\x11hello\" }",
+            "TickEnd(12)",
+            "TickStart(15)",
+            "PlayerOld { cid: 3, pos: (3, 4) }",
+            "ConsoleCommand { cid: -1, flag_mask: 1, cmd: \"echo\", args: [\"hi\"] }",
+            "Drop { cid: 5, reason: \"bye\" }",
+            "TickEnd(15)",
+        ];
+        assert_eq!(read(&data), expected);
+        Ok(())
+    }
+}
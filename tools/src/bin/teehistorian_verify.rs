@@ -0,0 +1,119 @@
+use libtw2_common::digest::Sha256;
+use libtw2_teehistorian::Buffer;
+use libtw2_teehistorian::Reader;
+use libtw2_tools::replay_verify::Verifier;
+use libtw2_world::ddrace;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+
+#[allow(dead_code)] // We add fields just for their `Debug` implementation.
+#[derive(Debug)]
+enum Error {
+    Map(libtw2_map::Error),
+    MapNotFound(String),
+    Teehistorian(libtw2_teehistorian::Error),
+}
+
+impl From<libtw2_map::Error> for Error {
+    fn from(e: libtw2_map::Error) -> Error {
+        Error::Map(e)
+    }
+}
+
+impl From<libtw2_teehistorian::Error> for Error {
+    fn from(e: libtw2_teehistorian::Error) -> Error {
+        Error::Teehistorian(e)
+    }
+}
+
+/// Finds the map in the maps directory, preferring the file name that
+/// includes the SHA256 like DDNet's downloaded maps.
+fn find_map(maps: &Path, name: &str, sha256: Option<Sha256>) -> Option<PathBuf> {
+    let mut candidates = Vec::new();
+    if let Some(sha256) = sha256 {
+        candidates.push(maps.join(format!("{}_{}.map", name, sha256)));
+    }
+    candidates.push(maps.join(format!("{}.map", name)));
+    candidates.into_iter().find(|p| p.is_file())
+}
+
+fn process(path: &Path, maps: &Path, tolerance: f32) -> Result<u64, Error> {
+    let mut buffer = Buffer::new();
+    let mut reader;
+    let map_path;
+    {
+        let (header, r) = Reader::open(path, &mut buffer)?;
+        reader = r;
+        map_path = find_map(maps, &header.map_name, header.map_sha256)
+            .ok_or_else(|| Error::MapNotFound(header.map_name.clone().into_owned()))?;
+    }
+    let map = ddrace::Map::load(&mut libtw2_map::Reader::open(&map_path)?)?;
+    let mut verifier = Verifier::new(map, tolerance);
+    let mut divergences = Vec::new();
+    let mut num_divergences = 0;
+    while let Some(item) = reader.read(&mut buffer)? {
+        verifier.item(&item, &mut divergences);
+        for d in divergences.drain(..) {
+            println!("{}", d);
+            num_divergences += 1;
+        }
+    }
+    Ok(num_divergences)
+}
+
+fn main() {
+    use clap::App;
+    use clap::Arg;
+
+    libtw2_logger::init();
+
+    let matches = App::new("Teehistorian verifier")
+        .about(
+            "Re-simulates the recorded inputs of a teehistorian file and \
+                reports every tick at which a simulated position diverges from \
+                the recorded one",
+        )
+        .arg(
+            Arg::with_name("TEEHISTORIAN")
+                .help("Sets the teehistorian file to verify")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("maps")
+                .long("maps")
+                .takes_value(true)
+                .value_name("DIR")
+                .default_value("maps")
+                .help("Directory containing the map referenced by the teehistorian file"),
+        )
+        .arg(
+            Arg::with_name("tolerance")
+                .long("tolerance")
+                .takes_value(true)
+                .value_name("DISTANCE")
+                .default_value("0")
+                .help("Sets the largest distance between positions that is not reported"),
+        )
+        .get_matches();
+
+    let path = Path::new(matches.value_of_os("TEEHISTORIAN").unwrap());
+    let maps = Path::new(matches.value_of_os("maps").unwrap());
+    let tolerance = matches
+        .value_of("tolerance")
+        .unwrap()
+        .parse()
+        .expect("invalid tolerance");
+
+    match process(path, maps, tolerance) {
+        Ok(0) => {}
+        Ok(n) => {
+            eprintln!("{}: {} divergences", path.display(), n);
+            process::exit(2);
+        }
+        Err(err) => {
+            eprintln!("{}: {:?}", path.display(), err);
+            process::exit(1);
+        }
+    }
+}
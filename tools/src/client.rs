@@ -1,3 +1,10 @@
+//! Argument handling for the connectionless query tools (`serverinfo`,
+//! `serverlist` and friends).
+//!
+//! These only exchange single connless packets with a server or master
+//! server and never establish a game connection, so they don't use
+//! `libtw2-client`.
+
 use std::env;
 use std::net::SocketAddr;
 use std::net::ToSocketAddrs;
@@ -9,6 +16,8 @@ fn to_socket_addr_or_panic(addr: &str) -> SocketAddr {
     //   io::Result        Iterator Option SocketAddr
 }
 
+/// Calls `do_` with a fresh UDP socket and the address given as the only
+/// command line argument.
 pub fn client<D: FnOnce(UdpSocket, SocketAddr)>(do_: D) {
     libtw2_logger::init();
 
@@ -21,6 +30,6 @@ pub fn client<D: FnOnce(UdpSocket, SocketAddr)>(do_: D) {
         let socket = UdpSocket::bind(bindaddr).unwrap();
         do_(socket, addr);
     } else {
-        println!("USAGE: {} <MAP>...", program_name);
+        println!("USAGE: {} <SERVER>", program_name);
     }
 }
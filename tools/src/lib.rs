@@ -1,5 +1,6 @@
 pub mod client;
 pub mod map_stats;
+pub mod replay_verify;
 pub mod unhexdump;
 pub mod warn_stderr;
 pub mod warn_stdout;
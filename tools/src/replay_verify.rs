@@ -0,0 +1,281 @@
+//! Re-simulation of teehistorian files using `libtw2_world`.
+//!
+//! The characters are spawned where the teehistorian file first records
+//! them and are then moved only by the recorded inputs. Positions recorded
+//! at the start of tick `t` are the state after the world tick `t - 1`,
+//! inputs recorded during tick `t` are first used in the world tick `t + 1`.
+//!
+//! All players are treated as teammates so that they can't damage each
+//! other, like in DDRace. Self-damage, weapon pickups and DDRace teams are
+//! not special-cased, see also the limitations of [`libtw2_world::ddrace`].
+
+use libtw2_gamenet_teeworlds_0_6::enums::Team;
+use libtw2_gamenet_teeworlds_0_6::enums::WEAPON_GAME;
+use libtw2_gamenet_teeworlds_0_6::snap_obj::PlayerInput;
+use libtw2_packer::IntUnpacker;
+use libtw2_teehistorian::Item;
+use libtw2_teehistorian::Pos;
+use libtw2_warn::Ignore;
+use libtw2_world::ddrace;
+use libtw2_world::vec2;
+use libtw2_world::CharacterId;
+use libtw2_world::World;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A tick at which the simulated position of a character differs from the
+/// recorded one.
+#[derive(Clone, Copy, Debug)]
+pub struct Divergence {
+    pub tick: i32,
+    pub cid: i32,
+    pub recorded: Pos,
+    /// `None` if the character died in the simulation.
+    pub simulated: Option<Pos>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "tick={} cid={} recorded=({}, {}) ",
+            self.tick, self.cid, self.recorded.x, self.recorded.y,
+        )?;
+        match self.simulated {
+            Some(pos) => write!(
+                f,
+                "simulated=({}, {}) distance={:.1}",
+                pos.x,
+                pos.y,
+                distance(self.recorded, pos),
+            ),
+            None => write!(f, "simulated=dead"),
+        }
+    }
+}
+
+fn distance(a: Pos, b: Pos) -> f32 {
+    vec2::distance(to_vec2(a), to_vec2(b))
+}
+
+fn to_vec2(pos: Pos) -> vec2 {
+    vec2::new(pos.x as f32, pos.y as f32)
+}
+
+/// Feeds teehistorian items into a simulated world and compares the
+/// positions of the characters after each tick.
+pub struct Verifier {
+    world: World,
+    map: ddrace::Map,
+    /// Maximum distance between the recorded and the simulated position
+    /// that is not reported.
+    tolerance: f32,
+    /// Tick that will be simulated next, `None` before the first tick.
+    next_tick: Option<i32>,
+    recorded: BTreeMap<i32, Pos>,
+    inputs: Vec<(i32, PlayerInput)>,
+}
+
+impl Verifier {
+    pub fn new(map: ddrace::Map, tolerance: f32) -> Verifier {
+        Verifier {
+            world: World::new(map.tuning()),
+            map,
+            tolerance,
+            next_tick: None,
+            recorded: BTreeMap::new(),
+            inputs: Vec::new(),
+        }
+    }
+    /// Processes the next item of the teehistorian file, adding the
+    /// divergences found to `divergences`.
+    pub fn item(&mut self, item: &Item, divergences: &mut Vec<Divergence>) {
+        match *item {
+            Item::TickStart(tick) => {
+                let next_tick = *self.next_tick.get_or_insert(tick);
+                // Skipped ticks didn't have any changes in positions or
+                // inputs, but they were still simulated on the server.
+                for t in next_tick..tick {
+                    self.compare(t, divergences);
+                    self.tick();
+                }
+                self.next_tick = Some(tick);
+            }
+            Item::TickEnd(tick) => {
+                self.compare(tick, divergences);
+                self.tick();
+                for (cid, input) in self.inputs.drain(..) {
+                    self.world
+                        .on_input(&mut self.map, CharacterId(cid as u32), input);
+                }
+                self.world.drain_events();
+                self.next_tick = Some(tick + 1);
+            }
+            Item::PlayerNew(p) => {
+                let cid = CharacterId(p.cid as u32);
+                self.recorded.insert(p.cid, p.pos);
+                self.world.kill(cid, WEAPON_GAME);
+                self.world.spawn(cid, to_vec2(p.pos));
+                self.world.set_team(cid, Some(Team::Red));
+                self.world.drain_events();
+            }
+            Item::PlayerChange(p) => {
+                self.recorded.insert(p.cid, p.pos);
+            }
+            Item::PlayerOld(p) => {
+                self.recorded.remove(&p.cid);
+                self.world.kill(CharacterId(p.cid as u32), WEAPON_GAME);
+                self.world.drain_events();
+            }
+            Item::Input(i) => {
+                if let Ok(input) = PlayerInput::decode(&mut Ignore, &mut IntUnpacker::new(&i.input))
+                {
+                    self.inputs.push((i.cid, input));
+                }
+            }
+            _ => {}
+        }
+    }
+    fn tick(&mut self) {
+        self.world.tick(&mut self.map);
+        self.world.drain_events();
+    }
+    fn compare(&self, tick: i32, divergences: &mut Vec<Divergence>) {
+        for (&cid, &recorded) in &self.recorded {
+            let simulated = self.world.character(CharacterId(cid as u32)).map(|tee| {
+                let core = tee.core().to_net();
+                Pos {
+                    x: core.x,
+                    y: core.y,
+                }
+            });
+            let diverged = match simulated {
+                Some(pos) => distance(recorded, pos) > self.tolerance,
+                None => true,
+            };
+            if diverged {
+                divergences.push(Divergence {
+                    tick,
+                    cid,
+                    recorded,
+                    simulated,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Divergence;
+    use super::Verifier;
+    use chrono::DateTime;
+    use libtw2_gamenet_teeworlds_0_6::snap_obj::PlayerInput;
+    use libtw2_teehistorian::Buffer;
+    use libtw2_teehistorian::Header;
+    use libtw2_teehistorian::Pos;
+    use libtw2_teehistorian::Reader;
+    use libtw2_teehistorian::Writer;
+    use libtw2_world::ddrace;
+    use libtw2_world::vec2;
+    use libtw2_world::CharacterId;
+    use libtw2_world::World;
+    use std::borrow::Cow;
+    use std::collections::HashMap;
+    use std::io::Seek;
+    use std::io::SeekFrom;
+    use uuid::Uuid;
+
+    const START_TICK: i32 = 10;
+    const NUM_TICKS: i32 = 100;
+
+    fn map() -> ddrace::Map {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../server/dm1.map");
+        ddrace::Map::load(&mut libtw2_map::Reader::open(path).unwrap()).unwrap()
+    }
+
+    fn header() -> Header<'static> {
+        Header {
+            version: 2,
+            game_uuid: Uuid::from_bytes([1; 16]),
+            timestamp: DateTime::parse_from_rfc3339("2018-04-01T12:34:56+02:00").unwrap(),
+            server_port: 8303,
+            map_name: Cow::Borrowed("dm1"),
+            map_size: 5805,
+            map_sha256: None,
+            map_crc: 0xf2159e6e,
+            config: HashMap::new(),
+        }
+    }
+
+    /// Runs to the right, jumps and runs back to the left.
+    fn input(tick: i32) -> PlayerInput {
+        let tick = tick - START_TICK;
+        PlayerInput {
+            direction: if tick < 50 { 1 } else { -1 },
+            target_x: 1,
+            jump: (20..30).contains(&tick) as i32,
+            ..Default::default()
+        }
+    }
+
+    /// Simulates a single character and records it like a server would,
+    /// optionally recording a wrong position at tick `tamper`.
+    fn record(tamper: Option<i32>) -> Vec<u8> {
+        let cid = CharacterId(0);
+        let mut map = map();
+        let mut world = World::new(map.tuning());
+        let mut writer = Writer::new(Vec::new(), &header()).unwrap();
+        // A spawn point in the middle of the map.
+        world.spawn(cid, vec2::new(1008.0, 560.0));
+        for tick in START_TICK..START_TICK + NUM_TICKS {
+            writer.begin_tick(tick);
+            let core = world.character(cid).unwrap().core().to_net();
+            let mut pos = Pos {
+                x: core.x,
+                y: core.y,
+            };
+            if tamper == Some(tick) {
+                pos.x += 50;
+            }
+            writer.player(0, Some(pos)).unwrap();
+            let mut raw = [0; 10];
+            raw.copy_from_slice(input(tick).encode());
+            writer.input(0, raw).unwrap();
+            world.tick(&mut map);
+            world.on_input(&mut map, cid, input(tick));
+        }
+        writer.finish().unwrap()
+    }
+
+    fn verify(data: &[u8]) -> Vec<Divergence> {
+        let mut file = tempfile::tempfile().unwrap();
+        std::io::Write::write_all(&mut file, data).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let mut buffer = Buffer::new();
+        let (_, mut reader) = Reader::new(file, &mut buffer).unwrap();
+        let mut verifier = Verifier::new(map(), 0.0);
+        let mut divergences = Vec::new();
+        while let Some(item) = reader.read(&mut buffer).unwrap() {
+            verifier.item(&item, &mut divergences);
+        }
+        divergences
+    }
+
+    #[test]
+    fn consistent() {
+        assert!(verify(&record(None)).is_empty());
+    }
+
+    #[test]
+    fn tampered() {
+        let tick = START_TICK + 42;
+        let divergences = verify(&record(Some(tick)));
+        assert_eq!(divergences.len(), 1);
+        let divergence = divergences[0];
+        assert_eq!((divergence.tick, divergence.cid), (tick, 0));
+        let simulated = divergence.simulated.unwrap();
+        assert_eq!(divergence.recorded.x, simulated.x + 50);
+        assert_eq!(divergence.recorded.y, simulated.y);
+    }
+}
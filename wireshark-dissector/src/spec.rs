@@ -85,6 +85,7 @@ pub enum Type {
     Flags(FlagsType),
     Int32(Int32Type),
     Int32String(SimpleType),
+    Int32Twstring,
     Optional(OptionalType),
     PackedAddresses,
     Rest(SimpleType),
@@ -700,6 +701,7 @@ impl Type {
                 max: i.max,
             }),
             Int32String => Type::Int32String(Default::default()),
+            Int32Twstring(..) => Type::Int32Twstring,
             Optional(i) => Type::Optional(OptionalType {
                 inner: Box::new(Type::from_gamenet(context, *i.inner)?),
             }),
@@ -790,6 +792,7 @@ impl Type {
             }
             Int32(i) => (sys::FT_INT32, i.id.as_ptr()),
             Int32String(i) => (sys::FT_INT32, i.id.as_ptr()),
+            Int32Twstring => return,
             Optional(i) => return i.inner.field_register_info(h, t, desc, identifier),
             PackedAddresses => return,
             Rest(i) => (sys::FT_BYTES, i.id.as_ptr()),
@@ -996,6 +999,7 @@ impl Type {
                     bformat!("{}: {}", desc, v),
                 );
             }
+            Int32Twstring => return Err(()),
             Optional(i) => {
                 let _ = i.inner.dissect(desc, tree, tvb, p);
                 return Ok(());
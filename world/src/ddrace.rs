@@ -0,0 +1,915 @@
+//! DDRace tiles: freeze, teleporters, speedups, stoppers, switches with
+//! their doors and tune zones.
+//!
+//! This follows the tile handling of DDNet's `CCharacter` and `CCollision`
+//! for a single DDRace team. Teleporters with several exits always use the
+//! first one in map order instead of a random one, and doors only affect
+//! movement, they're not part of the world's entities.
+
+use crate::vec2;
+use crate::Character;
+use crate::Collision;
+use crate::CollisionType;
+use crate::Hook;
+use crate::TICKS_PER_SECOND;
+use libtw2_common::num::Cast;
+use libtw2_common::num::CastFloat;
+use libtw2_gamenet::msg::game::SvTuneParams;
+use libtw2_gamenet::msg::game::TuneParam;
+use libtw2_gamenet::msg::game::SV_TUNE_PARAMS_DEFAULT;
+use libtw2_gamenet::snap_obj::PlayerInput;
+use libtw2_map::format;
+use libtw2_map::format::SpeedupTile;
+use libtw2_map::format::SwitchTile;
+use libtw2_map::format::TeleTile;
+use libtw2_map::format::Tile;
+use libtw2_map::format::TuneTile;
+use ndarray::Array2;
+use std::collections::BTreeMap;
+use std::f32::consts::PI;
+use std::str;
+
+/// Duration of a freeze in seconds, DDNet's `sv_freeze_delay`.
+pub const FREEZE_DELAY: i32 = 3;
+
+const TILE_SOLID: u8 = 1;
+const TILE_DEATH: u8 = 2;
+const TILE_NOHOOK: u8 = 3;
+const TILE_FREEZE: u8 = 9;
+const TILE_TELEINEVIL: u8 = 10;
+const TILE_UNFREEZE: u8 = 11;
+const TILE_DFREEZE: u8 = 12;
+const TILE_DUNFREEZE: u8 = 13;
+const TILE_SWITCHTIMEDOPEN: u8 = 22;
+const TILE_SWITCHTIMEDCLOSE: u8 = 23;
+const TILE_SWITCHOPEN: u8 = 24;
+const TILE_SWITCHCLOSE: u8 = 25;
+const TILE_TELEIN: u8 = 26;
+const TILE_TELEOUT: u8 = 27;
+const TILE_BOOST: u8 = 28;
+const TILE_TELECHECK: u8 = 29;
+const TILE_TELECHECKOUT: u8 = 30;
+const TILE_TELECHECKIN: u8 = 31;
+const TILE_STOP: u8 = 60;
+const TILE_STOPS: u8 = 61;
+const TILE_STOPA: u8 = 62;
+const TILE_TELECHECKINEVIL: u8 = 63;
+const ENTITY_OFFSET: u8 = 255 - 16 * 4;
+const ENTITY_SPAWN: u8 = ENTITY_OFFSET + 1;
+const ENTITY_SPAWN_BLUE: u8 = ENTITY_OFFSET + 3;
+const ENTITY_LASER_SHORT: u8 = ENTITY_OFFSET + 19;
+const ENTITY_LASER_LONG: u8 = ENTITY_OFFSET + 21;
+const ENTITY_DOOR: u8 = ENTITY_OFFSET + 49;
+
+// DDNet calls the flips `TILEFLAG_XFLIP` and `TILEFLAG_YFLIP`.
+const YFLIP: u8 = format::TILEFLAG_HFLIP;
+const ROTATION_0: u8 = 0;
+const ROTATION_90: u8 = format::TILEFLAG_ROTATE;
+const ROTATION_180: u8 = format::TILEFLAG_VFLIP | format::TILEFLAG_HFLIP;
+const ROTATION_270: u8 = ROTATION_180 | format::TILEFLAG_ROTATE;
+
+const CANTMOVE_LEFT: u8 = 1 << 0;
+const CANTMOVE_RIGHT: u8 = 1 << 1;
+const CANTMOVE_UP: u8 = 1 << 2;
+const CANTMOVE_DOWN: u8 = 1 << 3;
+
+/// Directions in which stoppers are searched, the first one is the tile of
+/// the character itself.
+const DIRECTIONS: [(f32, f32); 5] = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (-1.0, 0.0), (0.0, -1.0)];
+const DIRECTION_MASKS: [u8; 5] = [0, CANTMOVE_RIGHT, CANTMOVE_DOWN, CANTMOVE_LEFT, CANTMOVE_UP];
+
+fn move_restrictions(direction: usize, index: u8, flags: u8) -> u8 {
+    let flags = flags & (ROTATION_270 | YFLIP);
+    let result = match index {
+        TILE_STOP => match flags {
+            ROTATION_0 => CANTMOVE_DOWN,
+            ROTATION_90 => CANTMOVE_LEFT,
+            ROTATION_180 => CANTMOVE_UP,
+            ROTATION_270 => CANTMOVE_RIGHT,
+            f if f == YFLIP ^ ROTATION_0 => CANTMOVE_UP,
+            f if f == YFLIP ^ ROTATION_90 => CANTMOVE_RIGHT,
+            f if f == YFLIP ^ ROTATION_180 => CANTMOVE_DOWN,
+            f if f == YFLIP ^ ROTATION_270 => CANTMOVE_LEFT,
+            _ => 0,
+        },
+        TILE_STOPS => {
+            if flags & ROTATION_90 == 0 {
+                CANTMOVE_DOWN | CANTMOVE_UP
+            } else {
+                CANTMOVE_LEFT | CANTMOVE_RIGHT
+            }
+        }
+        TILE_STOPA => CANTMOVE_LEFT | CANTMOVE_RIGHT | CANTMOVE_UP | CANTMOVE_DOWN,
+        _ => 0,
+    };
+    // Stoppers only block moving onto them, except for one-way stoppers,
+    // they also block while standing on them.
+    if direction == 0 && index == TILE_STOP {
+        return result;
+    }
+    result & DIRECTION_MASKS[direction]
+}
+
+fn clamp_vel(restrictions: u8, mut vel: vec2) -> vec2 {
+    if vel.x > 0.0 && restrictions & CANTMOVE_RIGHT != 0 {
+        vel.x = 0.0;
+    }
+    if vel.x < 0.0 && restrictions & CANTMOVE_LEFT != 0 {
+        vel.x = 0.0;
+    }
+    if vel.y > 0.0 && restrictions & CANTMOVE_DOWN != 0 {
+        vel.y = 0.0;
+    }
+    if vel.y < 0.0 && restrictions & CANTMOVE_UP != 0 {
+        vel.y = 0.0;
+    }
+    vel
+}
+
+/// Angle as computed by DDNet for speedups, including its quirks.
+fn speedup_angle(v: vec2) -> f32 {
+    let half_pi = 1.0f32.asin();
+    let mut angle = if v.x > 0.0000001 {
+        -(v.y / v.x).atan()
+    } else if v.x < 0.0000001 {
+        (v.y / v.x).atan() + 2.0 * half_pi
+    } else if v.y > 0.0000001 {
+        half_pi
+    } else {
+        -half_pi
+    };
+    if angle < 0.0 {
+        angle += 4.0 * half_pi;
+    }
+    angle
+}
+
+fn tune_param<'a>(tuning: &'a mut SvTuneParams, name: &str) -> Option<&'a mut TuneParam> {
+    let t = tuning;
+    Some(match name {
+        "ground_control_speed" => &mut t.ground_control_speed,
+        "ground_control_accel" => &mut t.ground_control_accel,
+        "ground_friction" => &mut t.ground_friction,
+        "ground_jump_impulse" => &mut t.ground_jump_impulse,
+        "air_jump_impulse" => &mut t.air_jump_impulse,
+        "air_control_speed" => &mut t.air_control_speed,
+        "air_control_accel" => &mut t.air_control_accel,
+        "air_friction" => &mut t.air_friction,
+        "hook_length" => &mut t.hook_length,
+        "hook_fire_speed" => &mut t.hook_fire_speed,
+        "hook_drag_accel" => &mut t.hook_drag_accel,
+        "hook_drag_speed" => &mut t.hook_drag_speed,
+        "gravity" => &mut t.gravity,
+        "velramp_start" => &mut t.velramp_start,
+        "velramp_range" => &mut t.velramp_range,
+        "velramp_curvature" => &mut t.velramp_curvature,
+        "gun_curvature" => &mut t.gun_curvature,
+        "gun_speed" => &mut t.gun_speed,
+        "gun_lifetime" => &mut t.gun_lifetime,
+        "shotgun_curvature" => &mut t.shotgun_curvature,
+        "shotgun_speed" => &mut t.shotgun_speed,
+        "shotgun_speeddiff" => &mut t.shotgun_speeddiff,
+        "shotgun_lifetime" => &mut t.shotgun_lifetime,
+        "grenade_curvature" => &mut t.grenade_curvature,
+        "grenade_speed" => &mut t.grenade_speed,
+        "grenade_lifetime" => &mut t.grenade_lifetime,
+        "laser_reach" => &mut t.laser_reach,
+        "laser_bounce_delay" => &mut t.laser_bounce_delay,
+        "laser_bounce_num" => &mut t.laser_bounce_num,
+        "laser_bounce_cost" => &mut t.laser_bounce_cost,
+        "laser_damage" => &mut t.laser_damage,
+        "player_collision" => &mut t.player_collision,
+        "player_hooking" => &mut t.player_hooking,
+        _ => return None,
+    })
+}
+
+fn tile_pos((y, x): (usize, usize)) -> vec2 {
+    let pos = |t: usize| (t.assert_i32() * 32 + 16) as f32;
+    vec2::new(pos(x), pos(y))
+}
+
+/// DDRace state of a character.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CharacterState {
+    /// Remaining ticks of the freeze.
+    freeze_time: i32,
+    freeze_start: i32,
+    deep_freeze: bool,
+    tele_checkpoint: u8,
+    tune_zone: u8,
+    move_restrictions: u8,
+    /// Position of the core at the end of the last tick, tiles between this
+    /// and the current position are handled too.
+    prev_pos: vec2,
+}
+
+impl CharacterState {
+    pub(crate) fn new(pos: vec2) -> CharacterState {
+        CharacterState {
+            freeze_time: 0,
+            freeze_start: 0,
+            deep_freeze: false,
+            tele_checkpoint: 0,
+            tune_zone: 0,
+            move_restrictions: 0,
+            prev_pos: pos,
+        }
+    }
+    pub(crate) fn is_frozen(&self) -> bool {
+        self.freeze_time > 0
+    }
+    pub(crate) fn is_deep_frozen(&self) -> bool {
+        self.deep_freeze
+    }
+    pub(crate) fn tele_checkpoint(&self) -> u8 {
+        self.tele_checkpoint
+    }
+    pub(crate) fn tune_zone(&self) -> u8 {
+        self.tune_zone
+    }
+    pub(crate) fn set_tune_zone(&mut self, zone: u8) {
+        self.tune_zone = zone;
+    }
+    fn freeze(&mut self, seconds: i32, game_tick: i32) -> bool {
+        if seconds <= 0 || self.freeze_time > seconds * TICKS_PER_SECOND {
+            return false;
+        }
+        // Only freeze again after a second.
+        if self.freeze_start < game_tick - TICKS_PER_SECOND {
+            self.freeze_time = seconds * TICKS_PER_SECOND;
+            self.freeze_start = game_tick;
+            return true;
+        }
+        false
+    }
+    fn unfreeze(&mut self) -> bool {
+        if self.freeze_time > 0 {
+            self.freeze_time = 0;
+            self.freeze_start = 0;
+            return true;
+        }
+        false
+    }
+    /// Counts down the freeze before the character core ticks and removes
+    /// movement from the input while frozen.
+    ///
+    /// Returns the remaining seconds once per second, they're shown to the
+    /// players as damage indicators.
+    pub(crate) fn tick_freeze(&mut self, input: &mut PlayerInput) -> Option<i32> {
+        if self.freeze_time <= 0 {
+            return None;
+        }
+        let result = if self.freeze_time % TICKS_PER_SECOND == TICKS_PER_SECOND - 1 {
+            Some((self.freeze_time + 1) / TICKS_PER_SECOND)
+        } else {
+            None
+        };
+        self.freeze_time -= 1;
+        input.direction = 0;
+        input.jump = 0;
+        input.hook = 0;
+        if self.freeze_time == 1 {
+            self.unfreeze();
+        }
+        result
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Switch {
+    active: bool,
+    /// Tick at which a timed switch flips back.
+    end_tick: Option<i32>,
+}
+
+/// Map with the DDRace layers, implementing [`Collision`] for the
+/// [`World`](crate::World).
+///
+/// The state of the switches is kept in the map, so each world needs its own
+/// copy.
+#[derive(Clone)]
+pub struct Map {
+    game: Array2<Tile>,
+    front: Option<Array2<Tile>>,
+    tele: Option<Array2<TeleTile>>,
+    speedup: Option<Array2<SpeedupTile>>,
+    switch: Option<Array2<SwitchTile>>,
+    tune: Option<Array2<TuneTile>>,
+    /// Switch numbers of the doors covering a tile.
+    doors: Array2<Option<u8>>,
+    tele_outs: BTreeMap<u8, Vec<vec2>>,
+    tele_check_outs: BTreeMap<u8, Vec<vec2>>,
+    spawns: Vec<vec2>,
+    tuning: SvTuneParams,
+    tune_zones: Vec<SvTuneParams>,
+    switches: Vec<Switch>,
+}
+
+impl Map {
+    /// Reads the game and DDRace layers and the tunings from the map
+    /// settings.
+    pub fn load(map: &mut libtw2_map::Reader) -> Result<Map, libtw2_map::Error> {
+        let layers = map.game_layers()?;
+        let game = map.layer_tiles(layers.game())?;
+        let front = layers.front().map(|l| map.layer_tiles(l)).transpose()?;
+        let tele = layers
+            .teleport()
+            .map(|l| map.tele_layer_tiles(l))
+            .transpose()?;
+        let speedup = layers
+            .speedup()
+            .map(|l| map.speedup_layer_tiles(l))
+            .transpose()?;
+        let switch = layers
+            .switch()
+            .map(|l| map.switch_layer_tiles(l))
+            .transpose()?;
+        let tune = layers.tune().map(|l| map.tune_layer_tiles(l)).transpose()?;
+        let settings = match map.info().ok().and_then(|i| i.settings) {
+            Some(s) => Some(map.settings(s)?),
+            None => None,
+        };
+        let mut result = Map::new(game, front, tele, speedup, switch, tune);
+        if let Some(settings) = settings {
+            result.apply_settings(settings.iter());
+        }
+        Ok(result)
+    }
+    fn new(
+        game: Array2<Tile>,
+        front: Option<Array2<Tile>>,
+        tele: Option<Array2<TeleTile>>,
+        speedup: Option<Array2<SpeedupTile>>,
+        switch: Option<Array2<SwitchTile>>,
+        tune: Option<Array2<TuneTile>>,
+    ) -> Map {
+        let mut tele_outs: BTreeMap<u8, Vec<vec2>> = BTreeMap::new();
+        let mut tele_check_outs: BTreeMap<u8, Vec<vec2>> = BTreeMap::new();
+        for (i, tile) in tele.iter().flat_map(|t| t.indexed_iter()) {
+            match tile.index {
+                TILE_TELEOUT => tele_outs.entry(tile.number).or_default(),
+                TILE_TELECHECKOUT => tele_check_outs.entry(tile.number).or_default(),
+                _ => continue,
+            }
+            .push(tile_pos(i));
+        }
+        // Neutral spawn points are preferred over the red and then the blue
+        // ones.
+        let mut spawns: Vec<_> = game
+            .indexed_iter()
+            .filter(|(_, t)| (ENTITY_SPAWN..=ENTITY_SPAWN_BLUE).contains(&t.index))
+            .map(|(i, t)| (t.index, tile_pos(i)))
+            .collect();
+        spawns.sort_by_key(|&(index, _)| index);
+        let num_switches = switch
+            .iter()
+            .flat_map(|s| s.iter())
+            .map(|t| t.number.usize() + 1)
+            .max()
+            .unwrap_or(0);
+        let mut result = Map {
+            doors: Array2::from_elem(game.dim(), None),
+            game,
+            front,
+            tele,
+            speedup,
+            switch,
+            tune,
+            tele_outs,
+            tele_check_outs,
+            spawns: spawns.into_iter().map(|(_, pos)| pos).collect(),
+            tuning: SV_TUNE_PARAMS_DEFAULT,
+            tune_zones: vec![SV_TUNE_PARAMS_DEFAULT; 256],
+            switches: vec![
+                Switch {
+                    active: true,
+                    end_tick: None,
+                };
+                num_switches
+            ],
+        };
+        result.add_doors();
+        result
+    }
+    /// Sets up the doors from the door entities in the switch layer, the
+    /// adjacent laser length entities determine their direction and length.
+    fn add_doors(&mut self) {
+        let switch = match self.switch.take() {
+            Some(s) => s,
+            None => return,
+        };
+        let (height, width) = switch.dim();
+        for ((y, x), door) in switch.indexed_iter() {
+            if door.index != ENTITY_DOOR {
+                continue;
+            }
+            for i in 0..8 {
+                let (sin, cos) = (PI / 4.0 * i as f32).sin_cos();
+                let dir = vec2::new(sin, cos);
+                let nx = x.assert_i32() + dir.x.round_to_i32();
+                let ny = y.assert_i32() + dir.y.round_to_i32();
+                if nx < 0 || ny < 0 || nx >= width.assert_i32() || ny >= height.assert_i32() {
+                    continue;
+                }
+                let side = switch[(ny.assert_usize(), nx.assert_usize())].index;
+                if !(ENTITY_LASER_SHORT..=ENTITY_LASER_LONG).contains(&side) {
+                    continue;
+                }
+                let length = 32 * 3 + 32 * (side - ENTITY_LASER_SHORT).i32() * 3;
+                let pos = tile_pos((y, x));
+                for j in 0..length - 1 {
+                    let cur = pos + dir * j as f32;
+                    if self.check_point(cur).is_some() {
+                        break;
+                    }
+                    let index = self.index(cur);
+                    self.doors[index] = Some(door.number);
+                }
+            }
+        }
+        self.switch = Some(switch);
+    }
+    /// Applies the `tune` and `tune_zone` commands from the map settings.
+    /// Other commands are ignored.
+    fn apply_settings<'a, I: Iterator<Item = &'a [u8]>>(&mut self, settings: I) {
+        for setting in settings {
+            let setting = match str::from_utf8(setting) {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+            let args: Vec<&str> = setting.split_whitespace().collect();
+            let (tuning, name, value) = match args[..] {
+                ["tune", name, value] => (&mut self.tuning, name, value),
+                ["tune_zone", zone, name, value] => match zone.parse::<u8>() {
+                    Ok(zone) => (&mut self.tune_zones[zone.usize()], name, value),
+                    Err(_) => continue,
+                },
+                _ => continue,
+            };
+            if let (Some(param), Ok(value)) = (tune_param(tuning, name), value.parse::<f32>()) {
+                *param = TuneParam::from_float(value);
+            }
+        }
+    }
+    /// Global tuning from the map settings, to be passed to
+    /// [`World::new`](crate::World::new).
+    pub fn tuning(&self) -> SvTuneParams {
+        self.tuning
+    }
+    /// Tuning of a tune zone, `None` for zone 0 which uses the global tuning
+    /// of the world.
+    pub fn zone_tuning(&self, zone: u8) -> Option<&SvTuneParams> {
+        if zone == 0 {
+            return None;
+        }
+        Some(&self.tune_zones[zone.usize()])
+    }
+    /// Whether the switch with the given number is active. Doors are closed
+    /// while their switch is active, all switches start out active.
+    pub fn switch_active(&self, number: u8) -> bool {
+        self.switches
+            .get(number.usize())
+            .map(|s| s.active)
+            .unwrap_or(false)
+    }
+    fn clamp_index(&self, x: i32, y: i32) -> (usize, usize) {
+        let (height, width) = self.game.dim();
+        let x = x.clamp(0, width.assert_i32() - 1);
+        let y = y.clamp(0, height.assert_i32() - 1);
+        (y.assert_usize(), x.assert_usize())
+    }
+    fn index(&self, pos: vec2) -> (usize, usize) {
+        self.clamp_index(pos.x.round_to_i32() / 32, pos.y.round_to_i32() / 32)
+    }
+    /// Like `index`, but truncates the position like DDNet's
+    /// `GetPureMapIndex`.
+    fn pure_index(&self, pos: vec2) -> (usize, usize) {
+        self.clamp_index(pos.x.trunc_to_i32() / 32, pos.y.trunc_to_i32() / 32)
+    }
+    pub(crate) fn tune_zone(&self, pos: vec2) -> u8 {
+        match self.tune.as_ref().map(|t| t[self.index(pos)]) {
+            Some(tile) if tile.index != 0 => tile.number,
+            _ => 0,
+        }
+    }
+    fn tile_exists(&self, index: (usize, usize)) -> bool {
+        let ddrace = |t: &Tile| (TILE_FREEZE..=ENTITY_OFFSET).contains(&t.index);
+        ddrace(&self.game[index])
+            || self.front.as_ref().map_or(false, |f| ddrace(&f[index]))
+            || self.tele.as_ref().map_or(false, |t| {
+                matches!(
+                    t[index].index,
+                    TILE_TELEIN
+                        | TILE_TELEINEVIL
+                        | TILE_TELECHECKINEVIL
+                        | TILE_TELECHECK
+                        | TILE_TELECHECKIN
+                )
+            })
+            || self.speedup.as_ref().map_or(false, |s| s[index].force > 0)
+            || self.doors[index].is_some()
+            || self.switch.as_ref().map_or(false, |s| s[index].index != 0)
+            || self.tune.as_ref().map_or(false, |t| t[index].index != 0)
+    }
+    /// Tiles with DDRace tiles on the line between the two positions, like
+    /// DDNet's `GetMapIndices`.
+    fn indices(&self, prev_pos: vec2, pos: vec2) -> Vec<(usize, usize)> {
+        let distance = vec2::distance(prev_pos, pos);
+        if distance == 0.0 {
+            let index = self.pure_index(pos);
+            return if self.tile_exists(index) {
+                vec![index]
+            } else {
+                vec![]
+            };
+        }
+        let end = (distance + 1.0).trunc_to_i32();
+        let mut result = Vec::new();
+        let mut last = (0, 0);
+        for i in 0..end {
+            let index = self.pure_index(vec2::mix(prev_pos, pos, i as f32 / distance));
+            if self.tile_exists(index) && index != last {
+                result.push(index);
+                last = index;
+            }
+        }
+        result
+    }
+    fn move_restrictions(&self, pos: vec2, center: (usize, usize)) -> u8 {
+        let mut result = 0;
+        for (d, &(x, y)) in DIRECTIONS.iter().enumerate() {
+            let index = if d == 0 {
+                center
+            } else {
+                self.pure_index(pos + vec2::new(x, y) * 18.0)
+            };
+            let game = self.game[index];
+            result |= move_restrictions(d, game.index, game.flags);
+            if let Some(front) = &self.front {
+                let front = front[index];
+                result |= move_restrictions(d, front.index, front.flags);
+            }
+            if let Some(number) = self.doors[index] {
+                if self.switch_active(number) {
+                    result |= move_restrictions(d, TILE_STOPA, 0);
+                }
+            }
+        }
+        result
+    }
+    fn set_switch(&mut self, number: u8, active: bool, end_tick: Option<i32>) {
+        if let Some(switch) = self.switches.get_mut(number.usize()) {
+            switch.active = active;
+            switch.end_tick = end_tick;
+        }
+    }
+    /// Flips the timed switches back once their time is up.
+    pub(crate) fn tick_switches(&mut self, game_tick: i32) {
+        for switch in &mut self.switches {
+            if switch.end_tick.map_or(false, |t| t <= game_tick) {
+                switch.active = !switch.active;
+                switch.end_tick = None;
+            }
+        }
+    }
+    fn speedup(&self, state: &CharacterState, core: &mut Character, tile: SpeedupTile) {
+        let angle = tile.angle.get() as f32 * (PI / 180.0);
+        let direction = vec2::new(angle.cos(), angle.sin());
+        let force = tile.force.i32();
+        let mut max_speed = tile.max_speed.i32();
+        if force == 255 && max_speed != 0 {
+            core.vel = direction * (max_speed / 5) as f32;
+            return;
+        }
+        if max_speed > 0 && max_speed < 5 {
+            max_speed = 5;
+        }
+        let mut vel = core.vel;
+        if max_speed > 0 {
+            let diff_angle = speedup_angle(direction) - speedup_angle(vel);
+            let speed_left = max_speed as f32 / 5.0 - diff_angle.cos() * vel.length();
+            if speed_left.trunc_to_i32().abs() > force && speed_left > 0.0000001 {
+                vel += direction * force as f32;
+            } else if speed_left.trunc_to_i32().abs() > force {
+                vel += direction * -force as f32;
+            } else {
+                vel += direction * speed_left;
+            }
+        } else {
+            vel += direction * force as f32;
+        }
+        core.vel = clamp_vel(state.move_restrictions, vel);
+    }
+    /// First exit of the checkpoint teleporters, falling back to earlier
+    /// checkpoints and then the spawn.
+    fn checkpoint_out(&self, checkpoint: u8) -> Option<vec2> {
+        (1..=checkpoint)
+            .rev()
+            .find_map(|n| self.tele_check_outs.get(&n).and_then(|o| o.first()))
+            .or_else(|| self.spawns.first())
+            .cloned()
+    }
+    /// Handles the tiles touched by a character after its core ticked.
+    ///
+    /// Returns whether other characters must release their hook on this
+    /// one, because it went through an evil teleporter.
+    pub(crate) fn handle_tiles(
+        &mut self,
+        state: &mut CharacterState,
+        core: &mut Character,
+        pos: vec2,
+        game_tick: i32,
+    ) -> bool {
+        if state.deep_freeze {
+            state.freeze(FREEZE_DELAY, game_tick);
+        }
+        let current = self.index(pos);
+        if let Some(tile) = self.speedup.as_ref().map(|s| s[current]) {
+            if tile.force > 0 && tile.index == TILE_BOOST {
+                self.speedup(state, core, tile);
+            }
+        }
+        let mut indices = self.indices(state.prev_pos, pos);
+        if indices.is_empty() {
+            indices.push(current);
+        }
+        let mut release_hooked = false;
+        for index in indices {
+            release_hooked |= self.handle_tile(state, core, pos, index, game_tick);
+        }
+        state.prev_pos = core.pos;
+        release_hooked
+    }
+    fn handle_tile(
+        &mut self,
+        state: &mut CharacterState,
+        core: &mut Character,
+        pos: vec2,
+        index: (usize, usize),
+        game_tick: i32,
+    ) -> bool {
+        state.move_restrictions = self.move_restrictions(pos, index);
+        let game = self.game[index].index;
+        let front = self.front.as_ref().map_or(0, |f| f[index].index);
+        let is = |tile: u8| game == tile || front == tile;
+
+        if is(TILE_FREEZE) && !state.deep_freeze {
+            state.freeze(FREEZE_DELAY, game_tick);
+        } else if is(TILE_UNFREEZE) && !state.deep_freeze {
+            state.unfreeze();
+        }
+        if is(TILE_DFREEZE) {
+            state.deep_freeze = true;
+        } else if is(TILE_DUNFREEZE) {
+            state.deep_freeze = false;
+        }
+
+        // Standing on a stopper allows jumping again.
+        if core.vel.y > 0.0 && state.move_restrictions & CANTMOVE_DOWN != 0 {
+            core.jumped_already = false;
+            core.used_airjump = false;
+        }
+        core.vel = clamp_vel(state.move_restrictions, core.vel);
+
+        if let Some(switch) = self.switch.as_ref().map(|s| s[index]) {
+            let number = switch.number;
+            let end_tick = game_tick + 1 + switch.delay.i32() * TICKS_PER_SECOND;
+            let enabled = number == 0 || self.switch_active(number);
+            match switch.index {
+                TILE_SWITCHOPEN if number > 0 => self.set_switch(number, true, None),
+                TILE_SWITCHTIMEDOPEN if number > 0 => self.set_switch(number, true, Some(end_tick)),
+                TILE_SWITCHTIMEDCLOSE if number > 0 => {
+                    self.set_switch(number, false, Some(end_tick))
+                }
+                TILE_SWITCHCLOSE if number > 0 => self.set_switch(number, false, None),
+                TILE_FREEZE if enabled => {
+                    state.freeze(switch.delay.i32(), game_tick);
+                }
+                TILE_DFREEZE if enabled => state.deep_freeze = true,
+                TILE_DUNFREEZE if enabled => state.deep_freeze = false,
+                _ => {}
+            }
+        }
+
+        let tele = match self.tele.as_ref().map(|t| t[index]) {
+            Some(t) => t,
+            None => return false,
+        };
+        let (out, evil) = match tele.index {
+            TILE_TELECHECK => {
+                state.tele_checkpoint = tele.number;
+                return false;
+            }
+            TILE_TELEIN | TILE_TELEINEVIL => {
+                let out = self.tele_outs.get(&tele.number).and_then(|o| o.first());
+                (out.cloned(), tele.index == TILE_TELEINEVIL)
+            }
+            TILE_TELECHECKIN | TILE_TELECHECKINEVIL => (
+                self.checkpoint_out(state.tele_checkpoint),
+                tele.index == TILE_TELECHECKINEVIL,
+            ),
+            _ => return false,
+        };
+        let out = match out {
+            Some(o) => o,
+            None => return false,
+        };
+        core.pos = out;
+        core.hook = Hook::Retracted;
+        if evil {
+            core.vel = vec2::new(0.0, 0.0);
+        }
+        evil
+    }
+}
+
+impl Collision for Map {
+    fn check_point(&mut self, pos: vec2) -> Option<CollisionType> {
+        match self.game[self.index(pos)].index {
+            TILE_SOLID => Some(CollisionType::Normal),
+            TILE_NOHOOK => Some(CollisionType::Unhookable),
+            _ => None,
+        }
+    }
+    fn is_death(&mut self, pos: vec2) -> bool {
+        let index = self.index(pos);
+        self.game[index].index == TILE_DEATH
+            || self
+                .front
+                .as_ref()
+                .map_or(false, |f| f[index].index == TILE_DEATH)
+    }
+    fn is_clipped(&mut self, pos: vec2) -> bool {
+        let (x, y) = (pos.x.round_to_i32() / 32, pos.y.round_to_i32() / 32);
+        let (height, width) = self.game.dim();
+        let (width, height) = (width.assert_i32(), height.assert_i32());
+        x < -200 || x > width + 200 || y < -200 || y > height + 200
+    }
+    fn ddrace(&mut self) -> Option<&mut Map> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::tile_pos;
+    use super::Map;
+    use super::CANTMOVE_RIGHT;
+    use super::ENTITY_DOOR;
+    use super::ENTITY_LASER_SHORT;
+    use super::TILE_FREEZE;
+    use super::TILE_TELEIN;
+    use super::TILE_TELEOUT;
+    use crate::CharacterId;
+    use crate::World;
+    use libtw2_gamenet::msg::game::SV_TUNE_PARAMS_DEFAULT;
+    use libtw2_gamenet::snap_obj::PlayerInput;
+    use libtw2_map::format::SwitchTile;
+    use libtw2_map::format::TeleTile;
+    use libtw2_map::format::Tile;
+    use libtw2_map::format::TuneTile;
+    use ndarray::Array2;
+
+    const SIZE: usize = 10;
+
+    fn game(index: u8, at: (usize, usize)) -> Array2<Tile> {
+        let mut result = Array2::from_elem(
+            (SIZE, SIZE),
+            Tile {
+                index: 0,
+                flags: 0,
+                skip: 0,
+                reserved: 0,
+            },
+        );
+        result[at].index = index;
+        result
+    }
+
+    /// Ticks the world long enough that characters can be frozen.
+    fn world(map: &mut Map) -> World {
+        let mut world = World::new(SV_TUNE_PARAMS_DEFAULT);
+        for _ in 0..60 {
+            world.tick(map);
+        }
+        world
+    }
+
+    #[test]
+    fn freeze() {
+        let cid = CharacterId(0);
+        let mut map = Map::new(game(TILE_FREEZE, (5, 5)), None, None, None, None, None);
+        let mut world = world(&mut map);
+        let spawn = tile_pos((5, 5));
+        world.spawn(cid, spawn);
+        world.tick(&mut map);
+        assert!(world.character(cid).unwrap().is_frozen());
+        let input = PlayerInput {
+            direction: 1,
+            target_x: 1,
+            fire: 1,
+            ..Default::default()
+        };
+        for _ in 0..4 {
+            world.on_input(&mut map, cid, input);
+        }
+        for _ in 0..10 {
+            world.tick(&mut map);
+        }
+        assert_eq!(world.character(cid).unwrap().pos().x, spawn.x);
+        assert!(world.projectiles().is_empty());
+    }
+
+    #[test]
+    fn teleport() {
+        let cid = CharacterId(0);
+        let mut tele = Array2::from_elem(
+            (SIZE, SIZE),
+            TeleTile {
+                number: 0,
+                index: 0,
+            },
+        );
+        tele[(5, 5)] = TeleTile {
+            number: 1,
+            index: TILE_TELEIN,
+        };
+        tele[(2, 2)] = TeleTile {
+            number: 1,
+            index: TILE_TELEOUT,
+        };
+        let mut map = Map::new(game(0, (0, 0)), None, Some(tele), None, None, None);
+        let mut world = world(&mut map);
+        world.spawn(cid, tile_pos((5, 5)));
+        world.tick(&mut map);
+        world.tick(&mut map);
+        let pos = world.character(cid).unwrap().pos();
+        assert_eq!(map.index(pos), (2, 2));
+    }
+
+    #[test]
+    fn tune_zone() {
+        let cid = CharacterId(0);
+        let tune = Array2::from_elem(
+            (SIZE, SIZE),
+            TuneTile {
+                number: 1,
+                index: 68,
+            },
+        );
+        let mut map = Map::new(game(0, (0, 0)), None, None, None, None, Some(tune));
+        map.apply_settings(
+            [&b"tune_zone 1 gravity 0"[..], b"tune_zone 1"]
+                .iter()
+                .cloned(),
+        );
+        assert_eq!(map.zone_tuning(1).unwrap().gravity.0, 0);
+        let mut world = world(&mut map);
+        let spawn = tile_pos((5, 5));
+        world.spawn(cid, spawn);
+        for _ in 0..10 {
+            world.tick(&mut map);
+        }
+        let tee = world.character(cid).unwrap();
+        assert_eq!(tee.tune_zone(), 1);
+        assert_eq!(tee.pos().y, spawn.y);
+    }
+
+    #[test]
+    fn door() {
+        let mut switch = Array2::from_elem(
+            (SIZE, SIZE),
+            SwitchTile {
+                number: 0,
+                index: 0,
+                flags: 0,
+                delay: 0,
+            },
+        );
+        switch[(2, 5)] = SwitchTile {
+            number: 1,
+            index: ENTITY_DOOR,
+            flags: 0,
+            delay: 0,
+        };
+        switch[(3, 5)] = SwitchTile {
+            number: 0,
+            index: ENTITY_LASER_SHORT,
+            flags: 0,
+            delay: 0,
+        };
+        let mut map = Map::new(game(0, (0, 0)), None, None, None, Some(switch), None);
+        // Left of the door, which goes down from its entity.
+        let pos = tile_pos((3, 4));
+        let restrictions = |map: &Map| map.move_restrictions(pos, map.index(pos));
+        assert!(map.switch_active(1));
+        assert_ne!(restrictions(&map) & CANTMOVE_RIGHT, 0);
+        map.set_switch(1, false, Some(10));
+        assert_eq!(restrictions(&map) & CANTMOVE_RIGHT, 0);
+        map.tick_switches(9);
+        assert!(!map.switch_active(1));
+        map.tick_switches(10);
+        assert_ne!(restrictions(&map) & CANTMOVE_RIGHT, 0);
+    }
+}
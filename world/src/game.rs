@@ -0,0 +1,1758 @@
+//! Game world with weapons, projectiles, lasers and pickups on top of the
+//! character physics.
+//!
+//! This mirrors `CGameWorld` and its entities from Teeworlds 0.6, including
+//! the order in which the entities are ticked, so that the simulation can be
+//! compared to a vanilla server tick by tick. Sounds caused by movement, like
+//! jumping or hooking, are not reported.
+
+use crate::clamp;
+use crate::ddrace;
+use crate::vec2;
+use crate::weapon;
+use crate::weapon::NUM_WEAPONS;
+use crate::Character;
+use crate::CharacterId;
+use crate::Collision;
+use crate::Hook;
+use crate::OtherCharacters;
+use crate::CHARACTER_SIZE;
+use crate::TICKS_PER_SECOND;
+use libtw2_common::num::CastFloat;
+use libtw2_gamenet::enums::Emote;
+use libtw2_gamenet::enums::Sound;
+use libtw2_gamenet::enums::Team;
+use libtw2_gamenet::enums::Weapon;
+use libtw2_gamenet::enums::POWERUP_ARMOR;
+use libtw2_gamenet::enums::POWERUP_HEALTH;
+use libtw2_gamenet::enums::POWERUP_NINJA;
+use libtw2_gamenet::enums::POWERUP_WEAPON;
+use libtw2_gamenet::enums::WEAPON_NINJA;
+use libtw2_gamenet::enums::WEAPON_WORLD;
+use libtw2_gamenet::msg::game::SvTuneParams;
+use libtw2_gamenet::snap_obj;
+use libtw2_gamenet::snap_obj::PlayerInput;
+use libtw2_gamenet::snap_obj::Tick;
+use std::cell::Cell;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::f32::consts::PI;
+use std::vec;
+
+const PICKUP_RADIUS: f32 = 20.0;
+const INPUT_STATE_MASK: i32 = 0x3f;
+
+/// Something that happened in the world that the clients should be told
+/// about, through snapshot events or game messages.
+#[derive(Clone, Copy, Debug)]
+pub enum Event {
+    Sound {
+        pos: vec2,
+        sound: Sound,
+        /// Only the player of this character should hear the sound, if set.
+        receiver: Option<CharacterId>,
+    },
+    Explosion(vec2),
+    HammerHit(vec2),
+    Spawn(vec2),
+    Death {
+        pos: vec2,
+        cid: CharacterId,
+    },
+    DamageIndicator {
+        pos: vec2,
+        angle: i32,
+    },
+    /// `weapon` is either a [`Weapon`] or one of `WEAPON_GAME`,
+    /// `WEAPON_SELF` and `WEAPON_WORLD`.
+    Kill {
+        killer: CharacterId,
+        victim: CharacterId,
+        weapon: i32,
+    },
+    WeaponPickup {
+        cid: CharacterId,
+        weapon: Weapon,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PickupKind {
+    Health,
+    Armor,
+    Weapon(Weapon),
+    Ninja,
+}
+
+impl PickupKind {
+    /// Time until the pickup respawns after being picked up, in seconds.
+    fn respawn_time(self) -> i32 {
+        match self {
+            PickupKind::Ninja => 90,
+            _ => 15,
+        }
+    }
+    /// Time until the pickup spawns for the first time, in seconds.
+    fn spawn_delay(self) -> i32 {
+        match self {
+            PickupKind::Ninja => 90,
+            _ => 0,
+        }
+    }
+    fn to_net(self) -> (i32, i32) {
+        match self {
+            PickupKind::Health => (POWERUP_HEALTH, 0),
+            PickupKind::Armor => (POWERUP_ARMOR, 0),
+            PickupKind::Weapon(w) => (POWERUP_WEAPON, w.to_i32()),
+            PickupKind::Ninja => (POWERUP_NINJA, WEAPON_NINJA),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Pickup {
+    id: u16,
+    kind: PickupKind,
+    pos: vec2,
+    /// Tick after which the pickup spawns again, `None` if it's spawned.
+    spawn_tick: Option<i32>,
+}
+
+impl Pickup {
+    /// Snapshot ID, unique among the pickups of the world.
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+    pub fn kind(&self) -> PickupKind {
+        self.kind
+    }
+    pub fn pos(&self) -> vec2 {
+        self.pos
+    }
+    pub fn is_spawned(&self) -> bool {
+        self.spawn_tick.is_none()
+    }
+    /// Returns `None` if the pickup is currently not spawned.
+    pub fn to_net(&self) -> Option<snap_obj::Pickup> {
+        if !self.is_spawned() {
+            return None;
+        }
+        let (type_, subtype) = self.kind.to_net();
+        Some(snap_obj::Pickup {
+            x: self.pos.x.trunc_to_i32(),
+            y: self.pos.y.trunc_to_i32(),
+            type_,
+            subtype,
+        })
+    }
+}
+
+/// Bullet of the pistol or the shotgun, or a grenade.
+#[derive(Clone, Copy, Debug)]
+pub struct Projectile {
+    id: u16,
+    owner: CharacterId,
+    type_: Weapon,
+    pos: vec2,
+    dir: vec2,
+    life_span: i32,
+    start_tick: i32,
+    destroyed: bool,
+}
+
+impl Projectile {
+    /// Snapshot ID, unique among the projectiles of the world.
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+    pub fn owner(&self) -> CharacterId {
+        self.owner
+    }
+    pub fn type_(&self) -> Weapon {
+        self.type_
+    }
+    /// Position `time` seconds after the projectile was fired.
+    pub fn pos_at(&self, time: f32, tuning: &SvTuneParams) -> vec2 {
+        let (curvature, speed) = match self.type_ {
+            Weapon::Grenade => (tuning.grenade_curvature, tuning.grenade_speed),
+            Weapon::Shotgun => (tuning.shotgun_curvature, tuning.shotgun_speed),
+            _ => (tuning.gun_curvature, tuning.gun_speed),
+        };
+        let time = time * speed.to_float();
+        vec2::new(
+            self.pos.x + self.dir.x * time,
+            self.pos.y + self.dir.y * time + curvature.to_float() / 10000.0 * (time * time),
+        )
+    }
+    pub fn to_net(&self) -> snap_obj::Projectile {
+        snap_obj::Projectile {
+            x: self.pos.x.trunc_to_i32(),
+            y: self.pos.y.trunc_to_i32(),
+            vel_x: (self.dir.x * 100.0).trunc_to_i32(),
+            vel_y: (self.dir.y * 100.0).trunc_to_i32(),
+            type_: self.type_,
+            start_tick: Tick(self.start_tick),
+        }
+    }
+}
+
+/// Laser beam of the rifle.
+#[derive(Clone, Copy, Debug)]
+pub struct Laser {
+    id: u16,
+    owner: CharacterId,
+    pos: vec2,
+    from: vec2,
+    dir: vec2,
+    energy: f32,
+    bounces: i32,
+    eval_tick: i32,
+    destroyed: bool,
+}
+
+impl Laser {
+    /// Snapshot ID, unique among the lasers of the world.
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+    pub fn owner(&self) -> CharacterId {
+        self.owner
+    }
+    pub fn to_net(&self) -> snap_obj::Laser {
+        snap_obj::Laser {
+            x: self.pos.x.trunc_to_i32(),
+            y: self.pos.y.trunc_to_i32(),
+            from_x: self.from.x.trunc_to_i32(),
+            from_y: self.from.y.trunc_to_i32(),
+            start_tick: Tick(self.eval_tick),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct WeaponSlot {
+    got: bool,
+    /// Remaining ammo, -1 for unlimited ammo.
+    ammo: i32,
+    ammo_regen_start: Option<i32>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Ninja {
+    activation_tick: i32,
+    activation_dir: vec2,
+    current_move_time: i32,
+    old_vel_amount: f32,
+}
+
+/// A living character along with its health and weapons.
+#[derive(Clone)]
+pub struct Tee {
+    core: Cell<Character>,
+    /// Position of the character as of the end of the last tick. The core
+    /// already moves during the tick, weapons use this position.
+    pos: vec2,
+    health: i32,
+    armor: i32,
+    weapons: [WeaponSlot; NUM_WEAPONS],
+    active_weapon: Weapon,
+    last_weapon: Weapon,
+    queued_weapon: Option<Weapon>,
+    reload_timer: i32,
+    attack_tick: i32,
+    damage_taken: i32,
+    damage_taken_tick: i32,
+    emote: Emote,
+    emote_stop: i32,
+    ninja: Ninja,
+    /// Characters hit by the current hammer swing or ninja dash.
+    hit_characters: Vec<CharacterId>,
+    input: PlayerInput,
+    latest_input: PlayerInput,
+    latest_prev_input: PlayerInput,
+    num_inputs: u32,
+    last_action: i32,
+    ddrace: ddrace::CharacterState,
+}
+
+impl Tee {
+    fn spawn(pos: vec2) -> Tee {
+        let mut result = Tee {
+            core: Cell::new(Character::spawn(pos)),
+            pos,
+            health: 0,
+            armor: 0,
+            weapons: Default::default(),
+            active_weapon: Weapon::Pistol,
+            last_weapon: Weapon::Hammer,
+            queued_weapon: None,
+            reload_timer: 0,
+            attack_tick: 0,
+            damage_taken: 0,
+            damage_taken_tick: 0,
+            emote: Emote::Normal,
+            emote_stop: -1,
+            ninja: Default::default(),
+            hit_characters: Vec::new(),
+            input: Default::default(),
+            latest_input: Default::default(),
+            latest_prev_input: Default::default(),
+            num_inputs: 0,
+            last_action: -1,
+            ddrace: ddrace::CharacterState::new(pos),
+        };
+        // Like `IGameController::OnCharacterSpawn`.
+        result.increase_health(10);
+        result.give_weapon(Weapon::Hammer, -1);
+        result.give_weapon(Weapon::Pistol, 10);
+        result
+    }
+    pub fn core(&self) -> Character {
+        self.core.get()
+    }
+    pub fn pos(&self) -> vec2 {
+        self.pos
+    }
+    pub fn health(&self) -> i32 {
+        self.health
+    }
+    pub fn armor(&self) -> i32 {
+        self.armor
+    }
+    pub fn active_weapon(&self) -> Weapon {
+        self.active_weapon
+    }
+    /// Returns `None` if the character doesn't have the weapon, and -1 for
+    /// unlimited ammo.
+    pub fn ammo(&self, weapon: Weapon) -> Option<i32> {
+        let slot = &self.weapons[weapon::index(weapon)];
+        if slot.got {
+            Some(slot.ammo)
+        } else {
+            None
+        }
+    }
+    pub fn is_frozen(&self) -> bool {
+        self.ddrace.is_frozen()
+    }
+    pub fn is_deep_frozen(&self) -> bool {
+        self.ddrace.is_deep_frozen()
+    }
+    /// Number of the last teleporter checkpoint, 0 if none was reached.
+    pub fn tele_checkpoint(&self) -> u8 {
+        self.ddrace.tele_checkpoint()
+    }
+    /// Tune zone the character is in, 0 outside of tune zones.
+    pub fn tune_zone(&self) -> u8 {
+        self.ddrace.tune_zone()
+    }
+    /// `full` determines whether health, armor and ammo are included, they're
+    /// only sent to the player of the character and their spectators.
+    pub fn to_net(&self, game_tick: i32, full: bool) -> snap_obj::Character {
+        let mut emote = if self.emote_stop < game_tick {
+            Emote::Normal
+        } else {
+            self.emote
+        };
+        if emote == Emote::Normal && 250 - (game_tick - self.last_action) % 250 < 5 {
+            emote = Emote::Blink;
+        }
+        let mut ammo = self.weapons[weapon::index(self.active_weapon)].ammo;
+        let mut weapon = self.active_weapon;
+        // DDNet shows frozen characters with a ninja.
+        if self.ddrace.is_frozen() || self.ddrace.is_deep_frozen() {
+            if emote == Emote::Normal {
+                emote = Emote::Blink;
+            }
+            weapon = Weapon::Ninja;
+            ammo = 0;
+        }
+        snap_obj::Character {
+            character_core: self.core.get().to_net(),
+            player_flags: self.latest_input.player_flags,
+            health: if full { self.health } else { 0 },
+            armor: if full { self.armor } else { 0 },
+            ammo_count: if full && ammo > 0 { ammo } else { 0 },
+            weapon,
+            emote,
+            attack_tick: self.attack_tick,
+        }
+    }
+    fn increase_health(&mut self, amount: i32) -> bool {
+        if self.health >= 10 {
+            return false;
+        }
+        self.health = cmp::min(self.health + amount, 10);
+        true
+    }
+    fn increase_armor(&mut self, amount: i32) -> bool {
+        if self.armor >= 10 {
+            return false;
+        }
+        self.armor = cmp::min(self.armor + amount, 10);
+        true
+    }
+    fn give_weapon(&mut self, weapon: Weapon, ammo: i32) -> bool {
+        let max_ammo = weapon::info(weapon).max_ammo;
+        let slot = &mut self.weapons[weapon::index(weapon)];
+        if slot.ammo < max_ammo || !slot.got {
+            slot.got = true;
+            slot.ammo = cmp::min(max_ammo, ammo);
+            true
+        } else {
+            false
+        }
+    }
+    fn set_emote(&mut self, emote: Emote, stop: i32) {
+        self.emote = emote;
+        self.emote_stop = stop;
+    }
+    /// Returns whether the weapon changed.
+    fn set_weapon(&mut self, weapon: Weapon) -> bool {
+        if weapon == self.active_weapon {
+            return false;
+        }
+        self.last_weapon = self.active_weapon;
+        self.queued_weapon = None;
+        self.active_weapon = weapon;
+        true
+    }
+    /// Returns whether the weapon changed.
+    fn do_weapon_switch(&mut self) -> bool {
+        if self.reload_timer != 0 || self.weapons[weapon::index(Weapon::Ninja)].got {
+            return false;
+        }
+        match self.queued_weapon {
+            Some(w) => self.set_weapon(w),
+            None => false,
+        }
+    }
+    fn handle_weapon_switch(&mut self) -> bool {
+        let mut wanted = weapon::index(self.queued_weapon.unwrap_or(self.active_weapon)) as i32;
+        let next = count_presses(
+            self.latest_prev_input.next_weapon,
+            self.latest_input.next_weapon,
+        );
+        let prev = count_presses(
+            self.latest_prev_input.prev_weapon,
+            self.latest_input.prev_weapon,
+        );
+        let got = |w: i32| self.weapons[w as usize].got;
+        if self.weapons.iter().any(|w| w.got) {
+            let num = NUM_WEAPONS as i32;
+            let mut next = next;
+            while next != 0 {
+                wanted = (wanted + 1) % num;
+                if got(wanted) {
+                    next -= 1;
+                }
+            }
+            let mut prev = prev;
+            while prev != 0 {
+                wanted = (wanted - 1 + num) % num;
+                if got(wanted) {
+                    prev -= 1;
+                }
+            }
+        }
+        // Note: Teeworlds checks the latest input, but takes the weapon from
+        // the last predicted one.
+        if self.latest_input.wanted_weapon != 0 {
+            wanted = self.input.wanted_weapon - 1;
+        }
+        if let Ok(w) = Weapon::from_i32(wanted) {
+            if w != self.active_weapon && got(wanted) {
+                self.queued_weapon = Some(w);
+            }
+        }
+        self.do_weapon_switch()
+    }
+}
+
+fn count_presses(prev: i32, cur: i32) -> i32 {
+    let cur = cur & INPUT_STATE_MASK;
+    let mut i = prev & INPUT_STATE_MASK;
+    let mut presses = 0;
+    while i != cur {
+        i = (i + 1) & INPUT_STATE_MASK;
+        if i & 1 != 0 {
+            presses += 1;
+        }
+    }
+    presses
+}
+
+fn input_eq(a: &PlayerInput, b: &PlayerInput) -> bool {
+    a.direction == b.direction
+        && a.target_x == b.target_x
+        && a.target_y == b.target_y
+        && a.jump == b.jump
+        && a.fire == b.fire
+        && a.hook == b.hook
+        && a.player_flags == b.player_flags
+        && a.wanted_weapon == b.wanted_weapon
+        && a.next_weapon == b.next_weapon
+        && a.prev_weapon == b.prev_weapon
+}
+
+fn get_angle(dir: vec2) -> f32 {
+    if dir.x == 0.0 && dir.y == 0.0 {
+        return 0.0;
+    }
+    let mut a = (dir.y / dir.x).atan();
+    if dir.x < 0.0 {
+        a += PI;
+    }
+    a
+}
+
+/// Seconds since `start_tick`, like Teeworlds computes them.
+fn seconds(ticks: i32) -> f32 {
+    ticks as f32 / TICKS_PER_SECOND as f32
+}
+
+fn lifetime(seconds: f32) -> i32 {
+    (TICKS_PER_SECOND as f32 * seconds).trunc_to_i32()
+}
+
+struct Others<'a> {
+    own: CharacterId,
+    characters: &'a BTreeMap<CharacterId, Tee>,
+}
+
+impl<'a> OtherCharacters for Others<'a> {
+    type Iter = CharacterId;
+    fn is_self(&self, cid: CharacterId) -> bool {
+        cid == self.own
+    }
+    fn get(&self, cid: CharacterId) -> Character {
+        assert!(!self.is_self(cid));
+        self.characters[&cid].core.get()
+    }
+    fn modify<F: FnOnce(&mut Character)>(&self, cid: CharacterId, f: F) {
+        let mut character = self.get(cid);
+        f(&mut character);
+        self.characters[&cid].core.set(character);
+    }
+    fn iter(&self) -> CharacterId {
+        CharacterId(0)
+    }
+    fn next(&self, iter: &mut CharacterId) -> Option<(CharacterId, Character)> {
+        let (&cid, tee) = self
+            .characters
+            .range(*iter..)
+            .find(|&(&cid, _)| cid != self.own)?;
+        *iter = CharacterId(cid.0 + 1);
+        Some((cid, tee.core.get()))
+    }
+}
+
+/// All characters and other entities of a game.
+#[derive(Clone)]
+pub struct World {
+    tuning: SvTuneParams,
+    game_tick: i32,
+    next_id: u16,
+    characters: BTreeMap<CharacterId, Tee>,
+    /// Living characters in the order they were spawned. Like in Teeworlds,
+    /// newer entities are ticked first.
+    spawn_order: Vec<CharacterId>,
+    teams: BTreeMap<CharacterId, Team>,
+    projectiles: Vec<Projectile>,
+    lasers: Vec<Laser>,
+    pickups: Vec<Pickup>,
+    events: Vec<Event>,
+    /// Whether players can damage their teammates.
+    pub team_damage: bool,
+}
+
+impl World {
+    pub fn new(tuning: SvTuneParams) -> World {
+        World {
+            tuning,
+            game_tick: 0,
+            next_id: 0,
+            characters: BTreeMap::new(),
+            spawn_order: Vec::new(),
+            teams: BTreeMap::new(),
+            projectiles: Vec::new(),
+            lasers: Vec::new(),
+            pickups: Vec::new(),
+            events: Vec::new(),
+            team_damage: false,
+        }
+    }
+    pub fn tuning(&self) -> &SvTuneParams {
+        &self.tuning
+    }
+    pub fn set_tuning(&mut self, tuning: SvTuneParams) {
+        self.tuning = tuning;
+    }
+    /// Number of ticks simulated so far.
+    pub fn game_tick(&self) -> i32 {
+        self.game_tick
+    }
+    pub fn character(&self, cid: CharacterId) -> Option<&Tee> {
+        self.characters.get(&cid)
+    }
+    /// Living characters, ordered by ID.
+    pub fn characters(&self) -> impl Iterator<Item = (CharacterId, &Tee)> + Clone {
+        self.characters.iter().map(|(&cid, tee)| (cid, tee))
+    }
+    pub fn projectiles(&self) -> &[Projectile] {
+        &self.projectiles
+    }
+    pub fn lasers(&self) -> &[Laser] {
+        &self.lasers
+    }
+    pub fn pickups(&self) -> &[Pickup] {
+        &self.pickups
+    }
+    /// Returns the events that happened since the last call.
+    pub fn drain_events(&mut self) -> vec::Drain<'_, Event> {
+        self.events.drain(..)
+    }
+    /// Sets the team of the player controlling the character `cid`.
+    ///
+    /// Players can't hurt their teammates unless `team_damage` is set.
+    /// Players without team can hurt everyone.
+    pub fn set_team(&mut self, cid: CharacterId, team: Option<Team>) {
+        match team {
+            Some(t) => self.teams.insert(cid, t),
+            None => self.teams.remove(&cid),
+        };
+    }
+    fn new_id(&mut self) -> u16 {
+        let result = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        result
+    }
+    /// Adds a pickup, e.g. for the entities of a map.
+    pub fn add_pickup(&mut self, kind: PickupKind, pos: vec2) {
+        let spawn_delay = kind.spawn_delay();
+        let id = self.new_id();
+        self.pickups.push(Pickup {
+            id,
+            kind,
+            pos,
+            spawn_tick: if spawn_delay > 0 {
+                Some(self.game_tick + TICKS_PER_SECOND * spawn_delay)
+            } else {
+                None
+            },
+        });
+    }
+    /// Spawns a character with full health, a hammer and a pistol.
+    ///
+    /// Panics if the character is already alive.
+    pub fn spawn(&mut self, cid: CharacterId, pos: vec2) {
+        assert!(
+            !self.characters.contains_key(&cid),
+            "character already alive"
+        );
+        self.characters.insert(cid, Tee::spawn(pos));
+        self.spawn_order.push(cid);
+        self.events.push(Event::Spawn(pos));
+    }
+    /// Kills a character, e.g. `WEAPON_SELF` for suicides or `WEAPON_GAME`
+    /// for players leaving the game.
+    pub fn kill(&mut self, cid: CharacterId, weapon: i32) {
+        if self.characters.contains_key(&cid) {
+            self.die(cid, cid, weapon);
+        }
+    }
+    /// Processes a new input of the player controlling the character `cid`,
+    /// which fires and switches weapons immediately.
+    ///
+    /// The input is also used for the movement of the character in the
+    /// following ticks.
+    pub fn on_input<C: Collision>(
+        &mut self,
+        collision: &mut C,
+        cid: CharacterId,
+        input: PlayerInput,
+    ) {
+        let game_tick = self.game_tick;
+        let tee = match self.characters.get_mut(&cid) {
+            Some(t) => t,
+            None => return,
+        };
+        let raw_input = input;
+        let mut input = input;
+        // It is not allowed to aim in the center.
+        if input.target_x == 0 && input.target_y == 0 {
+            input.target_y = -1;
+        }
+        tee.latest_prev_input = tee.latest_input;
+        tee.latest_input = input;
+        if tee.num_inputs > 2 {
+            if tee.handle_weapon_switch() {
+                let pos = tee.pos;
+                self.sound(pos, Sound::WeaponSwitch);
+            }
+            self.fire_weapon(collision, cid);
+        }
+        let tee = match self.characters.get_mut(&cid) {
+            Some(t) => t,
+            None => return,
+        };
+        tee.latest_prev_input = tee.latest_input;
+        if !input_eq(&tee.input, &raw_input) {
+            tee.last_action = game_tick;
+        }
+        tee.input = input;
+        tee.num_inputs += 1;
+    }
+    /// Advances the world by one tick.
+    pub fn tick<C: Collision>(&mut self, collision: &mut C) {
+        self.game_tick += 1;
+        if let Some(map) = collision.ddrace() {
+            map.tick_switches(self.game_tick);
+        }
+        for i in (0..self.projectiles.len()).rev() {
+            self.tick_projectile(collision, i);
+        }
+        for i in (0..self.lasers.len()).rev() {
+            self.tick_laser(collision, i);
+        }
+        for i in (0..self.pickups.len()).rev() {
+            self.tick_pickup(i);
+        }
+        let order: Vec<CharacterId> = self.spawn_order.iter().rev().cloned().collect();
+        for &cid in &order {
+            if self.characters.contains_key(&cid) {
+                self.tick_character(collision, cid);
+            }
+        }
+        for &cid in &order {
+            if self.characters.contains_key(&cid) {
+                self.tick_character_deferred(collision, cid);
+            }
+        }
+        self.projectiles.retain(|p| !p.destroyed);
+        self.lasers.retain(|l| !l.destroyed);
+    }
+    fn sound(&mut self, pos: vec2, sound: Sound) {
+        self.events.push(Event::Sound {
+            pos,
+            sound,
+            receiver: None,
+        });
+    }
+    /// Characters within `radius` of `pos`, newest first.
+    fn find_characters(&self, pos: vec2, radius: f32) -> Vec<CharacterId> {
+        self.spawn_order
+            .iter()
+            .rev()
+            .cloned()
+            .filter(|cid| vec2::distance(self.characters[cid].pos, pos) < radius + CHARACTER_SIZE)
+            .collect()
+    }
+    fn closest_character(&self, pos: vec2, radius: f32) -> Option<CharacterId> {
+        let mut closest_range = radius * 2.0;
+        let mut result = None;
+        for &cid in self.spawn_order.iter().rev() {
+            let len = vec2::distance(pos, self.characters[&cid].pos);
+            if len < CHARACTER_SIZE + radius && len < closest_range {
+                closest_range = len;
+                result = Some(cid);
+            }
+        }
+        result
+    }
+    /// Returns the character closest to `pos0` that's touching the line from
+    /// `pos0` to `pos1`, along with the point on the line closest to it.
+    fn intersect_character(
+        &self,
+        pos0: vec2,
+        pos1: vec2,
+        radius: f32,
+        not_this: CharacterId,
+    ) -> Option<(CharacterId, vec2)> {
+        let mut closest_len = vec2::distance(pos0, pos1) * 100.0;
+        let mut result = None;
+        for &cid in self.spawn_order.iter().rev() {
+            if cid == not_this {
+                continue;
+            }
+            let pos = self.characters[&cid].pos;
+            let intersect_pos = pos.closest_point_on_line(pos0, pos1);
+            if vec2::distance(pos, intersect_pos) < CHARACTER_SIZE + radius {
+                let len = vec2::distance(pos0, intersect_pos);
+                if len < closest_len {
+                    closest_len = len;
+                    result = Some((cid, intersect_pos));
+                }
+            }
+        }
+        result
+    }
+    fn is_friendly_fire(&self, cid1: CharacterId, cid2: CharacterId) -> bool {
+        if cid1 == cid2 {
+            return false;
+        }
+        match (self.teams.get(&cid1), self.teams.get(&cid2)) {
+            (Some(t1), Some(t2)) => t1 == t2,
+            _ => false,
+        }
+    }
+    fn damage_indicator(&mut self, pos: vec2, angle: f32, amount: i32) {
+        let a = 3.0 * PI / 2.0 + angle;
+        let s = a - PI / 3.0;
+        let e = a + PI / 3.0;
+        for i in 0..amount {
+            let f = s + (e - s) * ((i + 1) as f32 / (amount + 2) as f32);
+            self.events.push(Event::DamageIndicator {
+                pos,
+                angle: (f * 256.0).trunc_to_i32(),
+            });
+        }
+    }
+    /// Returns whether the character survived and took damage.
+    fn take_damage(
+        &mut self,
+        cid: CharacterId,
+        force: vec2,
+        damage: i32,
+        from: CharacterId,
+        weapon: i32,
+    ) -> bool {
+        let game_tick = self.game_tick;
+        let friendly_fire = self.is_friendly_fire(cid, from);
+        let tee = self.characters.get_mut(&cid).unwrap();
+        let mut core = tee.core.get();
+        core.vel += force;
+        tee.core.set(core);
+        if friendly_fire && !self.team_damage {
+            return false;
+        }
+        let mut damage = damage;
+        // Players only inflict half damage on themselves.
+        if from == cid {
+            damage = cmp::max(1, damage / 2);
+        }
+        tee.damage_taken += 1;
+        // Make sure that the damage indicators don't group together.
+        let angle = if game_tick < tee.damage_taken_tick + 25 {
+            tee.damage_taken as f32 * 0.25
+        } else {
+            tee.damage_taken = 0;
+            0.0
+        };
+        let pos = tee.pos;
+        let indicated_damage = damage;
+        if damage != 0 {
+            if tee.armor != 0 {
+                if damage > 1 {
+                    tee.health -= 1;
+                    damage -= 1;
+                }
+                if damage > tee.armor {
+                    damage -= tee.armor;
+                    tee.armor = 0;
+                } else {
+                    tee.armor -= damage;
+                    damage = 0;
+                }
+            }
+            tee.health -= damage;
+        }
+        tee.damage_taken_tick = game_tick;
+        let health = tee.health;
+        self.damage_indicator(pos, angle, indicated_damage);
+
+        if from != cid {
+            let pos = self.characters.get(&from).map(|t| t.pos).unwrap_or(pos);
+            self.events.push(Event::Sound {
+                pos,
+                sound: Sound::Hit,
+                receiver: Some(from),
+            });
+        }
+        if health <= 0 {
+            self.die(cid, from, weapon);
+            // Set the attacker's face to happy (taunt!).
+            if from != cid {
+                if let Some(killer) = self.characters.get_mut(&from) {
+                    killer.set_emote(Emote::Happy, game_tick + TICKS_PER_SECOND);
+                }
+            }
+            return false;
+        }
+        self.sound(
+            pos,
+            if damage > 2 {
+                Sound::PlayerPainLong
+            } else {
+                Sound::PlayerPainShort
+            },
+        );
+        let tee = self.characters.get_mut(&cid).unwrap();
+        tee.set_emote(Emote::Pain, game_tick + weapon::ms_to_ticks(500));
+        true
+    }
+    fn die(&mut self, cid: CharacterId, killer: CharacterId, weapon: i32) {
+        let tee = self.characters.remove(&cid).unwrap();
+        self.spawn_order.retain(|&c| c != cid);
+        self.events.push(Event::Kill {
+            killer,
+            victim: cid,
+            weapon,
+        });
+        self.sound(tee.pos, Sound::PlayerDie);
+        self.events.push(Event::Death { pos: tee.pos, cid });
+    }
+    fn create_explosion(&mut self, pos: vec2, owner: CharacterId, weapon: i32) {
+        use crate::weapon::EXPLOSION_INNER_RADIUS as INNER_RADIUS;
+        use crate::weapon::EXPLOSION_RADIUS as RADIUS;
+        self.events.push(Event::Explosion(pos));
+        for cid in self.find_characters(pos, RADIUS) {
+            let target_pos = match self.characters.get(&cid) {
+                Some(t) => t.pos,
+                None => continue,
+            };
+            let diff = target_pos - pos;
+            let mut force_dir = vec2::new(0.0, 1.0);
+            let mut l = diff.length();
+            if l != 0.0 {
+                force_dir = diff.normalize();
+            }
+            l = 1.0 - clamp((l - INNER_RADIUS) / (RADIUS - INNER_RADIUS), 0.0, 1.0);
+            let damage = 6.0 * l;
+            if damage.trunc_to_i32() != 0 {
+                self.take_damage(
+                    cid,
+                    force_dir * damage * 2.0,
+                    damage.trunc_to_i32(),
+                    owner,
+                    weapon,
+                );
+            }
+        }
+    }
+    fn fire_projectile(&mut self, type_: Weapon, owner: CharacterId, pos: vec2, dir: vec2) {
+        let life_span = lifetime(
+            match type_ {
+                Weapon::Grenade => self.tuning.grenade_lifetime,
+                Weapon::Shotgun => self.tuning.shotgun_lifetime,
+                _ => self.tuning.gun_lifetime,
+            }
+            .to_float(),
+        );
+        let id = self.new_id();
+        self.projectiles.push(Projectile {
+            id,
+            owner,
+            type_,
+            pos,
+            dir,
+            life_span,
+            start_tick: self.game_tick,
+            destroyed: false,
+        });
+    }
+    fn tick_projectile<C: Collision>(&mut self, collision: &mut C, i: usize) {
+        let p = self.projectiles[i];
+        let prev_pos = p.pos_at(seconds(self.game_tick - p.start_tick - 1), &self.tuning);
+        let mut cur_pos = p.pos_at(seconds(self.game_tick - p.start_tick), &self.tuning);
+        let collided = collision.intersect_line(prev_pos, cur_pos);
+        if let Some((pos, _, _)) = collided {
+            cur_pos = pos;
+        }
+        let target = self.intersect_character(prev_pos, cur_pos, 6.0, p.owner);
+        if let Some((_, pos)) = target {
+            cur_pos = pos;
+        }
+        self.projectiles[i].life_span -= 1;
+        let life_span = self.projectiles[i].life_span;
+        if target.is_none()
+            && collided.is_none()
+            && life_span >= 0
+            && !collision.is_clipped(cur_pos)
+        {
+            return;
+        }
+        let weapon = p.type_.to_i32();
+        if p.type_ == Weapon::Grenade {
+            self.sound(cur_pos, Sound::GrenadeExplode);
+            self.create_explosion(cur_pos, p.owner, weapon);
+        } else if let Some((cid, _)) = target {
+            // Projectiles have no force, it's clamped to a minimum.
+            self.take_damage(cid, p.dir * 0.001, 1, p.owner, weapon);
+        }
+        self.projectiles[i].destroyed = true;
+    }
+    fn fire_laser<C: Collision>(
+        &mut self,
+        collision: &mut C,
+        owner: CharacterId,
+        pos: vec2,
+        dir: vec2,
+    ) {
+        let id = self.new_id();
+        self.lasers.push(Laser {
+            id,
+            owner,
+            pos,
+            from: pos,
+            dir,
+            energy: self.tuning.laser_reach.to_float(),
+            bounces: 0,
+            eval_tick: 0,
+            destroyed: false,
+        });
+        self.laser_bounce(collision, self.lasers.len() - 1);
+    }
+    fn tick_laser<C: Collision>(&mut self, collision: &mut C, i: usize) {
+        let delay = TICKS_PER_SECOND as f32 * self.tuning.laser_bounce_delay.to_float() / 1000.0;
+        if self.game_tick as f32 > self.lasers[i].eval_tick as f32 + delay {
+            self.laser_bounce(collision, i);
+        }
+    }
+    fn laser_hit_character(&mut self, i: usize, from: vec2, to: vec2) -> bool {
+        let laser = self.lasers[i];
+        let (cid, at) = match self.intersect_character(laser.pos, to, 0.0, laser.owner) {
+            Some(hit) => hit,
+            None => return false,
+        };
+        let laser = &mut self.lasers[i];
+        laser.from = from;
+        laser.pos = at;
+        laser.energy = -1.0;
+        let owner = laser.owner;
+        let damage = self.tuning.laser_damage.to_float().trunc_to_i32();
+        let weapon = Weapon::Rifle.to_i32();
+        self.take_damage(cid, vec2::new(0.0, 0.0), damage, owner, weapon);
+        true
+    }
+    fn laser_bounce<C: Collision>(&mut self, collision: &mut C, i: usize) {
+        let tuning = self.tuning;
+        let laser = &mut self.lasers[i];
+        laser.eval_tick = self.game_tick;
+        if laser.energy < 0.0 {
+            laser.destroyed = true;
+            return;
+        }
+        let pos = laser.pos;
+        let to = pos + laser.dir * laser.energy;
+        match collision.intersect_line(pos, to) {
+            Some((_, to, _)) => {
+                if self.laser_hit_character(i, pos, to) {
+                    return;
+                }
+                let laser = &mut self.lasers[i];
+                laser.from = pos;
+                let (new_pos, dir) = collision.move_point(to, laser.dir * 4.0, 1.0);
+                laser.pos = new_pos;
+                laser.dir = dir.normalize();
+                laser.energy -=
+                    vec2::distance(laser.from, laser.pos) + tuning.laser_bounce_cost.to_float();
+                laser.bounces += 1;
+                if laser.bounces as f32 > tuning.laser_bounce_num.to_float() {
+                    laser.energy = -1.0;
+                }
+                self.sound(new_pos, Sound::RifleBounce);
+            }
+            None => {
+                if self.laser_hit_character(i, pos, to) {
+                    return;
+                }
+                let laser = &mut self.lasers[i];
+                laser.from = pos;
+                laser.pos = to;
+                laser.energy = -1.0;
+            }
+        }
+    }
+    fn tick_pickup(&mut self, i: usize) {
+        let game_tick = self.game_tick;
+        let pickup = self.pickups[i];
+        if let Some(spawn_tick) = pickup.spawn_tick {
+            if game_tick <= spawn_tick {
+                return;
+            }
+            self.pickups[i].spawn_tick = None;
+            if let PickupKind::Weapon(_) = pickup.kind {
+                self.sound(pickup.pos, Sound::WeaponSpawn);
+            }
+        }
+        let cid = match self.closest_character(pickup.pos, PICKUP_RADIUS) {
+            Some(cid) => cid,
+            None => return,
+        };
+        let tee = self.characters.get_mut(&cid).unwrap();
+        let (picked_up, sound) = match pickup.kind {
+            PickupKind::Health => (tee.increase_health(1), Some(Sound::PickupHealth)),
+            PickupKind::Armor => (tee.increase_armor(1), Some(Sound::PickupArmor)),
+            PickupKind::Weapon(weapon) => {
+                let picked_up = tee.give_weapon(weapon, 10);
+                if picked_up {
+                    self.events.push(Event::WeaponPickup { cid, weapon });
+                }
+                let sound = match weapon {
+                    Weapon::Grenade => Some(Sound::PickupGrenade),
+                    Weapon::Shotgun | Weapon::Rifle => Some(Sound::PickupShotgun),
+                    _ => None,
+                };
+                (picked_up, sound)
+            }
+            PickupKind::Ninja => {
+                self.give_ninja(cid);
+                let stop = game_tick + TICKS_PER_SECOND;
+                for (&other, tee) in &mut self.characters {
+                    if other != cid {
+                        tee.set_emote(Emote::Surprise, stop);
+                    }
+                }
+                let tee = self.characters.get_mut(&cid).unwrap();
+                tee.set_emote(Emote::Angry, game_tick + weapon::ms_to_ticks(1200));
+                self.pickups[i].spawn_tick =
+                    Some(game_tick + TICKS_PER_SECOND * pickup.kind.respawn_time());
+                return;
+            }
+        };
+        if picked_up {
+            if let Some(sound) = sound {
+                self.sound(pickup.pos, sound);
+            }
+            self.pickups[i].spawn_tick =
+                Some(game_tick + TICKS_PER_SECOND * pickup.kind.respawn_time());
+        }
+    }
+    fn give_ninja(&mut self, cid: CharacterId) {
+        let game_tick = self.game_tick;
+        let tee = self.characters.get_mut(&cid).unwrap();
+        tee.ninja.activation_tick = game_tick;
+        let slot = &mut tee.weapons[weapon::index(Weapon::Ninja)];
+        slot.got = true;
+        slot.ammo = -1;
+        if tee.active_weapon != Weapon::Ninja {
+            tee.last_weapon = tee.active_weapon;
+        }
+        tee.active_weapon = Weapon::Ninja;
+        let pos = tee.pos;
+        self.sound(pos, Sound::PickupNinja);
+    }
+    /// Tuning of the tune zone the character is in.
+    fn character_tuning<C: Collision>(&self, collision: &mut C, cid: CharacterId) -> SvTuneParams {
+        let zone = self.characters[&cid].ddrace.tune_zone();
+        collision
+            .ddrace()
+            .and_then(|m| m.zone_tuning(zone).cloned())
+            .unwrap_or(self.tuning)
+    }
+    fn release_hooked(&self, cid: CharacterId) {
+        for tee in self.characters.values() {
+            let mut core = tee.core.get();
+            if core.hook.hooked_player() == Some(cid) {
+                core.hook = Hook::Retracted;
+                tee.core.set(core);
+            }
+        }
+    }
+    fn tick_character<C: Collision>(&mut self, collision: &mut C, cid: CharacterId) {
+        let tee = self.characters.get_mut(&cid).unwrap();
+        let mut input = tee.input;
+        if let Some(map) = collision.ddrace() {
+            let pos = tee.pos;
+            tee.ddrace.set_tune_zone(map.tune_zone(pos));
+            if let Some(seconds) = tee.ddrace.tick_freeze(&mut input) {
+                self.damage_indicator(pos, 0.0, seconds);
+            }
+        }
+        let tuning = self.character_tuning(collision, cid);
+        let tee = &self.characters[&cid];
+        let mut core = tee.core.get();
+        let others = &mut Others {
+            own: cid,
+            characters: &self.characters,
+        };
+        core.tick(collision, others, input, &tuning);
+        tee.core.set(core);
+
+        // Handle death tiles and leaving the game layer.
+        let pos = tee.pos;
+        let r = CHARACTER_SIZE / 3.0;
+        let corners = [
+            vec2::new(r, -r),
+            vec2::new(r, r),
+            vec2::new(-r, -r),
+            vec2::new(-r, r),
+        ];
+        if corners.iter().any(|&c| collision.is_death(pos + c)) || collision.is_clipped(pos) {
+            self.die(cid, cid, WEAPON_WORLD);
+            return;
+        }
+        self.handle_weapons(collision, cid);
+
+        let game_tick = self.game_tick;
+        if let Some(map) = collision.ddrace() {
+            let tee = match self.characters.get_mut(&cid) {
+                Some(t) => t,
+                None => return,
+            };
+            let mut core = tee.core.get();
+            let release_hooked = map.handle_tiles(&mut tee.ddrace, &mut core, tee.pos, game_tick);
+            tee.core.set(core);
+            if release_hooked {
+                self.release_hooked(cid);
+            }
+        }
+    }
+    fn tick_character_deferred<C: Collision>(&mut self, collision: &mut C, cid: CharacterId) {
+        let tuning = self.character_tuning(collision, cid);
+        let mut core = self.characters[&cid].core.get();
+        let others = &mut Others {
+            own: cid,
+            characters: &self.characters,
+        };
+        core.move_(collision, others, &tuning);
+        core.quantize();
+        let tee = self.characters.get_mut(&cid).unwrap();
+        tee.core.set(core);
+        tee.pos = core.pos;
+    }
+    fn handle_weapons<C: Collision>(&mut self, collision: &mut C, cid: CharacterId) {
+        self.handle_ninja(collision, cid);
+        let tee = self.characters.get_mut(&cid).unwrap();
+        if tee.reload_timer != 0 {
+            tee.reload_timer -= 1;
+            return;
+        }
+        self.fire_weapon(collision, cid);
+
+        let game_tick = self.game_tick;
+        let tee = self.characters.get_mut(&cid).unwrap();
+        let info = weapon::info(tee.active_weapon);
+        if info.ammo_regen_time != 0 {
+            let reloading = tee.reload_timer > 0;
+            let slot = &mut tee.weapons[weapon::index(tee.active_weapon)];
+            if reloading {
+                slot.ammo_regen_start = None;
+            } else {
+                let start = *slot.ammo_regen_start.get_or_insert(game_tick);
+                if game_tick - start >= weapon::ms_to_ticks(info.ammo_regen_time) {
+                    slot.ammo = cmp::min(slot.ammo + 1, info.max_ammo);
+                    slot.ammo_regen_start = None;
+                }
+            }
+        }
+    }
+    fn handle_ninja<C: Collision>(&mut self, collision: &mut C, cid: CharacterId) {
+        let game_tick = self.game_tick;
+        let tee = self.characters.get_mut(&cid).unwrap();
+        if tee.active_weapon != Weapon::Ninja {
+            return;
+        }
+        if game_tick - tee.ninja.activation_tick > weapon::ms_to_ticks(weapon::NINJA_DURATION) {
+            // Time's up, return to the previous weapon.
+            tee.weapons[weapon::index(Weapon::Ninja)].got = false;
+            tee.active_weapon = match tee.last_weapon {
+                Weapon::Ninja => Weapon::Pistol,
+                w => w,
+            };
+            return;
+        }
+        tee.ninja.current_move_time -= 1;
+        let mut core = tee.core.get();
+        if tee.ninja.current_move_time == 0 {
+            core.vel = tee.ninja.activation_dir * tee.ninja.old_vel_amount;
+        }
+        if tee.ninja.current_move_time <= 0 {
+            tee.core.set(core);
+            return;
+        }
+        core.vel = tee.ninja.activation_dir * weapon::NINJA_VELOCITY;
+        let box_ = vec2::new(CHARACTER_SIZE, CHARACTER_SIZE);
+        let (new_pos, _) = collision.move_box(core.pos, core.vel, box_);
+        core.pos = new_pos;
+        // Reset the velocity so the client doesn't predict stuff.
+        core.vel = vec2::new(0.0, 0.0);
+        tee.core.set(core);
+
+        // Check if we hit anything along the way. Note that Teeworlds uses
+        // the position from before this tick, so the dash itself doesn't
+        // count.
+        let pos = tee.pos;
+        for target in self.find_characters(pos, CHARACTER_SIZE * 2.0) {
+            if target == cid || self.characters[&cid].hit_characters.contains(&target) {
+                continue;
+            }
+            let target_pos = match self.characters.get(&target) {
+                Some(t) => t.pos,
+                None => continue,
+            };
+            if vec2::distance(target_pos, pos) > CHARACTER_SIZE * 2.0 {
+                continue;
+            }
+            self.sound(target_pos, Sound::NinjaHit);
+            let hit = &mut self.characters.get_mut(&cid).unwrap().hit_characters;
+            if hit.len() < 10 {
+                hit.push(target);
+            }
+            let damage = weapon::info(Weapon::Ninja).damage;
+            self.take_damage(target, vec2::new(0.0, -10.0), damage, cid, WEAPON_NINJA);
+        }
+    }
+    fn fire_weapon<C: Collision>(&mut self, collision: &mut C, cid: CharacterId) {
+        let game_tick = self.game_tick;
+        let tuning = self.tuning;
+        let tee = self.characters.get_mut(&cid).unwrap();
+        if tee.reload_timer != 0 {
+            return;
+        }
+        if tee.do_weapon_switch() {
+            let pos = tee.pos;
+            self.sound(pos, Sound::WeaponSwitch);
+        }
+        let tee = self.characters.get_mut(&cid).unwrap();
+        let input = tee.latest_input;
+        let direction = vec2::new(input.target_x as f32, input.target_y as f32).normalize();
+        let active = tee.active_weapon;
+        let info = weapon::info(active);
+        let ammo = tee.weapons[weapon::index(active)].ammo;
+        let will_fire = count_presses(tee.latest_prev_input.fire, input.fire) != 0
+            || (info.full_auto && input.fire & 1 != 0 && ammo != 0);
+        if !will_fire || tee.ddrace.is_frozen() {
+            return;
+        }
+        let pos = tee.pos;
+        if ammo == 0 {
+            // 125 ms is a magical limit of how fast a human can click.
+            tee.reload_timer = weapon::ms_to_ticks(125);
+            self.sound(pos, Sound::WeaponNoammo);
+            return;
+        }
+        let proj_start_pos = pos + direction * CHARACTER_SIZE * 0.75;
+        match active {
+            Weapon::Hammer => {
+                tee.hit_characters.clear();
+                self.sound(pos, Sound::HammerFire);
+                let mut hits = 0;
+                for target in self.find_characters(proj_start_pos, CHARACTER_SIZE * 0.5) {
+                    if target == cid {
+                        continue;
+                    }
+                    let target_pos = match self.characters.get(&target) {
+                        Some(t) => t.pos,
+                        None => continue,
+                    };
+                    if collision
+                        .intersect_line(proj_start_pos, target_pos)
+                        .is_some()
+                    {
+                        continue;
+                    }
+                    let to_target = target_pos - proj_start_pos;
+                    self.events
+                        .push(Event::HammerHit(if to_target.length() > 0.0 {
+                            target_pos - to_target.normalize() * CHARACTER_SIZE * 0.5
+                        } else {
+                            proj_start_pos
+                        }));
+                    let dir = if (target_pos - pos).length() > 0.0 {
+                        (target_pos - pos).normalize()
+                    } else {
+                        vec2::new(0.0, -1.0)
+                    };
+                    let force =
+                        vec2::new(0.0, -1.0) + (dir + vec2::new(0.0, -1.1)).normalize() * 10.0;
+                    self.take_damage(target, force, info.damage, cid, active.to_i32());
+                    hits += 1;
+                }
+                // If we hit anything, we have to wait for the reload.
+                if hits != 0 {
+                    self.characters.get_mut(&cid).unwrap().reload_timer = TICKS_PER_SECOND / 3;
+                }
+            }
+            Weapon::Pistol | Weapon::Grenade => {
+                self.fire_projectile(active, cid, proj_start_pos, direction);
+                self.sound(
+                    pos,
+                    if active == Weapon::Pistol {
+                        Sound::GunFire
+                    } else {
+                        Sound::GrenadeFire
+                    },
+                );
+            }
+            Weapon::Shotgun => {
+                const SPREADING: [f32; 5] = [-0.185, -0.070, 0.0, 0.070, 0.185];
+                let speed_diff = tuning.shotgun_speeddiff.to_float();
+                for i in -2i32..=2 {
+                    let a = get_angle(direction) + SPREADING[(i + 2) as usize];
+                    let v = 1.0 - i.abs() as f32 / 2.0;
+                    let speed = speed_diff + (1.0 - speed_diff) * v;
+                    let (sin, cos) = a.sin_cos();
+                    let dir = vec2::new(cos, sin) * speed;
+                    self.fire_projectile(active, cid, proj_start_pos, dir);
+                }
+                self.sound(pos, Sound::ShotgunFire);
+            }
+            Weapon::Rifle => {
+                self.fire_laser(collision, cid, pos, direction);
+                self.sound(pos, Sound::RifleFire);
+            }
+            Weapon::Ninja => {
+                tee.hit_characters.clear();
+                tee.ninja.activation_dir = direction;
+                tee.ninja.current_move_time = weapon::ms_to_ticks(weapon::NINJA_MOVE_TIME);
+                tee.ninja.old_vel_amount = tee.core.get().vel.length();
+                self.sound(pos, Sound::NinjaFire);
+            }
+        }
+        let tee = self.characters.get_mut(&cid).unwrap();
+        tee.attack_tick = game_tick;
+        let slot = &mut tee.weapons[weapon::index(active)];
+        // -1 means unlimited ammo.
+        if slot.ammo > 0 {
+            slot.ammo -= 1;
+        }
+        if tee.reload_timer == 0 {
+            tee.reload_timer = weapon::ms_to_ticks(info.fire_delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::count_presses;
+    use super::Event;
+    use super::PickupKind;
+    use super::World;
+    use crate::vec2;
+    use crate::CharacterId;
+    use crate::Collision;
+    use crate::CollisionType;
+    use libtw2_gamenet::enums::Sound;
+    use libtw2_gamenet::enums::Weapon;
+    use libtw2_gamenet::enums::WEAPON_GAME;
+    use libtw2_gamenet::msg::game::SV_TUNE_PARAMS_DEFAULT;
+    use libtw2_gamenet::snap_obj::PlayerInput;
+
+    struct Empty;
+
+    impl Collision for Empty {
+        fn check_point(&mut self, _: vec2) -> Option<CollisionType> {
+            None
+        }
+    }
+
+    /// Solid everywhere outside of `-x < pos.x < x`.
+    struct Walls(f32);
+
+    impl Collision for Walls {
+        fn check_point(&mut self, pos: vec2) -> Option<CollisionType> {
+            if pos.x.abs() >= self.0 {
+                Some(CollisionType::Normal)
+            } else {
+                None
+            }
+        }
+    }
+
+    #[track_caller]
+    fn assert_near(actual: vec2, expected: vec2) {
+        assert!(
+            vec2::distance(actual, expected) < 0.01,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    /// Spawns `cid` and feeds it enough inputs for it to be able to fire.
+    fn spawn_ready(world: &mut World, cid: CharacterId, pos: vec2, weapon: Weapon) {
+        world.spawn(cid, pos);
+        let tee = world.characters.get_mut(&cid).unwrap();
+        tee.give_weapon(weapon, 10);
+        tee.active_weapon = weapon;
+        let input = PlayerInput {
+            target_x: 1,
+            ..Default::default()
+        };
+        for _ in 0..3 {
+            world.on_input(&mut Empty, cid, input);
+        }
+    }
+
+    fn fire(world: &mut World, cid: CharacterId) {
+        let input = PlayerInput {
+            target_x: 1,
+            fire: 1,
+            ..Default::default()
+        };
+        world.on_input(&mut Empty, cid, input);
+    }
+
+    /// Releases the fire button, so that automatic weapons stop firing.
+    fn release(world: &mut World, cid: CharacterId) {
+        let input = PlayerInput {
+            target_x: 1,
+            fire: 2,
+            ..Default::default()
+        };
+        world.on_input(&mut Empty, cid, input);
+    }
+
+    #[test]
+    fn presses() {
+        assert_eq!(count_presses(0, 0), 0);
+        assert_eq!(count_presses(0, 1), 1);
+        assert_eq!(count_presses(1, 2), 0);
+        assert_eq!(count_presses(0, 5), 3);
+        assert_eq!(count_presses(0x3f, 1), 1);
+    }
+
+    #[test]
+    fn pistol_hit() {
+        let (a, b) = (CharacterId(0), CharacterId(1));
+        let mut world = World::new(SV_TUNE_PARAMS_DEFAULT);
+        world.spawn(a, vec2::new(0.0, 0.0));
+        world.spawn(b, vec2::new(200.0, 0.0));
+        let mut input = PlayerInput {
+            target_x: 1,
+            ..Default::default()
+        };
+        for _ in 0..3 {
+            world.on_input(&mut Empty, a, input);
+        }
+        input.fire = 1;
+        world.on_input(&mut Empty, a, input);
+        assert_eq!(world.projectiles().len(), 1);
+        assert_eq!(world.character(a).unwrap().ammo(Weapon::Pistol), Some(9));
+        for _ in 0..10 {
+            world.tick(&mut Empty);
+        }
+        assert!(world.projectiles().is_empty());
+        assert_eq!(world.character(b).unwrap().health(), 9);
+        assert!(world.drain_events().any(|e| matches!(
+            e,
+            Event::Sound {
+                receiver: Some(CharacterId(0)),
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn weapon_pickup() {
+        let cid = CharacterId(3);
+        let mut world = World::new(SV_TUNE_PARAMS_DEFAULT);
+        world.add_pickup(PickupKind::Weapon(Weapon::Grenade), vec2::new(0.0, 10.0));
+        world.spawn(cid, vec2::new(0.0, 0.0));
+        world.tick(&mut Empty);
+        assert_eq!(
+            world.character(cid).unwrap().ammo(Weapon::Grenade),
+            Some(10)
+        );
+        assert!(!world.pickups()[0].is_spawned());
+        assert!(world.drain_events().any(|e| matches!(
+            e,
+            Event::WeaponPickup {
+                weapon: Weapon::Grenade,
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn grenade_arc() {
+        let mut world = World::new(SV_TUNE_PARAMS_DEFAULT);
+        spawn_ready(
+            &mut world,
+            CharacterId(0),
+            vec2::new(0.0, 0.0),
+            Weapon::Grenade,
+        );
+        fire(&mut world, CharacterId(0));
+        release(&mut world, CharacterId(0));
+        let grenade = world.projectiles()[0];
+        // Fired from 3/4 of the character size in front of the character,
+        // with a speed of 1000 and a curvature of 7.
+        let tuning = &SV_TUNE_PARAMS_DEFAULT;
+        assert_near(grenade.pos_at(0.0, tuning), vec2::new(21.0, 0.0));
+        assert_near(grenade.pos_at(0.1, tuning), vec2::new(121.0, 7.0));
+        assert_near(grenade.pos_at(0.5, tuning), vec2::new(521.0, 175.0));
+        assert_near(grenade.pos_at(1.0, tuning), vec2::new(1021.0, 700.0));
+
+        // The grenade lives for two seconds, it explodes in the tick after.
+        world.drain_events().for_each(drop);
+        for _ in 0..100 {
+            world.tick(&mut Empty);
+        }
+        assert_eq!(world.projectiles().len(), 1);
+        world.tick(&mut Empty);
+        assert!(world.projectiles().is_empty());
+        let explosion = world
+            .drain_events()
+            .find_map(|e| match e {
+                Event::Explosion(pos) => Some(pos),
+                _ => None,
+            })
+            .unwrap();
+        assert_near(explosion, grenade.pos_at(2.02, tuning));
+    }
+
+    #[test]
+    fn explosion_radius() {
+        let mut world = World::new(SV_TUNE_PARAMS_DEFAULT);
+        // Full damage within the inner radius of 48.
+        world.spawn(CharacterId(0), vec2::new(40.0, 0.0));
+        // Halfway between the inner radius and the radius of 135.
+        world.spawn(CharacterId(1), vec2::new(0.0, 91.5));
+        // Found by the explosion due to the character size, but too far
+        // away to take damage.
+        world.spawn(CharacterId(2), vec2::new(-150.0, 0.0));
+        world.create_explosion(vec2::new(0.0, 0.0), CharacterId(9), 3);
+
+        let tee = world.character(CharacterId(0)).unwrap();
+        assert_eq!(tee.health(), 4);
+        assert_near(tee.core().vel, vec2::new(12.0, 0.0));
+        let tee = world.character(CharacterId(1)).unwrap();
+        assert_eq!(tee.health(), 7);
+        assert_near(tee.core().vel, vec2::new(0.0, 6.0));
+        let tee = world.character(CharacterId(2)).unwrap();
+        assert_eq!(tee.health(), 10);
+        assert_near(tee.core().vel, vec2::new(0.0, 0.0));
+    }
+
+    #[test]
+    fn laser_bounce() {
+        let mut world = World::new(SV_TUNE_PARAMS_DEFAULT);
+        let mut walls = Walls(100.0);
+        let origin = vec2::new(0.0, 0.0);
+        world.fire_laser(&mut walls, CharacterId(0), origin, vec2::new(1.0, 0.0));
+        let laser = world.lasers()[0];
+        assert_eq!(laser.bounces, 1);
+        assert_near(laser.from, origin);
+        // The beam stops at the last point before the wall.
+        assert_near(laser.pos, vec2::new(99.0, 0.0));
+        assert_near(laser.dir, vec2::new(-1.0, 0.0));
+        // The laser reach of 800 minus the distance travelled.
+        assert!((laser.energy - 701.0).abs() < 0.01);
+
+        // The next bounce happens after the bounce delay of 150 ms. The
+        // default tuning allows a single bounce, the beam stops at the
+        // second wall.
+        for _ in 0..7 {
+            world.tick(&mut walls);
+        }
+        assert_eq!(world.lasers()[0].bounces, 1);
+        world.tick(&mut walls);
+        let laser = world.lasers()[0];
+        assert_eq!(laser.bounces, 2);
+        assert_near(laser.pos, vec2::new(-99.0, 0.0));
+        assert!(laser.energy < 0.0);
+        let bounces = world
+            .drain_events()
+            .filter(|e| {
+                matches!(
+                    e,
+                    Event::Sound {
+                        sound: Sound::RifleBounce,
+                        ..
+                    }
+                )
+            })
+            .count();
+        assert_eq!(bounces, 2);
+        for _ in 0..8 {
+            world.tick(&mut walls);
+        }
+        assert!(world.lasers().is_empty());
+    }
+
+    #[test]
+    fn shotgun_spread() {
+        let mut world = World::new(SV_TUNE_PARAMS_DEFAULT);
+        spawn_ready(
+            &mut world,
+            CharacterId(0),
+            vec2::new(0.0, 0.0),
+            Weapon::Shotgun,
+        );
+        fire(&mut world, CharacterId(0));
+        let angles = [-0.185f32, -0.070, 0.0, 0.070, 0.185];
+        // The outer pellets are slower by the speed difference of 0.8.
+        let speeds = [0.8, 0.9, 1.0, 0.9, 0.8];
+        let pellets = world.projectiles();
+        assert_eq!(pellets.len(), 5);
+        for ((p, &a), &speed) in pellets.iter().zip(&angles).zip(&speeds) {
+            assert_eq!(p.type_(), Weapon::Shotgun);
+            assert_near(p.pos, vec2::new(21.0, 0.0));
+            assert_near(p.dir, vec2::new(a.cos(), a.sin()) * speed);
+            assert_eq!(p.life_span, 10);
+        }
+        assert_eq!(
+            world
+                .character(CharacterId(0))
+                .unwrap()
+                .ammo(Weapon::Shotgun),
+            Some(9)
+        );
+    }
+
+    #[test]
+    fn armor_damage() {
+        let (a, b) = (CharacterId(0), CharacterId(1));
+        let mut world = World::new(SV_TUNE_PARAMS_DEFAULT);
+        world.spawn(a, vec2::new(0.0, 0.0));
+        world.spawn(b, vec2::new(200.0, 0.0));
+        world.characters.get_mut(&a).unwrap().armor = 5;
+        let zero = vec2::new(0.0, 0.0);
+        // One point always goes to the health, the rest to the armor.
+        assert!(world.take_damage(a, zero, 4, b, 1));
+        let tee = world.character(a).unwrap();
+        assert_eq!((tee.health(), tee.armor()), (9, 2));
+        // Damage exceeding the armor goes to the health.
+        assert!(world.take_damage(a, zero, 6, b, 1));
+        let tee = world.character(a).unwrap();
+        assert_eq!((tee.health(), tee.armor()), (5, 0));
+        // Self damage is halved.
+        assert!(world.take_damage(a, zero, 6, a, 1));
+        assert_eq!(world.character(a).unwrap().health(), 2);
+        assert!(!world.take_damage(a, zero, 2, b, 1));
+        assert!(world.character(a).is_none());
+    }
+
+    #[test]
+    fn hammer_knockback() {
+        let (a, b) = (CharacterId(0), CharacterId(1));
+        let mut world = World::new(SV_TUNE_PARAMS_DEFAULT);
+        spawn_ready(&mut world, a, vec2::new(0.0, 0.0), Weapon::Hammer);
+        world.spawn(b, vec2::new(30.0, 0.0));
+        fire(&mut world, a);
+        let tee = world.character(b).unwrap();
+        assert_eq!(tee.health(), 7);
+        // `vec2(0, -1) + normalize(dir + vec2(0, -1.1)) * 10`.
+        assert_near(tee.core().vel, vec2::new(6.7267, -8.3994));
+        assert_eq!(world.character(a).unwrap().reload_timer, 16);
+    }
+
+    #[test]
+    fn pickup_respawn() {
+        let cid = CharacterId(0);
+        let mut world = World::new(SV_TUNE_PARAMS_DEFAULT);
+        world.add_pickup(PickupKind::Armor, vec2::new(0.0, 10.0));
+        world.add_pickup(PickupKind::Ninja, vec2::new(1000.0, 0.0));
+        world.spawn(cid, vec2::new(0.0, 0.0));
+        world.tick(&mut Empty);
+        assert_eq!(world.character(cid).unwrap().armor(), 1);
+        world.kill(cid, WEAPON_GAME);
+        // Armor respawns 15 seconds after being picked up in tick 1.
+        while world.game_tick() < 751 {
+            world.tick(&mut Empty);
+            assert!(!world.pickups()[0].is_spawned());
+        }
+        world.tick(&mut Empty);
+        assert!(world.pickups()[0].is_spawned());
+        // Ninja first spawns after 90 seconds.
+        while world.game_tick() < 4500 {
+            world.tick(&mut Empty);
+            assert!(!world.pickups()[1].is_spawned());
+        }
+        world.tick(&mut Empty);
+        assert!(world.pickups()[1].is_spawned());
+    }
+}
@@ -8,12 +8,25 @@ use std::f32::consts::PI;
 use std::fmt;
 use std::ops;
 
+pub mod ddrace;
+mod game;
+pub mod weapon;
+
+pub use self::game::Event;
+pub use self::game::Laser;
+pub use self::game::Pickup;
+pub use self::game::PickupKind;
+pub use self::game::Projectile;
+pub use self::game::Tee;
+pub use self::game::World;
+
+pub const TICKS_PER_SECOND: i32 = 50;
 pub const CHARACTER_SIZE: f32 = 28.0;
 pub const DISABLE_HOOK_DISTANCE: f32 = 46.0;
 pub const MAX_VELOCITY: f32 = 6000.0;
 pub const MAX_HOOK_GRAB_TIME: u32 = 60; // 1.2 s with 50 Hz ticks.
 
-#[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct CharacterId(pub u32);
 
 #[allow(non_camel_case_types)]
@@ -347,6 +360,54 @@ pub trait Collision {
         }
         None
     }
+    /// Like `check_line`, but also returns the last point before the
+    /// collision.
+    fn intersect_line(&mut self, from: vec2, to: vec2) -> Option<(vec2, vec2, CollisionType)> {
+        let dist = vec2::distance(from, to);
+        let end = (dist + 1.0).trunc_to_i32();
+        let mut last = from;
+        for i in 0..end {
+            let point = vec2::mix(from, to, i as f32 / dist);
+            if let Some(col) = self.check_point(point) {
+                return Some((point, last, col));
+            }
+            last = point;
+        }
+        None
+    }
+    /// Whether a character touching `pos` dies, e.g. due to a death tile.
+    fn is_death(&mut self, pos: vec2) -> bool {
+        let _ = pos;
+        false
+    }
+    /// Whether `pos` is so far outside of the map that entities there are
+    /// removed.
+    fn is_clipped(&mut self, pos: vec2) -> bool {
+        let _ = pos;
+        false
+    }
+    /// DDRace layers of the map, if it has any.
+    fn ddrace(&mut self) -> Option<&mut ddrace::Map> {
+        None
+    }
+    fn move_point(&mut self, pos: vec2, mut vel: vec2, elasticity: f32) -> (vec2, vec2) {
+        if self.check_point(pos + vel).is_none() {
+            return (pos + vel, vel);
+        }
+        let mut affected = false;
+        if self.check_point(vec2::new(pos.x + vel.x, pos.y)).is_some() {
+            vel.x *= -elasticity;
+            affected = true;
+        }
+        if self.check_point(vec2::new(pos.x, pos.y + vel.y)).is_some() {
+            vel.y *= -elasticity;
+            affected = true;
+        }
+        if !affected {
+            vel *= -elasticity;
+        }
+        (pos, vel)
+    }
     fn check_box(&mut self, pos: vec2, box_: vec2) -> bool {
         let diff1 = box_ * 0.5;
         let diff2 = vec2::new(diff1.x, -diff1.y);
@@ -404,6 +465,9 @@ impl Character {
             move_direction: Default::default(),
         }
     }
+    pub fn pos(&self) -> vec2 {
+        self.pos
+    }
     pub fn tick<C, OC>(
         &mut self,
         collision: &mut C,
@@ -0,0 +1,70 @@
+//! Weapon properties, copied from the `content.py` of Teeworlds 0.6.
+
+use crate::TICKS_PER_SECOND;
+use libtw2_gamenet::enums::Weapon;
+
+pub const NUM_WEAPONS: usize = 6;
+
+pub const WEAPONS: [Weapon; NUM_WEAPONS] = [
+    Weapon::Hammer,
+    Weapon::Pistol,
+    Weapon::Shotgun,
+    Weapon::Grenade,
+    Weapon::Rifle,
+    Weapon::Ninja,
+];
+
+/// Duration of the ninja powerup in milliseconds.
+pub const NINJA_DURATION: i32 = 15000;
+/// Duration of a ninja dash in milliseconds.
+pub const NINJA_MOVE_TIME: i32 = 200;
+pub const NINJA_VELOCITY: f32 = 50.0;
+
+pub const EXPLOSION_RADIUS: f32 = 135.0;
+pub const EXPLOSION_INNER_RADIUS: f32 = 48.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct WeaponInfo {
+    /// Time between two shots in milliseconds.
+    pub fire_delay: i32,
+    pub damage: i32,
+    /// Time it takes to regenerate one ammo in milliseconds, zero if the
+    /// weapon doesn't regenerate ammo.
+    pub ammo_regen_time: i32,
+    pub max_ammo: i32,
+    /// Whether the weapon keeps firing while the fire button is held.
+    pub full_auto: bool,
+}
+
+const fn weapon(fire_delay: i32, damage: i32, ammo_regen_time: i32, full_auto: bool) -> WeaponInfo {
+    WeaponInfo {
+        fire_delay,
+        damage,
+        ammo_regen_time,
+        max_ammo: 10,
+        full_auto,
+    }
+}
+
+const INFOS: [WeaponInfo; NUM_WEAPONS] = [
+    weapon(125, 3, 0, false),
+    weapon(125, 1, 500, false),
+    weapon(500, 1, 0, true),
+    weapon(500, 6, 0, true),
+    weapon(800, 5, 0, true),
+    weapon(800, 9, 0, false),
+];
+
+pub fn info(weapon: Weapon) -> &'static WeaponInfo {
+    &INFOS[index(weapon)]
+}
+
+pub fn index(weapon: Weapon) -> usize {
+    weapon.to_i32() as usize
+}
+
+/// Converts a duration in milliseconds to ticks, rounding down like
+/// Teeworlds.
+pub fn ms_to_ticks(ms: i32) -> i32 {
+    ms * TICKS_PER_SECOND / 1000
+}